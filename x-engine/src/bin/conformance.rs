@@ -2,25 +2,56 @@
 //!
 //! Run W3C conformance tests against xee, xrust, and xust engines.
 
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process;
 
-use x_engine::reporter::ComplianceReport;
-use x_engine::testdriver::qt3::run_xpath_tests;
-use x_engine::testdriver::xslt30::run_xslt_tests;
-use x_engine::testdriver::xsd::run_xsd_tests;
+use x_engine::reporter::{
+    feature_matrix, write_console_summary, write_failure_list, ComplianceReport,
+    ComplianceSummary, DetailedTestResult, ExitPolicy, CSV_HEADER,
+};
+use x_engine::testdriver::qt3::{
+    self, run_xpath_tests_streaming_with_options, run_xpath_tests_with_stats,
+};
+use x_engine::testdriver::xslt30::{self, run_xslt_tests_with_options};
+use x_engine::testdriver::xsd::{self, run_xsd_tests_with_options};
+use x_engine::testdriver::{parse_exclusions, run_suite, RunOptions, RunStats, Suite, TestResult};
 use x_engine::{Backend, XEngine};
 
+/// Stream output to stdout as it is written, exiting on write errors
+fn write_stdout(write: impl FnOnce(&mut io::BufWriter<io::StdoutLock<'static>>) -> io::Result<()>) {
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    if let Err(e) = write(&mut out).and_then(|()| out.flush()) {
+        eprintln!("Error: Failed to write output: {}", e);
+        process::exit(1);
+    }
+}
+
 fn print_usage() {
     eprintln!("x-engine conformance testing tool");
     eprintln!();
     eprintln!("Usage:");
-    eprintln!("  conformance run --engine <ENGINE> --suite <SUITE> [--filter <PATTERN>] [--output <FORMAT>]");
+    eprintln!("  conformance run --engine <ENGINE> --suite <SUITE> [--filter <PATTERN>] [--output <FORMAT>] [--only <OUTCOMES>] [--fail-fast] [--dry-run] [--allow-error-as-empty] [--exclude <FILE>] [--xfail <FILE>] [--verbose-failures] [--failures-only] [--min-pass-rate <PCT>] [--max-failures <N>]");
     eprintln!("  conformance report --engine <ENGINE> --suite <SUITE> --output <FORMAT>");
+    eprintln!("  conformance list --suite <SUITE>");
+    eprintln!("  conformance matrix-all [--filter <PATTERN>]");
+    eprintln!("  conformance run-one [--engine <ENGINE>] --set <TEST_SET> --case <TEST_CASE>");
+    eprintln!("  conformance dump-catalog --suite qt3 --set <TEST_SET>");
     eprintln!();
     eprintln!("Engines: xee, xrust, xust");
     eprintln!("Suites: qt3, xslt30, xsd");
     eprintln!("Output formats: summary (default), json, csv, markdown");
+    eprintln!("                (report also writes bincode, for storing baselines)");
+    eprintln!("Outcomes for --only: pass, fail, error, n/a, skipped, xfail, xpass (comma-separated)");
+    eprintln!("Thresholds: --min-pass-rate (percent of applicable tests) and --max-failures");
+    eprintln!("            (failed + errored) make the run exit with 1 when violated");
+    eprintln!("Exclusions: --exclude reads one <TEST_SET>/<TEST_ID> per line (# comments) and");
+    eprintln!("            records those tests as skipped without running them; --xfail takes");
+    eprintln!("            the same format and reports listed failures as xfail (not counted");
+    eprintln!("            against the pass rate) and listed passes as xpass");
+    eprintln!("Summary output: --verbose-failures lists every failure on stderr instead of the");
+    eprintln!("                first 10; --failures-only omits the outcome counts");
     eprintln!();
     eprintln!("Examples:");
     eprintln!("  conformance run --engine xee --suite qt3");
@@ -28,7 +59,16 @@ fn print_usage() {
     eprintln!("  conformance run --engine xee --suite qt3 --output json > results.json");
     eprintln!("  conformance run --engine xee --suite qt3 --output csv > results.csv");
     eprintln!("  conformance run --engine xust --suite xsd --filter nist");
+    eprintln!("  conformance run --engine xee --suite qt3 --filter fn-abs --fail-fast");
+    eprintln!("  conformance run --engine xee --suite qt3 --min-pass-rate 85 --max-failures 100");
+    eprintln!("  conformance run --engine xee --suite qt3 --filter fn-abs --dry-run");
+    eprintln!("  conformance run --engine xee --suite qt3 --verbose-failures --failures-only");
+    eprintln!("  conformance run --engine xee --suite qt3 --exclude known-failures.txt");
     eprintln!("  conformance report --engine xee --suite qt3 --output markdown");
+    eprintln!("  conformance report --engine xee --suite qt3 --output csv --only fail,error");
+    eprintln!("  conformance list --suite qt3");
+    eprintln!("  conformance run-one --engine xee --set fn-abs --case fn-abs-1");
+    eprintln!("  conformance dump-catalog --suite qt3 --set fn-abs");
 }
 
 fn main() {
@@ -42,6 +82,10 @@ fn main() {
     match args[1].as_str() {
         "run" => run_tests(&args[2..]),
         "report" => run_report(&args[2..]),
+        "list" => run_list(&args[2..]),
+        "matrix-all" => run_matrix_all(&args[2..]),
+        "run-one" => run_one(&args[2..]),
+        "dump-catalog" => run_dump_catalog(&args[2..]),
         "--help" | "-h" => {
             print_usage();
             process::exit(0);
@@ -54,18 +98,36 @@ fn main() {
     }
 }
 
-fn parse_args(args: &[String]) -> (Option<String>, Option<String>, Option<String>, Option<String>) {
-    let mut engine = None;
-    let mut suite = None;
-    let mut filter = None;
-    let mut output = None;
+/// Parsed command-line options
+#[derive(Default)]
+struct CliArgs {
+    engine: Option<String>,
+    suite: Option<String>,
+    filter: Option<String>,
+    output: Option<String>,
+    fail_fast: bool,
+    dry_run: bool,
+    allow_error_as_empty: bool,
+    verbose_failures: bool,
+    failures_only: bool,
+    only: Option<Vec<String>>,
+    exclude: Option<PathBuf>,
+    xfail: Option<PathBuf>,
+    min_pass_rate: Option<f64>,
+    max_failures: Option<usize>,
+    test_set: Option<String>,
+    test_case: Option<String>,
+}
+
+fn parse_args(args: &[String]) -> CliArgs {
+    let mut cli = CliArgs::default();
 
     let mut i = 0;
     while i < args.len() {
         match args[i].as_str() {
             "--engine" | "-e" => {
                 if i + 1 < args.len() {
-                    engine = Some(args[i + 1].clone());
+                    cli.engine = Some(args[i + 1].clone());
                     i += 2;
                 } else {
                     i += 1;
@@ -73,7 +135,7 @@ fn parse_args(args: &[String]) -> (Option<String>, Option<String>, Option<String
             }
             "--suite" | "-s" => {
                 if i + 1 < args.len() {
-                    suite = Some(args[i + 1].clone());
+                    cli.suite = Some(args[i + 1].clone());
                     i += 2;
                 } else {
                     i += 1;
@@ -81,7 +143,7 @@ fn parse_args(args: &[String]) -> (Option<String>, Option<String>, Option<String
             }
             "--filter" | "-f" => {
                 if i + 1 < args.len() {
-                    filter = Some(args[i + 1].clone());
+                    cli.filter = Some(args[i + 1].clone());
                     i += 2;
                 } else {
                     i += 1;
@@ -89,17 +151,125 @@ fn parse_args(args: &[String]) -> (Option<String>, Option<String>, Option<String
             }
             "--output" | "-o" => {
                 if i + 1 < args.len() {
-                    output = Some(args[i + 1].clone());
+                    cli.output = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            }
+            "--only" => {
+                if i + 1 < args.len() {
+                    cli.only = Some(args[i + 1].split(',').map(|s| s.trim().to_string()).collect());
                     i += 2;
                 } else {
                     i += 1;
                 }
             }
+            "--exclude" => {
+                if i + 1 < args.len() {
+                    cli.exclude = Some(PathBuf::from(&args[i + 1]));
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            }
+            "--xfail" => {
+                if i + 1 < args.len() {
+                    cli.xfail = Some(PathBuf::from(&args[i + 1]));
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            }
+            "--min-pass-rate" => {
+                if i + 1 < args.len() {
+                    cli.min_pass_rate = Some(parse_number(&args[i], &args[i + 1]));
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            }
+            "--max-failures" => {
+                if i + 1 < args.len() {
+                    cli.max_failures = Some(parse_number(&args[i], &args[i + 1]));
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            }
+            "--set" => {
+                if i + 1 < args.len() {
+                    cli.test_set = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            }
+            "--case" => {
+                if i + 1 < args.len() {
+                    cli.test_case = Some(args[i + 1].clone());
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            }
+            "--fail-fast" => {
+                cli.fail_fast = true;
+                i += 1;
+            }
+            "--dry-run" => {
+                cli.dry_run = true;
+                i += 1;
+            }
+            "--allow-error-as-empty" => {
+                cli.allow_error_as_empty = true;
+                i += 1;
+            }
+            "--verbose-failures" => {
+                cli.verbose_failures = true;
+                i += 1;
+            }
+            "--failures-only" => {
+                cli.failures_only = true;
+                i += 1;
+            }
             _ => i += 1,
         }
     }
 
-    (engine, suite, filter, output)
+    cli
+}
+
+/// Read a `--exclude`/`--xfail` list, exiting on failure
+fn read_test_list(path: &Path) -> HashSet<(String, String)> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => parse_exclusions(&text),
+        Err(e) => {
+            eprintln!("Error: Failed to read test list {:?}: {}", path, e);
+            process::exit(1);
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> T {
+    match value.parse() {
+        Ok(n) => n,
+        Err(_) => {
+            eprintln!("Error: Invalid value '{}' for {}", value, flag);
+            process::exit(1);
+        }
+    }
+}
+
+/// Exit according to the threshold policy, printing the decision to stderr
+fn exit_with_policy(policy: &ExitPolicy, summary: &ComplianceSummary) {
+    let (code, decision) = policy.decide(summary);
+    if let Some(decision) = decision {
+        eprintln!("{}", decision);
+    }
+    if code != 0 {
+        process::exit(code);
+    }
 }
 
 fn get_engine(name: &str) -> Option<XEngine> {
@@ -151,7 +321,17 @@ fn get_catalog_path(suite: &str) -> Option<PathBuf> {
 }
 
 fn run_tests(args: &[String]) {
-    let (engine_name, suite, filter, output) = parse_args(args);
+    let cli = parse_args(args);
+    let CliArgs { engine: engine_name, suite, filter, output, only, .. } = cli;
+    let options = RunOptions {
+        fail_fast: cli.fail_fast,
+        dry_run: cli.dry_run,
+        allow_error_as_empty: cli.allow_error_as_empty,
+        exclude: cli.exclude.as_deref().map(read_test_list).unwrap_or_default(),
+        xfail: cli.xfail.as_deref().map(read_test_list).unwrap_or_default(),
+    };
+    let policy = ExitPolicy { min_pass_rate: cli.min_pass_rate, max_failures: cli.max_failures };
+    let only: Option<Vec<&str>> = only.as_ref().map(|o| o.iter().map(String::as_str).collect());
 
     let engine_name = match engine_name {
         Some(e) => e,
@@ -198,72 +378,241 @@ fn run_tests(args: &[String]) {
     if let Some(ref f) = filter {
         eprintln!("Filter: {}", f);
     }
+    if options.fail_fast {
+        eprintln!("Fail-fast: stopping at the first failure");
+    }
+    if options.dry_run {
+        eprintln!("Dry run: checking applicability without executing tests");
+    }
+    if !options.exclude.is_empty() {
+        eprintln!("Excluding {} tests", options.exclude.len());
+    }
+    if !options.xfail.is_empty() {
+        eprintln!("Expecting {} tests to fail", options.xfail.len());
+    }
     eprintln!();
 
+    // QT3 CSV output is streamed row by row so huge runs don't accumulate
+    if suite == "qt3" && output_format == "csv" {
+        println!("{}", CSV_HEADER);
+        let mut summary = ComplianceSummary::default();
+        let stats = run_xpath_tests_streaming_with_options(&mut engine, &catalog_path, filter.as_deref(), &options, &mut |r| {
+            summary.add(&r);
+            if only.as_ref().is_none_or(|keep| keep.contains(&r.outcome.as_str())) {
+                println!("{}", DetailedTestResult::from(&r).to_csv_row());
+            }
+        });
+        warn_if_unmatched(&stats, filter.as_deref());
+        exit_with_policy(&policy, &summary);
+        return;
+    }
+
     // Run tests based on suite type
-    let results = match suite.as_str() {
-        "qt3" => run_xpath_tests(&mut engine, &catalog_path, filter.as_deref()),
-        "xslt30" => run_xslt_tests(&mut engine, &catalog_path, filter.as_deref()),
-        "xsd" => run_xsd_tests(&mut engine, &catalog_path, filter.as_deref()),
+    let (results, stats) = match suite.as_str() {
+        "qt3" => run_xpath_tests_with_stats(&mut engine, &catalog_path, filter.as_deref(), &options),
+        "xslt30" => with_result_count(run_xslt_tests_with_options(&mut engine, &catalog_path, filter.as_deref(), &options)),
+        "xsd" => with_result_count(run_xsd_tests_with_options(&mut engine, &catalog_path, filter.as_deref(), &options)),
         _ => {
             eprintln!("Error: Unknown suite '{}'", suite);
             process::exit(1);
         }
     };
+    warn_if_unmatched(&stats, filter.as_deref());
+
+    if options.fail_fast && results.last().is_some_and(|r| options.should_stop(r)) {
+        eprintln!("Stopped early after {} tests (--fail-fast)", results.len());
+    }
+
+    let summary = ComplianceSummary::from_results(&results);
 
     // Output based on format
+    let make_report = |results| {
+        let report = ComplianceReport::new(&engine_name, &suite, results);
+        match &only {
+            Some(keep) => report.filtered(keep),
+            None => report,
+        }
+    };
     match output_format.as_str() {
-        "json" => {
-            let report = ComplianceReport::new(&engine_name, &suite, results);
-            println!("{}", report.to_json());
+        "json" => write_stdout(|w| make_report(results).write_json(w).and_then(|()| writeln!(w))),
+        "csv" => write_stdout(|w| make_report(results).write_csv(w)),
+        "markdown" | "md" => write_stdout(|w| make_report(results).write_markdown(w)),
+        "summary" | _ => {
+            if !cli.failures_only {
+                write_stdout(|w| write_console_summary(w, &summary));
+            }
+            // The full list goes to stderr so stdout stays clean when piped
+            if cli.verbose_failures {
+                let _ = write_failure_list(&mut io::stderr().lock(), &results, true);
+            } else {
+                write_stdout(|w| write_failure_list(w, &results, false));
+            }
+        }
+    }
+
+    exit_with_policy(&policy, &summary);
+}
+
+/// Approximate run stats for drivers that only return results
+fn with_result_count(results: Vec<TestResult>) -> (Vec<TestResult>, RunStats) {
+    let mut sets: Vec<&str> = results.iter().map(|r| r.test_set.as_str()).collect();
+    sets.dedup();
+    let stats = RunStats { sets_matched: sets.len(), cases_run: results.len() };
+    (results, stats)
+}
+
+/// Warn on stderr when a filter matched nothing, so a typo doesn't look like success
+fn warn_if_unmatched(stats: &RunStats, filter: Option<&str>) {
+    if let Some(warning) = stats.no_match_warning(filter) {
+        eprintln!("{}", warning);
+    }
+}
+
+fn run_list(args: &[String]) {
+    let suite = match parse_args(args).suite {
+        Some(s) => s,
+        None => {
+            eprintln!("Error: --suite is required");
+            process::exit(1);
         }
-        "csv" => {
-            let report = ComplianceReport::new(&engine_name, &suite, results);
-            println!("{}", report.to_csv());
+    };
+
+    let catalog_path = match get_catalog_path(&suite) {
+        Some(p) => p,
+        None => {
+            eprintln!("Error: Unknown suite '{}'. Use qt3, xslt30, or xsd.", suite);
+            process::exit(1);
         }
-        "markdown" | "md" => {
-            let report = ComplianceReport::new(&engine_name, &suite, results);
-            println!("{}", report.to_markdown());
+    };
+
+    if !catalog_path.exists() {
+        eprintln!("Error: Catalog not found at {:?}", catalog_path);
+        process::exit(1);
+    }
+
+    let names = match suite.as_str() {
+        "qt3" => qt3::list_test_sets(&catalog_path),
+        "xslt30" => xslt30::list_test_sets(&catalog_path),
+        "xsd" => xsd::list_test_sets(&catalog_path),
+        _ => {
+            eprintln!("Error: Unknown suite '{}'", suite);
+            process::exit(1);
         }
-        "summary" | _ => {
-            // Print summary to stdout
-            let total = results.len();
-            let passed = results.iter().filter(|r| r.outcome.is_pass()).count();
-            let failed = results.iter().filter(|r| r.outcome.is_fail()).count();
-            let errors = results.iter().filter(|r| r.outcome.is_error()).count();
-            let not_applicable = results
-                .iter()
-                .filter(|r| matches!(r.outcome, x_engine::testdriver::TestOutcome::NotApplicable))
-                .count();
-
-            println!("Results:");
-            println!("  Total:          {}", total);
-            println!("  Passed:         {} ({:.1}%)", passed, if total > 0 { (passed as f64 / total as f64) * 100.0 } else { 0.0 });
-            println!("  Failed:         {}", failed);
-            println!("  Errors:         {}", errors);
-            println!("  Not Applicable: {}", not_applicable);
-            println!();
-
-            // Print first few failures
-            let failures: Vec<_> = results
-                .iter()
-                .filter(|r| r.outcome.is_fail() || r.outcome.is_error())
-                .take(10)
-                .collect();
-
-            if !failures.is_empty() {
-                println!("First {} failures:", failures.len());
-                for r in &failures {
-                    println!("  {}: {:?}", r.test_id, r.outcome);
-                }
-                println!();
+    };
+
+    match names {
+        Ok(names) => {
+            for name in &names {
+                println!("{}", name);
             }
+            eprintln!("{} test sets", names.len());
+        }
+        Err(e) => {
+            eprintln!("Error: Failed to read catalog: {}", e);
+            process::exit(1);
         }
     }
 }
 
+fn run_dump_catalog(args: &[String]) {
+    let cli = parse_args(args);
+    let Some(test_set) = cli.test_set else {
+        eprintln!("Error: --set is required");
+        process::exit(1);
+    };
+    let suite = cli.suite.unwrap_or_else(|| "qt3".to_string());
+    if suite != "qt3" {
+        eprintln!("Error: dump-catalog only supports the qt3 suite");
+        process::exit(1);
+    }
+
+    let catalog_path = match get_catalog_path(&suite) {
+        Some(p) if p.exists() => p,
+        _ => {
+            eprintln!("Error: QT3 catalog not found");
+            process::exit(1);
+        }
+    };
+
+    match qt3::dump_test_set(&catalog_path, &test_set) {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn run_one(args: &[String]) {
+    let cli = parse_args(args);
+    let engine_name = cli.engine.unwrap_or_else(|| "xee".to_string());
+    let (Some(test_set), Some(test_case)) = (cli.test_set, cli.test_case) else {
+        eprintln!("Error: --set and --case are required");
+        process::exit(1);
+    };
+
+    let mut engine = match get_engine(&engine_name) {
+        Some(e) => e,
+        None => {
+            eprintln!("Error: Unknown engine '{}'. Use xee, xrust, or xust.", engine_name);
+            process::exit(1);
+        }
+    };
+
+    let catalog_path = match get_catalog_path("qt3") {
+        Some(p) if p.exists() => p,
+        _ => {
+            eprintln!("Error: QT3 catalog not found");
+            process::exit(1);
+        }
+    };
+
+    let result = match qt3::run_single(&mut engine, &catalog_path, &test_set, &test_case) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    println!("{}/{}: {}", result.test_set, result.test_id, result.outcome);
+    if let Some(expected) = &result.expected {
+        println!("  Expected: {}", expected);
+    }
+    if let Some(actual) = &result.actual {
+        println!("  Actual:   {}", actual);
+    }
+    println!("  Duration: {:?}", result.duration);
+    if !result.outcome.is_pass() {
+        process::exit(1);
+    }
+}
+
+fn run_matrix_all(args: &[String]) {
+    let filter = parse_args(args).filter;
+
+    let mut reports = Vec::new();
+    for suite in [Suite::Qt3, Suite::Xslt30, Suite::Xsd] {
+        let catalog_path = match get_catalog_path(suite.name()) {
+            Some(p) if p.exists() => p,
+            _ => {
+                eprintln!("Skipping {}: catalog not found", suite.name());
+                continue;
+            }
+        };
+        for engine_name in ["xee", "xrust", "xust"] {
+            let mut engine = get_engine(engine_name).expect("known engine");
+            eprintln!("Running {} tests with {} engine...", suite.name(), engine_name);
+            reports.push(run_suite(&mut engine, suite, &catalog_path, filter.as_deref()));
+        }
+    }
+
+    print!("{}", feature_matrix(&reports));
+}
+
 fn run_report(args: &[String]) {
-    let (engine_name, suite, filter, output_format) = parse_args(args);
+    let cli = parse_args(args);
+    let CliArgs { engine: engine_name, suite, filter, output: output_format, only, .. } = cli;
 
     let engine_name = match engine_name {
         Some(e) => e,
@@ -306,26 +655,37 @@ fn run_report(args: &[String]) {
 
     eprintln!("Running {} tests with {} engine...", suite, engine_name);
 
-    // Run tests based on suite type
-    let results = match suite.as_str() {
-        "qt3" => run_xpath_tests(&mut engine, &catalog_path, filter.as_deref()),
-        "xslt30" => run_xslt_tests(&mut engine, &catalog_path, filter.as_deref()),
-        "xsd" => run_xsd_tests(&mut engine, &catalog_path, filter.as_deref()),
-        _ => {
-            eprintln!("Error: Unknown suite '{}'", suite);
-            process::exit(1);
-        }
+    let Some(suite) = Suite::from_name(&suite) else {
+        eprintln!("Error: Unknown suite '{}'", suite);
+        process::exit(1);
     };
 
-    let report = ComplianceReport::new(&engine_name, &suite, results);
+    let policy = ExitPolicy { min_pass_rate: cli.min_pass_rate, max_failures: cli.max_failures };
+    let mut report = run_suite(&mut engine, suite, &catalog_path, filter.as_deref());
+    if let Some(keep) = &only {
+        let keep: Vec<&str> = keep.iter().map(String::as_str).collect();
+        report = report.filtered(&keep);
+    }
 
     match output_format.as_str() {
-        "json" => println!("{}", report.to_json()),
-        "csv" => println!("{}", report.to_csv()),
-        "markdown" | "md" => println!("{}", report.to_markdown()),
+        "json" => write_stdout(|w| report.write_json(w).and_then(|()| writeln!(w))),
+        "csv" => write_stdout(|w| report.write_csv(w)),
+        "markdown" | "md" => write_stdout(|w| report.write_markdown(w)),
+        "bincode" => {
+            let bytes = report.to_bincode().unwrap_or_else(|e| {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            });
+            write_stdout(|w| w.write_all(&bytes))
+        }
         _ => {
-            eprintln!("Error: Unknown output format '{}'. Use json, csv, or markdown.", output_format);
+            eprintln!(
+                "Error: Unknown output format '{}'. Use json, csv, markdown, or bincode.",
+                output_format
+            );
             process::exit(1);
         }
     }
+
+    exit_with_policy(&policy, &report.summary);
 }
@@ -0,0 +1,274 @@
+//! Canonical XML comparison
+//!
+//! Compares serialized XML structurally rather than textually: attribute
+//! order, empty-element syntax, character references and CDATA sections
+//! don't affect equality.
+
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::serialization::{escape, markup_end, unescape};
+
+/// Options controlling canonical comparison
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanonicalOptions {
+    /// Ignore whitespace-only text between markup (e.g. pretty-print indentation)
+    pub ignore_whitespace: bool,
+    /// Compare element and attribute names by namespace URI and local name
+    /// rather than by prefix, ignoring namespace declarations
+    pub ignore_prefixes: bool,
+    /// Ignore the XML declaration and DOCTYPE, which aren't part of the
+    /// infoset being compared (on by default)
+    pub ignore_prolog: bool,
+}
+
+impl Default for CanonicalOptions {
+    fn default() -> Self {
+        Self {
+            ignore_whitespace: false,
+            ignore_prefixes: false,
+            ignore_prolog: true,
+        }
+    }
+}
+
+/// A node-level event in canonical form
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    Start { name: String, attributes: Vec<(String, String)> },
+    End(String),
+    Text(String),
+    Comment(String),
+    ProcessingInstruction(String),
+    Doctype(String),
+}
+
+/// Check whether two XML strings are equal in canonical form
+///
+/// Falls back to `false` if either side isn't well-formed enough to tokenize.
+pub fn xml_equivalent(expected: &str, actual: &str, options: &CanonicalOptions) -> bool {
+    match (events(expected, options), events(actual, options)) {
+        (Ok(e), Ok(a)) => e == a,
+        _ => false,
+    }
+}
+
+/// Options for [`xml_equal`], the same switches as canonical comparison
+pub type CompareOptions = CanonicalOptions;
+
+/// Check whether two XML strings are equal in canonical form
+///
+/// Unlike [`xml_equivalent`], input that can't be tokenized is an error
+/// rather than a mismatch.
+pub fn xml_equal(a: &str, b: &str, opts: CompareOptions) -> Result<bool> {
+    Ok(events(a, &opts)? == events(b, &opts)?)
+}
+
+/// Render XML in canonical form, one construct per line, for diffing
+pub fn canonicalize(xml: &str, options: &CanonicalOptions) -> Result<String> {
+    let mut lines = Vec::new();
+    let mut depth = 0usize;
+    for event in events(xml, options)? {
+        if let Event::End(_) = event {
+            depth = depth.saturating_sub(1);
+        }
+        let line = match &event {
+            Event::Start { name, attributes } => {
+                let mut tag = format!("<{}", name);
+                for (attr, value) in attributes {
+                    tag.push_str(&format!(" {}=\"{}\"", attr, escape(value)));
+                }
+                tag.push('>');
+                tag
+            }
+            Event::End(name) => format!("</{}>", name),
+            Event::Text(text) => escape(text),
+            Event::Comment(text) => format!("<!--{}-->", text),
+            Event::ProcessingInstruction(text) => format!("<?{}?>", text),
+            Event::Doctype(text) => format!("<!DOCTYPE {}>", text),
+        };
+        lines.push(format!("{}{}", "  ".repeat(depth), line));
+        if let Event::Start { .. } = event {
+            depth += 1;
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Tokenize XML into canonical events
+fn events(xml: &str, options: &CanonicalOptions) -> Result<Vec<Event>> {
+    let mut events = Vec::new();
+    let mut text = String::new();
+    let mut rest = xml;
+    // In-scope namespace bindings (prefix -> URI), one frame per open element
+    let mut scopes: Vec<HashMap<String, String>> = Vec::new();
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&unescape(&rest[..start])?);
+        rest = &rest[start..];
+        let end = if rest.starts_with("<!DOCTYPE") {
+            doctype_end(rest)?
+        } else {
+            markup_end(rest)?
+        };
+        let markup = &rest[..end];
+        rest = &rest[end..];
+
+        if let Some(cdata) = markup.strip_prefix("<![CDATA[") {
+            text.push_str(cdata.trim_end_matches("]]>"));
+            continue;
+        }
+
+        flush_text(&mut events, &mut text, options);
+        if let Some(comment) = markup.strip_prefix("<!--") {
+            events.push(Event::Comment(comment.trim_end_matches("-->").to_string()));
+        } else if let Some(pi) = markup.strip_prefix("<?") {
+            let pi = pi.trim_end_matches("?>").trim();
+            let is_declaration = pi == "xml" || pi.starts_with("xml ");
+            if !(is_declaration && options.ignore_prolog) {
+                events.push(Event::ProcessingInstruction(pi.to_string()));
+            }
+        } else if let Some(doctype) = markup.strip_prefix("<!DOCTYPE") {
+            if !options.ignore_prolog {
+                let doctype = doctype.trim_end_matches('>').split_whitespace();
+                events.push(Event::Doctype(doctype.collect::<Vec<_>>().join(" ")));
+            }
+        } else if markup.starts_with("<!") {
+            // Other declarations carry no content to compare
+        } else if let Some(name) = markup.strip_prefix("</") {
+            let name = name.trim_end_matches('>').trim();
+            events.push(Event::End(element_name(name, &scopes, options)));
+            scopes.pop();
+        } else {
+            let empty = markup.ends_with("/>");
+            let inner = markup[1..markup.len() - if empty { 2 } else { 1 }].trim();
+            let (name, attributes) = parse_start_tag(inner)?;
+            scopes.push(namespace_declarations(&attributes));
+
+            let name = element_name(&name, &scopes, options);
+            let mut attributes: Vec<(String, String)> = if options.ignore_prefixes {
+                attributes
+                    .into_iter()
+                    .filter(|(attr, _)| attr != "xmlns" && !attr.starts_with("xmlns:"))
+                    .map(|(attr, value)| (expanded_name(&attr, false, &scopes), value))
+                    .collect()
+            } else {
+                attributes
+            };
+            attributes.sort();
+
+            events.push(Event::Start { name: name.clone(), attributes });
+            if empty {
+                events.push(Event::End(name));
+                scopes.pop();
+            }
+        }
+    }
+    text.push_str(&unescape(rest)?);
+    flush_text(&mut events, &mut text, options);
+
+    Ok(events)
+}
+
+/// Find the end (exclusive) of a DOCTYPE declaration, including any
+/// internal subset, at the start of `s`
+///
+/// The subset ends at the first `]` followed (after whitespace) by `>`, so
+/// a `]` inside an entity value doesn't end it early.
+pub(crate) fn doctype_end(s: &str) -> Result<usize> {
+    let unterminated =
+        || Error::SerializationError("unterminated DOCTYPE declaration".to_string());
+    let close = s.find('>').ok_or_else(unterminated)?;
+    let Some(open) = s[..close].find('[') else {
+        return Ok(close + 1);
+    };
+    let mut from = open;
+    loop {
+        let subset_end = from + s[from..].find(']').ok_or_else(unterminated)?;
+        let after = s[subset_end + 1..].trim_start();
+        if after.starts_with('>') {
+            return Ok(s.len() - after.len() + 1);
+        }
+        from = subset_end + 1;
+    }
+}
+
+/// Emit pending character data, dropping it if it is insignificant whitespace
+fn flush_text(events: &mut Vec<Event>, text: &mut String, options: &CanonicalOptions) {
+    if text.is_empty() {
+        return;
+    }
+    if !(options.ignore_whitespace && text.trim().is_empty()) {
+        events.push(Event::Text(text.clone()));
+    }
+    text.clear();
+}
+
+/// Collect the namespace bindings declared by a start tag's attributes
+pub(crate) fn namespace_declarations(attributes: &[(String, String)]) -> HashMap<String, String> {
+    attributes
+        .iter()
+        .filter_map(|(attr, uri)| {
+            let prefix = if attr == "xmlns" { "" } else { attr.strip_prefix("xmlns:")? };
+            Some((prefix.to_string(), uri.clone()))
+        })
+        .collect()
+}
+
+/// Get an element's name as compared under `options`
+fn element_name(name: &str, scopes: &[HashMap<String, String>], options: &CanonicalOptions) -> String {
+    if options.ignore_prefixes {
+        expanded_name(name, true, scopes)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Expand a qualified name to `{uri}local` form
+///
+/// Unprefixed attributes are in no namespace; unprefixed elements take the
+/// default namespace. Unbound prefixes are left as written.
+fn expanded_name(name: &str, is_element: bool, scopes: &[HashMap<String, String>]) -> String {
+    let (prefix, local) = match name.split_once(':') {
+        Some((prefix, local)) => (prefix, local),
+        None if is_element => ("", name),
+        None => return name.to_string(),
+    };
+    if prefix == "xml" {
+        return format!("{{http://www.w3.org/XML/1998/namespace}}{}", local);
+    }
+    match scopes.iter().rev().find_map(|scope| scope.get(prefix)) {
+        Some(uri) if uri.is_empty() => local.to_string(),
+        Some(uri) => format!("{{{}}}{}", uri, local),
+        None if prefix.is_empty() => local.to_string(),
+        None => name.to_string(),
+    }
+}
+
+/// Split a start tag's contents into its name and attributes, in document order
+pub(crate) fn parse_start_tag(inner: &str) -> Result<(String, Vec<(String, String)>)> {
+    let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+    let name = inner[..name_end].to_string();
+    let mut attributes = Vec::new();
+
+    let mut rest = inner[name_end..].trim_start();
+    while !rest.is_empty() {
+        let eq = rest
+            .find('=')
+            .ok_or_else(|| Error::ParseError(format!("malformed attribute in <{}>", name)))?;
+        let attr = rest[..eq].trim().to_string();
+        let after = rest[eq + 1..].trim_start();
+        let quote = after
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| Error::ParseError(format!("unquoted attribute {} in <{}>", attr, name)))?;
+        let value_end = after[1..]
+            .find(quote)
+            .ok_or_else(|| Error::ParseError(format!("unterminated attribute {} in <{}>", attr, name)))?;
+        attributes.push((attr, unescape(&after[1..value_end + 1])?));
+        rest = after[value_end + 2..].trim_start();
+    }
+
+    Ok((name, attributes))
+}
@@ -0,0 +1,95 @@
+//! Collations for string comparison
+//!
+//! Assertions compare strings using the collation the test asks for,
+//! defaulting to the Unicode codepoint collation. Further collations can be
+//! registered by URI; the registry is per thread.
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The Unicode codepoint collation (the XPath default)
+pub const CODEPOINT_COLLATION: &str =
+    "http://www.w3.org/2005/xpath-functions/collation/codepoint";
+
+/// The HTML ASCII case-insensitive collation
+pub const HTML_ASCII_CASE_INSENSITIVE_COLLATION: &str =
+    "http://www.w3.org/2005/xpath-functions/collation/html-ascii-case-insensitive";
+
+/// String comparison under a particular collation
+pub trait Collator {
+    /// Compare two strings
+    fn compare(&self, a: &str, b: &str) -> Ordering;
+
+    /// Check two strings for equality
+    fn equals(&self, a: &str, b: &str) -> bool {
+        self.compare(a, b) == Ordering::Equal
+    }
+}
+
+/// Compares strings codepoint by codepoint
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CodepointCollator;
+
+impl Collator for CodepointCollator {
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        a.cmp(b)
+    }
+}
+
+/// Compares strings codepoint by codepoint, folding ASCII letters to lower case
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AsciiCaseInsensitiveCollator;
+
+impl Collator for AsciiCaseInsensitiveCollator {
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        a.chars()
+            .map(|c| c.to_ascii_lowercase())
+            .cmp(b.chars().map(|c| c.to_ascii_lowercase()))
+    }
+}
+
+/// A string comparison function registered as a collation
+pub type CollationFn = Rc<dyn Fn(&str, &str) -> Ordering>;
+
+/// Compares strings with a registered comparison function
+#[derive(Clone)]
+pub struct FnCollator(CollationFn);
+
+impl Collator for FnCollator {
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        (self.0)(a, b)
+    }
+}
+
+thread_local! {
+    /// Collations by URI, with the built-in collations pre-registered
+    static COLLATIONS: RefCell<HashMap<String, CollationFn>> = RefCell::new(HashMap::from([
+        (
+            CODEPOINT_COLLATION.to_string(),
+            Rc::new(|a: &str, b: &str| CodepointCollator.compare(a, b)) as CollationFn,
+        ),
+        (
+            HTML_ASCII_CASE_INSENSITIVE_COLLATION.to_string(),
+            Rc::new(|a: &str, b: &str| AsciiCaseInsensitiveCollator.compare(a, b)) as CollationFn,
+        ),
+    ]));
+}
+
+/// Register a collation under `uri`, replacing any previous registration
+pub fn register_collation(uri: &str, compare: Box<dyn Fn(&str, &str) -> Ordering>) {
+    COLLATIONS.with(|collations| {
+        collations.borrow_mut().insert(uri.to_string(), Rc::from(compare));
+    });
+}
+
+/// Look up a registered collator by collation URI
+pub fn collator_for_uri(uri: &str) -> Option<Box<dyn Collator>> {
+    COLLATIONS.with(|collations| {
+        collations
+            .borrow()
+            .get(uri)
+            .map(|compare| Box::new(FnCollator(compare.clone())) as Box<dyn Collator>)
+    })
+}
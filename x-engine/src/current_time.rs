@@ -0,0 +1,39 @@
+//! The current dateTime and implicit timezone of an evaluation
+//!
+//! `current-dateTime()`, `current-date()` and `current-time()` must return
+//! the same instant throughout one evaluation. xee takes that instant from
+//! its dynamic context: either a pinned dateTime or the time the evaluation
+//! started, in the implicit timezone if one is set. xrust and xust read the
+//! clock and local timezone themselves.
+
+use chrono::{DateTime, FixedOffset, Local, Offset, Utc};
+
+/// The instant an evaluation starting now should see
+///
+/// The pinned dateTime if one is set, otherwise the local clock, expressed
+/// in the implicit `timezone` if one is set.
+pub(crate) fn evaluation_instant(
+    pinned: Option<DateTime<FixedOffset>>,
+    timezone: Option<FixedOffset>,
+) -> DateTime<FixedOffset> {
+    let now = pinned.unwrap_or_else(|| Local::now().into());
+    match timezone {
+        Some(timezone) => now.with_timezone(&timezone),
+        None => now,
+    }
+}
+
+/// Check whether `expr` may call `current-dateTime`, `current-date` or
+/// `current-time`
+///
+/// A lexical check, so a mention in a string literal or comment counts too.
+pub(crate) fn reads_clock(expr: &str) -> bool {
+    expr.contains("current-date") || expr.contains("current-time")
+}
+
+/// Convert an offset in minutes east of UTC to a timezone, clamped to the
+/// `-14:00` to `+14:00` range allowed for XSD timezones
+pub(crate) fn timezone_from_minutes(offset_minutes: i32) -> FixedOffset {
+    let offset_minutes = offset_minutes.clamp(-14 * 60, 14 * 60);
+    FixedOffset::east_opt(offset_minutes * 60).unwrap_or_else(|| Utc.fix())
+}
@@ -0,0 +1,91 @@
+//! Parse diagnostics
+//!
+//! The backends silently accept (or reject) some recoverable oddities in
+//! well-formed documents, which makes the same input behave differently
+//! across engines. None of them exposes its warnings, so these are
+//! detected by a lightweight scan of the source text.
+
+use std::collections::HashMap;
+
+use crate::canonical::parse_start_tag;
+use crate::serialization::markup_end;
+
+/// Collect non-fatal warnings about an XML document
+pub fn scan(xml: &str) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if xml.starts_with('\u{feff}') {
+        warnings.push("Document starts with a byte order mark".to_string());
+    }
+
+    // In-scope namespace bindings, one frame per open element
+    let mut scopes: Vec<HashMap<String, String>> = vec![HashMap::new()];
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let Ok(end) = markup_end(rest) else {
+            break;
+        };
+        let markup = &rest[..end];
+        rest = &rest[end..];
+
+        if let Some(decl) = markup.strip_prefix("<?xml ") {
+            if let Some(encoding) = declared_encoding(decl) {
+                if !encoding.eq_ignore_ascii_case("utf-8") {
+                    warnings.push(format!(
+                        "Encoding declaration '{}' ignored; input is already decoded text",
+                        encoding
+                    ));
+                }
+            }
+        } else if markup.starts_with("<!DOCTYPE") {
+            warnings.push("DOCTYPE declaration present; backends differ in DTD processing".to_string());
+        } else if markup.starts_with("</") {
+            if scopes.len() > 1 {
+                scopes.pop();
+            }
+        } else if !markup.starts_with("<!") && !markup.starts_with("<?") {
+            let empty = markup.ends_with("/>");
+            let inner = markup[1..markup.len() - if empty { 2 } else { 1 }].trim();
+            let Ok((name, attributes)) = parse_start_tag(inner) else {
+                continue;
+            };
+
+            let mut frame = HashMap::new();
+            for (attr, uri) in attributes {
+                let prefix = match attr.as_str() {
+                    "xmlns" => "",
+                    other => match other.strip_prefix("xmlns:") {
+                        Some(prefix) => prefix,
+                        None => continue,
+                    },
+                };
+                let in_scope = scopes.iter().rev().find_map(|scope| scope.get(prefix));
+                if in_scope == Some(&uri) {
+                    let binding = if prefix.is_empty() {
+                        "default namespace".to_string()
+                    } else {
+                        format!("prefix '{}'", prefix)
+                    };
+                    warnings.push(format!(
+                        "Redundant declaration of {} on <{}>: already bound to '{}'",
+                        binding, name, uri
+                    ));
+                }
+                frame.insert(prefix.to_string(), uri);
+            }
+            if !empty {
+                scopes.push(frame);
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Extract the `encoding` pseudo-attribute from an XML declaration
+fn declared_encoding(decl: &str) -> Option<&str> {
+    let after = decl.split("encoding").nth(1)?.trim_start().strip_prefix('=')?.trim_start();
+    let quote = after.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &after[1..];
+    value.find(quote).map(|end| &value[..end])
+}
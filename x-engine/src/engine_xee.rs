@@ -9,18 +9,96 @@
 //! - XQuery
 //! - XSD validation
 
+use std::cell::OnceCell;
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
+use chrono::{DateTime, FixedOffset};
+use iri_string::types::{IriAbsoluteStr, IriReferenceStr, IriString};
+use xee_interpreter::interpreter::Program;
+
+use crate::current_time::evaluation_instant;
 use crate::error::{Error, Result};
-use crate::result::{NodeInfo, NodeType, ResultItem, ValidationResult};
+use crate::parse_limits::ParseLimits;
+use crate::query_cache::{next_document_id, next_engine_id, QueryCache};
+use crate::result::{
+    LocateNode, NodeId, NodeInfo, NodeLocation, NodeType, ResultItem, ValidationResult,
+};
 use crate::traits::{
-    QueryResult, XPathEngine, XPathVersion, XQueryEngine, XQueryVersion, XmlDocument, XmlParser,
-    XsdValidator, XsdVersion, XsltEngine, XsltVersion,
+    DecimalFormat, QueryResult, TextResolver, TraceHook, XPathEngine, XPathVersion, XQueryEngine,
+    XQueryVersion, XmlDocument, XmlParser, XsdValidator, XsdVersion, XsltEngine, XsltVersion,
 };
 
+/// Default function namespace of compiled stylesheets
+const FN_NAMESPACE: &str = "http://www.w3.org/2005/xpath-functions";
+
+/// The Xot arena holding an engine's trees, shared with its documents
+///
+/// Results can be sent to other threads (see
+/// [`SharedEngine`](crate::SharedEngine)), so this is an `Arc<Mutex<_>>`
+/// rather than an `Rc`.
+#[derive(Clone, Default)]
+struct SharedXot(Arc<Mutex<xot::Xot>>);
+
+impl SharedXot {
+    fn lock(&self) -> MutexGuard<'_, xot::Xot> {
+        // A panic while the arena was locked leaves at worst an orphaned tree
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Fail unless `doc` lives in this arena
+    fn check_owns(&self, doc: &XeeDocument) -> Result<()> {
+        if Arc::ptr_eq(&self.0, &doc.xot.0) {
+            Ok(())
+        } else {
+            Err(Error::EngineError("Document was parsed by a different engine".to_string()))
+        }
+    }
+}
+
 /// xee engine wrapper
 pub struct XeeEngine {
-    xot: xot::Xot,
+    id: u64,
+    xot: SharedXot,
+    static_base_uri: Option<String>,
+    default_element_namespace: Option<String>,
+    decimal_formats: Vec<DecimalFormat>,
+    trace: Option<TraceHook>,
+    query_cache: Option<QueryCache<XeeQueryResult>>,
+    text_resolver: Option<TextResolver>,
+    current_datetime: Option<DateTime<FixedOffset>>,
+    implicit_timezone: Option<FixedOffset>,
+    parse_limits: Option<ParseLimits>,
+    /// Stylesheets compiled by [`compile_stylesheet`](Self::compile_stylesheet),
+    /// `None` once dropped
+    stylesheets: Vec<Option<Program>>,
+    stylesheet_compilations: usize,
+    last_result: Option<LastResult>,
+}
+
+static NEXT_RESULT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// The sequence behind the engine's latest XPath result, kept so it can be
+/// bound to `$result` (see [`XeeEngine::evaluate_xpath_with_result`])
+struct LastResult {
+    id: u64,
+    sequence: xee_interpreter::sequence::Sequence,
+    /// Root of each tree seen during the evaluation, with its document id
+    documents: Vec<(xot::Node, u64)>,
+}
+
+/// Values bound for an evaluation besides the context document
+#[derive(Default)]
+struct Bindings<'a> {
+    /// External variables, by name
+    variables: Vec<(String, xee_interpreter::sequence::Sequence)>,
+    /// Documents available to `fn:doc`, by URI
+    registered: &'a [(String, &'a XeeDocument)],
+    /// Root of each tree the variables' nodes belong to, with its document id
+    documents: Vec<(xot::Node, u64)>,
+    /// Context item in place of the document node
+    context_item: Option<xee_interpreter::sequence::Item>,
 }
 
 impl Default for XeeEngine {
@@ -31,30 +109,593 @@ impl Default for XeeEngine {
 
 impl XeeEngine {
     pub fn new() -> Self {
-        Self { xot: xot::Xot::new() }
+        Self {
+            id: next_engine_id(),
+            xot: SharedXot::default(),
+            static_base_uri: None,
+            default_element_namespace: None,
+            decimal_formats: Vec::new(),
+            trace: None,
+            query_cache: None,
+            text_resolver: None,
+            current_datetime: None,
+            implicit_timezone: None,
+            parse_limits: None,
+            stylesheets: Vec::new(),
+            stylesheet_compilations: 0,
+            last_result: None,
+        }
+    }
+
+    /// Set the static base URI used by subsequent evaluations
+    pub fn set_static_base_uri(&mut self, uri: Option<&str>) {
+        self.static_base_uri = uri.map(String::from);
+    }
+
+    /// Get the static base URI, if set
+    pub fn static_base_uri(&self) -> Option<&str> {
+        self.static_base_uri.as_deref()
+    }
+
+    /// Set the namespace of unprefixed element names in subsequent queries
+    pub fn set_default_element_namespace(&mut self, uri: Option<&str>) {
+        self.default_element_namespace = uri.map(String::from);
+    }
+
+    /// Parse `xml` and serialize it with xot's indenting serializer
+    ///
+    /// xot indents by two spaces per level and leaves mixed content as-is.
+    pub fn pretty_print(&mut self, xml: &str) -> Result<String> {
+        let mut xot = self.xot.lock();
+        let root = xot.parse(xml).map_err(|e| Error::ParseError(e.to_string()))?;
+        let parameters = xot::output::xml::Parameters {
+            indentation: Some(Default::default()),
+            ..Default::default()
+        };
+        xot.serialize_xml_string(parameters, root)
+            .map_err(|e| Error::SerializationError(e.to_string()))
+    }
+
+    /// Set the decimal formats available to `fn:format-number`
+    pub fn set_decimal_formats(&mut self, formats: Vec<DecimalFormat>) {
+        self.decimal_formats = formats;
+    }
+
+    /// Install or remove the evaluation trace hook
+    pub fn set_trace(&mut self, hook: Option<TraceHook>) {
+        self.trace = hook;
+    }
+
+    /// Get the evaluation trace hook, if installed
+    pub fn trace_hook(&mut self) -> Option<&mut TraceHook> {
+        self.trace.as_mut()
+    }
+
+    /// Install or remove the query result cache
+    pub fn set_query_cache(&mut self, cache: Option<QueryCache<XeeQueryResult>>) {
+        self.query_cache = cache;
+    }
+
+    /// Get the query result cache, if enabled
+    pub fn query_cache(&mut self) -> Option<&mut QueryCache<XeeQueryResult>> {
+        self.query_cache.as_mut()
+    }
+
+    /// Install or remove (restoring filesystem loading) the text resolver
+    pub fn set_text_resolver(&mut self, resolver: Option<TextResolver>) {
+        self.text_resolver = resolver;
+    }
+
+    /// Get the text resolver, if one is installed
+    pub fn text_resolver(&self) -> Option<&TextResolver> {
+        self.text_resolver.as_ref()
+    }
+
+    /// Pin (or unpin, going back to the clock) the current dateTime
+    pub fn set_current_datetime(&mut self, datetime: Option<DateTime<FixedOffset>>) {
+        self.current_datetime = datetime;
+    }
+
+    /// Get the pinned current dateTime, if set
+    pub fn current_datetime(&self) -> Option<DateTime<FixedOffset>> {
+        self.current_datetime
+    }
+
+    /// Set (or unset, going back to the local timezone) the implicit timezone
+    pub fn set_implicit_timezone(&mut self, timezone: Option<FixedOffset>) {
+        self.implicit_timezone = timezone;
+    }
+
+    /// Get the implicit timezone, if set
+    pub fn implicit_timezone(&self) -> Option<FixedOffset> {
+        self.implicit_timezone
+    }
+
+    /// Set (or remove) the limits checked before parsing
+    pub fn set_parse_limits(&mut self, limits: Option<ParseLimits>) {
+        self.parse_limits = limits;
+    }
+
+    /// Get the parse limits, if set
+    pub fn parse_limits(&self) -> Option<ParseLimits> {
+        self.parse_limits
+    }
+
+    /// Replace the Xot arena, e.g. after a panic left it inconsistent
+    ///
+    /// The arena also collects trees built during evaluation (by
+    /// `parse-xml`, say), which are only freed here. Configuration is kept;
+    /// documents parsed before are no longer valid with this engine.
+    pub fn reset(&mut self) {
+        self.xot = SharedXot::default();
+        self.last_result = None;
+        if let Some(cache) = &mut self.query_cache {
+            cache.clear();
+        }
+    }
+
+    /// Create a document node with no children, for evaluating without a
+    /// context document
+    pub fn empty_document(&self) -> XeeDocument {
+        let root = self.xot.lock().new_document();
+        XeeDocument::new(&self.xot, root)
+    }
+
+    /// Process-unique identifier of this engine instance
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Compile a stylesheet for later transforms, returning its index
+    pub fn compile_stylesheet(&mut self, stylesheet: &str) -> Result<usize> {
+        let program = self.compile(stylesheet)?;
+        self.stylesheets.push(Some(program));
+        Ok(self.stylesheets.len() - 1)
+    }
+
+    /// Free a stylesheet from [`compile_stylesheet`](Self::compile_stylesheet)
+    ///
+    /// Returns whether it was still held. Indices aren't reused.
+    pub fn drop_stylesheet(&mut self, stylesheet: usize) -> bool {
+        self.stylesheets.get_mut(stylesheet).and_then(Option::take).is_some()
+    }
+
+    /// Transform with a stylesheet from [`compile_stylesheet`](Self::compile_stylesheet)
+    pub fn transform_compiled_to_string(
+        &mut self,
+        doc: &XeeDocument,
+        stylesheet: usize,
+    ) -> Result<String> {
+        let program = self
+            .stylesheets
+            .get(stylesheet)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| Error::XsltError("Unknown compiled stylesheet".to_string()))?;
+        let sequence = self.run_stylesheet(doc, program)?;
+        Ok(self.serialize_nodes(&sequence))
+    }
+
+    /// Number of stylesheet compilations so far
+    pub fn stylesheet_compilations(&self) -> usize {
+        self.stylesheet_compilations
+    }
+
+    /// Compile a stylesheet into a program
+    fn compile(&mut self, stylesheet: &str) -> Result<Program> {
+        self.stylesheet_compilations += 1;
+        let namespaces = xee_interpreter::Namespaces::new(
+            xee_interpreter::Namespaces::default_namespaces(),
+            String::new(),
+            FN_NAMESPACE.to_string(),
+        );
+        let context = xee_interpreter::context::StaticContext::from_namespaces(namespaces);
+        xee_xslt_compiler::parse(context, stylesheet)
+            .map_err(|e| Error::XsltError(format!("{:?}", e)))
+    }
+
+    /// Run a compiled stylesheet over `doc`, in the engine's own Xot
+    fn run_stylesheet(&self, doc: &XeeDocument, program: &Program) -> Result<xee_xpath::Sequence> {
+        self.xot.check_owns(doc)?;
+        let mut documents = xee_interpreter::xml::Documents::new();
+        documents.add_root(None, doc.root).map_err(|e| Error::EngineError(e.to_string()))?;
+        let mut builder = program.dynamic_context_builder();
+        builder.context_node(doc.root);
+        builder.documents(documents);
+        builder.current_datetime(evaluation_instant(self.current_datetime, self.implicit_timezone));
+        let context = builder.build();
+        let sequence = program.runnable(&context).many(&mut self.xot.lock());
+        sequence.map_err(|e| Error::XsltError(format!("{:?}", e)))
+    }
+
+    /// Compile and run a stylesheet over `doc`
+    fn evaluate_stylesheet(
+        &mut self,
+        doc: &XeeDocument,
+        stylesheet: &str,
+    ) -> Result<xee_xpath::Sequence> {
+        let program = self.compile(stylesheet)?;
+        self.run_stylesheet(doc, &program)
+    }
+
+    /// Serialize the nodes of a transform's result, skipping other items
+    fn serialize_nodes(&self, sequence: &xee_xpath::Sequence) -> String {
+        let mut result = String::new();
+        let xot = self.xot.lock();
+        for item in sequence.iter() {
+            if let Ok(node) = item.to_node() {
+                if let Ok(s) = xot.to_string(node) {
+                    result.push_str(&s);
+                }
+            }
+        }
+        result
+    }
+
+    /// Evaluate an XPath expression with documents bound to variables
+    ///
+    /// Each `(name, document)` pair is declared as the external variable
+    /// `$name`, whose value is the document node.
+    pub fn evaluate_xpath_with_variables(
+        &mut self,
+        doc: &XeeDocument,
+        variables: &[(String, &XeeDocument)],
+        xpath: &str,
+    ) -> Result<XeeQueryResult> {
+        for (_, variable) in variables {
+            self.xot.check_owns(variable)?;
+        }
+        let bindings = Bindings {
+            variables: variables
+                .iter()
+                .map(|(name, variable)| (name.clone(), variable.root.into()))
+                .collect(),
+            documents: variables.iter().map(|(_, variable)| (variable.root, variable.id)).collect(),
+            ..Bindings::default()
+        };
+        self.run_xpath(doc, bindings, xpath)
+    }
+
+    /// Evaluate an XPath expression with documents available to `fn:doc`
+    ///
+    /// Each `(uri, document)` pair is registered under its URI, resolved
+    /// against the static base URI if relative, so `doc(uri)` returns the
+    /// document node.
+    pub fn evaluate_xpath_with_documents(
+        &mut self,
+        doc: &XeeDocument,
+        documents: &[(String, &XeeDocument)],
+        xpath: &str,
+    ) -> Result<XeeQueryResult> {
+        for (_, registered) in documents {
+            self.xot.check_owns(registered)?;
+        }
+        let bindings = Bindings { registered: documents, ..Bindings::default() };
+        self.run_xpath(doc, bindings, xpath)
+    }
+
+    /// Evaluate an XPath expression with `$result` bound to an earlier result
+    ///
+    /// The variable holds the items the earlier evaluation produced, with
+    /// their types and node identities, so nothing is evaluated again. Only
+    /// the engine's latest result is kept; older ones fail with an error.
+    pub fn evaluate_xpath_with_result(
+        &mut self,
+        doc: &XeeDocument,
+        result: &XeeQueryResult,
+        xpath: &str,
+    ) -> Result<XeeQueryResult> {
+        let last = self.last_result.as_ref().filter(|last| last.id == result.id);
+        let last = last.ok_or_else(|| {
+            Error::EngineError("Result is no longer held by the engine".to_string())
+        })?;
+        let bindings = Bindings {
+            variables: vec![("result".to_string(), last.sequence.clone())],
+            documents: last.documents.clone(),
+            ..Bindings::default()
+        };
+        self.run_xpath(doc, bindings, xpath)
+    }
+
+    /// Evaluate an XPath expression with an atomic value as the context item
+    ///
+    /// `value` is an expression giving the value, such as a literal; it is
+    /// evaluated first without a context. Path expressions then raise
+    /// `XPDY0050` since the context item isn't a node.
+    pub fn evaluate_xpath_with_context_item(
+        &mut self,
+        value: &str,
+        xpath: &str,
+    ) -> Result<XeeQueryResult> {
+        let doc = self.empty_document();
+        self.run_xpath(&doc, Bindings::default(), value)?;
+        let item = self
+            .last_result
+            .as_ref()
+            .and_then(|last| last.sequence.iter().next())
+            .filter(|item| matches!(item, xee_xpath::Item::Atomic(_)))
+            .ok_or_else(|| {
+                Error::EngineError(format!("'{}' doesn't give an atomic context item", value))
+            })?;
+        let bindings = Bindings { context_item: Some(item), ..Bindings::default() };
+        self.run_xpath(&doc, bindings, xpath)
+    }
+
+    /// Check whether `result` is the engine's latest, which
+    /// [`evaluate_xpath_with_result`](Self::evaluate_xpath_with_result) can bind
+    pub fn holds_result(&self, result: &XeeQueryResult) -> bool {
+        self.last_result.as_ref().is_some_and(|last| last.id == result.id)
+    }
+
+    /// Evaluate `xpath` against `doc` with `bindings`
+    fn run_xpath(
+        &mut self,
+        doc: &XeeDocument,
+        bindings: Bindings<'_>,
+        xpath: &str,
+    ) -> Result<XeeQueryResult> {
+        use xee_xpath::Query;
+
+        self.xot.check_owns(doc)?;
+        let names = bindings.variables.iter().map(|(name, _)| variable_name(name));
+        let queries = self.queries(names);
+        let query = queries
+            .sequence(xpath)
+            .map_err(xee_error)?;
+
+        // Run the program on the engine's own Xot, so the document isn't
+        // copied and result nodes stay in a tree later calls can read
+        let program = query.program();
+        let mut documents = xee_interpreter::xml::Documents::new();
+        for (uri, registered) in bindings.registered {
+            let uri = self.document_uri(uri)?;
+            documents
+                .add_root(Some(&*uri), registered.root)
+                .map_err(|e| Error::EngineError(e.to_string()))?;
+        }
+        let mut roots = vec![doc.root];
+        for (root, _) in &bindings.documents {
+            if !roots.contains(root) {
+                roots.push(*root);
+            }
+        }
+        for root in roots {
+            if bindings.registered.iter().all(|(_, registered)| registered.root != root) {
+                documents
+                    .add_root(None, root)
+                    .map_err(|e| Error::EngineError(e.to_string()))?;
+            }
+        }
+        let mut variables = xee_interpreter::context::Variables::default();
+        for (name, value) in bindings.variables {
+            variables.insert(variable_name(&name), value);
+        }
+        let mut builder = program.dynamic_context_builder();
+        match bindings.context_item {
+            Some(item) => builder.context_item(item),
+            None => builder.context_node(doc.root),
+        };
+        builder.documents(documents);
+        builder.variables(variables);
+        // The dynamic context's current dateTime also sets its implicit timezone
+        let now = evaluation_instant(self.current_datetime, self.implicit_timezone);
+        builder.current_datetime(now);
+        let context = builder.build();
+        let mut xot = self.xot.lock();
+        let sequence = program.runnable(&context).many(&mut xot).map_err(xee_error)?;
+
+        // Convert sequence to our result types
+        let mut items = Vec::new();
+        let mut documents = vec![(doc.root, doc.id)];
+        documents.extend(bindings.registered.iter().map(|(_, other)| (other.root, other.id)));
+        documents.extend(bindings.documents);
+
+        for item in sequence.iter() {
+            // The canonical lexical form, for atomics whose Rust types don't
+            // display as XSD values
+            let lexical = match &item {
+                xee_xpath::Item::Atomic(_) => item.string_value(&xot).ok(),
+                _ => None,
+            };
+            match item {
+                xee_xpath::Item::Atomic(atomic) => {
+                    let result_item = convert_atomic_to_result_item(&atomic, lexical);
+                    items.push(result_item);
+                }
+                xee_xpath::Item::Node(node) => {
+                    let info = node_info(&self.xot, &xot, node, &mut documents);
+                    items.push(ResultItem::Node(info));
+                }
+                xee_xpath::Item::Function(_) => {
+                    items.push(ResultItem::String("<function>".to_string()));
+                }
+            }
+        }
+        drop(xot);
+
+        let id = NEXT_RESULT_ID.fetch_add(1, Ordering::Relaxed);
+        self.last_result = Some(LastResult { id, sequence, documents });
+        Ok(XeeQueryResult {
+            id,
+            items,
+            string_repr: OnceCell::new(),
+        })
+    }
+
+    /// Absolute form of a document URI, resolving a relative one against the
+    /// static base URI as `fn:doc` does
+    fn document_uri(&self, uri: &str) -> Result<IriString> {
+        let reference = IriReferenceStr::new(uri)
+            .map_err(|e| Error::EngineError(format!("Invalid document URI '{}': {}", uri, e)))?;
+        match reference.to_iri() {
+            Ok(iri) => Ok(iri.into()),
+            Err(relative) => {
+                let base = self
+                    .static_base_uri
+                    .as_deref()
+                    .and_then(|base| IriAbsoluteStr::new(base).ok())
+                    .ok_or_else(|| {
+                        Error::EngineError(format!(
+                            "Relative document URI '{}' needs a static base URI",
+                            uri
+                        ))
+                    })?;
+                Ok(relative.resolve_against(base).into())
+            }
+        }
+    }
+
+    /// Build a query compiler configured with this engine's static context,
+    /// declaring the external variables `variable_names`
+    fn queries(
+        &self,
+        variable_names: impl IntoIterator<Item = xot::xmlname::OwnedName>,
+    ) -> xee_xpath::Queries<'_> {
+        let mut builder = xee_xpath::context::StaticContextBuilder::default();
+        builder.variable_names(variable_names);
+        if let Some(uri) = &self.static_base_uri {
+            builder.static_base_uri(Some(uri));
+        }
+        if let Some(uri) = &self.default_element_namespace {
+            builder.default_element_namespace(uri);
+        }
+        for format in &self.decimal_formats {
+            builder.decimal_format(format.name.as_deref(), xee_decimal_format(format));
+        }
+        xee_xpath::Queries::new(builder)
     }
 }
 
+/// Name of the external variable `$name`, which has no namespace
+fn variable_name(name: &str) -> xot::xmlname::OwnedName {
+    xot::xmlname::OwnedName::new(name.to_string(), String::new(), String::new())
+}
+
+/// Convert an xee error, keeping its error code
+///
+/// The message is the code followed by xee's description of it.
+fn xee_error(e: xee_xpath::error::Error) -> Error {
+    let code = e.error.code();
+    let message = match e.error.message() {
+        "" => code.clone(),
+        description => format!("{}: {}", code, description),
+    };
+    Error::XPathError { code: Some(code), message }
+}
+
+/// Convert a decimal format to xee's, keeping defaults for unset properties
+fn xee_decimal_format(format: &DecimalFormat) -> xee_xpath::context::DecimalFormat {
+    let mut xee_format = xee_xpath::context::DecimalFormat::default();
+    let chars = [
+        (format.decimal_separator, &mut xee_format.decimal_separator),
+        (format.grouping_separator, &mut xee_format.grouping_separator),
+        (format.exponent_separator, &mut xee_format.exponent_separator),
+        (format.zero_digit, &mut xee_format.zero_digit),
+        (format.digit, &mut xee_format.digit),
+        (format.minus_sign, &mut xee_format.minus_sign),
+        (format.percent, &mut xee_format.percent),
+        (format.per_mille, &mut xee_format.per_mille),
+        (format.pattern_separator, &mut xee_format.pattern_separator),
+    ];
+    for (value, slot) in chars {
+        if let Some(c) = value {
+            *slot = c;
+        }
+    }
+    if let Some(infinity) = &format.infinity {
+        xee_format.infinity = infinity.clone();
+    }
+    if let Some(nan) = &format.nan {
+        xee_format.nan = nan.clone();
+    }
+    xee_format
+}
+
 /// Document handle for xee (wraps xot::Node)
 pub struct XeeDocument {
+    id: u64,
     root: xot::Node,
+    xot: SharedXot,
+    /// Serialized form, built on first use
+    xml: OnceCell<String>,
+}
+
+impl XeeDocument {
+    fn new(xot: &SharedXot, root: xot::Node) -> Self {
+        Self { id: next_document_id(), root, xot: xot.clone(), xml: OnceCell::new() }
+    }
+
+    /// Process-unique identifier assigned when the document was created
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Describe the document element
+    ///
+    /// A transform may produce a bare element rather than a document node;
+    /// then that element is described.
+    pub fn document_element(&self) -> Result<NodeInfo> {
+        let xot = self.xot.lock();
+        let element = if xot.is_element(self.root) {
+            self.root
+        } else {
+            xot.document_element(self.root).map_err(|e| Error::EngineError(e.to_string()))?
+        };
+        let mut documents = vec![(self.root, self.id)];
+        Ok(node_info(&self.xot, &xot, element, &mut documents))
+    }
 }
 
 impl XmlDocument for XeeDocument {
     fn to_string(&self) -> Result<String> {
-        // We need access to Xot to serialize, but we only have the node
-        // This is a limitation - we'd need to store xot reference
-        // For now, return an error indicating this limitation
-        Err(Error::EngineError(
-            "XeeDocument::to_string requires Xot context".to_string(),
-        ))
+        if let Some(xml) = self.xml.get() {
+            return Ok(xml.clone());
+        }
+        let xml = self
+            .xot
+            .lock()
+            .to_string(self.root)
+            .map_err(|e| Error::EngineError(e.to_string()))?;
+        Ok(self.xml.get_or_init(|| xml).clone())
     }
 }
 
 /// Query result for xee
+#[derive(Clone)]
 pub struct XeeQueryResult {
+    /// Identifies the evaluation that produced the result
+    id: u64,
     items: Vec<ResultItem>,
-    string_repr: String,
+    /// Newline-joined string form, built on first use
+    string_repr: OnceCell<String>,
+}
+
+impl XeeQueryResult {
+    /// Check whether the joined string form has been built yet
+    pub fn has_string_repr(&self) -> bool {
+        self.string_repr.get().is_some()
+    }
+
+    /// Serialize the items, writing nodes again from their tree with the
+    /// namespace prefixes in scope there
+    pub fn serialize_preserving_prefixes(&self) -> Result<String> {
+        let items = self
+            .items
+            .iter()
+            .map(|item| match item {
+                ResultItem::Node(info) => {
+                    info.serialize_from_tree().unwrap_or_else(|| Ok(item.as_string()))
+                }
+                _ => Ok(item.as_string()),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(items.join("\n"))
+    }
+
+    fn string_repr(&self) -> &str {
+        self.string_repr.get_or_init(|| {
+            self.items.iter().map(ResultItem::as_string).collect::<Vec<_>>().join("\n")
+        })
+    }
 }
 
 impl QueryResult for XeeQueryResult {
@@ -67,28 +708,39 @@ impl QueryResult for XeeQueryResult {
     }
 
     fn to_string(&self) -> String {
-        self.string_repr.clone()
+        self.string_repr().to_string()
     }
 
     fn to_xml(&self) -> Result<String> {
         // Return string representation for non-node results
-        Ok(self.string_repr.clone())
+        Ok(self.string_repr().to_string())
     }
 
     fn items(&self) -> Vec<ResultItem> {
         self.items.clone()
     }
+
+    fn first(&self) -> Option<ResultItem> {
+        self.items.first().cloned()
+    }
 }
 
 impl XmlParser for XeeEngine {
     type Document = XeeDocument;
 
     fn parse(&mut self, xml: &str) -> Result<Self::Document> {
+        if let Some(limits) = &self.parse_limits {
+            limits.check(xml)?;
+        }
         let root = self
             .xot
+            .lock()
             .parse(xml)
             .map_err(|e| Error::ParseError(e.to_string()))?;
-        Ok(XeeDocument { root })
+        if let Some(cache) = &mut self.query_cache {
+            cache.clear();
+        }
+        Ok(XeeDocument::new(&self.xot, root))
     }
 }
 
@@ -100,83 +752,175 @@ impl XPathEngine for XeeEngine {
         doc: &Self::Document,
         xpath: &str,
     ) -> Result<Self::QueryResult> {
-        use xee_xpath::{Documents, Queries, Query};
+        self.evaluate_xpath_with_variables(doc, &[], xpath)
+    }
 
-        let mut documents = Documents::new();
+    fn xpath_version(&self) -> XPathVersion {
+        XPathVersion::V3_1
+    }
+}
 
-        // Add the document from our Xot instance
-        // We need to serialize and re-parse because Documents manages its own Xot
-        let xml_str = self
-            .xot
-            .to_string(doc.root)
-            .map_err(|e| Error::EngineError(e.to_string()))?;
+/// Describe a node of the engine's Xot, locked as `xot`
+///
+/// `documents` holds the root of each tree seen with its document id; other
+/// trees, built during evaluation, are given fresh ids.
+fn node_info(
+    shared: &SharedXot,
+    xot: &xot::Xot,
+    node: xot::Node,
+    documents: &mut Vec<(xot::Node, u64)>,
+) -> NodeInfo {
+    let node_type = match xot.value_type(node) {
+        xot::ValueType::Document => NodeType::Document,
+        xot::ValueType::Element => NodeType::Element,
+        xot::ValueType::Text => NodeType::Text,
+        xot::ValueType::Comment => NodeType::Comment,
+        xot::ValueType::ProcessingInstruction => NodeType::ProcessingInstruction,
+        xot::ValueType::Attribute => NodeType::Attribute,
+        xot::ValueType::Namespace => NodeType::Namespace,
+    };
+    let name_id = xot.node_name(node);
+    let name = name_id.map(|n| xot.local_name_str(n).to_string());
+    let namespace = name_id.map(|n| xot.namespace_for_name(n));
+    let namespace_uri = namespace
+        .map(|ns| xot.namespace_str(ns).to_string())
+        .filter(|uri| !uri.is_empty());
+    let prefix = namespace
+        .and_then(|ns| xot.prefix_for_namespace(node, ns))
+        .map(|p| xot.prefix_str(p).to_string())
+        .filter(|p| !p.is_empty());
+    let value = xot.to_string(node).ok();
+    let mut root = node;
+    while let Some(parent) = xot.parent(root) {
+        root = parent;
+    }
+    let document = match documents.iter().find(|(known, _)| *known == root) {
+        Some((_, id)) => *id,
+        None => {
+            let id = next_document_id();
+            documents.push((root, id));
+            id
+        }
+    };
+    let xee_node = XeeNode { xot: shared.clone(), node, document };
+    let location = NodeLocation::Tree(Arc::new(xee_node));
+    let attributes = if node_type == NodeType::Element {
+        xot.attributes(node)
+            .iter()
+            .map(|(name, value)| (xot.local_name_str(name).to_string(), value.clone()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    NodeInfo {
+        node_type,
+        name,
+        value,
+        attributes,
+        namespace_uri,
+        prefix,
+        location: Some(location),
+    }
+}
 
-        let doc_handle = documents
-            .add_string_without_uri(&xml_str)
-            .map_err(|e| Error::XPathError(format!("{:?}", e)))?;
+/// A node of an engine's Xot, read when its location is asked for
+struct XeeNode {
+    xot: SharedXot,
+    node: xot::Node,
+    document: u64,
+}
 
-        let queries = Queries::default();
-        let query = queries
-            .sequence(xpath)
-            .map_err(|e| Error::XPathError(format!("{:?}", e)))?;
+impl LocateNode for XeeNode {
+    fn path(&self) -> String {
+        node_path(&self.xot.lock(), self.node)
+    }
 
-        let sequence = query
-            .execute(&mut documents, doc_handle)
-            .map_err(|e| Error::XPathError(format!("{:?}", e)))?;
+    fn identity(&self) -> NodeId {
+        node_identity(&self.xot.lock(), self.node, self.document)
+    }
 
-        // Convert sequence to our result types
-        let mut items = Vec::new();
-        let mut string_parts = Vec::new();
+    /// xot names carry a namespace rather than a prefix; its serializer
+    /// declares every prefix in scope at the node, so `p:` bound on an
+    /// ancestor outside the result is written as `p:` again
+    fn serialize(&self) -> Option<Result<String>> {
+        let xot = self.xot.lock();
+        let xml = xot
+            .serialize_xml_string(xot::output::xml::Parameters::default(), self.node)
+            .map_err(|e| Error::SerializationError(e.to_string()));
+        Some(xml)
+    }
+}
 
-        for item in sequence.iter() {
-            match item {
-                xee_xpath::Item::Atomic(atomic) => {
-                    let result_item = convert_atomic_to_result_item(&atomic);
-                    string_parts.push(result_item.as_string());
-                    items.push(result_item);
-                }
-                xee_xpath::Item::Node(node) => {
-                    let xot = documents.xot();
-                    let node_type = match xot.value_type(node) {
-                        xot::ValueType::Document => NodeType::Document,
-                        xot::ValueType::Element => NodeType::Element,
-                        xot::ValueType::Text => NodeType::Text,
-                        xot::ValueType::Comment => NodeType::Comment,
-                        xot::ValueType::ProcessingInstruction => {
-                            NodeType::ProcessingInstruction
-                        }
-                        xot::ValueType::Attribute => NodeType::Attribute,
-                        xot::ValueType::Namespace => NodeType::Namespace,
-                    };
-                    let name = xot.node_name(node).map(|n| xot.name_ns_str(n).1.to_string());
-                    let value = xot.to_string(node).ok();
-                    string_parts.push(value.clone().unwrap_or_default());
-                    items.push(ResultItem::Node(NodeInfo {
-                        node_type,
-                        name,
-                        value,
-                    }));
-                }
-                xee_xpath::Item::Function(_) => {
-                    items.push(ResultItem::String("<function>".to_string()));
-                    string_parts.push("<function>".to_string());
-                }
+/// Build an XPath-like location path for a node by walking its ancestors
+///
+/// Elements are addressed by name and position among same-named siblings,
+/// other node kinds by their kind test (e.g. `/root[1]/item[2]/text()[1]`).
+fn node_path(xot: &xot::Xot, node: xot::Node) -> String {
+    let mut steps = Vec::new();
+    let mut current = Some(node);
+
+    while let Some(n) = current {
+        let step = match xot.value_type(n) {
+            xot::ValueType::Document => break,
+            xot::ValueType::Attribute => {
+                let name = xot.node_name(n).map(|name| xot.local_name_str(name).to_string());
+                format!("@{}", name.unwrap_or_default())
             }
-        }
-
-        Ok(XeeQueryResult {
-            items,
-            string_repr: string_parts.join("\n"),
-        })
+            xot::ValueType::Namespace => "namespace::*".to_string(),
+            value_type => {
+                let position = xot
+                    .parent(n)
+                    .map(|parent| {
+                        xot.children(parent)
+                            .take_while(|sibling| *sibling != n)
+                            .filter(|sibling| {
+                                xot.value_type(*sibling) == value_type
+                                    && xot.node_name(*sibling) == xot.node_name(n)
+                            })
+                            .count()
+                            + 1
+                    })
+                    .unwrap_or(1);
+                let test = match value_type {
+                    xot::ValueType::Text => "text()".to_string(),
+                    xot::ValueType::Comment => "comment()".to_string(),
+                    xot::ValueType::ProcessingInstruction => "processing-instruction()".to_string(),
+                    _ => xot
+                        .node_name(n)
+                        .map(|name| xot.local_name_str(name).to_string())
+                        .unwrap_or_default(),
+                };
+                format!("{}[{}]", test, position)
+            }
+        };
+        steps.push(step);
+        current = xot.parent(n);
     }
 
-    fn xpath_version(&self) -> XPathVersion {
-        XPathVersion::V3_1
+    steps.reverse();
+    format!("/{}", steps.join("/"))
+}
+
+/// Compute a node's identity from its ordinals among all children
+/// (including attribute and namespace nodes) of each ancestor
+fn node_identity(xot: &xot::Xot, node: xot::Node, document: u64) -> NodeId {
+    let mut ordinals = Vec::new();
+    let mut current = node;
+    while let Some(parent) = xot.parent(current) {
+        let ordinal = xot
+            .all_children(parent)
+            .position(|child| child == current)
+            .unwrap_or(0);
+        ordinals.push(ordinal);
+        current = parent;
     }
+    ordinals.reverse();
+    NodeId::new(document, ordinals)
 }
 
-fn convert_atomic_to_result_item(atomic: &xee_xpath::Atomic) -> ResultItem {
+fn convert_atomic_to_result_item(atomic: &xee_xpath::Atomic, lexical: Option<String>) -> ResultItem {
     use xee_xpath::Atomic;
+    let lexical = || lexical.clone().unwrap_or_else(|| format!("{:?}", atomic));
     match atomic {
         Atomic::String(_, s) => ResultItem::String(s.to_string()),
         Atomic::Untyped(s) => ResultItem::String(s.to_string()),
@@ -192,13 +936,16 @@ fn convert_atomic_to_result_item(atomic: &xee_xpath::Atomic) -> ResultItem {
         Atomic::Decimal(d) => ResultItem::Double(d.to_string().parse().unwrap_or(0.0)),
         Atomic::Float(f) => ResultItem::Double(f.into_inner() as f64),
         Atomic::Double(d) => ResultItem::Double(d.into_inner()),
-        Atomic::Date(d) => ResultItem::Date(format!("{:?}", d)),
-        Atomic::DateTime(dt) => ResultItem::DateTime(format!("{:?}", dt)),
-        Atomic::Time(t) => ResultItem::DateTime(format!("{:?}", t)),
-        Atomic::Duration(d) => ResultItem::Duration(format!("{:?}", d)),
-        Atomic::YearMonthDuration(d) => ResultItem::Duration(format!("{:?}", d)),
-        Atomic::DayTimeDuration(d) => ResultItem::Duration(format!("{:?}", d)),
-        Atomic::QName(q) => ResultItem::QName(format!("{:?}", q)),
+        Atomic::Date(_) => ResultItem::Date(lexical()),
+        Atomic::DateTime(_) | Atomic::Time(_) => ResultItem::DateTime(lexical()),
+        Atomic::Duration(_) | Atomic::YearMonthDuration(_) | Atomic::DayTimeDuration(_) => {
+            ResultItem::Duration(lexical())
+        }
+        Atomic::QName(q) => ResultItem::QName {
+            prefix: Some(q.prefix().to_string()).filter(|p| !p.is_empty()),
+            local: q.local_name().to_string(),
+            uri: Some(q.namespace().to_string()).filter(|u| !u.is_empty()),
+        },
         _ => ResultItem::String(format!("{:?}", atomic)),
     }
 }
@@ -209,20 +956,12 @@ impl XsltEngine for XeeEngine {
         doc: &Self::Document,
         stylesheet: &str,
     ) -> Result<Self::Document> {
-        // Serialize the input document
-        let xml_str = self
-            .xot
-            .to_string(doc.root)
-            .map_err(|e| Error::EngineError(e.to_string()))?;
-
-        // Use xee_xslt_compiler::evaluate
-        let sequence = xee_xslt_compiler::evaluate(&mut self.xot, &xml_str, stylesheet)
-            .map_err(|e| Error::XsltError(format!("{:?}", e)))?;
+        let sequence = self.evaluate_stylesheet(doc, stylesheet)?;
 
         // Get the first node from the result
         if let Some(item) = sequence.iter().next() {
             if let Ok(node) = item.to_node() {
-                return Ok(XeeDocument { root: node });
+                return Ok(XeeDocument::new(&self.xot, node));
             }
         }
 
@@ -236,25 +975,8 @@ impl XsltEngine for XeeEngine {
         doc: &Self::Document,
         stylesheet: &str,
     ) -> Result<String> {
-        let xml_str = self
-            .xot
-            .to_string(doc.root)
-            .map_err(|e| Error::EngineError(e.to_string()))?;
-
-        let sequence = xee_xslt_compiler::evaluate(&mut self.xot, &xml_str, stylesheet)
-            .map_err(|e| Error::XsltError(format!("{:?}", e)))?;
-
-        // Serialize all nodes in the result
-        let mut result = String::new();
-        for item in sequence.iter() {
-            if let Ok(node) = item.to_node() {
-                if let Ok(s) = self.xot.to_string(node) {
-                    result.push_str(&s);
-                }
-            }
-        }
-
-        Ok(result)
+        let sequence = self.evaluate_stylesheet(doc, stylesheet)?;
+        Ok(self.serialize_nodes(&sequence))
     }
 
     fn xslt_version(&self) -> XsltVersion {
@@ -14,20 +14,32 @@ use std::path::Path;
 use xrust::item::{Item as XrustItem, Node, NodeType as XrustNodeType, SequenceTrait};
 use xrust::parser::xml::parse as parse_xml;
 use xrust::parser::xpath::parse as parse_xpath;
-use xrust::transform::context::{ContextBuilder, StaticContextBuilder};
+use xrust::transform::context::{Context, ContextBuilder, StaticContextBuilder};
 use xrust::trees::smite::RNode;
 use xrust::xdmerror::{Error as XrustError, ErrorKind};
 use xrust::xslt::from_document;
 
 use crate::error::{Error, Result};
+use crate::parse_limits::ParseLimits;
+use crate::query_cache::{next_document_id, next_engine_id, QueryCache};
 use crate::result::{NodeInfo, NodeType, ResultItem, ValidationResult};
 use crate::traits::{
-    QueryResult, XPathEngine, XPathVersion, XQueryEngine, XQueryVersion, XmlDocument, XmlParser,
-    XsdValidator, XsdVersion, XsltEngine, XsltVersion,
+    QueryResult, TextResolver, TraceHook, XPathEngine, XPathVersion, XQueryEngine, XQueryVersion,
+    XmlDocument, XmlParser, XsdValidator, XsdVersion, XsltEngine, XsltVersion,
 };
 
 /// xrust engine wrapper
-pub struct XrustEngine;
+pub struct XrustEngine {
+    id: u64,
+    trace: Option<TraceHook>,
+    query_cache: Option<QueryCache<XrustQueryResult>>,
+    text_resolver: Option<TextResolver>,
+    parse_limits: Option<ParseLimits>,
+    /// Stylesheets compiled by [`compile_stylesheet`](Self::compile_stylesheet),
+    /// `None` once dropped
+    stylesheets: Vec<Option<Context<RNode>>>,
+    stylesheet_compilations: usize,
+}
 
 impl Default for XrustEngine {
     fn default() -> Self {
@@ -37,15 +49,181 @@ impl Default for XrustEngine {
 
 impl XrustEngine {
     pub fn new() -> Self {
-        Self
+        Self {
+            id: next_engine_id(),
+            trace: None,
+            query_cache: None,
+            text_resolver: None,
+            parse_limits: None,
+            stylesheets: Vec::new(),
+            stylesheet_compilations: 0,
+        }
+    }
+
+    /// Process-unique identifier of this engine instance
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Install or remove the evaluation trace hook
+    pub fn set_trace(&mut self, hook: Option<TraceHook>) {
+        self.trace = hook;
+    }
+
+    /// Get the evaluation trace hook, if installed
+    pub fn trace_hook(&mut self) -> Option<&mut TraceHook> {
+        self.trace.as_mut()
+    }
+
+    /// Install or remove the query result cache
+    pub fn set_query_cache(&mut self, cache: Option<QueryCache<XrustQueryResult>>) {
+        self.query_cache = cache;
+    }
+
+    /// Get the query result cache, if enabled
+    pub fn query_cache(&mut self) -> Option<&mut QueryCache<XrustQueryResult>> {
+        self.query_cache.as_mut()
+    }
+
+    /// Install or remove (restoring filesystem loading) the text resolver
+    pub fn set_text_resolver(&mut self, resolver: Option<TextResolver>) {
+        self.text_resolver = resolver;
+    }
+
+    /// Get the text resolver, if one is installed
+    pub fn text_resolver(&self) -> Option<&TextResolver> {
+        self.text_resolver.as_ref()
+    }
+
+    /// Set (or remove) the limits checked before parsing
+    pub fn set_parse_limits(&mut self, limits: Option<ParseLimits>) {
+        self.parse_limits = limits;
+    }
+
+    /// Get the parse limits, if set
+    pub fn parse_limits(&self) -> Option<ParseLimits> {
+        self.parse_limits
+    }
+
+    /// Drop cached results, e.g. after a panic
+    ///
+    /// xrust trees belong to their documents, so there is no other
+    /// evaluation state; configuration is kept.
+    pub fn reset(&mut self) {
+        if let Some(cache) = &mut self.query_cache {
+            cache.clear();
+        }
+    }
+
+    /// Create a document node with no children, for evaluating without a
+    /// context document
+    pub fn empty_document(&self) -> XrustDocument {
+        XrustDocument { id: next_document_id(), root: RNode::new_document() }
+    }
+
+    /// Compile a stylesheet once for [`transform_compiled`](Self::transform_compiled),
+    /// returning its index
+    pub fn compile_stylesheet(&mut self, stylesheet: &str) -> Result<usize> {
+        let context = self.compile(stylesheet)?;
+        self.stylesheets.push(Some(context));
+        Ok(self.stylesheets.len() - 1)
+    }
+
+    /// Free a stylesheet from [`compile_stylesheet`](Self::compile_stylesheet)
+    ///
+    /// Returns whether it was still held. Indices aren't reused.
+    pub fn drop_stylesheet(&mut self, stylesheet: usize) -> bool {
+        self.stylesheets.get_mut(stylesheet).and_then(Option::take).is_some()
     }
+
+    /// Transform with a stylesheet from [`compile_stylesheet`](Self::compile_stylesheet)
+    pub fn transform_compiled(
+        &mut self,
+        doc: &XrustDocument,
+        stylesheet: usize,
+    ) -> Result<XrustDocument> {
+        let context = self
+            .stylesheets
+            .get(stylesheet)
+            .and_then(Option::as_ref)
+            .cloned()
+            .ok_or_else(|| Error::XsltError("Unknown compiled stylesheet".to_string()))?;
+        run_stylesheet(context, doc)
+    }
+
+    /// Number of stylesheets compiled so far, by transforms or
+    /// [`compile_stylesheet`](Self::compile_stylesheet)
+    pub fn stylesheet_compilations(&self) -> usize {
+        self.stylesheet_compilations
+    }
+
+    /// Parse and compile a stylesheet
+    fn compile(&mut self, stylesheet: &str) -> Result<Context<RNode>> {
+        let style = RNode::new_document();
+        parse_xml(style.clone(), stylesheet, None)
+            .map_err(|e| Error::XsltError(format!("Failed to parse stylesheet: {}", e)))?;
+
+        let context = from_document(
+            style,
+            None,
+            |s: &str| {
+                let doc = RNode::new_document();
+                parse_xml(doc.clone(), s, None)?;
+                Ok(doc)
+            },
+            |_| Ok(String::new()),
+        )
+        .map_err(|e| Error::XsltError(e.to_string()))?;
+        self.stylesheet_compilations += 1;
+        Ok(context)
+    }
+}
+
+/// Run a compiled stylesheet over `doc`
+fn run_stylesheet(mut context: Context<RNode>, doc: &XrustDocument) -> Result<XrustDocument> {
+    // Set source document as context
+    context.context(vec![XrustItem::Node(doc.root.clone())], 0);
+
+    // Create result document
+    let result_doc = RNode::new_document();
+    context.result_document(result_doc.clone());
+
+    // Create static context and evaluate
+    let mut static_context = StaticContextBuilder::new()
+        .message(|_| Ok(()))
+        .fetcher(|_| Err(XrustError::new(ErrorKind::NotImplemented, "not implemented")))
+        .parser(|_| Err(XrustError::new(ErrorKind::NotImplemented, "not implemented")))
+        .build();
+
+    context
+        .evaluate(&mut static_context)
+        .map_err(|e| Error::XsltError(e.to_string()))?;
+
+    Ok(XrustDocument { id: next_document_id(), root: result_doc })
 }
 
 /// Document handle for xrust (wraps RNode)
 pub struct XrustDocument {
+    id: u64,
     root: RNode,
 }
 
+impl XrustDocument {
+    /// Process-unique identifier assigned when the document was created
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Describe the document element
+    pub fn document_element(&self) -> Result<NodeInfo> {
+        self.root
+            .child_iter()
+            .find(|child| child.node_type() == XrustNodeType::Element)
+            .map(|element| node_info(&element))
+            .ok_or_else(|| Error::EngineError("Document has no document element".to_string()))
+    }
+}
+
 impl XmlDocument for XrustDocument {
     fn to_string(&self) -> Result<String> {
         Ok(self.root.to_xml())
@@ -53,6 +231,7 @@ impl XmlDocument for XrustDocument {
 }
 
 /// Query result for xrust
+#[derive(Clone)]
 pub struct XrustQueryResult {
     items: Vec<ResultItem>,
     string_repr: String,
@@ -78,15 +257,25 @@ impl QueryResult for XrustQueryResult {
     fn items(&self) -> Vec<ResultItem> {
         self.items.clone()
     }
+
+    fn first(&self) -> Option<ResultItem> {
+        self.items.first().cloned()
+    }
 }
 
 impl XmlParser for XrustEngine {
     type Document = XrustDocument;
 
     fn parse(&mut self, xml: &str) -> Result<Self::Document> {
+        if let Some(limits) = &self.parse_limits {
+            limits.check(xml)?;
+        }
         let doc = RNode::new_document();
         parse_xml(doc.clone(), xml, None).map_err(|e| Error::ParseError(e.to_string()))?;
-        Ok(XrustDocument { root: doc })
+        if let Some(cache) = &mut self.query_cache {
+            cache.clear();
+        }
+        Ok(XrustDocument { id: next_document_id(), root: doc })
     }
 }
 
@@ -94,9 +283,14 @@ impl XPathEngine for XrustEngine {
     type QueryResult = XrustQueryResult;
 
     fn evaluate_xpath(&mut self, doc: &Self::Document, xpath: &str) -> Result<Self::QueryResult> {
+        // xrust only implements XPath 1.0; refuse 2.0 syntax it would mis-evaluate
+        if let Some(construct) = xpath2_construct(xpath) {
+            return Err(Error::FeatureNotSupported(format!("XPath 2.0 {}", construct)));
+        }
+
         // Parse the XPath expression
         let xpath_transform =
-            parse_xpath::<RNode>(xpath, None).map_err(|e| Error::XPathError(e.to_string()))?;
+            parse_xpath::<RNode>(xpath, None).map_err(|e| Error::xpath(e.to_string()))?;
 
         // Create context with the document as context item
         let context = ContextBuilder::new()
@@ -113,7 +307,7 @@ impl XPathEngine for XrustEngine {
         // Evaluate
         let sequence = context
             .dispatch(&mut static_context, &xpath_transform)
-            .map_err(|e| Error::XPathError(e.to_string()))?;
+            .map_err(|e| Error::xpath(e.to_string()))?;
 
         // Convert to our result types
         let mut items = Vec::new();
@@ -121,34 +315,7 @@ impl XPathEngine for XrustEngine {
 
         for item in &sequence {
             match item {
-                XrustItem::Node(n) => {
-                    let node_type = match n.node_type() {
-                        XrustNodeType::Document => NodeType::Document,
-                        XrustNodeType::Element => NodeType::Element,
-                        XrustNodeType::Text => NodeType::Text,
-                        XrustNodeType::Attribute => NodeType::Attribute,
-                        XrustNodeType::Comment => NodeType::Comment,
-                        XrustNodeType::ProcessingInstruction => {
-                            NodeType::ProcessingInstruction
-                        }
-                        XrustNodeType::Namespace => NodeType::Namespace,
-                        _ => NodeType::Element, // Unknown/Reference
-                    };
-                    let name = {
-                        let qn = n.name();
-                        let local = qn.localname_to_string();
-                        if local.is_empty() {
-                            None
-                        } else {
-                            Some(local)
-                        }
-                    };
-                    items.push(ResultItem::Node(NodeInfo {
-                        node_type,
-                        name,
-                        value: Some(n.to_string()),
-                    }));
-                }
+                XrustItem::Node(n) => items.push(ResultItem::Node(node_info(n))),
                 XrustItem::Value(v) => {
                     use xrust::value::Value;
                     let result_item = match v.as_ref() {
@@ -177,43 +344,8 @@ impl XPathEngine for XrustEngine {
 
 impl XsltEngine for XrustEngine {
     fn transform(&mut self, doc: &Self::Document, stylesheet: &str) -> Result<Self::Document> {
-        // Parse the stylesheet
-        let style = RNode::new_document();
-        parse_xml(style.clone(), stylesheet, None)
-            .map_err(|e| Error::XsltError(format!("Failed to parse stylesheet: {}", e)))?;
-
-        // Compile stylesheet
-        let mut context = from_document(
-            style,
-            None,
-            |s: &str| {
-                let doc = RNode::new_document();
-                parse_xml(doc.clone(), s, None)?;
-                Ok(doc)
-            },
-            |_| Ok(String::new()),
-        )
-        .map_err(|e| Error::XsltError(e.to_string()))?;
-
-        // Set source document as context
-        context.context(vec![XrustItem::Node(doc.root.clone())], 0);
-
-        // Create result document
-        let result_doc = RNode::new_document();
-        context.result_document(result_doc.clone());
-
-        // Create static context and evaluate
-        let mut static_context = StaticContextBuilder::new()
-            .message(|_| Ok(()))
-            .fetcher(|_| Err(XrustError::new(ErrorKind::NotImplemented, "not implemented")))
-            .parser(|_| Err(XrustError::new(ErrorKind::NotImplemented, "not implemented")))
-            .build();
-
-        context
-            .evaluate(&mut static_context)
-            .map_err(|e| Error::XsltError(e.to_string()))?;
-
-        Ok(XrustDocument { root: result_doc })
+        let context = self.compile(stylesheet)?;
+        run_stylesheet(context, doc)
     }
 
     fn transform_to_string(&mut self, doc: &Self::Document, stylesheet: &str) -> Result<String> {
@@ -259,3 +391,104 @@ impl XsdValidator for XrustEngine {
         XsdVersion::V1_0
     }
 }
+
+/// Describe an xrust node
+fn node_info(n: &RNode) -> NodeInfo {
+    let node_type = match n.node_type() {
+        XrustNodeType::Document => NodeType::Document,
+        XrustNodeType::Element => NodeType::Element,
+        XrustNodeType::Text => NodeType::Text,
+        XrustNodeType::Attribute => NodeType::Attribute,
+        XrustNodeType::Comment => NodeType::Comment,
+        XrustNodeType::ProcessingInstruction => NodeType::ProcessingInstruction,
+        XrustNodeType::Namespace => NodeType::Namespace,
+        _ => NodeType::Element, // Unknown/Reference
+    };
+    let qn = n.name();
+    let name = {
+        let local = qn.localname_to_string();
+        if local.is_empty() {
+            None
+        } else {
+            Some(local)
+        }
+    };
+    let attributes = if node_type == NodeType::Element {
+        n.attribute_iter()
+            .map(|a| (a.name().localname_to_string(), a.to_string()))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    NodeInfo {
+        node_type,
+        name,
+        value: Some(n.to_string()),
+        attributes,
+        // xrust's parser only resolves prefixed names, so elements in a
+        // default namespace have no namespace URI
+        namespace_uri: qn.namespace_uri_to_string().filter(|uri| !uri.is_empty()),
+        prefix: qn.prefix_to_string().filter(|prefix| !prefix.is_empty()),
+        location: None,
+    }
+}
+
+/// Find XPath 2.0-only syntax in an expression
+///
+/// Looks for `for $x ...`, `if (...)` and the sequence type operators
+/// (`instance of`, `treat as`, `cast as`, `castable as`) outside string
+/// literals and comments, skipping words used as name tests (`/if`, `@for`).
+fn xpath2_construct(xpath: &str) -> Option<&'static str> {
+    // Words and single punctuation characters; string literals become `"`
+    let mut tokens: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut chars = xpath.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '$') {
+            word.push(c);
+            continue;
+        }
+        if !word.is_empty() {
+            tokens.push(std::mem::take(&mut word));
+        }
+        match c {
+            '"' | '\'' => {
+                // Skip the string literal; doubled quotes are escapes
+                while let Some(s) = chars.next() {
+                    if s == c && chars.next_if_eq(&c).is_none() {
+                        break;
+                    }
+                }
+                tokens.push("\"".to_string());
+            }
+            '(' if chars.next_if_eq(&':').is_some() => {
+                let mut prev = ' ';
+                for s in chars.by_ref() {
+                    if prev == ':' && s == ')' {
+                        break;
+                    }
+                    prev = s;
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => tokens.push(c.to_string()),
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+
+    tokens.iter().enumerate().find_map(|(i, token)| {
+        let name_test = i > 0 && matches!(tokens[i - 1].as_str(), "/" | "@" | ":");
+        let next = tokens.get(i + 1).map(String::as_str).unwrap_or("");
+        match (token.as_str(), next) {
+            _ if name_test => None,
+            ("for", next) if next.starts_with('$') => Some("for expressions"),
+            ("if", "(") => Some("conditional expressions"),
+            ("instance", "of") | ("treat" | "cast" | "castable", "as") => {
+                Some("sequence type expressions")
+            }
+            _ => None,
+        }
+    })
+}
@@ -9,9 +9,15 @@
 //! Does NOT support:
 //! - XSLT
 
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::fmt;
 use std::path::Path;
 use std::rc::Rc;
 
+use chrono::FixedOffset;
+use iri_string::types::{IriAbsoluteStr, IriReferenceStr};
+
 use xust_eval::eval::context::{default_tree_context_init, Context, GlobalContext};
 use xust_eval::eval::eval_xquery;
 use xust_eval::r#fn::function_definitions;
@@ -25,17 +31,32 @@ use xust_xsd::load_validator;
 use xust_xsd::xsd_validator::XsdValidator as XustXsdValidator;
 
 use crate::error::{Error, Result};
-use crate::result::{NodeInfo, NodeType, ResultItem, ValidationError, ValidationResult};
+use crate::parse_limits::ParseLimits;
+use crate::query_cache::{next_document_id, QueryCache};
+use crate::result::{
+    parse_double, NodeId, NodeInfo, NodeLocation, NodeType, ResultItem, ValidationError,
+    ValidationErrorKind, ValidationResult,
+};
+use crate::serialization::escape;
 use crate::traits::{
-    QueryResult, XPathEngine, XPathVersion, XQueryEngine, XQueryVersion, XmlDocument, XmlParser,
-    XsdValidator, XsdVersion, XsltEngine, XsltVersion,
+    QueryResult, TextResolver, TraceHook, XPathEngine, XPathVersion, XQueryEngine, XQueryVersion,
+    XmlDocument, XmlParser, XsdValidator, XsdVersion, XsltEngine, XsltVersion,
 };
+use crate::unparsed_text::redirect_document_calls;
 
 type XustTree = Tree<Atomic>;
+type XustNode = Node<Rc<XustTree>>;
 
 /// xust engine wrapper
 pub struct XustEngine {
     validator: Option<XustXsdValidator>,
+    static_base_uri: Option<String>,
+    default_element_namespace: Option<String>,
+    trace: Option<TraceHook>,
+    query_cache: Option<QueryCache<XustQueryResult>>,
+    text_resolver: Option<TextResolver>,
+    implicit_timezone: Option<FixedOffset>,
+    parse_limits: Option<ParseLimits>,
 }
 
 impl Default for XustEngine {
@@ -46,27 +67,198 @@ impl Default for XustEngine {
 
 impl XustEngine {
     pub fn new() -> Self {
-        Self { validator: None }
+        Self {
+            validator: None,
+            static_base_uri: None,
+            default_element_namespace: None,
+            trace: None,
+            query_cache: None,
+            text_resolver: None,
+            implicit_timezone: None,
+            parse_limits: None,
+        }
+    }
+
+    /// Set the static base URI used by subsequent evaluations
+    pub fn set_static_base_uri(&mut self, uri: Option<&str>) {
+        self.static_base_uri = uri.map(String::from);
+    }
+
+    /// Get the static base URI, if set
+    pub fn static_base_uri(&self) -> Option<&str> {
+        self.static_base_uri.as_deref()
+    }
+
+    /// Set the namespace of unprefixed element names in subsequent queries
+    pub fn set_default_element_namespace(&mut self, uri: Option<&str>) {
+        self.default_element_namespace = uri.map(String::from);
+    }
+
+    /// Install or remove the evaluation trace hook
+    pub fn set_trace(&mut self, hook: Option<TraceHook>) {
+        self.trace = hook;
+    }
+
+    /// Get the evaluation trace hook, if installed
+    pub fn trace_hook(&mut self) -> Option<&mut TraceHook> {
+        self.trace.as_mut()
+    }
+
+    /// Install or remove the query result cache
+    pub fn set_query_cache(&mut self, cache: Option<QueryCache<XustQueryResult>>) {
+        self.query_cache = cache;
+    }
+
+    /// Get the query result cache, if enabled
+    pub fn query_cache(&mut self) -> Option<&mut QueryCache<XustQueryResult>> {
+        self.query_cache.as_mut()
+    }
+
+    /// Install or remove (restoring filesystem loading) the text resolver
+    pub fn set_text_resolver(&mut self, resolver: Option<TextResolver>) {
+        self.text_resolver = resolver;
+    }
+
+    /// Get the text resolver, if one is installed
+    pub fn text_resolver(&self) -> Option<&TextResolver> {
+        self.text_resolver.as_ref()
+    }
+
+    /// Set (or unset, going back to the local timezone) the implicit timezone
+    pub fn set_implicit_timezone(&mut self, timezone: Option<FixedOffset>) {
+        self.implicit_timezone = timezone;
+    }
+
+    /// Get the implicit timezone, if set
+    pub fn implicit_timezone(&self) -> Option<FixedOffset> {
+        self.implicit_timezone
+    }
+
+    /// Set (or remove) the limits checked before parsing
+    pub fn set_parse_limits(&mut self, limits: Option<ParseLimits>) {
+        self.parse_limits = limits;
+    }
+
+    /// Get the parse limits, if set
+    pub fn parse_limits(&self) -> Option<ParseLimits> {
+        self.parse_limits
+    }
+
+    /// Drop cached results, e.g. after a panic
+    ///
+    /// xust builds a fresh evaluation context per query, so there is no
+    /// other evaluation state; configuration and the loaded schema are kept.
+    pub fn reset(&mut self) {
+        if let Some(cache) = &mut self.query_cache {
+            cache.clear();
+        }
+    }
+
+    /// Create a document node with no children, for evaluating without a
+    /// context document
+    pub fn empty_document(&self) -> Result<XustDocument> {
+        Ok(XustDocument { id: next_document_id(), tree: None, root: empty_document_node()? })
+    }
+
+    /// Validate a document and return the typed tree xust builds
+    ///
+    /// Queries against the result see schema types (an `xs:int` element
+    /// atomizes to a number) instead of untyped text.
+    pub fn validated_document(&self, doc: &XustDocument) -> Result<XustDocument> {
+        let tree = self
+            .loaded_validator()?
+            .validate_to_tree(&normalized_xml(doc)?, None)
+            .map_err(|e| Error::XsdError(e.to_string()))?;
+        Ok(XustDocument::new(tree))
+    }
+
+    /// Validate a document and report each element's assigned schema type
+    ///
+    /// Returns `(path, type)` pairs in document order, read from the typed
+    /// tree xust builds during validation. Built-in types use the `xs:` prefix.
+    pub fn validate_to_typed(&self, doc: &XustDocument) -> Result<Vec<(String, String)>> {
+        let tree = self
+            .loaded_validator()?
+            .validate_to_tree(&normalized_xml(doc)?, None)
+            .map_err(|e| Error::XsdError(e.to_string()))?;
+        let root = Node::root(Rc::new(tree));
+        let mut locator = NodeLocator::new(&root, doc.id);
+        Ok(root
+            .descendants()
+            .filter(|node| node.node_kind() == NodeKind::Element)
+            .map(|node| (locator.locate(&node).0, type_annotation(&node)))
+            .collect())
+    }
+
+    fn loaded_validator(&self) -> Result<&XustXsdValidator> {
+        self.validator
+            .as_ref()
+            .ok_or_else(|| Error::XsdError("No schema loaded".to_string()))
     }
 }
 
 /// Document handle for xust (wraps Tree<Atomic>)
 pub struct XustDocument {
-    tree: Rc<XustTree>,
+    id: u64,
+    /// `None` for the document of [`XustEngine::empty_document`]
+    tree: Option<Rc<XustTree>>,
+    root: XustNode,
+}
+
+impl XustDocument {
+    fn new(tree: XustTree) -> Self {
+        let tree = Rc::new(tree);
+        let root = Node::root(tree.clone());
+        XustDocument { id: next_document_id(), tree: Some(tree), root }
+    }
+
+    /// Process-unique identifier assigned when the document was created
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Describe the document element
+    pub fn document_element(&self) -> Result<NodeInfo> {
+        let mut locator = NodeLocator::new(&self.root, self.id);
+        let element = self
+            .root
+            .children()
+            .find(|child| child.node_kind() == NodeKind::Element)
+            .ok_or_else(|| Error::EngineError("Document has no document element".to_string()))?;
+        Ok(node_info(&element, &mut locator))
+    }
 }
 
 impl XmlDocument for XustDocument {
     fn to_string(&self) -> Result<String> {
         use xust_xml::write::{tree_to_string, XmlOutputParameters};
-        tree_to_string(&*self.tree, XmlOutputParameters::default())
+        let Some(tree) = &self.tree else {
+            return Ok(String::new());
+        };
+        tree_to_string(&**tree, XmlOutputParameters::default())
             .map_err(|e| Error::EngineError(e.to_string()))
     }
 }
 
 /// Query result for xust
+#[derive(Clone)]
 pub struct XustQueryResult {
     items: Vec<ResultItem>,
-    string_repr: String,
+    /// Newline-joined string form, built on first use
+    string_repr: OnceCell<String>,
+}
+
+impl XustQueryResult {
+    /// Check whether the joined string form has been built yet
+    pub fn has_string_repr(&self) -> bool {
+        self.string_repr.get().is_some()
+    }
+
+    fn string_repr(&self) -> &str {
+        self.string_repr.get_or_init(|| {
+            self.items.iter().map(ResultItem::as_string).collect::<Vec<_>>().join("\n")
+        })
+    }
 }
 
 impl QueryResult for XustQueryResult {
@@ -79,28 +271,36 @@ impl QueryResult for XustQueryResult {
     }
 
     fn to_string(&self) -> String {
-        self.string_repr.clone()
+        self.string_repr().to_string()
     }
 
     fn to_xml(&self) -> Result<String> {
-        Ok(self.string_repr.clone())
+        Ok(self.string_repr().to_string())
     }
 
     fn items(&self) -> Vec<ResultItem> {
         self.items.clone()
     }
+
+    fn first(&self) -> Option<ResultItem> {
+        self.items.first().cloned()
+    }
 }
 
 impl XmlParser for XustEngine {
     type Document = XustDocument;
 
     fn parse(&mut self, xml: &str) -> Result<Self::Document> {
+        if let Some(limits) = &self.parse_limits {
+            limits.check(xml)?;
+        }
         let bytes = xml.as_bytes().to_vec();
         let tree: XustTree = parse_xml_from_bytes(bytes, None, None)
             .map_err(|e| Error::ParseError(e.to_string()))?;
-        Ok(XustDocument {
-            tree: Rc::new(tree),
-        })
+        if let Some(cache) = &mut self.query_cache {
+            cache.clear();
+        }
+        Ok(XustDocument::new(tree))
     }
 }
 
@@ -121,7 +321,99 @@ impl XQueryEngine for XustEngine {
     type QueryResult = XustQueryResult;
 
     fn execute_xquery(&mut self, doc: &Self::Document, xquery: &str) -> Result<Self::QueryResult> {
-        use std::collections::HashMap;
+        self.evaluate(doc, &Item::Node(doc.root.clone()), xquery)
+    }
+
+    fn xquery_version(&self) -> XQueryVersion {
+        XQueryVersion::V3_1
+    }
+}
+
+impl XustEngine {
+    /// Execute a query with documents available to `fn:doc`
+    ///
+    /// Each `(uri, path)` pair is registered under its URI, resolved against
+    /// the static base URI if relative. xust's `fn:doc` only loads files, so
+    /// calls with a literal registered URI are pointed at the file's own
+    /// URI; computed URIs are resolved by xust as usual. There is no context
+    /// document.
+    pub fn execute_xquery_with_documents(
+        &mut self,
+        documents: &[(String, &Path)],
+        xquery: &str,
+    ) -> Result<XustQueryResult> {
+        let mut registered = Vec::with_capacity(documents.len());
+        for (uri, path) in documents {
+            let file = std::fs::canonicalize(path)?;
+            registered.push((self.document_uri(uri)?, format!("file://{}", file.display())));
+        }
+        let xquery = redirect_document_calls(xquery, &mut |uri| {
+            let uri = self.document_uri(uri).ok()?;
+            registered.iter().find(|(known, _)| *known == uri).map(|(_, file)| file.clone())
+        })?;
+        let doc = self.empty_document()?;
+        self.evaluate(&doc, &Item::Node(doc.root.clone()), &xquery)
+    }
+
+    /// Absolute form of a document URI, resolving a relative one against the
+    /// static base URI as `fn:doc` does
+    fn document_uri(&self, uri: &str) -> Result<String> {
+        let reference = IriReferenceStr::new(uri)
+            .map_err(|e| Error::EngineError(format!("Invalid document URI '{}': {}", uri, e)))?;
+        match reference.to_iri() {
+            Ok(iri) => Ok(iri.to_string()),
+            Err(relative) => {
+                let base = self
+                    .static_base_uri
+                    .as_deref()
+                    .and_then(|base| IriAbsoluteStr::new(base).ok())
+                    .ok_or_else(|| {
+                        Error::EngineError(format!(
+                            "Relative document URI '{}' needs a static base URI",
+                            uri
+                        ))
+                    })?;
+                Ok(relative.resolve_against(base).to_string())
+            }
+        }
+    }
+
+    /// Evaluate a query with an atomic value as the context item
+    ///
+    /// `value` is an expression giving the value, such as a literal; it is
+    /// evaluated first without a context. Path expressions then raise
+    /// `XPDY0050` since the context item isn't a node.
+    pub fn execute_xquery_with_context_item(
+        &mut self,
+        value: &str,
+        xquery: &str,
+    ) -> Result<XustQueryResult> {
+        let items = evaluate_each(value, &[None], |sequence| {
+            sequence.into_iter().next().filter(|item| matches!(item, Item::Atomic(_))).cloned()
+        })?;
+        let item = items.into_iter().flatten().next().ok_or_else(|| {
+            Error::EngineError(format!("'{}' doesn't give an atomic context item", value))
+        })?;
+        let doc = self.empty_document()?;
+        self.evaluate(&doc, &item, xquery)
+    }
+
+    /// Evaluate a query against `doc` with `context_item` as the context item
+    fn evaluate(
+        &mut self,
+        doc: &XustDocument,
+        context_item: &Item<Rc<XustTree>>,
+        xquery: &str,
+    ) -> Result<XustQueryResult> {
+        // xust takes no default element namespace in ParseInit; declare it in the prolog
+        let declared;
+        let xquery = match &self.default_element_namespace {
+            Some(uri) => {
+                declared = declare_default_element_namespace(xquery, uri);
+                declared.as_str()
+            }
+            None => xquery,
+        };
 
         // Create function definitions first - these contain count(), etc.
         let fd = function_definitions();
@@ -131,12 +423,19 @@ impl XQueryEngine for XustEngine {
         let parse_init = ParseInit {
             fd: &fd,
             namespaces: &empty_namespaces,
+            static_base_uri: self.static_base_uri.as_deref(),
             ..ParseInit::default()
         };
 
-        // Parse the query
-        let parsed_query = parse_xquery(xquery, parse_init)
-            .map_err(|e| Error::XQueryError(format!("{:?}", e)))?;
+        // Parse the query; xust's grammar has no update facility, so a query
+        // it rejects may be an update expression rather than a syntax error
+        let parsed_query = parse_xquery(xquery, parse_init).map_err(|e| {
+            if is_update_expression(xquery) {
+                Error::FeatureNotSupported("XQuery Update".to_string())
+            } else {
+                Error::XQueryError(format!("{:?}", e))
+            }
+        })?;
 
         // Create context initialization using qnames from parsed query
         let context_init = default_tree_context_init(parsed_query.qnames().clone(), fd);
@@ -148,70 +447,554 @@ impl XQueryEngine for XustEngine {
         let mut context =
             Context::new(global_context).map_err(|e| Error::XQueryError(format!("{:?}", e)))?;
 
-        // Set context item to the document root
-        let root = Node::root(doc.tree.clone());
-        let context_item = Item::Node(root);
-        context.set_only_item(&context_item);
+        context.set_only_item(context_item);
+        if let Some(timezone) = self.implicit_timezone {
+            context.set_implicit_timezone(timezone);
+        }
 
         // Evaluate
         let sequence: Sequence<Rc<XustTree>> =
             eval_xquery(&mut context).map_err(|e| Error::XQueryError(format!("{:?}", e)))?;
 
-        // Convert to our result types
+        // Convert to our result types; only QNames need a second query
         let mut items = Vec::new();
-        let mut string_parts = Vec::new();
+        let mut locator = NodeLocator::new(&doc.root, doc.id);
+        let qnames: Vec<&Item<Rc<XustTree>>> = (&sequence)
+            .into_iter()
+            .filter(|item| matches!(item, Item::Atomic(a) if variant_name(a) == "QName"))
+            .collect();
+        let mut qnames = qname_items(&qnames)?.into_iter();
 
         for item in &sequence {
             match item {
                 Item::Atomic(atomic) => {
-                    // Use Display implementation
-                    let s = atomic.to_string();
-                    string_parts.push(s.clone());
-                    items.push(ResultItem::String(s));
+                    let typed = match variant_name(atomic).as_str() {
+                        "QName" => qnames.next(),
+                        _ => None,
+                    };
+                    items.push(typed.unwrap_or_else(|| typed_atomic(atomic)));
                 }
                 Item::Node(node) => {
-                    let node_type = match node.node_kind() {
-                        NodeKind::Document => NodeType::Document,
-                        NodeKind::Element => NodeType::Element,
-                        NodeKind::Text => NodeType::Text,
-                        NodeKind::Comment => NodeType::Comment,
-                        NodeKind::ProcessingInstruction => NodeType::ProcessingInstruction,
-                        NodeKind::Attribute => NodeType::Attribute,
-                        NodeKind::Namespace => NodeType::Namespace,
-                    };
-                    let name = node.node_name().map(|qn| format!("{}", qn));
-                    // Use Debug for node value since Display isn't implemented
-                    let value = Some(format!("{:?}", node));
-                    string_parts.push(value.clone().unwrap_or_default());
-                    items.push(ResultItem::Node(NodeInfo {
-                        node_type,
-                        name,
-                        value,
-                    }));
+                    items.push(ResultItem::Node(node_info(node, &mut locator)));
                 }
                 Item::Array(_) => {
                     items.push(ResultItem::String("<array>".to_string()));
-                    string_parts.push("<array>".to_string());
                 }
                 Item::Map(_) => {
                     items.push(ResultItem::String("<map>".to_string()));
-                    string_parts.push("<map>".to_string());
                 }
                 Item::Function(_) => {
                     items.push(ResultItem::String("<function>".to_string()));
-                    string_parts.push("<function>".to_string());
                 }
             }
         }
 
         Ok(XustQueryResult {
             items,
-            string_repr: string_parts.join("\n"),
+            string_repr: OnceCell::new(),
         })
     }
+}
 
-    fn xquery_version(&self) -> XQueryVersion {
-        XQueryVersion::V3_1
+/// Reports the prefix, local name and namespace URI of the context QName
+const QNAME_PARTS_QUERY: &str = "(string(prefix-from-QName(.)),
+    string(local-name-from-QName(.)), string(namespace-uri-from-QName(.)))";
+
+/// Run `query` with each of `items` as the context item, returning the
+/// string values of each run's result
+///
+/// The query is compiled once. This reads what xust offers no Rust
+/// accessor for, such as the namespace URI of a QName value.
+fn query_each(query: &str, items: &[&Item<Rc<XustTree>>]) -> Result<Vec<Vec<String>>> {
+    let items: Vec<_> = items.iter().copied().map(Some).collect();
+    evaluate_each(query, &items, |sequence| {
+        sequence
+            .into_iter()
+            .map(|value| match value {
+                Item::Atomic(value) => value.to_string(),
+                _ => String::new(),
+            })
+            .collect()
+    })
+}
+
+/// Run `query` once per entry of `items`, with the item (if any) as the
+/// context item, and pass each run's result to `read`
+fn evaluate_each<R>(
+    query: &str,
+    items: &[Option<&Item<Rc<XustTree>>>],
+    mut read: impl FnMut(&Sequence<Rc<XustTree>>) -> R,
+) -> Result<Vec<R>> {
+    if items.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fd = function_definitions();
+    let namespaces: HashMap<String, String> = HashMap::new();
+    let parse_init = ParseInit { fd: &fd, namespaces: &namespaces, ..ParseInit::default() };
+    let query =
+        parse_xquery(query, parse_init).map_err(|e| Error::XQueryError(format!("{:?}", e)))?;
+    let context_init = default_tree_context_init(query.qnames().clone(), fd);
+    let global_context = GlobalContext::new(&context_init, query);
+    let mut context =
+        Context::new(global_context).map_err(|e| Error::XQueryError(format!("{:?}", e)))?;
+
+    let mut results = Vec::with_capacity(items.len());
+    for item in items {
+        if let Some(item) = item {
+            context.set_only_item(item);
+        }
+        let sequence: Sequence<Rc<XustTree>> =
+            eval_xquery(&mut context).map_err(|e| Error::XQueryError(format!("{:?}", e)))?;
+        results.push(read(&sequence));
+    }
+    Ok(results)
+}
+
+/// A document node with no children
+///
+/// xust only builds trees by parsing a document, which needs an element,
+/// so the node comes from an empty fragment parsed by xust itself.
+fn empty_document_node() -> Result<XustNode> {
+    let nodes = evaluate_each("parse-xml-fragment('')", &[None], |sequence| {
+        sequence.into_iter().find_map(|item| match item {
+            Item::Node(node) => Some(node.clone()),
+            _ => None,
+        })
+    })?;
+    nodes
+        .into_iter()
+        .flatten()
+        .next()
+        .ok_or_else(|| Error::EngineError("parse-xml-fragment('') returned no node".to_string()))
+}
+
+/// Name of the variant of a xust enum value: the type of an atomic value
+/// (`Integer`, `Boolean`, `DayTimeDuration`, ...) or the kind of an error
+///
+/// Neither `Atomic` nor xust_xsd's errors have an accessor for their kind,
+/// but their derived `Debug` output starts with the variant.
+fn variant_name(value: &impl fmt::Debug) -> String {
+    let debug = format!("{:?}", value);
+    let end = debug.find(|c: char| !c.is_alphanumeric()).unwrap_or(debug.len());
+    debug[..end].to_string()
+}
+
+/// Convert a xust_xsd validation error, classified by its variant
+///
+/// The variant names the violated constraint (a missing attribute, a facet,
+/// ...). Variants that don't fall into a [`ValidationErrorKind`] are
+/// classified from the message instead.
+fn validation_error(error: &(impl fmt::Debug + fmt::Display)) -> ValidationError {
+    let variant = variant_name(error);
+    let has = |words: &[&str]| words.iter().any(|word| variant.contains(word));
+    let kind = if has(&["Missing", "Required", "Absent"]) {
+        ValidationErrorKind::MissingRequired
+    } else if has(&["Unexpected", "NotAllowed", "Undeclared"]) {
+        ValidationErrorKind::UnexpectedElement
+    } else if has(&["ContentModel", "Incomplete", "Occurs"]) {
+        ValidationErrorKind::StructuralModel
+    } else if has(&["Facet", "Pattern", "Enumeration", "Length", "Datatype", "SimpleType"]) {
+        ValidationErrorKind::SimpleType
+    } else {
+        return ValidationError::new(error.to_string());
+    };
+    ValidationError { message: error.to_string(), line: None, column: None, kind }
+}
+
+/// Convert a xust atomic value to a typed result item, from its type and
+/// lexical form
+///
+/// QNames need their namespace URI too, see [`qname_items`].
+fn typed_atomic(atomic: &(impl fmt::Debug + fmt::Display)) -> ResultItem {
+    let value = atomic.to_string();
+    match variant_name(atomic).as_str() {
+        "Boolean" => ResultItem::Boolean(value == "true"),
+        "Integer" | "NonPositiveInteger" | "NegativeInteger" | "Long" | "Int" | "Short"
+        | "Byte" | "NonNegativeInteger" | "UnsignedLong" | "UnsignedInt" | "UnsignedShort"
+        | "UnsignedByte" | "PositiveInteger" => value
+            .parse()
+            .map(ResultItem::Integer)
+            .unwrap_or(ResultItem::String(value)),
+        "Decimal" | "Double" | "Float" => match parse_double(&value) {
+            Some(double) => ResultItem::Double(double),
+            None => ResultItem::String(value),
+        },
+        "Date" => ResultItem::Date(value),
+        "DateTime" | "DateTimeStamp" | "Time" => ResultItem::DateTime(value),
+        "Duration" | "DayTimeDuration" | "YearMonthDuration" => ResultItem::Duration(value),
+        _ => ResultItem::String(value),
+    }
+}
+
+/// Convert QName items, with their parts read by [`QNAME_PARTS_QUERY`]
+fn qname_items(qnames: &[&Item<Rc<XustTree>>]) -> Result<Vec<ResultItem>> {
+    let parts = query_each(QNAME_PARTS_QUERY, qnames)?;
+    Ok(parts
+        .into_iter()
+        .map(|parts| match parts.as_slice() {
+            [prefix, local, uri] => ResultItem::QName {
+                prefix: Some(prefix.clone()).filter(|p| !p.is_empty()),
+                local: local.clone(),
+                uri: Some(uri.clone()).filter(|u| !u.is_empty()),
+            },
+            _ => ResultItem::String(parts.concat()),
+        })
+        .collect())
+}
+
+/// Attributes of an element, as (local name, value) pairs
+fn element_attributes(element: &XustNode) -> Vec<(String, String)> {
+    element
+        .attributes()
+        .map(|attribute| {
+            let name = attribute.node_name().map(|qn| qn.local_name().to_string());
+            (name.unwrap_or_default(), attribute.string_value().to_string())
+        })
+        .collect()
+}
+
+/// Check whether a query xust failed to parse uses XQuery Update Facility
+/// expressions
+///
+/// Looks for the update keywords (`insert node`, `delete nodes`, `replace
+/// value of node`, `rename node`, `copy $x := ... modify ...`) outside string
+/// literals and comments. Only consulted once parsing has failed, so the
+/// keywords in queries xust accepts, such as constructor content, don't
+/// count.
+fn is_update_expression(query: &str) -> bool {
+    // Words, each flagged with whether only whitespace precedes it
+    let mut words: Vec<(String, bool)> = Vec::new();
+    let mut word = String::new();
+    let mut spaced = true;
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '$') {
+            word.push(c);
+            continue;
+        }
+        if !word.is_empty() {
+            words.push((std::mem::take(&mut word), spaced));
+            spaced = true;
+        }
+        match c {
+            '"' | '\'' => {
+                // Skip the string literal; doubled quotes are escapes
+                while let Some(s) = chars.next() {
+                    if s == c && chars.next_if_eq(&c).is_none() {
+                        break;
+                    }
+                }
+                spaced = false;
+            }
+            '(' if chars.next_if_eq(&':').is_some() => {
+                // Comments separate words like whitespace
+                let mut prev = ' ';
+                for s in chars.by_ref() {
+                    if prev == ':' && s == ')' {
+                        break;
+                    }
+                    prev = s;
+                }
+            }
+            c if c.is_whitespace() => {}
+            _ => spaced = false,
+        }
+    }
+    if !word.is_empty() {
+        words.push((word, spaced));
+    }
+
+    words.windows(2).any(|pair| {
+        let (first, (second, spaced)) = (pair[0].0.as_str(), (pair[1].0.as_str(), pair[1].1));
+        spaced
+            && (matches!(
+                (first, second),
+                ("insert" | "delete", "node" | "nodes")
+                    | ("replace", "node" | "value")
+                    | ("rename", "node")
+                    | ("transform", "with")
+            ) || (first == "copy" && second.starts_with('$')))
+    })
+}
+
+/// Add a `declare default element namespace` to a query's prolog
+///
+/// The declaration goes after a leading version declaration, which must
+/// come first.
+fn declare_default_element_namespace(query: &str, uri: &str) -> String {
+    let declaration = format!(
+        "declare default element namespace \"{}\";",
+        uri.replace('&', "&amp;").replace('"', "\"\"")
+    );
+    let has_version_decl = query
+        .trim_start()
+        .strip_prefix("xquery")
+        .map(str::trim_start)
+        .is_some_and(|rest| rest.starts_with("version") || rest.starts_with("encoding"));
+    match query.find(';') {
+        Some(end) if has_version_decl => {
+            format!("{} {}{}", &query[..=end], declaration, &query[end + 1..])
+        }
+        _ => format!("{} {}", declaration, query),
+    }
+}
+
+/// Describe a xust node
+///
+/// xust trees are `Rc`s and can't be read from the threads results may be
+/// sent to, so the node's path and identity are worked out here rather
+/// than on demand.
+fn node_info(node: &XustNode, locator: &mut NodeLocator) -> NodeInfo {
+    let node_type = match node.node_kind() {
+        NodeKind::Document => NodeType::Document,
+        NodeKind::Element => NodeType::Element,
+        NodeKind::Text => NodeType::Text,
+        NodeKind::Comment => NodeType::Comment,
+        NodeKind::ProcessingInstruction => NodeType::ProcessingInstruction,
+        NodeKind::Attribute => NodeType::Attribute,
+        NodeKind::Namespace => NodeType::Namespace,
+    };
+    let name = node.node_name().map(|qn| format!("{}", qn));
+    let namespace_uri = node
+        .node_name()
+        .map(|qn| qn.namespace().to_string())
+        .filter(|uri| !uri.is_empty());
+    let prefix = node
+        .node_name()
+        .map(|qn| qn.prefix().to_string())
+        .filter(|p| !p.is_empty());
+    let value = Some(node_xml(node));
+    let attributes = match node.node_kind() {
+        NodeKind::Element => element_attributes(node),
+        _ => Vec::new(),
+    };
+    let (path, identity) = locator.locate(node);
+    NodeInfo {
+        node_type,
+        name,
+        value,
+        attributes,
+        namespace_uri,
+        prefix,
+        location: Some(NodeLocation::Known { path, identity }),
+    }
+}
+
+/// Serialize a xust node, as xot does for xee nodes
+///
+/// Text and attribute nodes give their escaped string value. Elements
+/// declare the namespace of their name where it isn't already in scope.
+fn node_xml(node: &XustNode) -> String {
+    let mut out = String::new();
+    write_node_xml(node, &mut Vec::new(), &mut out);
+    out
+}
+
+/// Append the serialization of `node` to `out`, given the (prefix, URI)
+/// bindings declared by the elements around it
+fn write_node_xml(node: &XustNode, scope: &mut Vec<(String, String)>, out: &mut String) {
+    match node.node_kind() {
+        NodeKind::Document => {
+            for child in node.children() {
+                write_node_xml(&child, scope, out);
+            }
+        }
+        NodeKind::Element => {
+            let Some(qn) = node.node_name() else {
+                return;
+            };
+            let name = lexical_name(node);
+            let (prefix, uri) = (qn.prefix().to_string(), qn.namespace().to_string());
+            out.push('<');
+            out.push_str(&name);
+            let in_scope = scope.iter().rev().find(|(p, _)| *p == prefix).map(|(_, u)| u.as_str());
+            let declared = in_scope.unwrap_or("") != uri;
+            if declared {
+                match prefix.as_str() {
+                    "" => out.push_str(" xmlns"),
+                    prefix => out.push_str(&format!(" xmlns:{}", prefix)),
+                }
+                out.push_str(&format!("=\"{}\"", escape(&uri)));
+                scope.push((prefix, uri));
+            }
+            for attribute in node.attributes() {
+                let value = escape(&attribute.string_value().to_string());
+                out.push_str(&format!(" {}=\"{}\"", lexical_name(&attribute), value));
+            }
+            let mut children = node.children().peekable();
+            if children.peek().is_none() {
+                out.push_str("/>");
+            } else {
+                out.push('>');
+                for child in children {
+                    write_node_xml(&child, scope, out);
+                }
+                out.push_str("</");
+                out.push_str(&name);
+                out.push('>');
+            }
+            if declared {
+                scope.pop();
+            }
+        }
+        NodeKind::Text | NodeKind::Attribute | NodeKind::Namespace => {
+            out.push_str(&escape(&node.string_value().to_string()));
+        }
+        NodeKind::Comment => out.push_str(&format!("<!--{}-->", node.string_value())),
+        NodeKind::ProcessingInstruction => {
+            let target = node.node_name().map(|qn| qn.local_name().to_string()).unwrap_or_default();
+            out.push_str(&format!("<?{} {}?>", target, node.string_value()));
+        }
+    }
+}
+
+/// Name of an element or attribute as written, `prefix:local` or `local`
+fn lexical_name(node: &XustNode) -> String {
+    match node.node_name() {
+        Some(qn) if qn.prefix().is_empty() => qn.local_name().to_string(),
+        Some(qn) => format!("{}:{}", qn.prefix(), qn.local_name()),
+        None => String::new(),
+    }
+}
+
+/// Works out the XPath-like location paths (e.g. `/root[1]/item[2]`) and
+/// identities of the nodes of one result
+///
+/// Each depth keeps the path steps of the children of the parent last seen
+/// there, and where the last child was found among them. A result in
+/// document order then costs one pass over each parent's children rather
+/// than one per node.
+struct NodeLocator {
+    levels: Vec<Level>,
+    /// Root of each tree seen, with the document id its identities carry
+    roots: Vec<(XustNode, u64)>,
+}
+
+/// The children of one parent, with their path steps
+struct Level {
+    parent: XustNode,
+    children: Vec<XustNode>,
+    steps: Vec<String>,
+    attribute_count: usize,
+    cursor: usize,
+}
+
+impl NodeLocator {
+    /// Locate nodes of the tree under `root`, and of any other trees the
+    /// result holds, which get fresh document ids
+    fn new(root: &XustNode, document: u64) -> Self {
+        Self { levels: Vec::new(), roots: vec![(root.clone(), document)] }
+    }
+
+    fn locate(&mut self, node: &XustNode) -> (String, NodeId) {
+        let mut ancestors = vec![node.clone()];
+        while let Some(parent) = ancestors.last().and_then(|n| n.parent()) {
+            ancestors.push(parent);
+        }
+        ancestors.reverse();
+
+        let mut steps = Vec::with_capacity(ancestors.len());
+        // A parentless element (e.g. one built by the query) is its own first step
+        if ancestors[0].node_kind() != NodeKind::Document {
+            steps.push(format!("{}[1]", kind_test(&ancestors[0])));
+        }
+        let mut ordinals = Vec::with_capacity(ancestors.len());
+        for (depth, pair) in ancestors.windows(2).enumerate() {
+            let (step, ordinal) = self.step(depth, &pair[0], &pair[1]);
+            steps.push(step);
+            ordinals.push(ordinal);
+        }
+        let document = self.document(&ancestors[0]);
+        (format!("/{}", steps.join("/")), NodeId::new(document, ordinals))
+    }
+
+    /// Path step and ordinal for `child` of `parent`, which sits `depth`
+    /// levels down
+    ///
+    /// Attributes are numbered first, followed by child nodes, so
+    /// identities sort in document order.
+    fn step(&mut self, depth: usize, parent: &XustNode, child: &XustNode) -> (String, usize) {
+        if self.levels.get(depth).is_none_or(|level| level.parent != *parent) {
+            self.levels.truncate(depth);
+            self.levels.push(Level::new(parent));
+        }
+        let level = &mut self.levels[depth];
+        match child.node_kind() {
+            NodeKind::Attribute => {
+                let ordinal = parent.attributes().position(|a| a == *child).unwrap_or(0);
+                return (format!("@{}", kind_test(child)), ordinal);
+            }
+            NodeKind::Namespace => return ("namespace::*".to_string(), 0),
+            _ => {}
+        }
+        let count = level.children.len();
+        let found = (level.cursor..count)
+            .chain(0..level.cursor)
+            .find(|&i| level.children[i] == *child);
+        match found {
+            Some(i) => {
+                level.cursor = i;
+                (level.steps[i].clone(), level.attribute_count + i)
+            }
+            None => (format!("{}[1]", kind_test(child)), level.attribute_count),
+        }
+    }
+
+    /// Document id of the tree with this root
+    fn document(&mut self, root: &XustNode) -> u64 {
+        if let Some((_, id)) = self.roots.iter().find(|(known, _)| known == root) {
+            return *id;
+        }
+        let id = next_document_id();
+        self.roots.push((root.clone(), id));
+        id
+    }
+}
+
+impl Level {
+    fn new(parent: &XustNode) -> Self {
+        let children: Vec<XustNode> = parent.children().collect();
+        let mut positions = HashMap::new();
+        let steps = children
+            .iter()
+            .map(|child| {
+                let test = kind_test(child);
+                let name = child.node_name().map(|qn| qn.to_string());
+                let position = positions.entry((test.clone(), name)).or_insert(0);
+                *position += 1;
+                format!("{}[{}]", test, position)
+            })
+            .collect();
+        let attribute_count = parent.attributes().count();
+        Self { parent: parent.clone(), children, steps, attribute_count, cursor: 0 }
+    }
+}
+
+/// Node test selecting a node of this kind and name, without a predicate
+fn kind_test(node: &XustNode) -> String {
+    match node.node_kind() {
+        NodeKind::Text => "text()".to_string(),
+        NodeKind::Comment => "comment()".to_string(),
+        NodeKind::ProcessingInstruction => "processing-instruction()".to_string(),
+        _ => node.node_name().map(|qn| qn.local_name().to_string()).unwrap_or_default(),
+    }
+}
+
+/// Re-serialize a document into the normalized form the validator reads
+fn normalized_xml(doc: &XustDocument) -> Result<String> {
+    let bytes = doc.to_string()?.into_bytes();
+    let (_, normalized_xml) = xust_xml::read::decode_bytes(bytes)
+        .map_err(|e| Error::XsdError(format!("Failed to decode XML: {}", e)))?;
+    Ok(normalized_xml)
+}
+
+/// Get the name of the schema type a validated node was annotated with
+fn type_annotation(node: &XustNode) -> String {
+    let qn = node.type_annotation();
+    if qn.namespace() == "http://www.w3.org/2001/XMLSchema" {
+        format!("xs:{}", qn.local_name())
+    } else {
+        qn.to_string()
     }
 }
 
@@ -226,8 +1009,9 @@ impl XsltEngine for XustEngine {
 }
 
 impl XsdValidator for XustEngine {
+    #[cfg(feature = "native")]
     fn load_schema(&mut self, xsd: &str) -> Result<()> {
-        // Write XSD to a temp file and load it
+        // xust only loads schemas from files, so write the XSD to a temp file
         use std::io::Write;
         let mut temp_file = tempfile::NamedTempFile::new()
             .map_err(|e| Error::XsdError(format!("Failed to create temp file: {}", e)))?;
@@ -243,6 +1027,13 @@ impl XsdValidator for XustEngine {
         Ok(())
     }
 
+    #[cfg(not(feature = "native"))]
+    fn load_schema(&mut self, _xsd: &str) -> Result<()> {
+        // xust only loads schemas from files, and there's no temp file to
+        // write one to; load_schema_file still works
+        Err(Error::Unsupported)
+    }
+
     fn load_schema_file(&mut self, path: &Path) -> Result<()> {
         let validator = load_validator(&[path.to_path_buf()], None)
             .map_err(|e| Error::XsdError(format!("Failed to load schema: {}", e)))?;
@@ -251,26 +1042,17 @@ impl XsdValidator for XustEngine {
         Ok(())
     }
 
-    fn validate(&self, doc: &Self::Document) -> Result<ValidationResult> {
-        let validator = self
-            .validator
-            .as_ref()
-            .ok_or_else(|| Error::XsdError("No schema loaded".to_string()))?;
+    fn clear_schema(&mut self) {
+        self.validator = None;
+    }
 
-        // Re-serialize document for validation
-        let xml_str = doc.to_string()?;
-        let bytes = xml_str.as_bytes().to_vec();
-        let (_, normalized_xml) = xust_xml::read::decode_bytes(bytes)
-            .map_err(|e| Error::XsdError(format!("Failed to decode XML: {}", e)))?;
+    fn validate(&self, doc: &Self::Document) -> Result<ValidationResult> {
+        let validator = self.loaded_validator()?;
 
-        match validator.validate_to_tree(&normalized_xml, None) {
+        match validator.validate_to_tree(&normalized_xml(doc)?, None) {
             Ok(_) => Ok(ValidationResult::valid()),
             Err(e) => {
-                let errors = vec![ValidationError {
-                    message: e.to_string(),
-                    line: None,
-                    column: None,
-                }];
+                let errors = vec![validation_error(&e)];
                 Ok(ValidationResult::invalid(errors))
             }
         }
@@ -1,17 +1,24 @@
 //! Error types for x-engine
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+use crate::unified::Backend;
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Operation not supported by this engine")]
     Unsupported,
 
+    #[error("{0} is not supported by this engine")]
+    FeatureNotSupported(String),
+
     #[error("XML parsing error: {0}")]
     ParseError(String),
 
-    #[error("XPath evaluation error: {0}")]
-    XPathError(String),
+    /// `code` is the W3C error code (e.g. `FOAR0001`) when known
+    #[error("XPath evaluation error: {message}")]
+    XPathError { code: Option<String>, message: String },
 
     #[error("XQuery evaluation error: {0}")]
     XQueryError(String),
@@ -22,11 +29,137 @@ pub enum Error {
     #[error("XSD validation error: {0}")]
     XsdError(String),
 
+    #[error("Serialization error: {0}")]
+    SerializationError(String),
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 
     #[error("Engine error: {0}")]
     EngineError(String),
+
+    #[error(
+        "Document was created by the {} engine but used with the {} engine",
+        .got.name(),
+        .expected.name()
+    )]
+    BackendMismatch { expected: Backend, got: Backend },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+/// Backend-independent classification of an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ErrorClass {
+    /// Malformed input or a static error (e.g. `XPST0003`)
+    Syntax,
+    /// Type error (e.g. `XPTY0004`)
+    Type,
+    /// Dynamic (evaluation-time) error (e.g. `FOAR0001`)
+    Dynamic,
+    /// Feature not supported by the backend
+    Unsupported,
+    /// I/O failure
+    Io,
+    /// Schema validation failure
+    Validation,
+}
+
+impl ErrorClass {
+    /// Classify a W3C error code such as `XPST0003` or `FOAR0001`
+    pub fn from_code(code: &str) -> Option<Self> {
+        if code.len() != 8 || !code.is_ascii() {
+            return None;
+        }
+        let category = &code[2..4];
+        Some(match (&code[..2], category) {
+            (_, "ST") | (_, "SE") => Self::Syntax,
+            (_, "TY") | (_, "TE") => Self::Type,
+            ("XQ", "DY") | ("XP", "DY") | ("XT", "DE") | ("FO", _) | ("SE", _) => Self::Dynamic,
+            _ => return None,
+        })
+    }
+
+    /// Classify an error message, preferring an error code embedded in it
+    pub fn from_message(message: &str) -> Self {
+        if let Some(class) = find_error_code(message).and_then(Self::from_code) {
+            return class;
+        }
+        let lower = message.to_ascii_lowercase();
+        if lower.contains("not supported") || lower.contains("unsupported") {
+            Self::Unsupported
+        } else if lower.contains("parsing error") || lower.contains("parse error") {
+            Self::Syntax
+        } else if lower.contains("io error") || lower.contains("failed to read") {
+            Self::Io
+        } else if lower.contains("validation") {
+            Self::Validation
+        } else {
+            Self::Dynamic
+        }
+    }
+}
+
+impl Error {
+    /// Build an XPath error, taking the error code from the message if it
+    /// contains one
+    pub fn xpath(message: impl Into<String>) -> Self {
+        let message = message.into();
+        let code = find_error_code(&message).map(String::from);
+        Error::XPathError { code, message }
+    }
+
+    /// The W3C error code of this error, if known
+    ///
+    /// Structured codes are preferred; otherwise the first code-shaped word
+    /// in the message is used.
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Error::XPathError { code: Some(code), .. } => Some(code),
+            Error::XPathError { message, .. }
+            | Error::ParseError(message)
+            | Error::XQueryError(message)
+            | Error::XsltError(message)
+            | Error::XsdError(message)
+            | Error::SerializationError(message)
+            | Error::EngineError(message)
+            | Error::FeatureNotSupported(message) => find_error_code(message),
+            Error::Unsupported | Error::IoError(_) | Error::BackendMismatch { .. } => None,
+        }
+    }
+
+    /// Classify this error, independently of how the backend formatted it
+    pub fn classify(&self) -> ErrorClass {
+        match self {
+            Error::Unsupported | Error::FeatureNotSupported(_) => ErrorClass::Unsupported,
+            Error::ParseError(_) => ErrorClass::Syntax,
+            Error::IoError(_) => ErrorClass::Io,
+            Error::XsdError(_) => ErrorClass::Validation,
+            Error::BackendMismatch { .. } => ErrorClass::Dynamic,
+            Error::XPathError { .. }
+            | Error::XQueryError(_)
+            | Error::XsltError(_)
+            | Error::SerializationError(_)
+            | Error::EngineError(_) => {
+                self.code().and_then(ErrorClass::from_code).unwrap_or(ErrorClass::Dynamic)
+            }
+        }
+    }
+}
+
+/// Find the first W3C-style error code (four letters, four digits) in a message
+fn find_error_code(message: &str) -> Option<&str> {
+    let bytes = message.as_bytes();
+    (0..bytes.len().saturating_sub(7)).find_map(|i| {
+        let candidate = &bytes[i..i + 8];
+        let bounded = (i == 0 || !bytes[i - 1].is_ascii_alphanumeric())
+            && bytes.get(i + 8).is_none_or(|b| !b.is_ascii_alphanumeric());
+        let shaped = candidate[..4].iter().all(u8::is_ascii_uppercase)
+            && candidate[4..].iter().all(u8::is_ascii_digit);
+        if bounded && shaped {
+            Some(&message[i..i + 8])
+        } else {
+            None
+        }
+    })
+}
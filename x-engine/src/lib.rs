@@ -14,9 +14,20 @@
 //! println!("{}", result.to_string());
 //! ```
 
+pub mod canonical;
+pub mod collation;
+mod current_time;
+pub mod diagnostics;
 pub mod error;
+pub mod parse_limits;
+pub mod query_cache;
 pub mod result;
+pub mod sequence_type;
+pub mod serialization;
+pub mod shared;
 pub mod traits;
+pub mod unparsed_text;
+pub mod xinclude;
 
 pub mod engine_xee;
 pub mod engine_xrust;
@@ -27,9 +38,18 @@ pub mod testdriver;
 pub mod reporter;
 
 // Re-export core types
-pub use error::Error;
-pub use result::{NodeType, ResultItem, ValidationResult};
-pub use traits::{QueryResult, XmlDocument, XmlParser, XPathEngine, XQueryEngine, XsdValidator, XsltEngine};
+pub use canonical::{xml_equal, CompareOptions};
+pub use error::{Error, ErrorClass};
+pub use parse_limits::ParseLimits;
+pub use result::{
+    document_order_lt, NodeId, NodeType, ResultItem, ValidationError, ValidationErrorKind,
+    ValidationResult,
+};
+pub use traits::{Capability, DecimalFormat, QueryResult, TextResolver, XmlDocument, XmlParser, XPathEngine, XQueryEngine, XsdValidator, XsltEngine};
 
 // Re-export unified API
-pub use unified::{Backend, XDocument, XEngine, XQueryResult};
+pub use unified::{
+    compare_expression, roundtrip_diff, Backend, StylesheetHandle, XDocument, XEngine, XQueryResult,
+};
+pub use shared::{EnginePool, SharedDocument, SharedEngine};
+pub use testdriver::survey_backends;
@@ -0,0 +1,162 @@
+//! Limits for untrusted XML input
+//!
+//! Entity expansion ("billion laughs") and very deep nesting can exhaust
+//! memory or the stack inside a backend's parser before it reports
+//! anything. None of the backends exposes limits of its own, so the input
+//! is checked by a lightweight scan of the source text before parsing.
+
+use std::collections::HashMap;
+
+use crate::canonical::doctype_end;
+use crate::error::{Error, Result};
+use crate::serialization::markup_end;
+
+/// Caps on the size and shape of parsed XML
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Entity references expanded, including those inside entity values
+    pub max_entity_expansions: usize,
+    /// Element nesting depth
+    pub max_depth: usize,
+    /// Element, text, comment and processing-instruction nodes
+    pub max_nodes: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self { max_entity_expansions: 10_000, max_depth: 1_000, max_nodes: 10_000_000 }
+    }
+}
+
+impl ParseLimits {
+    /// Check `xml` against the limits
+    ///
+    /// Fails with a `limit exceeded` parse error. Malformed markup is left
+    /// for the parser to report.
+    pub fn check(&self, xml: &str) -> Result<()> {
+        let mut entities = HashMap::new();
+        let mut costs = HashMap::new();
+        let (mut depth, mut nodes, mut expansions) = (0usize, 0usize, 0usize);
+        let mut rest = xml;
+        while !rest.is_empty() {
+            let len = if rest.starts_with("<!DOCTYPE") {
+                let Ok(len) = doctype_end(rest) else {
+                    return Ok(());
+                };
+                declare_entities(&rest[..len], &mut entities);
+                len
+            } else if rest.starts_with('<') {
+                let Ok(len) = markup_end(rest) else {
+                    return Ok(());
+                };
+                len
+            } else {
+                rest.find('<').unwrap_or(rest.len())
+            };
+            let token = &rest[..len];
+            rest = &rest[len..];
+
+            if token.starts_with("</") {
+                depth = depth.saturating_sub(1);
+                continue;
+            }
+            if token.starts_with("<!DOCTYPE") || token.trim().is_empty() {
+                continue;
+            }
+            nodes += 1;
+            if nodes > self.max_nodes {
+                return Err(exceeded(format!("more than {} nodes", self.max_nodes)));
+            }
+            let is_markup = ["<!", "<?"].iter().any(|p| token.starts_with(p));
+            if token.starts_with('<') && !is_markup {
+                if depth >= self.max_depth {
+                    let limit = format!("elements nested deeper than {}", self.max_depth);
+                    return Err(exceeded(limit));
+                }
+                if !token.ends_with("/>") {
+                    depth += 1;
+                }
+            }
+            if !is_markup {
+                for name in entity_references(token) {
+                    let cost = expansion_cost(name, &entities, &mut costs, &mut Vec::new());
+                    expansions = expansions.saturating_add(cost);
+                    if expansions > self.max_entity_expansions {
+                        return Err(exceeded(format!(
+                            "more than {} entity expansions",
+                            self.max_entity_expansions
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn exceeded(limit: String) -> Error {
+    Error::ParseError(format!("limit exceeded: {}", limit))
+}
+
+/// Record the internal general entities declared in a DOCTYPE
+fn declare_entities<'a>(doctype: &'a str, entities: &mut HashMap<&'a str, &'a str>) {
+    let mut rest = doctype;
+    while let Some(start) = rest.find("<!ENTITY") {
+        rest = rest[start + "<!ENTITY".len()..].trim_start();
+        // Parameter entities only expand inside the DTD
+        if rest.starts_with('%') {
+            continue;
+        }
+        let name_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            // External entities aren't loaded
+            continue;
+        };
+        if let Some(end) = rest[1..].find(quote) {
+            entities.entry(name).or_insert(&rest[1..end + 1]);
+            rest = &rest[end + 2..];
+        }
+    }
+}
+
+/// Names of the entity references in `text`
+fn entity_references(text: &str) -> impl Iterator<Item = &str> {
+    text.split('&').skip(1).filter_map(|s| {
+        let name = &s[..s.find(';')?];
+        let valid = !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || c == '<');
+        valid.then_some(name)
+    })
+}
+
+/// Number of expansions a reference to entity `name` causes
+///
+/// Predefined entities and character references don't count. Recursive
+/// entities are left for the parser to reject.
+fn expansion_cost<'a>(
+    name: &'a str,
+    entities: &HashMap<&'a str, &'a str>,
+    costs: &mut HashMap<&'a str, usize>,
+    expanding: &mut Vec<&'a str>,
+) -> usize {
+    if name.starts_with('#') || matches!(name, "lt" | "gt" | "amp" | "quot" | "apos") {
+        return 0;
+    }
+    if let Some(cost) = costs.get(name) {
+        return *cost;
+    }
+    let Some(value) = entities.get(name) else {
+        return 1;
+    };
+    if expanding.contains(&name) {
+        return 0;
+    }
+    expanding.push(name);
+    let cost = entity_references(*value).fold(1usize, |cost, reference| {
+        cost.saturating_add(expansion_cost(reference, entities, costs, expanding))
+    });
+    expanding.pop();
+    costs.insert(name, cost);
+    cost
+}
@@ -0,0 +1,82 @@
+//! Per-engine query result cache
+//!
+//! Catalog parsing evaluates the same `count(...)`/`string(...)` expressions
+//! against one document over and over. A [`QueryCache`] remembers recent
+//! results keyed by document and expression, evicting the least recently
+//! used entry once full.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_DOCUMENT_ID: AtomicU64 = AtomicU64::new(1);
+static NEXT_ENGINE_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Allocate a process-unique document identifier
+pub(crate) fn next_document_id() -> u64 {
+    NEXT_DOCUMENT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Allocate a process-unique engine identifier
+pub(crate) fn next_engine_id() -> u64 {
+    NEXT_ENGINE_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Bounded LRU cache of query results keyed by `(document id, expression)`
+#[derive(Debug, Clone)]
+pub struct QueryCache<R> {
+    capacity: usize,
+    /// Entries in recency order, most recently used last
+    entries: VecDeque<((u64, String), R)>,
+    hits: usize,
+}
+
+impl<R: Clone> QueryCache<R> {
+    /// Create a cache holding at most `capacity` results
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: VecDeque::with_capacity(capacity), hits: 0 }
+    }
+
+    /// Look up a cached result, marking it as recently used
+    pub fn get(&mut self, document: u64, expression: &str) -> Option<R> {
+        let pos = self
+            .entries
+            .iter()
+            .position(|((doc, expr), _)| *doc == document && expr == expression)?;
+        let entry = self.entries.remove(pos)?;
+        let result = entry.1.clone();
+        self.entries.push_back(entry);
+        self.hits += 1;
+        Some(result)
+    }
+
+    /// Store a result, evicting the least recently used entry if full
+    pub fn insert(&mut self, document: u64, expression: &str, result: R) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(((document, expression.to_string()), result));
+    }
+
+    /// Drop every cached result
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Number of lookups answered from the cache
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Number of results currently cached
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether the cache holds no results
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
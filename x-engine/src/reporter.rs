@@ -2,13 +2,18 @@
 //!
 //! Generates reports showing how each engine performs against W3C specs.
 
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::error::{Error, ErrorClass, Result};
 use crate::testdriver::{TestOutcome, TestResult};
 
 /// Summary of compliance test results
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct ComplianceSummary {
     pub total: usize,
     pub passed: usize,
@@ -17,6 +22,35 @@ pub struct ComplianceSummary {
     pub not_applicable: usize,
     pub skipped: usize,
     pub pass_rate: f64,
+    #[serde(default)]
+    pub total_duration_ms: u64,
+    #[serde(default)]
+    pub mean_duration_ms: f64,
+    /// Known failures that failed; not counted against the pass rate
+    #[serde(default)]
+    pub expected_failures: usize,
+    /// Known failures that passed; counted as passes in the pass rate
+    #[serde(default)]
+    pub unexpected_passes: usize,
+    /// Exact total behind `total_duration_ms`, so sub-millisecond
+    /// durations still add up when streaming results
+    #[serde(default, rename = "total_duration_ns", with = "nanoseconds")]
+    total_duration: Duration,
+}
+
+/// (De)serialize a `Duration` as whole nanoseconds
+mod nanoseconds {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_nanos)
+    }
 }
 
 impl ComplianceSummary {
@@ -33,28 +67,117 @@ impl ComplianceSummary {
             .iter()
             .filter(|r| matches!(r.outcome, TestOutcome::Skipped))
             .count();
+        let expected_failures = results
+            .iter()
+            .filter(|r| matches!(r.outcome, TestOutcome::ExpectedFail(_)))
+            .count();
+        let unexpected_passes = results
+            .iter()
+            .filter(|r| matches!(r.outcome, TestOutcome::UnexpectedPass))
+            .count();
 
-        let applicable = total - not_applicable - skipped;
-        let pass_rate = if applicable > 0 {
-            (passed as f64 / applicable as f64) * 100.0
+        let total_duration: Duration = results.iter().map(|r| r.duration).sum();
+        let mean_duration_ms = if total > 0 {
+            total_duration.as_secs_f64() * 1000.0 / total as f64
         } else {
             0.0
         };
 
-        Self {
+        let mut summary = Self {
             total,
             passed,
             failed,
             errors,
             not_applicable,
             skipped,
-            pass_rate,
+            pass_rate: 0.0,
+            total_duration_ms: total_duration.as_millis() as u64,
+            mean_duration_ms,
+            expected_failures,
+            unexpected_passes,
+            total_duration,
+        };
+        summary.update_pass_rate();
+        summary
+    }
+
+    /// Add a single result to the summary, e.g. while streaming a run
+    pub fn add(&mut self, result: &TestResult) {
+        self.total += 1;
+        match result.outcome {
+            TestOutcome::Pass => self.passed += 1,
+            TestOutcome::Fail(_) => self.failed += 1,
+            TestOutcome::Error(_) => self.errors += 1,
+            TestOutcome::NotApplicable => self.not_applicable += 1,
+            TestOutcome::Skipped => self.skipped += 1,
+            TestOutcome::ExpectedFail(_) => self.expected_failures += 1,
+            TestOutcome::UnexpectedPass => self.unexpected_passes += 1,
+        }
+        self.update_pass_rate();
+        self.total_duration += result.duration;
+        self.total_duration_ms = self.total_duration.as_millis() as u64;
+        self.mean_duration_ms = self.total_duration.as_secs_f64() * 1000.0 / self.total as f64;
+    }
+
+    /// Recompute the pass rate over applicable tests
+    ///
+    /// Not-applicable, skipped and expected-fail tests are left out.
+    fn update_pass_rate(&mut self) {
+        let applicable =
+            self.total - self.not_applicable - self.skipped - self.expected_failures;
+        let passed = self.passed + self.unexpected_passes;
+        self.pass_rate = if applicable > 0 {
+            (passed as f64 / applicable as f64) * 100.0
+        } else {
+            0.0
+        };
+    }
+}
+
+// ============== Exit Policy ==============
+
+/// Thresholds a run must meet for the CLI to exit successfully
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExitPolicy {
+    /// Minimum pass rate, as a percentage of applicable tests
+    pub min_pass_rate: Option<f64>,
+    /// Maximum number of failed plus errored tests
+    pub max_failures: Option<usize>,
+}
+
+impl ExitPolicy {
+    /// Decide the exit code for a run
+    ///
+    /// Returns 0 when all thresholds hold (or none are set) and 1 otherwise,
+    /// along with a description of the decision when thresholds are set.
+    pub fn decide(&self, summary: &ComplianceSummary) -> (i32, Option<String>) {
+        if self.min_pass_rate.is_none() && self.max_failures.is_none() {
+            return (0, None);
+        }
+
+        let mut violations = Vec::new();
+        if let Some(min) = self.min_pass_rate {
+            if summary.pass_rate < min {
+                violations.push(format!("pass rate {:.1}% is below {:.1}%", summary.pass_rate, min));
+            }
+        }
+        if let Some(max) = self.max_failures {
+            let failures = summary.failed + summary.errors;
+            if failures > max {
+                violations.push(format!("{} failures exceed the maximum of {}", failures, max));
+            }
+        }
+
+        if violations.is_empty() {
+            (0, Some("Thresholds met; exiting with 0".to_string()))
+        } else {
+            (1, Some(format!("Thresholds violated ({}); exiting with 1", violations.join(", "))))
         }
     }
 }
 
 /// A compliance report for a single engine
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ComplianceReport {
     pub engine: String,
     pub timestamp: DateTime<Utc>,
@@ -65,7 +188,7 @@ pub struct ComplianceReport {
 }
 
 /// Detailed test result for serialization (includes all metadata)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DetailedTestResult {
     pub test_id: String,
     pub test_set: String,
@@ -76,6 +199,10 @@ pub struct DetailedTestResult {
     pub expected: Option<String>,
     pub actual: Option<String>,
     pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub covers: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
 }
 
 impl From<&TestResult> for DetailedTestResult {
@@ -86,6 +213,8 @@ impl From<&TestResult> for DetailedTestResult {
             TestOutcome::Error(msg) => ("error".to_string(), Some(msg.clone())),
             TestOutcome::NotApplicable => ("n/a".to_string(), None),
             TestOutcome::Skipped => ("skipped".to_string(), None),
+            TestOutcome::ExpectedFail(msg) => ("xfail".to_string(), Some(msg.clone())),
+            TestOutcome::UnexpectedPass => ("xpass".to_string(), None),
         };
 
         Self {
@@ -98,6 +227,8 @@ impl From<&TestResult> for DetailedTestResult {
             expected: r.expected.clone(),
             actual: r.actual.clone(),
             duration_ms: r.duration.as_millis() as u64,
+            covers: r.covers.clone(),
+            modified: r.modified.clone(),
         }
     }
 }
@@ -119,6 +250,8 @@ impl From<&TestResult> for TestResultSummary {
             TestOutcome::Error(msg) => ("error".to_string(), Some(msg.clone())),
             TestOutcome::NotApplicable => ("not_applicable".to_string(), None),
             TestOutcome::Skipped => ("skipped".to_string(), None),
+            TestOutcome::ExpectedFail(msg) => ("expected_fail".to_string(), Some(msg.clone())),
+            TestOutcome::UnexpectedPass => ("unexpected_pass".to_string(), None),
         };
 
         Self {
@@ -145,84 +278,445 @@ impl ComplianceReport {
         }
     }
 
+    /// Return a copy keeping only results whose outcome is in `keep`
+    ///
+    /// Outcomes are matched by their short code (`pass`, `fail`, `error`,
+    /// `n/a`, `skipped`, `xfail`, `xpass`). The summary still describes the
+    /// whole run.
+    pub fn filtered(&self, keep: &[&str]) -> ComplianceReport {
+        ComplianceReport {
+            results: self
+                .results
+                .iter()
+                .filter(|r| keep.contains(&r.outcome.as_str()))
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Count failed and errored tests per covered spec area, most failures first
+    ///
+    /// A test covering several areas (whitespace-separated) counts towards each.
+    pub fn failures_by_coverage(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for r in self.results.iter().filter(|r| r.outcome == "fail" || r.outcome == "error") {
+            if let Some(covers) = &r.covers {
+                for area in covers.split_whitespace() {
+                    *counts.entry(area).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut buckets: Vec<(String, usize)> =
+            counts.into_iter().map(|(area, n)| (area.to_string(), n)).collect();
+        buckets.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        buckets
+    }
+
+    /// Count errored tests per error class, most common first
+    pub fn errors_by_class(&self) -> Vec<(ErrorClass, usize)> {
+        let mut counts: HashMap<ErrorClass, usize> = HashMap::new();
+        for r in self.results.iter().filter(|r| r.outcome == "error") {
+            let class = ErrorClass::from_message(r.message.as_deref().unwrap_or_default());
+            *counts.entry(class).or_insert(0) += 1;
+        }
+
+        let mut buckets: Vec<(ErrorClass, usize)> = counts.into_iter().collect();
+        buckets.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        buckets
+    }
+
+    /// Get the `n` slowest tests, slowest first
+    pub fn slowest_tests(&self, n: usize) -> Vec<&DetailedTestResult> {
+        let mut by_duration: Vec<_> = self.results.iter().collect();
+        by_duration.sort_by(|a, b| b.duration_ms.cmp(&a.duration_ms));
+        by_duration.truncate(n);
+        by_duration
+    }
+
     /// Generate a markdown report
     pub fn to_markdown(&self) -> String {
-        let mut md = String::new();
-
-        md.push_str(&format!("# {} Compliance Report\n\n", self.engine));
-        md.push_str(&format!("**Suite:** {}\n", self.suite));
-        md.push_str(&format!("**Date:** {}\n\n", self.timestamp.format("%Y-%m-%d %H:%M:%S UTC")));
-
-        md.push_str("## Summary\n\n");
-        md.push_str("| Metric | Count |\n");
-        md.push_str("|--------|-------|\n");
-        md.push_str(&format!("| Total | {} |\n", self.summary.total));
-        md.push_str(&format!("| Passed | {} |\n", self.summary.passed));
-        md.push_str(&format!("| Failed | {} |\n", self.summary.failed));
-        md.push_str(&format!("| Errors | {} |\n", self.summary.errors));
-        md.push_str(&format!("| Not Applicable | {} |\n", self.summary.not_applicable));
-        md.push_str(&format!("| Skipped | {} |\n", self.summary.skipped));
-        md.push_str(&format!("| **Pass Rate** | **{:.2}%** |\n\n", self.summary.pass_rate));
+        render(|out| self.write_markdown(out))
+    }
+
+    /// Write a markdown report, streaming it to `w`
+    pub fn write_markdown<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        writeln!(w, "# {} Compliance Report\n", self.engine)?;
+        writeln!(w, "**Suite:** {}", self.suite)?;
+        writeln!(w, "**Date:** {}\n", self.timestamp.format("%Y-%m-%d %H:%M:%S UTC"))?;
+
+        writeln!(w, "## Summary\n")?;
+        writeln!(w, "| Metric | Count |")?;
+        writeln!(w, "|--------|-------|")?;
+        writeln!(w, "| Total | {} |", self.summary.total)?;
+        writeln!(w, "| Passed | {} |", self.summary.passed)?;
+        writeln!(w, "| Failed | {} |", self.summary.failed)?;
+        writeln!(w, "| Errors | {} |", self.summary.errors)?;
+        writeln!(w, "| Not Applicable | {} |", self.summary.not_applicable)?;
+        writeln!(w, "| Skipped | {} |", self.summary.skipped)?;
+        if self.summary.expected_failures + self.summary.unexpected_passes > 0 {
+            writeln!(w, "| Expected Failures | {} |", self.summary.expected_failures)?;
+            writeln!(w, "| Unexpected Passes | {} |", self.summary.unexpected_passes)?;
+        }
+        writeln!(w, "| Total Duration | {} ms |", self.summary.total_duration_ms)?;
+        writeln!(w, "| Mean Duration | {:.1} ms |", self.summary.mean_duration_ms)?;
+        writeln!(w, "| **Pass Rate** | **{:.2}%** |\n", self.summary.pass_rate)?;
 
         if !self.results.is_empty() {
-            md.push_str("## Failed Tests\n\n");
+            writeln!(w, "## Failed Tests\n")?;
             let failed: Vec<_> = self.results.iter().filter(|r| r.outcome == "fail" || r.outcome == "error").collect();
 
             if failed.is_empty() {
-                md.push_str("No failed tests!\n\n");
+                writeln!(w, "No failed tests!\n")?;
             } else {
-                md.push_str("| Test Set | Test ID | Outcome | Message |\n");
-                md.push_str("|----------|---------|---------|--------|\n");
+                writeln!(w, "| Test Set | Test ID | Outcome | Message |")?;
+                writeln!(w, "|----------|---------|---------|--------|")?;
                 for r in failed.iter().take(100) {
-                    md.push_str(&format!(
-                        "| {} | {} | {} | {} |\n",
+                    let first_line = r.message.as_deref().and_then(|m| m.lines().next()).unwrap_or("-");
+                    writeln!(
+                        w,
+                        "| {} | {} | {} | {} |",
                         r.test_set,
                         r.test_id,
                         r.outcome,
-                        r.message.as_deref().unwrap_or("-").chars().take(50).collect::<String>()
-                    ));
+                        first_line.chars().take(50).collect::<String>()
+                    )?;
                 }
                 if failed.len() > 100 {
-                    md.push_str(&format!("\n... and {} more failed tests\n", failed.len() - 100));
+                    writeln!(w, "\n... and {} more failed tests", failed.len() - 100)?;
+                }
+
+                // Multi-line messages carry an expected/actual diff
+                let diffs: Vec<_> = failed
+                    .iter()
+                    .take(100)
+                    .filter(|r| r.message.as_deref().is_some_and(|m| m.contains('\n')))
+                    .collect();
+                if !diffs.is_empty() {
+                    writeln!(w, "\n## Failure Diffs\n")?;
+                    for r in diffs {
+                        let message = r.message.as_deref().unwrap_or_default();
+                        let (summary, diff) = message.split_once('\n').unwrap_or((message, ""));
+                        writeln!(w, "### {} / {}\n", r.test_set, r.test_id)?;
+                        writeln!(w, "{}\n\n```diff\n{}\n```\n", summary, diff)?;
+                    }
                 }
             }
+
+            let error_classes = self.errors_by_class();
+            if !error_classes.is_empty() {
+                writeln!(w, "## Errors by Class\n")?;
+                writeln!(w, "| Class | Errors |")?;
+                writeln!(w, "|-------|--------|")?;
+                for (class, count) in &error_classes {
+                    writeln!(w, "| {:?} | {} |", class, count)?;
+                }
+                writeln!(w)?;
+            }
+
+            let coverage = self.failures_by_coverage();
+            if !coverage.is_empty() {
+                writeln!(w, "## Failures by Coverage\n")?;
+                writeln!(w, "| Covers | Failures |")?;
+                writeln!(w, "|--------|----------|")?;
+                for (area, count) in coverage.iter().take(20) {
+                    writeln!(w, "| {} | {} |", area, count)?;
+                }
+                writeln!(w)?;
+            }
+
+            writeln!(w, "## Slowest Tests\n")?;
+            writeln!(w, "| Test Set | Test ID | Outcome | Duration |")?;
+            writeln!(w, "|----------|---------|---------|----------|")?;
+            for r in self.slowest_tests(10) {
+                writeln!(
+                    w,
+                    "| {} | {} | {} | {} ms |",
+                    r.test_set, r.test_id, r.outcome, r.duration_ms
+                )?;
+            }
+            writeln!(w)?;
         }
 
-        md
+        Ok(())
     }
 
     /// Generate a JSON report
     pub fn to_json(&self) -> String {
-        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+        render(|out| self.write_json(out))
+    }
+
+    /// Write a JSON report, streaming it to `w`
+    pub fn write_json<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        serde_json::to_writer_pretty(w, self).map_err(io::Error::from)
+    }
+
+    /// Encode the report with bincode, for storing baselines
+    ///
+    /// Much smaller and faster to load than JSON; read it back with
+    /// [`from_bincode`](Self::from_bincode).
+    pub fn to_bincode(&self) -> Result<Vec<u8>> {
+        bincode::serialize(&BinaryReport::from(self))
+            .map_err(|e| Error::SerializationError(format!("Cannot encode report: {}", e)))
+    }
+
+    /// Decode a report written by [`to_bincode`](Self::to_bincode)
+    pub fn from_bincode(bytes: &[u8]) -> Result<Self> {
+        bincode::deserialize::<BinaryReport>(bytes)
+            .map(Self::from)
+            .map_err(|e| Error::SerializationError(format!("Invalid bincode report: {}", e)))
     }
 
     /// Generate a CSV report with all test results
     pub fn to_csv(&self) -> String {
-        let mut csv = String::new();
+        render(|out| self.write_csv(out))
+    }
 
-        // Header
-        csv.push_str("test_suite,test_set,test_id,description,outcome,message,duration_ms\n");
+    /// Write a CSV report with all test results, streaming it to `w`
+    pub fn write_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.write_csv_rows(w, true)
+    }
 
-        // Data rows
+    /// Write one CSV row per result, optionally preceded by the header
+    ///
+    /// Write the header with the first report only to append several
+    /// reports to one CSV file.
+    pub fn write_csv_rows<W: Write>(&self, w: &mut W, include_header: bool) -> io::Result<()> {
+        if include_header {
+            writeln!(w, "{}", CSV_HEADER)?;
+        }
         for r in &self.results {
-            let desc = r.description.as_deref().unwrap_or("").replace('"', "\"\"");
-            let msg = r.message.as_deref().unwrap_or("").replace('"', "\"\"");
-            csv.push_str(&format!(
-                "{},{},{},\"{}\",{},\"{}\",{}\n",
-                r.test_suite,
-                r.test_set,
-                r.test_id,
-                desc,
-                r.outcome,
-                msg,
-                r.duration_ms
-            ));
+            writeln!(w, "{}", r.to_csv_row())?;
         }
+        Ok(())
+    }
+}
 
-        csv
+/// Bincode form of a [`ComplianceReport`]
+///
+/// Bincode isn't self-describing, so unlike the JSON form it can't skip
+/// empty fields; these mirrors always write every field.
+#[derive(Serialize, Deserialize)]
+struct BinaryReport {
+    engine: String,
+    timestamp: DateTime<Utc>,
+    suite: String,
+    summary: ComplianceSummary,
+    results: Vec<BinaryResult>,
+}
+
+/// Bincode form of a [`DetailedTestResult`]
+#[derive(Serialize, Deserialize)]
+struct BinaryResult {
+    test_id: String,
+    test_set: String,
+    test_suite: String,
+    description: Option<String>,
+    outcome: String,
+    message: Option<String>,
+    expected: Option<String>,
+    actual: Option<String>,
+    duration_ms: u64,
+    covers: Option<String>,
+    modified: Option<String>,
+}
+
+impl From<&ComplianceReport> for BinaryReport {
+    fn from(report: &ComplianceReport) -> Self {
+        let results = report
+            .results
+            .iter()
+            .cloned()
+            .map(|r| BinaryResult {
+                test_id: r.test_id,
+                test_set: r.test_set,
+                test_suite: r.test_suite,
+                description: r.description,
+                outcome: r.outcome,
+                message: r.message,
+                expected: r.expected,
+                actual: r.actual,
+                duration_ms: r.duration_ms,
+                covers: r.covers,
+                modified: r.modified,
+            })
+            .collect();
+        Self {
+            engine: report.engine.clone(),
+            timestamp: report.timestamp,
+            suite: report.suite.clone(),
+            summary: report.summary.clone(),
+            results,
+        }
     }
 }
 
+impl From<BinaryReport> for ComplianceReport {
+    fn from(report: BinaryReport) -> Self {
+        let results = report
+            .results
+            .into_iter()
+            .map(|r| DetailedTestResult {
+                test_id: r.test_id,
+                test_set: r.test_set,
+                test_suite: r.test_suite,
+                description: r.description,
+                outcome: r.outcome,
+                message: r.message,
+                expected: r.expected,
+                actual: r.actual,
+                duration_ms: r.duration_ms,
+                covers: r.covers,
+                modified: r.modified,
+            })
+            .collect();
+        Self {
+            engine: report.engine,
+            timestamp: report.timestamp,
+            suite: report.suite,
+            summary: report.summary,
+            results,
+        }
+    }
+}
+
+/// Render a report written by `write` into a string
+fn render(write: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+    let mut out = Vec::new();
+    // Writing to a Vec can't fail, and reports are built from UTF-8 strings
+    let _ = write(&mut out);
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Header line for CSV reports
+pub const CSV_HEADER: &str = "test_suite,test_set,test_id,description,outcome,message,duration_ms";
+
+impl DetailedTestResult {
+    /// Format this result as a single CSV row (without trailing newline)
+    ///
+    /// Fields are quoted per RFC 4180, so a row may span several lines when
+    /// a field contains a newline.
+    pub fn to_csv_row(&self) -> String {
+        [
+            self.test_suite.as_str(),
+            self.test_set.as_str(),
+            self.test_id.as_str(),
+            self.description.as_deref().unwrap_or(""),
+            self.outcome.as_str(),
+            self.message.as_deref().unwrap_or(""),
+            &self.duration_ms.to_string(),
+        ]
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Number of failures the console summary lists unless asked for all
+pub const CONSOLE_FAILURE_LIMIT: usize = 10;
+
+/// Write the outcome counts shown by the console summary
+pub fn write_console_summary<W: Write>(w: &mut W, summary: &ComplianceSummary) -> io::Result<()> {
+    let passed_percent = if summary.total > 0 {
+        (summary.passed as f64 / summary.total as f64) * 100.0
+    } else {
+        0.0
+    };
+    writeln!(w, "Results:")?;
+    writeln!(w, "  Total:          {}", summary.total)?;
+    writeln!(w, "  Passed:         {} ({:.1}%)", summary.passed, passed_percent)?;
+    writeln!(w, "  Failed:         {}", summary.failed)?;
+    writeln!(w, "  Errors:         {}", summary.errors)?;
+    writeln!(w, "  Not Applicable: {}", summary.not_applicable)?;
+    if summary.expected_failures + summary.unexpected_passes > 0 {
+        writeln!(w, "  XFail:          {}", summary.expected_failures)?;
+        writeln!(w, "  XPass:          {}", summary.unexpected_passes)?;
+    }
+    writeln!(w)
+}
+
+/// Write failed and errored tests, one line each
+///
+/// Lists the first [`CONSOLE_FAILURE_LIMIT`] failures, or every failure when
+/// `all` is set. Only the first line of each outcome message is shown.
+pub fn write_failure_list<W: Write>(w: &mut W, results: &[TestResult], all: bool) -> io::Result<()> {
+    let limit = if all { usize::MAX } else { CONSOLE_FAILURE_LIMIT };
+    let failures: Vec<&TestResult> = results
+        .iter()
+        .filter(|r| r.outcome.is_fail() || r.outcome.is_error())
+        .take(limit)
+        .collect();
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    if all {
+        writeln!(w, "{} failures:", failures.len())?;
+    } else {
+        writeln!(w, "First {} failures:", failures.len())?;
+    }
+    for r in &failures {
+        let outcome = r.outcome.to_string();
+        writeln!(w, "  {}: {}", r.test_id, outcome.lines().next().unwrap_or(""))?;
+    }
+    writeln!(w)
+}
+
+/// Render a line-oriented diff between expected and actual values
+///
+/// Lines only in `expected` are prefixed with `- `, lines only in `actual`
+/// with `+ `, and common lines with two spaces. The alignment is computed
+/// with a longest-common-subsequence table, which is fine for the sizes of
+/// values seen in test assertions.
+pub fn render_diff(expected: &str, actual: &str) -> String {
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let (n, m) = (expected_lines.len(), actual_lines.len());
+
+    // lcs[i][j] = LCS length of expected_lines[i..] and actual_lines[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if expected_lines[i] == actual_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if expected_lines[i] == actual_lines[j] {
+            out.push(format!("  {}", expected_lines[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push(format!("- {}", expected_lines[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", actual_lines[j]));
+            j += 1;
+        }
+    }
+    out.extend(expected_lines[i..].iter().map(|l| format!("- {}", l)));
+    out.extend(actual_lines[j..].iter().map(|l| format!("+ {}", l)));
+
+    out.join("\n")
+}
+
 /// Comparison report across multiple engines
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ComparisonReport {
@@ -241,10 +735,13 @@ pub struct EngineSummary {
 }
 
 /// Compare reports from multiple engines
+///
+/// Engines are sorted by name, so the output doesn't depend on the order
+/// the reports were passed in.
 pub fn compare_reports(reports: &[ComplianceReport]) -> ComparisonReport {
     let suite = reports.first().map(|r| r.suite.clone()).unwrap_or_default();
 
-    let engines = reports
+    let mut engines: Vec<EngineSummary> = reports
         .iter()
         .map(|r| EngineSummary {
             name: r.engine.clone(),
@@ -253,6 +750,7 @@ pub fn compare_reports(reports: &[ComplianceReport]) -> ComparisonReport {
             pass_rate: r.summary.pass_rate,
         })
         .collect();
+    engines.sort_by(|a, b| a.name.cmp(&b.name));
 
     ComparisonReport {
         timestamp: Utc::now(),
@@ -260,3 +758,47 @@ pub fn compare_reports(reports: &[ComplianceReport]) -> ComparisonReport {
         engines,
     }
 }
+
+/// Render a markdown grid of engines × suites with each combination's pass rate
+///
+/// Rows and columns follow the order engines and suites first appear in
+/// `reports`. Combinations without a report are shown as `—`.
+pub fn feature_matrix(reports: &[ComplianceReport]) -> String {
+    let mut engines: Vec<&str> = Vec::new();
+    let mut suites: Vec<&str> = Vec::new();
+    for report in reports {
+        if !engines.contains(&report.engine.as_str()) {
+            engines.push(&report.engine);
+        }
+        if !suites.contains(&report.suite.as_str()) {
+            suites.push(&report.suite);
+        }
+    }
+
+    let mut md = String::new();
+    md.push_str("| Engine |");
+    for suite in &suites {
+        md.push_str(&format!(" {} |", suite));
+    }
+    md.push('\n');
+    md.push_str("|--------|");
+    for suite in &suites {
+        md.push_str(&format!("{}|", "-".repeat(suite.len() + 2)));
+    }
+    md.push('\n');
+
+    for engine in &engines {
+        md.push_str(&format!("| {} |", engine));
+        for suite in &suites {
+            match reports.iter().find(|r| r.engine == *engine && r.suite == *suite) {
+                Some(report) => md.push_str(&format!(" {:.1}% |", report.summary.pass_rate)),
+                None => md.push_str(" — |"),
+            }
+        }
+        md.push('\n');
+    }
+
+    md.push_str("\n**Legend:** pass rate = passed / applicable tests (excluding n/a and skipped); ");
+    md.push_str("— = not run\n");
+    md
+}
@@ -1,7 +1,12 @@
 //! Result types for x-engine
 
+use std::fmt;
+use std::sync::Arc;
+
 use serde::{Deserialize, Serialize};
 
+use crate::serialization::{apply_output_method, OutputMethod};
+
 /// XML node type
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NodeType {
@@ -14,12 +19,131 @@ pub enum NodeType {
     Namespace,
 }
 
+/// Opaque, stable identity of a node
+///
+/// Built from the id of the node's document and the node's child ordinals
+/// along its ancestor chain (attributes and namespaces ordered before child
+/// nodes), so the same node selected by two different queries has equal
+/// identities, nodes of different documents never do, and identities within
+/// a document order by document order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NodeId {
+    document: u64,
+    ordinals: Vec<usize>,
+}
+
+impl NodeId {
+    pub(crate) fn new(document: u64, ordinals: Vec<usize>) -> Self {
+        Self { document, ordinals }
+    }
+}
+
+/// Check whether node `a` precedes node `b` in document order
+///
+/// Nodes of different documents are ordered by document, which is stable
+/// but otherwise arbitrary.
+pub fn document_order_lt(a: &NodeId, b: &NodeId) -> bool {
+    (a.document, &a.ordinals) < (b.document, &b.ordinals)
+}
+
 /// Information about an XML node
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeInfo {
     pub node_type: NodeType,
     pub name: Option<String>,
     pub value: Option<String>,
+    /// Attributes (local name, value) of an element, in document order
+    #[serde(default)]
+    pub attributes: Vec<(String, String)>,
+    /// Namespace URI of an element or attribute name, if it has one
+    #[serde(default)]
+    pub namespace_uri: Option<String>,
+    /// Prefix the name is written with, if any
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Where the node sits in its tree; not serialized
+    #[serde(skip)]
+    pub(crate) location: Option<NodeLocation>,
+}
+
+impl NodeInfo {
+    /// XPath-like location of the node in its tree (e.g. `/root[1]/item[2]`)
+    ///
+    /// Built when called, by walking the node's ancestors. `None` for
+    /// xrust nodes and deserialized ones.
+    pub fn path(&self) -> Option<String> {
+        self.location.as_ref().map(NodeLocation::path)
+    }
+
+    /// Identity of the node, see [`NodeId`]
+    ///
+    /// Built when called, like [`path`](Self::path). `None` for xrust nodes,
+    /// whose trees don't expose a stable position, and deserialized ones.
+    pub fn identity(&self) -> Option<NodeId> {
+        self.location.as_ref().map(NodeLocation::identity)
+    }
+
+    /// Serialize the node again from its tree, keeping the namespace
+    /// prefixes in scope there
+    ///
+    /// `None` where the backend can't, which leaves [`value`](Self::value).
+    pub(crate) fn serialize_from_tree(&self) -> Option<crate::error::Result<String>> {
+        match self.location.as_ref()? {
+            NodeLocation::Known { .. } => None,
+            NodeLocation::Tree(node) => node.serialize(),
+        }
+    }
+}
+
+/// Where a node sits in its backend's tree
+#[derive(Clone)]
+pub(crate) enum NodeLocation {
+    /// Worked out when the node was described
+    Known { path: String, identity: NodeId },
+    /// Worked out from the tree when asked for
+    Tree(Arc<dyn LocateNode>),
+}
+
+impl NodeLocation {
+    fn path(&self) -> String {
+        match self {
+            Self::Known { path, .. } => path.clone(),
+            Self::Tree(node) => node.path(),
+        }
+    }
+
+    fn identity(&self) -> NodeId {
+        match self {
+            Self::Known { identity, .. } => identity.clone(),
+            Self::Tree(node) => node.identity(),
+        }
+    }
+}
+
+impl fmt::Debug for NodeLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Known { path, identity } => f
+                .debug_struct("Known")
+                .field("path", path)
+                .field("identity", identity)
+                .finish(),
+            Self::Tree(_) => f.write_str("Tree"),
+        }
+    }
+}
+
+/// A node in a tree that can be read from any thread
+pub(crate) trait LocateNode: Send + Sync {
+    /// Build the node's XPath-like location path
+    fn path(&self) -> String;
+    /// Build the node's identity
+    fn identity(&self) -> NodeId;
+    /// Serialize the node with the prefixes in scope in its tree, if the
+    /// backend supports it
+    fn serialize(&self) -> Option<crate::error::Result<String>> {
+        None
+    }
 }
 
 /// A single item in a query result
@@ -33,7 +157,11 @@ pub enum ResultItem {
     Date(String),
     DateTime(String),
     Duration(String),
-    QName(String),
+    QName {
+        prefix: Option<String>,
+        local: String,
+        uri: Option<String>,
+    },
     Empty,
 }
 
@@ -48,10 +176,114 @@ impl ResultItem {
             ResultItem::Date(s) => s.clone(),
             ResultItem::DateTime(s) => s.clone(),
             ResultItem::Duration(s) => s.clone(),
-            ResultItem::QName(s) => s.clone(),
+            ResultItem::QName { prefix: Some(prefix), local, .. } => format!("{}:{}", prefix, local),
+            ResultItem::QName { prefix: None, local, .. } => local.clone(),
             ResultItem::Empty => String::new(),
         }
     }
+
+    /// Name of the item's kind: the node kind for nodes (`element`,
+    /// `text`, ...), the atomic type's local name otherwise
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            ResultItem::Node(info) => match info.node_type {
+                NodeType::Document => "document",
+                NodeType::Element => "element",
+                NodeType::Attribute => "attribute",
+                NodeType::Text => "text",
+                NodeType::Comment => "comment",
+                NodeType::ProcessingInstruction => "processing-instruction",
+                NodeType::Namespace => "namespace",
+            },
+            ResultItem::String(_) => "string",
+            ResultItem::Integer(_) => "integer",
+            ResultItem::Double(_) => "double",
+            ResultItem::Boolean(_) => "boolean",
+            ResultItem::Date(_) => "date",
+            ResultItem::DateTime(_) => "dateTime",
+            ResultItem::Duration(_) => "duration",
+            ResultItem::QName { .. } => "QName",
+            ResultItem::Empty => "empty",
+        }
+    }
+
+    /// Get the item's string value
+    ///
+    /// Unlike [`as_string`](Self::as_string), node items yield their text
+    /// content rather than their markup.
+    pub fn string_value(&self) -> String {
+        match self {
+            ResultItem::Node(info) => {
+                let value = info.value.clone().unwrap_or_default();
+                apply_output_method(&value, OutputMethod::Text).unwrap_or(value)
+            }
+            other => other.as_string(),
+        }
+    }
+
+    /// Convert the item to a double following `fn:number` casting rules
+    ///
+    /// Returns `None` for items with no numeric interpretation (e.g. dates
+    /// or non-numeric strings).
+    pub fn to_f64(&self) -> Option<f64> {
+        match self {
+            ResultItem::Integer(i) => Some(*i as f64),
+            ResultItem::Double(d) => Some(*d),
+            ResultItem::Boolean(b) => Some(if *b { 1.0 } else { 0.0 }),
+            ResultItem::String(s) => parse_double(s),
+            ResultItem::Node(_) => parse_double(&self.string_value()),
+            _ => None,
+        }
+    }
+
+    /// Render an atomic item as an XPath expression producing the same value
+    ///
+    /// Returns `None` for nodes and the empty item, which have no
+    /// self-contained literal form.
+    pub fn to_xpath_literal(&self) -> Option<String> {
+        let literal = match self {
+            ResultItem::String(s) => string_literal(s),
+            ResultItem::Integer(i) => i.to_string(),
+            ResultItem::Double(d) => {
+                let lexical = if d.is_nan() {
+                    "NaN".to_string()
+                } else if d.is_infinite() {
+                    if *d > 0.0 { "INF" } else { "-INF" }.to_string()
+                } else {
+                    d.to_string()
+                };
+                format!("xs:double({})", string_literal(&lexical))
+            }
+            ResultItem::Boolean(b) => format!("{}()", b),
+            ResultItem::Date(s) => format!("xs:date({})", string_literal(s)),
+            ResultItem::DateTime(s) => format!("xs:dateTime({})", string_literal(s)),
+            ResultItem::Duration(s) => format!("xs:duration({})", string_literal(s)),
+            ResultItem::QName { uri, .. } => format!(
+                "QName({}, {})",
+                string_literal(uri.as_deref().unwrap_or_default()),
+                string_literal(&self.as_string())
+            ),
+            ResultItem::Node(_) | ResultItem::Empty => return None,
+        };
+        Some(literal)
+    }
+}
+
+/// Parse an `xs:double` lexical form
+pub(crate) fn parse_double(s: &str) -> Option<f64> {
+    match s.trim() {
+        "INF" | "+INF" => Some(f64::INFINITY),
+        "-INF" => Some(f64::NEG_INFINITY),
+        "NaN" => Some(f64::NAN),
+        // Rust also accepts spellings like "inf" and "nan", which XPath doesn't
+        t if t.chars().any(|c| c.is_ascii_alphabetic() && !matches!(c, 'e' | 'E')) => None,
+        t => t.parse().ok(),
+    }
+}
+
+/// Quote a string as an XPath string literal
+fn string_literal(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "\"\""))
 }
 
 /// XSD validation error
@@ -60,6 +292,88 @@ pub struct ValidationError {
     pub message: String,
     pub line: Option<usize>,
     pub column: Option<usize>,
+    /// What kind of constraint was violated
+    #[serde(default)]
+    pub kind: ValidationErrorKind,
+}
+
+impl ValidationError {
+    /// Create an error, classifying its kind from the message with
+    /// [`ValidationErrorKind::from_message`]
+    pub fn new(message: impl Into<String>) -> Self {
+        let message = message.into();
+        let kind = ValidationErrorKind::from_message(&message);
+        Self { message, line: None, column: None, kind }
+    }
+}
+
+/// Kind of constraint an XSD validation error violates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ValidationErrorKind {
+    /// Content doesn't fit the element's content model
+    StructuralModel,
+    /// A value isn't valid for its simple type or facets
+    SimpleType,
+    /// A required attribute or element is absent
+    MissingRequired,
+    /// An element or attribute appears where none is allowed
+    UnexpectedElement,
+    #[default]
+    Other,
+}
+
+impl ValidationErrorKind {
+    /// Classify a validation message
+    ///
+    /// Uses the XSD validation rule (`cvc-...`) when the message names one,
+    /// otherwise keywords in the text. Backends classify their own errors
+    /// where they can; this is the fallback for messages alone.
+    pub fn from_message(message: &str) -> Self {
+        let lower = message.to_ascii_lowercase();
+        if let Some(start) = lower.find("cvc-") {
+            let rule: String = lower[start..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.'))
+                .collect();
+            let rule = rule.trim_end_matches('.');
+            match rule {
+                "cvc-complex-type.4" | "cvc-complex-type.2.4.b" => return Self::MissingRequired,
+                "cvc-complex-type.2.4.a" | "cvc-complex-type.2.4.d" | "cvc-complex-type.3.2.2" => {
+                    return Self::UnexpectedElement
+                }
+                r if r.starts_with("cvc-complex-type") || r.starts_with("cvc-elt") => {
+                    return Self::StructuralModel
+                }
+                r if r.starts_with("cvc-datatype")
+                    || r.starts_with("cvc-simple-type")
+                    || r.starts_with("cvc-type.3.1.3")
+                    || r.starts_with("cvc-attribute.3")
+                    || r.ends_with("-valid") =>
+                {
+                    return Self::SimpleType
+                }
+                _ => {}
+            }
+        }
+
+        if lower.contains("missing") || lower.contains("required") || lower.contains("must appear") {
+            Self::MissingRequired
+        } else if lower.contains("unexpected")
+            || lower.contains("not allowed")
+            || lower.contains("not expected")
+        {
+            Self::UnexpectedElement
+        } else if lower.contains("content model") || lower.contains("incomplete") {
+            Self::StructuralModel
+        } else if ["datatype", "facet", "pattern", "enumeration", "not a valid value", "invalid value"]
+            .iter()
+            .any(|word| lower.contains(word))
+        {
+            Self::SimpleType
+        } else {
+            Self::Other
+        }
+    }
 }
 
 /// Result of XSD validation
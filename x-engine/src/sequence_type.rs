@@ -0,0 +1,219 @@
+//! SequenceType parsing and matching
+//!
+//! Recognizes the common SequenceType forms (`xs:integer`, `element()`,
+//! `node()*`, `xs:string+`, `item()?`, ...) so simple `assert-type` checks
+//! can be decided from result items without another evaluation. Forms that
+//! aren't recognized, or that the result items can't decide (e.g. `xs:int`
+//! when backends only report an integer), are left to the engine.
+
+use crate::result::{NodeType, ResultItem};
+
+/// How many items a sequence type allows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Occurrence {
+    /// Exactly one item
+    One,
+    /// `?`: zero or one item
+    Optional,
+    /// `*`: any number of items
+    ZeroOrMore,
+    /// `+`: at least one item
+    OneOrMore,
+}
+
+impl Occurrence {
+    fn allows(self, count: usize) -> bool {
+        match self {
+            Occurrence::One => count == 1,
+            Occurrence::Optional => count <= 1,
+            Occurrence::ZeroOrMore => true,
+            Occurrence::OneOrMore => count >= 1,
+        }
+    }
+}
+
+/// An item type test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemType {
+    /// `item()`
+    Item,
+    /// `node()`
+    Node,
+    /// `element()` or `element(name)`
+    Element(Option<String>),
+    /// `attribute()` or `attribute(name)`
+    Attribute(Option<String>),
+    /// `text()`
+    Text,
+    /// `comment()`
+    Comment,
+    /// `processing-instruction()`
+    ProcessingInstruction,
+    /// `document-node()`
+    Document,
+    /// An atomic type such as `xs:integer`
+    Atomic(String),
+}
+
+/// A parsed SequenceType
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SequenceType {
+    /// `empty-sequence()`
+    Empty,
+    /// An item type with an occurrence indicator
+    Items(ItemType, Occurrence),
+}
+
+impl SequenceType {
+    /// Parse a SequenceType, returning `None` for unrecognized forms
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if s == "empty-sequence()" {
+            return Some(Self::Empty);
+        }
+
+        let (item, occurrence) = match s.chars().last()? {
+            '?' => (&s[..s.len() - 1], Occurrence::Optional),
+            '*' => (&s[..s.len() - 1], Occurrence::ZeroOrMore),
+            '+' => (&s[..s.len() - 1], Occurrence::OneOrMore),
+            _ => (s, Occurrence::One),
+        };
+        Some(Self::Items(parse_item_type(item.trim())?, occurrence))
+    }
+
+    /// Check whether a sequence of items matches this type
+    ///
+    /// Undecidable cases count as non-matching; use
+    /// [`try_match`](Self::try_match) to tell them apart.
+    pub fn matches(&self, items: &[ResultItem]) -> bool {
+        self.try_match(items).unwrap_or(false)
+    }
+
+    /// Check whether a sequence of items matches this type, or `None` if
+    /// the items don't carry enough type information to decide
+    pub fn try_match(&self, items: &[ResultItem]) -> Option<bool> {
+        let items: Vec<&ResultItem> =
+            items.iter().filter(|item| !matches!(item, ResultItem::Empty)).collect();
+        match self {
+            Self::Empty => Some(items.is_empty()),
+            Self::Items(item_type, occurrence) => {
+                if !occurrence.allows(items.len()) {
+                    return Some(false);
+                }
+                let mut decided = true;
+                for item in items {
+                    match match_item(item_type, item) {
+                        Some(true) => {}
+                        Some(false) => return Some(false),
+                        None => decided = false,
+                    }
+                }
+                decided.then_some(true)
+            }
+        }
+    }
+}
+
+/// Parse an item type, returning `None` for unrecognized forms
+fn parse_item_type(s: &str) -> Option<ItemType> {
+    if let Some(atomic) = s.strip_prefix("xs:") {
+        let is_name = !atomic.is_empty()
+            && atomic.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.');
+        return is_name.then(|| ItemType::Atomic(atomic.to_string()));
+    }
+
+    let (kind, args) = s.strip_suffix(')')?.split_once('(')?;
+    let name = match args.trim() {
+        "" | "*" => None,
+        name if !name.contains(',') => Some(name.to_string()),
+        _ => return None,
+    };
+    let item_type = match (kind.trim(), name) {
+        ("item", None) => ItemType::Item,
+        ("node", None) => ItemType::Node,
+        ("element", name) => ItemType::Element(name),
+        ("attribute", name) => ItemType::Attribute(name),
+        ("text", None) => ItemType::Text,
+        ("comment", None) => ItemType::Comment,
+        ("processing-instruction", None) => ItemType::ProcessingInstruction,
+        ("document-node", None) => ItemType::Document,
+        _ => return None,
+    };
+    Some(item_type)
+}
+
+/// Match a single item, or `None` if undecidable
+fn match_item(item_type: &ItemType, item: &ResultItem) -> Option<bool> {
+    let node = match item {
+        ResultItem::Node(info) => Some(info),
+        _ => None,
+    };
+    let node_is = |node_type: NodeType| Some(node.is_some_and(|info| info.node_type == node_type));
+    let named = |node_type: NodeType, name: &Option<String>| match (node, name) {
+        (Some(info), _) if info.node_type != node_type => Some(false),
+        (None, _) => Some(false),
+        (Some(_), None) => Some(true),
+        // Node names are reported without prefixes
+        (Some(_), Some(name)) if name.contains(':') => None,
+        (Some(info), Some(name)) => Some(info.name.as_deref() == Some(name.as_str())),
+    };
+
+    match item_type {
+        ItemType::Item => Some(true),
+        ItemType::Node => Some(node.is_some()),
+        ItemType::Element(name) => named(NodeType::Element, name),
+        ItemType::Attribute(name) => named(NodeType::Attribute, name),
+        ItemType::Text => node_is(NodeType::Text),
+        ItemType::Comment => node_is(NodeType::Comment),
+        ItemType::ProcessingInstruction => node_is(NodeType::ProcessingInstruction),
+        ItemType::Document => node_is(NodeType::Document),
+        ItemType::Atomic(name) => match_atomic(name, item),
+    }
+}
+
+/// Match an atomic type name against an item
+///
+/// Result items only record a broad value category, so derived types
+/// (e.g. `xs:int` for an integer) are undecidable.
+fn match_atomic(name: &str, item: &ResultItem) -> Option<bool> {
+    if matches!(item, ResultItem::Node(_)) {
+        return Some(false);
+    }
+    if name == "anyAtomicType" {
+        return Some(true);
+    }
+
+    let (certain, possible): (&[&str], &[&str]) = match item {
+        ResultItem::Integer(_) => (
+            &["integer", "decimal", "numeric"],
+            &[
+                "long", "int", "short", "byte", "nonNegativeInteger", "nonPositiveInteger",
+                "positiveInteger", "negativeInteger", "unsignedLong", "unsignedInt",
+                "unsignedShort", "unsignedByte",
+            ],
+        ),
+        // xee reports decimals and floats as doubles too
+        ResultItem::Double(_) => (&["numeric"], &["double", "float", "decimal"]),
+        ResultItem::Boolean(_) => (&["boolean"], &[]),
+        ResultItem::String(_) => (
+            &[],
+            &[
+                "string", "untypedAtomic", "anyURI", "normalizedString", "token", "language",
+                "NMTOKEN", "Name", "NCName", "ID", "IDREF", "ENTITY",
+            ],
+        ),
+        ResultItem::Date(_) => (&["date"], &[]),
+        ResultItem::DateTime(_) => (&[], &["dateTime", "dateTimeStamp", "time"]),
+        ResultItem::Duration(_) => (&["duration"], &["yearMonthDuration", "dayTimeDuration"]),
+        ResultItem::QName { .. } => (&["QName"], &[]),
+        ResultItem::Node(_) | ResultItem::Empty => (&[], &[]),
+    };
+
+    if certain.contains(&name) {
+        Some(true)
+    } else if possible.contains(&name) {
+        None
+    } else {
+        Some(false)
+    }
+}
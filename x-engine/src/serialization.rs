@@ -0,0 +1,445 @@
+//! Output-method-aware serialization
+//!
+//! Backends always serialize as XML. These helpers re-serialize that XML
+//! according to the XSLT/XQuery output method (`xml`, `html` or `text`).
+//! [`reindent`] pretty-prints it.
+
+use std::collections::HashMap;
+
+use crate::canonical::{namespace_declarations, parse_start_tag};
+use crate::error::{Error, Result};
+
+/// HTML elements that must be written without an end tag
+const HTML_VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "basefont", "br", "col", "embed", "frame", "hr", "img", "input", "isindex",
+    "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// Serialization output method
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputMethod {
+    #[default]
+    Xml,
+    Html,
+    Text,
+}
+
+impl OutputMethod {
+    /// Parse an output method name (`xml`, `html`, `text`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            "xml" => Some(Self::Xml),
+            "html" | "xhtml" => Some(Self::Html),
+            "text" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    /// Determine the output method declared by a stylesheet's `xsl:output`
+    ///
+    /// Defaults to `xml` when no (recognised) method is declared.
+    pub fn from_stylesheet(stylesheet: &str) -> Self {
+        let mut rest = stylesheet;
+        while let Some(start) = rest.find("<xsl:output") {
+            let tag = &rest[start..];
+            let end = tag.find('>').unwrap_or(tag.len());
+            if let Some(method) = attribute_value(&tag[..end], "method") {
+                return Self::from_name(method).unwrap_or_default();
+            }
+            rest = &tag[end..];
+        }
+        Self::Xml
+    }
+}
+
+/// `xsl:output` serialization parameters
+///
+/// Unset parameters fall back to the XSLT defaults: `xml` output, not
+/// indented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OutputParams {
+    pub method: Option<OutputMethod>,
+    pub indent: Option<bool>,
+}
+
+impl OutputParams {
+    /// Read the parameters declared by a stylesheet's `xsl:output`
+    pub fn from_stylesheet(stylesheet: &str) -> Self {
+        let mut params = Self::default();
+        let mut rest = stylesheet;
+        while let Some(start) = rest.find("<xsl:output") {
+            let tag = &rest[start..];
+            let end = tag.find('>').unwrap_or(tag.len());
+            if params.method.is_none() {
+                params.method =
+                    attribute_value(&tag[..end], "method").and_then(OutputMethod::from_name);
+            }
+            if params.indent.is_none() {
+                params.indent = attribute_value(&tag[..end], "indent")
+                    .map(|indent| matches!(indent.trim(), "yes" | "true" | "1"));
+            }
+            rest = &tag[end..];
+        }
+        params
+    }
+
+    /// These parameters with every parameter set in `overrides` replaced
+    pub fn merge(self, overrides: &OutputParams) -> Self {
+        Self {
+            method: overrides.method.or(self.method),
+            indent: overrides.indent.or(self.indent),
+        }
+    }
+
+    /// Re-serialize XML output according to these parameters
+    pub fn apply(&self, xml: &str) -> Result<String> {
+        let method = self.method.unwrap_or_default();
+        if self.indent == Some(true) && method != OutputMethod::Text {
+            apply_output_method(&reindent(xml, 2)?, method)
+        } else {
+            apply_output_method(xml, method)
+        }
+    }
+}
+
+/// Options controlling serialization of query and transform results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializationOptions {
+    pub method: OutputMethod,
+    /// Keep the namespace prefixes as written by the backend; otherwise
+    /// rename them to generated `ns1`, `ns2`, ... in order of first use
+    pub preserve_prefixes: bool,
+}
+
+impl Default for SerializationOptions {
+    fn default() -> Self {
+        Self {
+            method: OutputMethod::Xml,
+            preserve_prefixes: true,
+        }
+    }
+}
+
+/// Re-serialize XML output according to serialization options
+pub fn apply_serialization_options(xml: &str, options: &SerializationOptions) -> Result<String> {
+    if options.preserve_prefixes {
+        apply_output_method(xml, options.method)
+    } else {
+        apply_output_method(&normalize_prefixes(xml)?, options.method)
+    }
+}
+
+/// Re-serialize XML output according to an output method
+pub fn apply_output_method(xml: &str, method: OutputMethod) -> Result<String> {
+    match method {
+        OutputMethod::Xml => Ok(xml.to_string()),
+        OutputMethod::Html => to_html(xml),
+        OutputMethod::Text => to_text(xml),
+    }
+}
+
+/// Serialize as HTML: no XML declaration, void elements unclosed, other
+/// empty elements written with an explicit end tag
+fn to_html(xml: &str) -> Result<String> {
+    let mut out = String::with_capacity(xml.len());
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = markup_end(rest)?;
+        let markup = &rest[..end];
+        if markup.starts_with("<?xml ") || markup.starts_with("<?xml?") {
+            // Drop the XML declaration
+        } else if markup.ends_with("/>") && !markup.starts_with("<!") && !markup.starts_with("<?") {
+            let inner = markup[1..markup.len() - 2].trim_end();
+            let name = inner.split(|c: char| c.is_whitespace()).next().unwrap_or("");
+            if HTML_VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+                out.push('<');
+                out.push_str(inner);
+                out.push('>');
+            } else {
+                out.push('<');
+                out.push_str(inner);
+                out.push_str("></");
+                out.push_str(name);
+                out.push('>');
+            }
+        } else if let Some(name) = markup.strip_prefix("</") {
+            let name = name.trim_end_matches('>').trim();
+            if !HTML_VOID_ELEMENTS.contains(&name.to_ascii_lowercase().as_str()) {
+                out.push_str(markup);
+            }
+        } else {
+            out.push_str(markup);
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Rename namespace prefixes to generated ones, one per namespace URI
+fn normalize_prefixes(xml: &str) -> Result<String> {
+    let mut out = String::with_capacity(xml.len());
+    // Generated prefix per namespace URI
+    let mut generated: HashMap<String, String> = HashMap::new();
+    // In-scope namespace bindings (prefix -> URI), one frame per open element
+    let mut scopes: Vec<HashMap<String, String>> = Vec::new();
+
+    let rename = |name: &str, scopes: &[HashMap<String, String>], generated: &HashMap<String, String>| {
+        let Some((prefix, local)) = name.split_once(':') else {
+            return name.to_string();
+        };
+        scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(prefix))
+            .and_then(|uri| generated.get(uri))
+            .map(|generated| format!("{}:{}", generated, local))
+            .unwrap_or_else(|| name.to_string())
+    };
+
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = markup_end(rest)?;
+        let markup = &rest[..end];
+        rest = &rest[end..];
+
+        if markup.starts_with("<!") || markup.starts_with("<?") {
+            out.push_str(markup);
+        } else if let Some(name) = markup.strip_prefix("</") {
+            let name = name.trim_end_matches('>').trim();
+            out.push_str(&format!("</{}>", rename(name, &scopes, &generated)));
+            scopes.pop();
+        } else {
+            let empty = markup.ends_with("/>");
+            let inner = markup[1..markup.len() - if empty { 2 } else { 1 }].trim();
+            let (name, attributes) = parse_start_tag(inner)
+                .map_err(|e| Error::SerializationError(e.to_string()))?;
+            for (_, uri) in attributes.iter().filter(|(attr, _)| attr.starts_with("xmlns:")) {
+                let next = format!("ns{}", generated.len() + 1);
+                generated.entry(uri.clone()).or_insert(next);
+            }
+            scopes.push(namespace_declarations(&attributes));
+
+            let mut tag = format!("<{}", rename(&name, &scopes, &generated));
+            let mut declared = Vec::new();
+            for (attr, value) in &attributes {
+                let attr = match attr.strip_prefix("xmlns:") {
+                    Some(_) => {
+                        let prefix = &generated[value];
+                        if declared.contains(&prefix) {
+                            continue;
+                        }
+                        declared.push(prefix);
+                        format!("xmlns:{}", prefix)
+                    }
+                    None => rename(attr, &scopes, &generated),
+                };
+                tag.push_str(&format!(" {}=\"{}\"", attr, escape(value)));
+            }
+            tag.push_str(if empty { "/>" } else { ">" });
+            out.push_str(&tag);
+            if empty {
+                scopes.pop();
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Serialize as text: the concatenated, unescaped character data
+fn to_text(xml: &str) -> Result<String> {
+    let mut out = String::with_capacity(xml.len());
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&unescape(&rest[..start])?);
+        rest = &rest[start..];
+        let end = markup_end(rest)?;
+        if let Some(cdata) = rest[..end].strip_prefix("<![CDATA[") {
+            out.push_str(cdata.trim_end_matches("]]>"));
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(&unescape(rest)?);
+    Ok(out)
+}
+
+/// Re-indent serialized XML by `indent` spaces per level
+///
+/// Elements whose content is only elements (and whitespace) put each child
+/// on its own line; existing whitespace between them is replaced. Elements
+/// with text content, and those marked `xml:space="preserve"`, are written
+/// as-is since their whitespace is significant.
+pub fn reindent(xml: &str, indent: usize) -> Result<String> {
+    let mut tokens = Vec::new();
+    let mut rest = xml;
+    while !rest.is_empty() {
+        let len = if rest.starts_with('<') {
+            markup_end(rest)?
+        } else {
+            rest.find('<').unwrap_or(rest.len())
+        };
+        tokens.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+
+    // Mark the start tags of elements whose content must be kept verbatim
+    let mut verbatim = vec![false; tokens.len()];
+    let mut open = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.starts_with("</") {
+            open.pop();
+        } else if is_start_tag(token) {
+            verbatim[i] =
+                token.contains("xml:space=\"preserve\"") || token.contains("xml:space='preserve'");
+            open.push(i);
+        } else if (!token.starts_with('<') && !token.trim().is_empty())
+            || token.starts_with("<![CDATA[")
+        {
+            if let Some(&parent) = open.last() {
+                verbatim[parent] = true;
+            }
+        }
+    }
+
+    let mut out = String::with_capacity(xml.len());
+    let mut depth: usize = 0;
+    // Open elements inside the verbatim element being copied
+    let mut copying = 0;
+    let mut previous_start = false;
+    for (i, token) in tokens.iter().enumerate() {
+        if copying > 0 {
+            out.push_str(token);
+            if token.starts_with("</") {
+                copying -= 1;
+            } else if is_start_tag(token) {
+                copying += 1;
+            }
+            continue;
+        }
+        if !token.starts_with('<') && token.trim().is_empty() {
+            continue;
+        }
+        if token.starts_with("</") {
+            depth = depth.saturating_sub(1);
+            if !previous_start {
+                push_line(&mut out, depth * indent);
+            }
+            out.push_str(token);
+            previous_start = false;
+            continue;
+        }
+        push_line(&mut out, depth * indent);
+        out.push_str(token);
+        previous_start = is_start_tag(token);
+        if verbatim[i] {
+            copying = 1;
+            previous_start = false;
+        } else if previous_start {
+            depth += 1;
+        }
+    }
+    Ok(out)
+}
+
+/// Start a new line indented by `width` spaces, unless at the start of output
+fn push_line(out: &mut String, width: usize) {
+    if !out.is_empty() {
+        out.push('\n');
+        out.push_str(&" ".repeat(width));
+    }
+}
+
+/// Check whether a markup token is a non-empty element's start tag
+fn is_start_tag(token: &str) -> bool {
+    token.starts_with('<')
+        && !token.starts_with("</")
+        && !token.starts_with("<!")
+        && !token.starts_with("<?")
+        && !token.ends_with("/>")
+}
+
+/// Find the end (exclusive) of the markup construct at the start of `s`
+pub(crate) fn markup_end(s: &str) -> Result<usize> {
+    let terminator = if s.starts_with("<!--") {
+        "-->"
+    } else if s.starts_with("<![CDATA[") {
+        "]]>"
+    } else if s.starts_with("<?") {
+        "?>"
+    } else {
+        ">"
+    };
+    s.find(terminator)
+        .map(|i| i + terminator.len())
+        .ok_or_else(|| Error::SerializationError("unterminated markup in XML output".to_string()))
+}
+
+/// Resolve predefined entities and character references
+pub(crate) fn unescape(text: &str) -> Result<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(';')
+            .ok_or_else(|| Error::SerializationError("unterminated entity reference".to_string()))?;
+        let entity = &rest[1..end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse::<u32>()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => out.push(c),
+            None => {
+                return Err(Error::SerializationError(format!(
+                    "unknown entity reference &{};",
+                    entity
+                )))
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Escape text for use in character data or a double-quoted attribute
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Extract an attribute value from a start tag
+fn attribute_value<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = tag;
+    while let Some(pos) = rest.find(name) {
+        let preceded_by_space = rest[..pos].ends_with(char::is_whitespace);
+        let after = rest[pos + name.len()..].trim_start();
+        if preceded_by_space {
+            if let Some(after) = after.strip_prefix('=') {
+                let after = after.trim_start();
+                let quote = after.chars().next()?;
+                if quote == '"' || quote == '\'' {
+                    let value = &after[1..];
+                    return value.find(quote).map(|end| &value[..end]);
+                }
+            }
+        }
+        rest = &rest[pos + name.len()..];
+    }
+    None
+}
@@ -0,0 +1,190 @@
+//! Thread-safe engine handles
+//!
+//! [`XEngine`] is not `Send`: the backends keep documents and callbacks in
+//! `Rc`s and boxed closures. A [`SharedEngine`] owns an engine on a
+//! dedicated thread and forwards requests to it, so one engine can serve
+//! several threads. Operations are serialized: each runs to completion
+//! before the next one starts. An [`EnginePool`] runs several engines, one
+//! per worker thread, for concurrent workloads.
+
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::error::{Error, Result};
+use crate::unified::{Backend, XDocument, XEngine, XQueryResult};
+
+/// Work sent to the engine thread
+type Job = Box<dyn FnOnce(&mut Worker) + Send>;
+
+/// State owned by the engine thread
+struct Worker {
+    engine: XEngine,
+    /// Parsed documents by id, until their [`SharedDocument`] is dropped
+    documents: HashMap<u64, XDocument>,
+}
+
+/// Cloneable handle to an engine running on its own thread
+///
+/// Clones share the same engine.
+#[derive(Clone)]
+pub struct SharedEngine {
+    jobs: Sender<Job>,
+}
+
+/// A document parsed by a [`SharedEngine`]
+///
+/// Only valid with the engine that parsed it. The document is released when
+/// the handle is dropped.
+pub struct SharedDocument {
+    id: u64,
+    jobs: Sender<Job>,
+}
+
+impl SharedEngine {
+    /// Start an engine with the given backend on a new thread
+    pub fn new(backend: Backend) -> Self {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        thread::spawn(move || {
+            let mut worker =
+                Worker { engine: XEngine::with_backend(backend), documents: HashMap::new() };
+            for job in receiver {
+                job(&mut worker);
+            }
+        });
+        Self { jobs }
+    }
+
+    /// Run `job` on the engine thread and wait for its result
+    fn run<T: Send + 'static>(
+        &self,
+        job: impl FnOnce(&mut Worker) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let stopped = || Error::EngineError("Shared engine thread has stopped".to_string());
+        let (reply, response) = mpsc::channel();
+        self.jobs
+            .send(Box::new(move |worker| {
+                let _ = reply.send(job(worker));
+            }))
+            .map_err(|_| stopped())?;
+        response.recv().map_err(|_| stopped())?
+    }
+
+    /// Parse XML from a string
+    pub fn parse(&self, xml: &str) -> Result<SharedDocument> {
+        let xml = xml.to_string();
+        let id = self.run(move |worker| {
+            let doc = worker.engine.parse(&xml)?;
+            let id = doc.id();
+            worker.documents.insert(id, doc);
+            Ok(id)
+        })?;
+        Ok(SharedDocument { id, jobs: self.jobs.clone() })
+    }
+
+    /// Evaluate an XPath expression
+    pub fn xpath(&self, doc: &SharedDocument, xpath: &str) -> Result<XQueryResult> {
+        let (id, xpath) = (doc.id, xpath.to_string());
+        self.run(move |worker| worker.engine.xpath(document(&worker.documents, id)?, &xpath))
+    }
+
+    /// Execute an XQuery expression
+    pub fn xquery(&self, doc: &SharedDocument, query: &str) -> Result<XQueryResult> {
+        let (id, query) = (doc.id, query.to_string());
+        self.run(move |worker| worker.engine.xquery(document(&worker.documents, id)?, &query))
+    }
+
+    /// Transform with XSLT, returning the serialized result
+    pub fn transform(&self, doc: &SharedDocument, stylesheet: &str) -> Result<String> {
+        let (id, stylesheet) = (doc.id, stylesheet.to_string());
+        self.run(move |worker| {
+            worker.engine.transform(document(&worker.documents, id)?, &stylesheet)
+        })
+    }
+}
+
+impl Drop for SharedDocument {
+    fn drop(&mut self) {
+        let id = self.id;
+        // The engine thread may already be gone; then there is nothing to free
+        let _ = self.jobs.send(Box::new(move |worker| {
+            worker.documents.remove(&id);
+        }));
+    }
+}
+
+/// Work for a pool worker
+type PoolJob = Box<dyn FnOnce(&mut XEngine) + Send>;
+
+/// A fixed set of engines, each on its own worker thread
+///
+/// [`with`](Self::with) checks an idle engine out for the duration of a
+/// closure, so up to `size` closures run at once; further jobs queue until
+/// an engine is returned.
+pub struct EnginePool {
+    jobs: Sender<PoolJob>,
+    size: usize,
+}
+
+impl EnginePool {
+    /// Start `size` engines (at least one) built by `factory`
+    ///
+    /// `factory` runs on each worker thread, since engines can't move
+    /// between threads. A worker whose job panics rebuilds its engine.
+    pub fn new(factory: impl Fn() -> XEngine + Send + Sync + 'static, size: usize) -> Self {
+        let size = size.max(1);
+        let (jobs, receiver) = mpsc::channel::<PoolJob>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let factory = Arc::new(factory);
+        for _ in 0..size {
+            let (receiver, factory) = (Arc::clone(&receiver), Arc::clone(&factory));
+            thread::spawn(move || {
+                let mut engine = factory();
+                loop {
+                    // Hold the queue lock only while waiting for a job
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => break,
+                    };
+                    let Ok(job) = job else {
+                        break;
+                    };
+                    if panic::catch_unwind(AssertUnwindSafe(|| job(&mut engine))).is_err() {
+                        engine = factory();
+                    }
+                }
+            });
+        }
+        Self { jobs, size }
+    }
+
+    /// Number of engines in the pool
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Run `job` with an idle engine and wait for its result
+    ///
+    /// Fails if the job panicked.
+    pub fn with<T: Send + 'static>(
+        &self,
+        job: impl FnOnce(&mut XEngine) -> T + Send + 'static,
+    ) -> Result<T> {
+        let failed = || Error::EngineError("Engine pool job did not complete".to_string());
+        let (reply, response) = mpsc::channel();
+        self.jobs
+            .send(Box::new(move |engine| {
+                let _ = reply.send(job(engine));
+            }))
+            .map_err(|_| failed())?;
+        response.recv().map_err(|_| failed())
+    }
+}
+
+fn document(documents: &HashMap<u64, XDocument>, id: u64) -> Result<&XDocument> {
+    documents
+        .get(&id)
+        .ok_or_else(|| Error::EngineError("Document was parsed by a different engine".to_string()))
+}
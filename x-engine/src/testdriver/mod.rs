@@ -9,9 +9,20 @@ pub mod qt3;
 pub mod xslt30;
 pub mod xsd;
 
-use std::time::Duration;
+use std::any::Any;
+use std::collections::HashSet;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
 use serde::{Deserialize, Serialize};
 
+use crate::error::Result;
+use crate::reporter::{ComplianceReport, ComplianceSummary};
+use crate::serialization::markup_end;
+use crate::traits::Capability;
+use crate::unified::{Backend, XDocument, XEngine};
+
 /// Result of running a single test
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TestResult {
@@ -31,6 +42,12 @@ pub struct TestResult {
     pub actual: Option<String>,
     /// Duration of test execution
     pub duration: Duration,
+    /// Spec areas the test covers (QT3 `covers` attribute)
+    #[serde(default)]
+    pub covers: Option<String>,
+    /// Date the test was last modified
+    #[serde(default)]
+    pub modified: Option<String>,
 }
 
 impl TestResult {
@@ -52,6 +69,8 @@ impl TestResult {
             expected: None,
             actual: None,
             duration,
+            covers: None,
+            modified: None,
         }
     }
 
@@ -63,6 +82,413 @@ impl TestResult {
     }
 }
 
+/// Options controlling a conformance run
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Stop after the first failing or erroring test
+    pub fail_fast: bool,
+    /// Parse and check applicability only, recording runnable tests as skipped
+    pub dry_run: bool,
+    /// Accept a dynamic error where a QT3 test asserts the empty sequence
+    pub allow_error_as_empty: bool,
+    /// `(test set, test id)` pairs to record as skipped without running them
+    pub exclude: HashSet<(String, String)>,
+    /// `(test set, test id)` pairs that are known to fail
+    pub xfail: HashSet<(String, String)>,
+}
+
+impl RunOptions {
+    /// Check whether the run should stop after recording this result
+    pub fn should_stop(&self, result: &TestResult) -> bool {
+        self.fail_fast && (result.outcome.is_fail() || result.outcome.is_error())
+    }
+
+    /// Check whether a test is on the exclusion list
+    pub fn is_excluded(&self, test_set: &str, test_id: &str) -> bool {
+        self.exclude.contains(&(test_set.to_string(), test_id.to_string()))
+    }
+
+    /// Rewrite the outcome of a test on the expected-fail list
+    ///
+    /// A failure or error becomes `ExpectedFail` and a pass becomes
+    /// `UnexpectedPass`; other results are left alone.
+    pub fn apply_xfail(&self, result: &mut TestResult) {
+        if !self.xfail.contains(&(result.test_set.clone(), result.test_id.clone())) {
+            return;
+        }
+        result.outcome = match std::mem::replace(&mut result.outcome, TestOutcome::Pass) {
+            TestOutcome::Fail(msg) | TestOutcome::Error(msg) => TestOutcome::ExpectedFail(msg),
+            TestOutcome::Pass => TestOutcome::UnexpectedPass,
+            other => other,
+        };
+    }
+}
+
+/// Parse an exclusion or expected-fail list with one `test_set/test_id` per line
+///
+/// Blank lines and lines starting with `#` are ignored. The test id is
+/// everything after the first `/`, so XSD ids like `group/test` work.
+pub fn parse_exclusions(text: &str) -> HashSet<(String, String)> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('/'))
+        .map(|(set, id)| (set.trim().to_string(), id.trim().to_string()))
+        .collect()
+}
+
+/// Result recorded for a test on the exclusion list
+pub fn excluded_result(
+    test_id: impl Into<String>,
+    test_set: impl Into<String>,
+    test_suite: &str,
+    description: Option<String>,
+) -> TestResult {
+    TestResult::new(test_id, test_set, test_suite, description, TestOutcome::Skipped, Duration::ZERO)
+        .with_values(None, Some("Excluded".to_string()))
+}
+
+/// Counts of what a (possibly filtered) run matched
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunStats {
+    /// Number of test sets whose name matched the filter
+    pub sets_matched: usize,
+    /// Number of test cases run across the matched test sets
+    pub cases_run: usize,
+}
+
+impl RunStats {
+    /// Get a warning to show when the run matched no test cases
+    pub fn no_match_warning(&self, filter: Option<&str>) -> Option<String> {
+        if self.cases_run > 0 {
+            return None;
+        }
+        Some(match filter {
+            Some(f) => format!(
+                "Warning: filter '{}' matched {} test sets and 0 test cases",
+                f, self.sets_matched
+            ),
+            None => "Warning: no test cases were run".to_string(),
+        })
+    }
+}
+
+/// Conformance test suite understood by [`run_suite`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suite {
+    /// QT3 (XPath/XQuery)
+    Qt3,
+    /// XSLT 3.0
+    Xslt30,
+    /// W3C XSD
+    Xsd,
+}
+
+impl Suite {
+    /// Look up a suite by its report name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "qt3" => Some(Suite::Qt3),
+            "xslt30" => Some(Suite::Xslt30),
+            "xsd" => Some(Suite::Xsd),
+            _ => None,
+        }
+    }
+
+    /// Suite name as used in reports (`qt3`, `xslt30`, `xsd`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Suite::Qt3 => "qt3",
+            Suite::Xslt30 => "xslt30",
+            Suite::Xsd => "xsd",
+        }
+    }
+
+    /// The capability a backend needs to run this suite at all
+    pub fn primary_capability(&self) -> Capability {
+        match self {
+            Suite::Qt3 => Capability::XPath,
+            Suite::Xslt30 => Capability::Xslt,
+            Suite::Xsd => Capability::XsdValidation,
+        }
+    }
+}
+
+/// Run a conformance suite with the matching driver and build its report
+pub fn run_suite(
+    engine: &mut XEngine,
+    suite: Suite,
+    catalog: &Path,
+    filter: Option<&str>,
+) -> ComplianceReport {
+    let results = match suite {
+        Suite::Qt3 => qt3::run_xpath_tests(engine, catalog, filter),
+        Suite::Xslt30 => xslt30::run_xslt_tests(engine, catalog, filter),
+        Suite::Xsd => xsd::run_xsd_tests(engine, catalog, filter),
+    };
+    ComplianceReport::new(engine.backend().name(), suite.name(), results)
+}
+
+/// Run a suite on every backend that supports it and summarize each run
+///
+/// Backends lacking the suite's [`primary_capability`](Suite::primary_capability)
+/// are left out.
+pub fn survey_backends(
+    suite: Suite,
+    catalog: &Path,
+    filter: Option<&str>,
+) -> Vec<(Backend, ComplianceSummary)> {
+    Backend::ALL
+        .into_iter()
+        .filter_map(|backend| {
+            let mut engine = XEngine::with_backend(backend);
+            if !engine.supports(suite.primary_capability()) {
+                return None;
+            }
+            let report = run_suite(&mut engine, suite, catalog, filter);
+            Some((backend, report.summary))
+        })
+        .collect()
+}
+
+/// Resolve a catalog `file` attribute or URI against a base directory
+///
+/// Handles `file:` URIs, percent-encoding, absolute paths and `..`/`.`
+/// segments, e.g. `("/a/b", "../c/d.xml")` resolves to `/a/c/d.xml`.
+pub fn resolve_source_path(base_dir: &Path, file_or_uri: &str) -> PathBuf {
+    let reference = match file_or_uri.strip_prefix("file://") {
+        // file:///abs/path and file://localhost/abs/path
+        Some(rest) => rest.strip_prefix("localhost").unwrap_or(rest),
+        None => file_or_uri.strip_prefix("file:").unwrap_or(file_or_uri),
+    };
+    let decoded = percent_decode(reference);
+
+    let joined = if Path::new(&decoded).is_absolute() {
+        PathBuf::from(&decoded)
+    } else {
+        base_dir.join(&decoded)
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    resolved.push("..");
+                }
+            }
+            other => resolved.push(other),
+        }
+    }
+    resolved
+}
+
+/// Decode `%XX` escapes in a URI reference
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(Ok(byte)) = s.get(i + 1..i + 3).map(|hex| u8::from_str_radix(hex, 16)) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Extract a readable message from a panic payload
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// Build the error result reported for a malformed catalog entry
+pub fn catalog_entry_error(test_suite: &str, index: usize, message: &str) -> TestResult {
+    TestResult {
+        test_id: format!("catalog_entry_{}", index + 1),
+        test_set: "catalog".to_string(),
+        test_suite: test_suite.to_string(),
+        description: Some("Parse catalog entry".to_string()),
+        outcome: TestOutcome::Error(format!("Skipped malformed catalog entry: {}", message)),
+        expected: None,
+        actual: None,
+        duration: Duration::ZERO,
+        covers: None,
+        modified: None,
+    }
+}
+
+/// Parse a catalog or test-set document, optionally recovering from errors
+///
+/// In lenient mode a document that fails to parse is retried without the
+/// top-level entries (children of the root element) that don't parse on
+/// their own; the returned messages describe the skipped entries. Errors
+/// outside any entry are still returned.
+pub(crate) fn parse_catalog_document(
+    engine: &mut XEngine,
+    content: &str,
+    lenient: bool,
+) -> Result<(XDocument, Vec<String>)> {
+    let error = match engine.parse(content) {
+        Ok(doc) => return Ok((doc, Vec::new())),
+        Err(e) if !lenient => return Err(e),
+        Err(e) => e,
+    };
+    let Some((recovered, skipped)) = drop_malformed_entries(engine, content)? else {
+        return Err(error);
+    };
+    Ok((engine.parse(&recovered)?, skipped))
+}
+
+/// Remove the root's child elements that aren't well-formed on their own
+///
+/// Returns `None` when every entry parses, since the error lies elsewhere.
+fn drop_malformed_entries(
+    engine: &mut XEngine,
+    content: &str,
+) -> Result<Option<(String, Vec<String>)>> {
+    let mut tokens = Vec::new();
+    let mut rest = content;
+    while !rest.is_empty() {
+        let len = if rest.starts_with('<') {
+            markup_end(rest)?
+        } else {
+            rest.find('<').unwrap_or(rest.len())
+        };
+        tokens.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+    let is_element = |t: &str| t.starts_with('<') && !t.starts_with("<!") && !t.starts_with("<?");
+    let Some(root) = tokens.iter().position(|t| is_element(t)) else {
+        return Ok(None);
+    };
+    let root_name = tag_name(tokens[root]);
+
+    let mut out: String = tokens[..=root].concat();
+    let mut skipped = Vec::new();
+    let mut entries = 0;
+    let mut i = root + 1;
+    while i < tokens.len() {
+        let token = tokens[i];
+        if !is_element(token) || token.starts_with("</") {
+            out.push_str(token);
+            i += 1;
+            continue;
+        }
+        // Collect the entry up to its matching end tag
+        let start = i;
+        let mut depth = 0;
+        loop {
+            let token = tokens[i];
+            if token.starts_with("</") {
+                depth -= 1;
+            } else if is_element(token) && !token.ends_with("/>") {
+                depth += 1;
+            }
+            i += 1;
+            if depth == 0 || i == tokens.len() {
+                break;
+            }
+        }
+        entries += 1;
+        let entry = tokens[start..i].concat();
+        let standalone = format!("{}{}</{}>", tokens[root], entry, root_name);
+        match engine.parse(&standalone) {
+            Ok(_) => out.push_str(&entry),
+            Err(e) => {
+                let label = match attribute(tokens[start], "name") {
+                    Some(name) => format!("{} #{} ({})", tag_name(tokens[start]), entries, name),
+                    None => format!("{} #{}", tag_name(tokens[start]), entries),
+                };
+                skipped.push(format!("{}: {}", label, e));
+            }
+        }
+    }
+    if skipped.is_empty() {
+        return Ok(None);
+    }
+    if !out.trim_end().ends_with(&format!("</{}>", root_name)) {
+        out.push_str(&format!("</{}>", root_name));
+    }
+    Ok(Some((out, skipped)))
+}
+
+/// The element name of a start or end tag
+fn tag_name(tag: &str) -> &str {
+    let name = tag.trim_start_matches('<').trim_start_matches('/');
+    let end = name.find(|c: char| c.is_whitespace() || c == '>' || c == '/').unwrap_or(name.len());
+    &name[..end]
+}
+
+/// The value of a quoted attribute in a start tag, found lexically
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    ['"', '\''].iter().find_map(|quote| {
+        let needle = format!(" {}={}", name, quote);
+        let start = tag.find(&needle)? + needle.len();
+        let len = tag[start..].find(*quote)?;
+        Some(&tag[start..start + len])
+    })
+}
+
+/// Environment variable that enables evaluation tracing in the drivers
+pub const TRACE_ENV_VAR: &str = "X_ENGINE_TRACE";
+
+/// Trace evaluations to stderr when `X_ENGINE_TRACE=1` is set
+pub fn install_env_trace(engine: &mut XEngine) {
+    if std::env::var(TRACE_ENV_VAR).map(|v| v == "1").unwrap_or(false) {
+        engine.set_trace(Some(Box::new(|line| eprintln!("{}", line))));
+    }
+}
+
+/// Run a single test, converting an engine panic into an error result
+///
+/// After a panic the engine's backend state is replaced, since backends
+/// hold `Rc` state that may be left inconsistent; its configuration (see
+/// [`XEngine::reset`]) is kept.
+pub fn run_isolated<F>(
+    engine: &mut XEngine,
+    test_id: &str,
+    test_set: &str,
+    test_suite: &str,
+    description: Option<String>,
+    run: F,
+) -> TestResult
+where
+    F: FnOnce(&mut XEngine) -> TestResult,
+{
+    let start = Instant::now();
+    match panic::catch_unwind(AssertUnwindSafe(|| run(&mut *engine))) {
+        Ok(result) => result,
+        Err(payload) => {
+            engine.reset();
+            TestResult {
+                test_id: test_id.to_string(),
+                test_set: test_set.to_string(),
+                test_suite: test_suite.to_string(),
+                description,
+                outcome: TestOutcome::Error(format!("Panic: {}", panic_message(payload.as_ref()))),
+                expected: None,
+                actual: Some("PANIC".to_string()),
+                duration: start.elapsed(),
+                covers: None,
+                modified: None,
+            }
+        }
+    }
+}
+
 /// Outcome of a test
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TestOutcome {
@@ -76,6 +502,10 @@ pub enum TestOutcome {
     NotApplicable,
     /// Test was skipped
     Skipped,
+    /// Test on the expected-fail list failed or errored, with the reason
+    ExpectedFail(String),
+    /// Test on the expected-fail list passed
+    UnexpectedPass,
 }
 
 impl TestOutcome {
@@ -99,14 +529,28 @@ impl TestOutcome {
             TestOutcome::Error(_) => "error",
             TestOutcome::NotApplicable => "n/a",
             TestOutcome::Skipped => "skipped",
+            TestOutcome::ExpectedFail(_) => "xfail",
+            TestOutcome::UnexpectedPass => "xpass",
         }
     }
 
     /// Get the message if any
     pub fn message(&self) -> Option<&str> {
         match self {
-            TestOutcome::Fail(msg) | TestOutcome::Error(msg) => Some(msg),
+            TestOutcome::Fail(msg) | TestOutcome::Error(msg) | TestOutcome::ExpectedFail(msg) => {
+                Some(msg)
+            }
             _ => None,
         }
     }
 }
+
+impl fmt::Display for TestOutcome {
+    /// Formats as the short code, followed by the message if any (e.g. `fail: <message>`)
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.message() {
+            Some(msg) => write!(f, "{}: {}", self.as_str(), msg),
+            None => f.write_str(self.as_str()),
+        }
+    }
+}
@@ -2,37 +2,53 @@
 //!
 //! Runs tests from the W3C QT3 test suite against any XPathEngine or XQueryEngine.
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs;
 use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::rc::Rc;
+use std::time::{Instant, SystemTime};
 
-use crate::error::Result;
-use crate::unified::{XDocument, XEngine, XQueryResult};
+use serde::Serialize;
 
-use super::{TestOutcome, TestResult};
+use crate::canonical::{canonicalize, xml_equivalent, CanonicalOptions};
+use crate::collation::{collator_for_uri, CodepointCollator, Collator};
+use crate::error::Result;
+use crate::reporter::render_diff;
+use crate::result::ResultItem;
+use crate::sequence_type::SequenceType;
+use crate::serialization::SerializationOptions;
+use crate::traits::{Capability, DecimalFormat};
+use crate::unified::{Backend, XDocument, XEngine, XQueryResult};
+
+use super::{
+    catalog_entry_error, excluded_result, install_env_trace, parse_catalog_document,
+    resolve_source_path, run_isolated, RunOptions, RunStats, TestOutcome, TestResult,
+};
 
 // ============== Data Model ==============
 
 /// A parsed QT3 catalog
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Catalog {
     /// Global environments available to all tests
     pub environments: HashMap<String, Environment>,
     /// Test set references (name -> relative file path)
     pub test_sets: Vec<TestSetRef>,
+    /// Malformed catalog entries that were skipped, one message per entry
+    pub errors: Vec<String>,
 }
 
 /// Reference to a test set file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TestSetRef {
     pub name: String,
     pub file: String,
 }
 
 /// A test set containing multiple test cases
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct TestSet {
     pub name: String,
     /// Local environments defined in this test set
@@ -41,10 +57,13 @@ pub struct TestSet {
     pub dependencies: Vec<Dependency>,
     /// Test cases
     pub test_cases: Vec<TestCase>,
+    /// Entries skipped by lenient parsing and test cases that failed to
+    /// load, one message per entry
+    pub errors: Vec<String>,
 }
 
 /// Test environment configuration
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Environment {
     pub name: Option<String>,
     /// Source documents with roles
@@ -59,10 +78,14 @@ pub struct Environment {
     pub collections: Vec<Collection>,
     /// Static base URI
     pub static_base_uri: Option<String>,
+    /// Default collation URI
+    pub default_collation: Option<String>,
+    /// Decimal formats for `fn:format-number`
+    pub decimal_formats: Vec<DecimalFormat>,
 }
 
 /// Source document for an environment
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Source {
     /// Role: "." for context item, "$varname" for variables
     pub role: String,
@@ -75,7 +98,7 @@ pub struct Source {
 }
 
 /// Parameter definition
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Param {
     pub name: String,
     pub select: String,
@@ -83,21 +106,21 @@ pub struct Param {
 }
 
 /// Schema reference
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SchemaRef {
     pub uri: String,
     pub file: PathBuf,
 }
 
 /// Collection reference
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Collection {
     pub uri: String,
     pub sources: Vec<Source>,
 }
 
 /// Dependency specification
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Dependency {
     pub dep_type: String,
     pub value: String,
@@ -105,10 +128,16 @@ pub struct Dependency {
 }
 
 /// A single test case
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct TestCase {
     pub name: String,
     pub description: String,
+    /// Spec areas covered by this test (`covers` attribute)
+    pub covers: Option<String>,
+    /// Date the test was created (`created/@on`)
+    pub created: Option<String>,
+    /// Date of the most recent modification (`modified/@on`)
+    pub modified: Option<String>,
     /// Environment reference or inline environment
     pub environment: Option<EnvironmentRef>,
     /// Dependencies specific to this test
@@ -120,7 +149,7 @@ pub struct TestCase {
 }
 
 /// Reference to an environment
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum EnvironmentRef {
     /// Reference by name
     Named(String),
@@ -129,7 +158,7 @@ pub enum EnvironmentRef {
 }
 
 /// Expected result assertions
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub enum Assertion {
     /// All nested assertions must pass
     AllOf(Vec<Assertion>),
@@ -156,8 +185,17 @@ pub enum Assertion {
     },
     /// Expected error code
     Error(String),
+    /// Expected serialization error code
+    SerializationError(String),
     /// XML comparison
-    AssertXml { xml: Option<String>, file: Option<String>, ignore_prefixes: bool },
+    AssertXml {
+        xml: Option<String>,
+        file: Option<String>,
+        ignore_prefixes: bool,
+        /// Whitespace-only text between elements is insignificant (unless
+        /// `xml:space="preserve"` is in scope on the assertion)
+        ignore_whitespace: bool,
+    },
     /// Deep equality with sequence
     AssertDeepEq(String),
     /// Result is permutation of expected
@@ -172,112 +210,186 @@ pub enum Assertion {
     },
 }
 
+impl Assertion {
+    /// Concise human-readable form, e.g. `assert-eq '3'` or `error XPTY0004`
+    pub fn describe(&self) -> String {
+        let nested = |assertions: &[Assertion]| {
+            assertions.iter().map(Assertion::describe).collect::<Vec<_>>().join(", ")
+        };
+        match self {
+            Assertion::AllOf(assertions) => format!("all-of[{}]", nested(assertions)),
+            Assertion::AnyOf(assertions) => format!("any-of[{}]", nested(assertions)),
+            Assertion::Not(assertion) => format!("not[{}]", assertion.describe()),
+            Assertion::AssertEq(value) => format!("assert-eq '{}'", value),
+            Assertion::AssertCount(count) => format!("assert-count {}", count),
+            Assertion::AssertEmpty => "assert-empty".to_string(),
+            Assertion::AssertTrue => "assert-true".to_string(),
+            Assertion::AssertFalse => "assert-false".to_string(),
+            Assertion::AssertType(type_name) => format!("assert-type {}", type_name),
+            Assertion::AssertStringValue { value, normalize_space } => format!(
+                "assert-string-value '{}'{}",
+                value,
+                if *normalize_space { " (normalize-space)" } else { "" }
+            ),
+            Assertion::Error(code) => format!("error {}", code),
+            Assertion::SerializationError(code) => format!("serialization-error {}", code),
+            Assertion::AssertXml { xml: Some(xml), .. } => format!("assert-xml '{}'", xml),
+            Assertion::AssertXml { file, .. } => {
+                format!("assert-xml file {}", file.as_deref().unwrap_or("?"))
+            }
+            Assertion::AssertDeepEq(value) => format!("assert-deep-eq '{}'", value),
+            Assertion::AssertPermutation(value) => format!("assert-permutation '{}'", value),
+            Assertion::Assert(expr) => format!("assert '{}'", expr),
+            Assertion::SerializationMatches { regex, file, flags } => {
+                let pattern = match (regex, file) {
+                    (Some(regex), _) => format!("'{}'", regex),
+                    (None, file) => format!("file {}", file.as_deref().unwrap_or("?")),
+                };
+                match flags {
+                    Some(flags) => format!("serialization-matches {} flags={}", pattern, flags),
+                    None => format!("serialization-matches {}", pattern),
+                }
+            }
+        }
+    }
+}
+
 // ============== Catalog Parsing ==============
 
+/// Results kept by the query cache while parsing a test set
+const QUERY_CACHE_CAPACITY: usize = 64;
+
 /// Parse a QT3 catalog file
 pub fn parse_catalog(catalog_path: &Path) -> Result<Catalog> {
+    parse_catalog_with_mode(catalog_path, false)
+}
+
+/// Parse a QT3 catalog file, skipping entries that aren't well-formed
+///
+/// If the catalog fails to parse, entries that don't parse on their own
+/// are dropped and recorded in [`Catalog::errors`].
+pub fn parse_catalog_lenient(catalog_path: &Path) -> Result<Catalog> {
+    parse_catalog_with_mode(catalog_path, true)
+}
+
+fn parse_catalog_with_mode(catalog_path: &Path, lenient: bool) -> Result<Catalog> {
     let content = fs::read_to_string(catalog_path)
         .map_err(|e| crate::error::Error::EngineError(format!("Failed to read catalog: {}", e)))?;
 
     let mut engine = XEngine::xee();
-    let doc = engine.parse(&content)?;
+    let (doc, skipped) = parse_catalog_document(&mut engine, &content, lenient)?;
 
     let mut catalog = Catalog {
         environments: HashMap::new(),
         test_sets: Vec::new(),
+        errors: skipped,
     };
 
     let base_dir = catalog_path.parent().unwrap_or(Path::new("."));
 
-    // Parse global environments
-    let env_result = engine.xpath(&doc, "//*[local-name()='environment' and parent::*[local-name()='catalog']]")?;
-    for _item in env_result.items() {
-        // For now, we'll parse environments lazily when needed
-    }
-
-    // Parse test-set references
-    let test_sets_result = engine.xpath(&doc, "//*[local-name()='test-set']/@name | //*[local-name()='test-set']/@file")?;
-    let items = test_sets_result.items();
-
-    // Process pairs of name/file attributes
-    let mut i = 0;
-    while i + 1 < items.len() {
-        if let (crate::result::ResultItem::String(name), crate::result::ResultItem::String(file)) =
-            (&items[i], &items[i + 1])
-        {
-            catalog.test_sets.push(TestSetRef {
-                name: name.clone(),
-                file: file.clone(),
-            });
-            i += 2;
-        } else {
-            i += 1;
-        }
-    }
-
-    // Parse test-set references using string() to get attribute values
-    let count_result = engine.xpath(&doc, "count(//*[local-name()='test-set'])")?;
-    let count_str = count_result.to_string();
-    let count: usize = count_str.trim().parse().unwrap_or(0);
+    // Parse test-set references, skipping (and recording) malformed entries
+    let count = engine.xpath_as_usize(&doc, "count(//*[local-name()='test-set'])")?;
 
     for idx in 1..=count {
-        // Use string() function to extract attribute values
         let name_xpath = format!("string(//*[local-name()='test-set'][{}]/@name)", idx);
         let file_xpath = format!("string(//*[local-name()='test-set'][{}]/@file)", idx);
 
-        let name_result = engine.xpath(&doc, &name_xpath)?;
-        let file_result = engine.xpath(&doc, &file_xpath)?;
-
-        let name = name_result.to_string().trim().to_string();
-        let file = file_result.to_string().trim().to_string();
+        let (name, file) = match (
+            engine.xpath_as_string(&doc, &name_xpath),
+            engine.xpath_as_string(&doc, &file_xpath),
+        ) {
+            (Ok(name), Ok(file)) => (name, file),
+            (Err(e), _) | (_, Err(e)) => {
+                catalog.errors.push(format!("test-set #{}: {}", idx, e));
+                continue;
+            }
+        };
 
-        if !name.is_empty() && !file.is_empty() {
-            catalog.test_sets.push(TestSetRef { name, file });
+        match (name.is_empty(), file.is_empty()) {
+            (false, false) => catalog.test_sets.push(TestSetRef { name, file }),
+            (true, _) => catalog.errors.push(format!("test-set #{}: missing @name", idx)),
+            (false, true) => catalog.errors.push(format!("test-set #{} ({}): missing @file", idx, name)),
         }
     }
 
     // Parse global environments
-    let env_count_result = engine.xpath(&doc, "count(//*[local-name()='catalog']/*[local-name()='environment'])")?;
-    let env_count: usize = env_count_result.to_string().trim().parse().unwrap_or(0);
+    let env_count = engine.xpath_as_usize(&doc, "count(//*[local-name()='catalog']/*[local-name()='environment'])")?;
 
     for idx in 1..=env_count {
-        if let Ok(env) = parse_environment_at_index(&mut engine, &doc, idx, base_dir, true) {
-            if let Some(name) = &env.name {
-                catalog.environments.insert(name.clone(), env);
+        match parse_environment_at_index(&mut engine, &doc, idx, base_dir, true) {
+            Ok(env) => {
+                if let Some(name) = &env.name {
+                    catalog.environments.insert(name.clone(), env);
+                }
             }
+            Err(e) => catalog.errors.push(format!("environment #{}: {}", idx, e)),
         }
     }
 
     Ok(catalog)
 }
 
+/// List the names of the test sets referenced by a QT3 catalog
+///
+/// Only the catalog itself is parsed; the test-set files are not read.
+pub fn list_test_sets(catalog_path: &Path) -> Result<Vec<String>> {
+    let catalog = parse_catalog(catalog_path)?;
+    let mut names: Vec<String> = Vec::new();
+    for test_set in catalog.test_sets {
+        if !names.contains(&test_set.name) {
+            names.push(test_set.name);
+        }
+    }
+    Ok(names)
+}
+
 /// Parse a test set file
 pub fn parse_test_set(
     test_set_path: &Path,
     global_envs: &HashMap<String, Environment>,
+) -> Result<TestSet> {
+    parse_test_set_with_mode(test_set_path, global_envs, false)
+}
+
+/// Parse a test set file, skipping entries that aren't well-formed
+///
+/// If the file fails to parse, entries that don't parse on their own are
+/// dropped and recorded in [`TestSet::errors`].
+pub fn parse_test_set_lenient(
+    test_set_path: &Path,
+    global_envs: &HashMap<String, Environment>,
+) -> Result<TestSet> {
+    parse_test_set_with_mode(test_set_path, global_envs, true)
+}
+
+fn parse_test_set_with_mode(
+    test_set_path: &Path,
+    global_envs: &HashMap<String, Environment>,
+    lenient: bool,
 ) -> Result<TestSet> {
     let content = fs::read_to_string(test_set_path)
         .map_err(|e| crate::error::Error::EngineError(format!("Failed to read test set: {}", e)))?;
 
     let mut engine = XEngine::xee();
-    let doc = engine.parse(&content)?;
+    // Assertion parsing re-runs the same count()/string() probes per test case
+    engine.enable_query_cache(QUERY_CACHE_CAPACITY);
+    let (doc, skipped) = parse_catalog_document(&mut engine, &content, lenient)?;
 
     let base_dir = test_set_path.parent().unwrap_or(Path::new("."));
 
     // Get test set name
-    let name_result = engine.xpath(&doc, "string(/*[local-name()='test-set']/@name)")?;
-    let name = name_result.to_string().trim().to_string();
+    let name = engine.xpath_as_string(&doc, "string(/*[local-name()='test-set']/@name)")?;
 
     let mut test_set = TestSet {
         name,
         environments: global_envs.clone(),
         dependencies: Vec::new(),
         test_cases: Vec::new(),
+        errors: skipped,
     };
 
     // Parse local environments
-    let env_count_result = engine.xpath(&doc, "count(/*[local-name()='test-set']/*[local-name()='environment'])")?;
-    let env_count: usize = env_count_result.to_string().trim().parse().unwrap_or(0);
+    let env_count = engine.xpath_as_usize(&doc, "count(/*[local-name()='test-set']/*[local-name()='environment'])")?;
 
     for idx in 1..=env_count {
         if let Ok(env) = parse_test_set_environment(&mut engine, &doc, idx, base_dir) {
@@ -288,12 +400,12 @@ pub fn parse_test_set(
     }
 
     // Parse test cases
-    let tc_count_result = engine.xpath(&doc, "count(//*[local-name()='test-case'])")?;
-    let tc_count: usize = tc_count_result.to_string().trim().parse().unwrap_or(0);
+    let tc_count = engine.xpath_as_usize(&doc, "count(//*[local-name()='test-case'])")?;
 
     for idx in 1..=tc_count {
-        if let Ok(tc) = parse_test_case(&mut engine, &doc, idx, base_dir) {
-            test_set.test_cases.push(tc);
+        match parse_test_case(&mut engine, &doc, idx, base_dir) {
+            Ok(tc) => test_set.test_cases.push(tc),
+            Err(e) => test_set.errors.push(format!("test-case #{}: {}", idx, e)),
         }
     }
 
@@ -335,51 +447,72 @@ fn parse_environment_with_prefix(
     let mut env = Environment::default();
 
     // Get name
-    let name_result = engine.xpath(doc, &format!("string({}/@name)", prefix))?;
-    let name = name_result.to_string().trim().to_string();
+    let name = engine.xpath_as_string(doc, &format!("string({}/@name)", prefix))?;
     if !name.is_empty() {
         env.name = Some(name);
     }
 
     // Get sources
-    let source_count_result = engine.xpath(doc, &format!("count({}/*[local-name()='source'])", prefix))?;
-    let source_count: usize = source_count_result.to_string().trim().parse().unwrap_or(0);
+    let source_count = engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='source'])", prefix))?;
 
     for sidx in 1..=source_count {
-        let role_result = engine.xpath(doc, &format!("string({}/*[local-name()='source'][{}]/@role)", prefix, sidx))?;
-        let file_result = engine.xpath(doc, &format!("string({}/*[local-name()='source'][{}]/@file)", prefix, sidx))?;
-        let uri_result = engine.xpath(doc, &format!("string({}/*[local-name()='source'][{}]/@uri)", prefix, sidx))?;
-
-        let role = role_result.to_string().trim().to_string();
-        let file = file_result.to_string().trim().to_string();
-        let uri = uri_result.to_string().trim().to_string();
+        let role = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='source'][{}]/@role)", prefix, sidx))?;
+        let file = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='source'][{}]/@file)", prefix, sidx))?;
+        let uri = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='source'][{}]/@uri)", prefix, sidx))?;
 
         if !file.is_empty() {
             env.sources.push(Source {
                 role: if role.is_empty() { ".".to_string() } else { role },
-                file: base_dir.join(&file),
+                file: resolve_source_path(base_dir, &file),
                 uri: if uri.is_empty() { None } else { Some(uri) },
                 validation: None,
             });
         }
     }
 
+    // Get static base URI
+    let base_uri = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='static-base-uri']/@uri)", prefix))?;
+    if !base_uri.is_empty() {
+        env.static_base_uri = Some(base_uri);
+    }
+
+    // Get default collation
+    let collation = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='collation'][@default='true']/@uri)", prefix))?;
+    if !collation.is_empty() {
+        env.default_collation = Some(collation);
+    }
+
     // Get namespaces
-    let ns_count_result = engine.xpath(doc, &format!("count({}/*[local-name()='namespace'])", prefix))?;
-    let ns_count: usize = ns_count_result.to_string().trim().parse().unwrap_or(0);
+    let ns_count = engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='namespace'])", prefix))?;
 
     for nidx in 1..=ns_count {
-        let prefix_result = engine.xpath(doc, &format!("string({}/*[local-name()='namespace'][{}]/@prefix)", prefix, nidx))?;
-        let uri_result = engine.xpath(doc, &format!("string({}/*[local-name()='namespace'][{}]/@uri)", prefix, nidx))?;
-
-        let ns_prefix = prefix_result.to_string().trim().to_string();
-        let ns_uri = uri_result.to_string().trim().to_string();
+        let ns_prefix = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='namespace'][{}]/@prefix)", prefix, nidx))?;
+        let ns_uri = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='namespace'][{}]/@uri)", prefix, nidx))?;
 
         if !ns_uri.is_empty() {
             env.namespaces.insert(ns_prefix, ns_uri);
         }
     }
 
+    // Get decimal formats
+    let df_count = engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='decimal-format'])", prefix))?;
+
+    for didx in 1..=df_count {
+        let df_prefix = format!("{}/*[local-name()='decimal-format'][{}]", prefix, didx);
+        let name = engine.xpath_as_string(doc, &format!("string({}/@name)", df_prefix))?;
+        let mut format = DecimalFormat {
+            name: Some(name).filter(|n| !n.is_empty()),
+            ..Default::default()
+        };
+        for property in DecimalFormat::PROPERTIES {
+            let value = engine.xpath_as_string(doc, &format!("string({}/@{})", df_prefix, property))?;
+            if !value.is_empty() {
+                format.set_property(property, &value);
+            }
+        }
+        env.decimal_formats.push(format);
+    }
+
     Ok(env)
 }
 
@@ -392,23 +525,24 @@ fn parse_test_case(
     let prefix = format!("//*[local-name()='test-case'][{}]", idx);
 
     // Get name
-    let name_result = engine.xpath(doc, &format!("string({}/@name)", prefix))?;
-    let name = name_result.to_string().trim().to_string();
+    let name = engine.xpath_as_string(doc, &format!("string({}/@name)", prefix))?;
 
     // Get description
-    let desc_result = engine.xpath(doc, &format!("string({}/*[local-name()='description'])", prefix))?;
-    let description = desc_result.to_string().trim().to_string();
+    let description = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='description'])", prefix))?;
+
+    // Get coverage and versioning metadata
+    let covers = engine.xpath_as_string(doc, &format!("string({}/@covers)", prefix))?;
+    let created = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='created']/@on)", prefix))?;
+    let modified = engine.xpath_as_string(doc, &format!("string(({}/*[local-name()='modified']/@on)[last()])", prefix))?;
 
     // Get environment reference
-    let env_ref_result = engine.xpath(doc, &format!("string({}/*[local-name()='environment']/@ref)", prefix))?;
-    let env_ref = env_ref_result.to_string().trim().to_string();
+    let env_ref = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='environment']/@ref)", prefix))?;
 
     let environment = if !env_ref.is_empty() {
         Some(EnvironmentRef::Named(env_ref))
     } else {
         // Check for inline environment
-        let has_inline_result = engine.xpath(doc, &format!("count({}/*[local-name()='environment'])", prefix))?;
-        let has_inline: usize = has_inline_result.to_string().trim().parse().unwrap_or(0);
+        let has_inline = engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='environment'])", prefix))?;
         if has_inline > 0 {
             let env_prefix = format!("{}/*[local-name()='environment']", prefix);
             if let Ok(env) = parse_environment_with_prefix(engine, doc, &env_prefix, base_dir) {
@@ -421,26 +555,30 @@ fn parse_test_case(
         }
     };
 
-    // Get test expression
-    let test_result = engine.xpath(doc, &format!("string({}/*[local-name()='test'])", prefix))?;
-    let test = test_result.to_string().trim().to_string();
+    // Get test expression, inline or from a file relative to the test set
+    let test_file = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='test']/@file)", prefix))?;
+    let test = if test_file.is_empty() {
+        engine.xpath_as_string(doc, &format!("string({}/*[local-name()='test'])", prefix))?
+    } else {
+        let path = resolve_source_path(base_dir, &test_file);
+        let text = fs::read_to_string(&path).map_err(|e| {
+            crate::error::Error::EngineError(format!("Failed to read test {}: {}", path.display(), e))
+        })?;
+        // Trimmed like inline test text
+        text.trim().to_string()
+    };
 
     // Parse result assertion
     let result = parse_assertion(engine, doc, &format!("{}/*[local-name()='result']", prefix))?;
 
     // Parse dependencies
     let mut dependencies = Vec::new();
-    let dep_count_result = engine.xpath(doc, &format!("count({}/*[local-name()='dependency'])", prefix))?;
-    let dep_count: usize = dep_count_result.to_string().trim().parse().unwrap_or(0);
+    let dep_count = engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='dependency'])", prefix))?;
 
     for didx in 1..=dep_count {
-        let type_result = engine.xpath(doc, &format!("string({}/*[local-name()='dependency'][{}]/@type)", prefix, didx))?;
-        let value_result = engine.xpath(doc, &format!("string({}/*[local-name()='dependency'][{}]/@value)", prefix, didx))?;
-        let satisfied_result = engine.xpath(doc, &format!("string({}/*[local-name()='dependency'][{}]/@satisfied)", prefix, didx))?;
-
-        let dep_type = type_result.to_string().trim().to_string();
-        let value = value_result.to_string().trim().to_string();
-        let satisfied_str = satisfied_result.to_string().trim().to_string();
+        let dep_type = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='dependency'][{}]/@type)", prefix, didx))?;
+        let value = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='dependency'][{}]/@value)", prefix, didx))?;
+        let satisfied_str = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='dependency'][{}]/@satisfied)", prefix, didx))?;
         let satisfied = satisfied_str != "false";
 
         if !dep_type.is_empty() {
@@ -451,6 +589,9 @@ fn parse_test_case(
     Ok(TestCase {
         name,
         description,
+        covers: if covers.is_empty() { None } else { Some(covers) },
+        created: if created.is_empty() { None } else { Some(created) },
+        modified: if modified.is_empty() { None } else { Some(modified) },
         environment,
         dependencies,
         test,
@@ -466,123 +607,106 @@ fn parse_assertion(
     // Check for each assertion type
 
     // all-of
-    let all_of_count_result = engine.xpath(doc, &format!("count({}/*[local-name()='all-of'])", prefix))?;
-    if all_of_count_result.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='all-of'])", prefix))? > 0 {
         let inner_prefix = format!("{}/*[local-name()='all-of']", prefix);
         let assertions = parse_nested_assertions(engine, doc, &inner_prefix)?;
         return Ok(Assertion::AllOf(assertions));
     }
 
     // any-of
-    let any_of_count_result = engine.xpath(doc, &format!("count({}/*[local-name()='any-of'])", prefix))?;
-    if any_of_count_result.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='any-of'])", prefix))? > 0 {
         let inner_prefix = format!("{}/*[local-name()='any-of']", prefix);
         let assertions = parse_nested_assertions(engine, doc, &inner_prefix)?;
         return Ok(Assertion::AnyOf(assertions));
     }
 
     // assert-eq
-    let assert_eq_count = engine.xpath(doc, &format!("count({}/*[local-name()='assert-eq'])", prefix))?;
-    if assert_eq_count.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
-        let assert_eq_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert-eq'])", prefix))?;
-        let assert_eq_val = assert_eq_result.to_string().trim().to_string();
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-eq'])", prefix))? > 0 {
+        let assert_eq_val = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-eq'])", prefix))?;
         return Ok(Assertion::AssertEq(assert_eq_val));
     }
 
     // assert-true
-    let assert_true_count = engine.xpath(doc, &format!("count({}/*[local-name()='assert-true'])", prefix))?;
-    if assert_true_count.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-true'])", prefix))? > 0 {
         return Ok(Assertion::AssertTrue);
     }
 
     // assert-false
-    let assert_false_count = engine.xpath(doc, &format!("count({}/*[local-name()='assert-false'])", prefix))?;
-    if assert_false_count.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-false'])", prefix))? > 0 {
         return Ok(Assertion::AssertFalse);
     }
 
     // assert-empty
-    let assert_empty_count = engine.xpath(doc, &format!("count({}/*[local-name()='assert-empty'])", prefix))?;
-    if assert_empty_count.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-empty'])", prefix))? > 0 {
         return Ok(Assertion::AssertEmpty);
     }
 
     // assert-count
-    let assert_count_check = engine.xpath(doc, &format!("count({}/*[local-name()='assert-count'])", prefix))?;
-    if assert_count_check.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
-        let assert_count_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert-count'])", prefix))?;
-        let assert_count_val = assert_count_result.to_string().trim().to_string();
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-count'])", prefix))? > 0 {
+        let assert_count_val = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-count'])", prefix))?;
         if let Ok(count) = assert_count_val.parse() {
             return Ok(Assertion::AssertCount(count));
         }
     }
 
     // assert-type
-    let assert_type_check = engine.xpath(doc, &format!("count({}/*[local-name()='assert-type'])", prefix))?;
-    if assert_type_check.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
-        let assert_type_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert-type'])", prefix))?;
-        let assert_type_val = assert_type_result.to_string().trim().to_string();
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-type'])", prefix))? > 0 {
+        let assert_type_val = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-type'])", prefix))?;
         return Ok(Assertion::AssertType(assert_type_val));
     }
 
     // assert-string-value
-    let assert_sv_count = engine.xpath(doc, &format!("count({}/*[local-name()='assert-string-value'])", prefix))?;
-    if assert_sv_count.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
-        let assert_sv_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert-string-value'])", prefix))?;
-        let normalize_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert-string-value']/@normalize-space)", prefix))?;
-        let normalize = normalize_result.to_string().trim() == "true";
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-string-value'])", prefix))? > 0 {
+        let assert_sv = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-string-value'])", prefix))?;
+        let normalize = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-string-value']/@normalize-space)", prefix))? == "true";
         return Ok(Assertion::AssertStringValue {
-            value: assert_sv_result.to_string().trim().to_string(),
+            value: assert_sv,
             normalize_space: normalize,
         });
     }
 
     // error
-    let error_result = engine.xpath(doc, &format!("string({}/*[local-name()='error']/@code)", prefix))?;
-    let error_code = error_result.to_string().trim().to_string();
+    let error_code = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='error']/@code)", prefix))?;
     if !error_code.is_empty() {
         return Ok(Assertion::Error(error_code));
     }
 
-    // assert-xml
-    let assert_xml_count = engine.xpath(doc, &format!("count({}/*[local-name()='assert-xml'])", prefix))?;
-    if assert_xml_count.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
-        let xml_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert-xml'])", prefix))?;
-        let file_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert-xml']/@file)", prefix))?;
-        let ignore_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert-xml']/@ignore-prefixes)", prefix))?;
+    // assert-serialization-error
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-serialization-error'])", prefix))? > 0 {
+        let code = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-serialization-error']/@code)", prefix))?;
+        return Ok(Assertion::SerializationError(code));
+    }
 
-        let xml = xml_result.to_string().trim().to_string();
-        let file = file_result.to_string().trim().to_string();
-        let ignore = ignore_result.to_string().trim() == "true";
+    // assert-xml
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-xml'])", prefix))? > 0 {
+        let xml = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-xml'])", prefix))?;
+        let file = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-xml']/@file)", prefix))?;
+        let ignore = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-xml']/@ignore-prefixes)", prefix))? == "true";
+        let space = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-xml']/ancestor-or-self::*[@xml:space][1]/@xml:space)", prefix))?;
 
         return Ok(Assertion::AssertXml {
             xml: if xml.is_empty() { None } else { Some(xml) },
             file: if file.is_empty() { None } else { Some(file) },
             ignore_prefixes: ignore,
+            ignore_whitespace: space != "preserve",
         });
     }
 
     // assert (custom XPath)
-    let assert_check = engine.xpath(doc, &format!("count({}/*[local-name()='assert'])", prefix))?;
-    if assert_check.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
-        let assert_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert'])", prefix))?;
-        let assert_val = assert_result.to_string().trim().to_string();
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert'])", prefix))? > 0 {
+        let assert_val = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert'])", prefix))?;
         return Ok(Assertion::Assert(assert_val));
     }
 
     // assert-deep-eq
-    let deep_eq_check = engine.xpath(doc, &format!("count({}/*[local-name()='assert-deep-eq'])", prefix))?;
-    if deep_eq_check.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
-        let deep_eq_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert-deep-eq'])", prefix))?;
-        let deep_eq_val = deep_eq_result.to_string().trim().to_string();
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-deep-eq'])", prefix))? > 0 {
+        let deep_eq_val = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-deep-eq'])", prefix))?;
         return Ok(Assertion::AssertDeepEq(deep_eq_val));
     }
 
     // assert-permutation
-    let perm_check = engine.xpath(doc, &format!("count({}/*[local-name()='assert-permutation'])", prefix))?;
-    if perm_check.to_string().trim().parse::<usize>().unwrap_or(0) > 0 {
-        let perm_result = engine.xpath(doc, &format!("string({}/*[local-name()='assert-permutation'])", prefix))?;
-        let perm_val = perm_result.to_string().trim().to_string();
+    if engine.xpath_as_usize(doc, &format!("count({}/*[local-name()='assert-permutation'])", prefix))? > 0 {
+        let perm_val = engine.xpath_as_string(doc, &format!("string({}/*[local-name()='assert-permutation'])", prefix))?;
         return Ok(Assertion::AssertPermutation(perm_val));
     }
 
@@ -598,15 +722,13 @@ fn parse_nested_assertions(
     let mut assertions = Vec::new();
 
     // Count all child elements that are assertions
-    let children_count_result = engine.xpath(doc, &format!("count({}/*)", prefix))?;
-    let children_count: usize = children_count_result.to_string().trim().parse().unwrap_or(0);
+    let children_count = engine.xpath_as_usize(doc, &format!("count({}/*)", prefix))?;
 
     for idx in 1..=children_count {
         let child_prefix = format!("{}/*[{}]", prefix, idx);
 
         // Get the local name of this element
-        let name_result = engine.xpath(doc, &format!("local-name({})", child_prefix))?;
-        let local_name = name_result.to_string().trim().to_string();
+        let local_name = engine.xpath_as_string(doc, &format!("local-name({})", child_prefix))?;
 
         let assertion = match local_name.as_str() {
             "all-of" => {
@@ -626,67 +748,65 @@ fn parse_nested_assertions(
                 }
             }
             "assert-eq" => {
-                let val_result = engine.xpath(doc, &format!("string({})", child_prefix))?;
-                Assertion::AssertEq(val_result.to_string().trim().to_string())
+                let val = engine.xpath_as_string(doc, &format!("string({})", child_prefix))?;
+                Assertion::AssertEq(val)
             }
             "assert-true" => Assertion::AssertTrue,
             "assert-false" => Assertion::AssertFalse,
             "assert-empty" => Assertion::AssertEmpty,
             "assert-count" => {
-                let val_result = engine.xpath(doc, &format!("string({})", child_prefix))?;
-                let count: usize = val_result.to_string().trim().parse().unwrap_or(0);
+                let count = engine.xpath_as_usize(doc, &format!("string({})", child_prefix))?;
                 Assertion::AssertCount(count)
             }
             "assert-type" => {
-                let val_result = engine.xpath(doc, &format!("string({})", child_prefix))?;
-                Assertion::AssertType(val_result.to_string().trim().to_string())
+                let val = engine.xpath_as_string(doc, &format!("string({})", child_prefix))?;
+                Assertion::AssertType(val)
             }
             "assert-string-value" => {
-                let val_result = engine.xpath(doc, &format!("string({})", child_prefix))?;
-                let normalize_result = engine.xpath(doc, &format!("string({}/@normalize-space)", child_prefix))?;
+                let val = engine.xpath_as_string(doc, &format!("string({})", child_prefix))?;
+                let normalize = engine.xpath_as_string(doc, &format!("string({}/@normalize-space)", child_prefix))?;
                 Assertion::AssertStringValue {
-                    value: val_result.to_string().trim().to_string(),
-                    normalize_space: normalize_result.to_string().trim() == "true",
+                    value: val,
+                    normalize_space: normalize == "true",
                 }
             }
             "error" => {
-                let code_result = engine.xpath(doc, &format!("string({}/@code)", child_prefix))?;
-                Assertion::Error(code_result.to_string().trim().to_string())
+                let code = engine.xpath_as_string(doc, &format!("string({}/@code)", child_prefix))?;
+                Assertion::Error(code)
+            }
+            "assert-serialization-error" => {
+                let code = engine.xpath_as_string(doc, &format!("string({}/@code)", child_prefix))?;
+                Assertion::SerializationError(code)
             }
             "assert-xml" => {
-                let xml_result = engine.xpath(doc, &format!("string({})", child_prefix))?;
-                let file_result = engine.xpath(doc, &format!("string({}/@file)", child_prefix))?;
-                let ignore_result = engine.xpath(doc, &format!("string({}/@ignore-prefixes)", child_prefix))?;
-
-                let xml = xml_result.to_string().trim().to_string();
-                let file = file_result.to_string().trim().to_string();
+                let xml = engine.xpath_as_string(doc, &format!("string({})", child_prefix))?;
+                let file = engine.xpath_as_string(doc, &format!("string({}/@file)", child_prefix))?;
+                let ignore = engine.xpath_as_string(doc, &format!("string({}/@ignore-prefixes)", child_prefix))?;
+                let space = engine.xpath_as_string(doc, &format!("string({}/ancestor-or-self::*[@xml:space][1]/@xml:space)", child_prefix))?;
 
                 Assertion::AssertXml {
                     xml: if xml.is_empty() { None } else { Some(xml) },
                     file: if file.is_empty() { None } else { Some(file) },
-                    ignore_prefixes: ignore_result.to_string().trim() == "true",
+                    ignore_prefixes: ignore == "true",
+                    ignore_whitespace: space != "preserve",
                 }
             }
             "assert" => {
-                let val_result = engine.xpath(doc, &format!("string({})", child_prefix))?;
-                Assertion::Assert(val_result.to_string().trim().to_string())
+                let val = engine.xpath_as_string(doc, &format!("string({})", child_prefix))?;
+                Assertion::Assert(val)
             }
             "assert-deep-eq" => {
-                let val_result = engine.xpath(doc, &format!("string({})", child_prefix))?;
-                Assertion::AssertDeepEq(val_result.to_string().trim().to_string())
+                let val = engine.xpath_as_string(doc, &format!("string({})", child_prefix))?;
+                Assertion::AssertDeepEq(val)
             }
             "assert-permutation" => {
-                let val_result = engine.xpath(doc, &format!("string({})", child_prefix))?;
-                Assertion::AssertPermutation(val_result.to_string().trim().to_string())
+                let val = engine.xpath_as_string(doc, &format!("string({})", child_prefix))?;
+                Assertion::AssertPermutation(val)
             }
             "serialization-matches" => {
-                let regex_result = engine.xpath(doc, &format!("string({})", child_prefix))?;
-                let file_result = engine.xpath(doc, &format!("string({}/@file)", child_prefix))?;
-                let flags_result = engine.xpath(doc, &format!("string({}/@flags)", child_prefix))?;
-
-                let regex = regex_result.to_string().trim().to_string();
-                let file = file_result.to_string().trim().to_string();
-                let flags = flags_result.to_string().trim().to_string();
+                let regex = engine.xpath_as_string(doc, &format!("string({})", child_prefix))?;
+                let file = engine.xpath_as_string(doc, &format!("string({}/@file)", child_prefix))?;
+                let flags = engine.xpath_as_string(doc, &format!("string({}/@flags)", child_prefix))?;
 
                 Assertion::SerializationMatches {
                     regex: if regex.is_empty() { None } else { Some(regex) },
@@ -703,8 +823,112 @@ fn parse_nested_assertions(
     Ok(assertions)
 }
 
+// ============== Catalog Cache ==============
+
+/// Memoized catalogs and test sets, keyed by file path and modification time
+///
+/// A file is re-parsed when its modification time changes. Cached test sets
+/// keep the global environments they were first parsed with.
+#[derive(Debug, Default)]
+pub struct CatalogCache {
+    catalogs: HashMap<PathBuf, (SystemTime, Rc<Catalog>)>,
+    test_sets: HashMap<PathBuf, (SystemTime, Rc<TestSet>)>,
+    hits: usize,
+}
+
+impl CatalogCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of lookups answered from the cache
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Drop all cached entries
+    pub fn clear(&mut self) {
+        self.catalogs.clear();
+        self.test_sets.clear();
+    }
+
+    /// Parse a catalog, reusing the cached result if the file is unchanged
+    pub fn catalog(&mut self, catalog_path: &Path) -> Result<Rc<Catalog>> {
+        cached(&mut self.catalogs, &mut self.hits, catalog_path, || parse_catalog(catalog_path))
+    }
+
+    /// Parse a test set, reusing the cached result if the file is unchanged
+    pub fn test_set(
+        &mut self,
+        test_set_path: &Path,
+        global_envs: &HashMap<String, Environment>,
+    ) -> Result<Rc<TestSet>> {
+        cached(&mut self.test_sets, &mut self.hits, test_set_path, || {
+            parse_test_set(test_set_path, global_envs)
+        })
+    }
+}
+
+/// Look up a parsed file, parsing and caching it on a miss
+///
+/// Files whose modification time can't be read are parsed but not cached.
+fn cached<T>(
+    entries: &mut HashMap<PathBuf, (SystemTime, Rc<T>)>,
+    hits: &mut usize,
+    path: &Path,
+    parse: impl FnOnce() -> Result<T>,
+) -> Result<Rc<T>> {
+    let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) else {
+        return parse().map(Rc::new);
+    };
+    if let Some((cached_modified, value)) = entries.get(path) {
+        if *cached_modified == modified {
+            *hits += 1;
+            return Ok(Rc::clone(value));
+        }
+    }
+    let value = Rc::new(parse()?);
+    entries.insert(path.to_path_buf(), (modified, Rc::clone(&value)));
+    Ok(value)
+}
+
+thread_local! {
+    static CATALOG_CACHE: RefCell<CatalogCache> = RefCell::new(CatalogCache::new());
+}
+
+/// Parse a catalog through this thread's shared [`CatalogCache`]
+pub fn parse_catalog_cached(catalog_path: &Path) -> Result<Rc<Catalog>> {
+    CATALOG_CACHE.with(|cache| cache.borrow_mut().catalog(catalog_path))
+}
+
+/// Parse a test set through this thread's shared [`CatalogCache`]
+pub fn parse_test_set_cached(
+    test_set_path: &Path,
+    global_envs: &HashMap<String, Environment>,
+) -> Result<Rc<TestSet>> {
+    CATALOG_CACHE.with(|cache| cache.borrow_mut().test_set(test_set_path, global_envs))
+}
+
+/// Number of lookups answered from this thread's shared [`CatalogCache`]
+pub fn catalog_cache_hits() -> usize {
+    CATALOG_CACHE.with(|cache| cache.borrow().hits())
+}
+
 // ============== Test Execution ==============
 
+/// QT3 feature dependency names and the matching `XEngine::supported_features`
+/// entries; features no backend lists are never satisfied
+const QT3_FEATURES: &[(&str, &str)] = &[
+    ("higherOrderFunctions", "higher-order-functions"),
+    ("serialization", "serialization"),
+    ("schemaImport", "schema-import"),
+    ("schemaValidation", "schema-validation"),
+    ("staticTyping", "static-typing"),
+    ("moduleImport", "module-import"),
+    ("collection-stability", "collection-stability"),
+    ("directory-as-collection-uri", "directory-as-collection-uri"),
+];
+
 /// Check if a dependency is satisfied by the engine
 fn check_dependency(dependency: &Dependency, engine: &XEngine) -> bool {
     match dependency.dep_type.as_str() {
@@ -730,26 +954,69 @@ fn check_dependency(dependency: &Dependency, engine: &XEngine) -> bool {
             }
         }
         "feature" => {
-            // Check feature support
-            // For now, skip most advanced features
-            let unsupported = [
-                "serialization", "schema-import", "schema-validation",
-                "static-typing", "module", "collection-stability",
-                "directory-as-collection-uri", "higherOrderFunctions",
-            ];
-            !unsupported.iter().any(|f| dependency.value.contains(f))
+            // Features outside QT3_FEATURES are assumed to be available
+            let supported = engine.supported_features();
+            dependency.value.split_whitespace().all(|value| {
+                match QT3_FEATURES.iter().find(|(qt3, _)| *qt3 == value) {
+                    Some((_, feature)) => {
+                        supported.iter().any(|f| f == feature) == dependency.satisfied
+                    }
+                    None => true,
+                }
+            })
+        }
+        "collation" => collator_for_uri(&dependency.value).is_some(),
+        // Configured on the engine before the test runs
+        "implicit-timezone" => {
+            engine.supports(Capability::ImplicitTimezone)
+                && timezone_offset_minutes(&dependency.value).is_some()
         }
         _ => dependency.satisfied,
     }
 }
 
+/// Parse a `Z` or `±HH:MM` timezone into minutes east of UTC
+fn timezone_offset_minutes(value: &str) -> Option<i32> {
+    let value = value.trim();
+    if value == "Z" {
+        return Some(0);
+    }
+    let (sign, offset) = match value.split_at_checked(1)? {
+        ("+", offset) => (1, offset),
+        ("-", offset) => (-1, offset),
+        _ => return None,
+    };
+    let (hours, minutes) = offset.split_once(':')?;
+    let (hours, minutes) = (hours.parse::<i32>().ok()?, minutes.parse::<i32>().ok()?);
+    (hours <= 14 && minutes < 60).then_some(sign * (hours * 60 + minutes))
+}
+
 /// Run a single test case
 pub fn run_test_case(
     engine: &mut XEngine,
     test_case: &TestCase,
     test_set_name: &str,
     environments: &HashMap<String, Environment>,
-    _base_dir: &Path,
+    base_dir: &Path,
+) -> TestResult {
+    run_test_case_with_options(
+        engine,
+        test_case,
+        test_set_name,
+        environments,
+        base_dir,
+        &RunOptions::default(),
+    )
+}
+
+/// Run a single test case, honoring the assertion policies in `options`
+pub fn run_test_case_with_options(
+    engine: &mut XEngine,
+    test_case: &TestCase,
+    test_set_name: &str,
+    environments: &HashMap<String, Environment>,
+    base_dir: &Path,
+    options: &RunOptions,
 ) -> TestResult {
     let start = Instant::now();
 
@@ -764,27 +1031,55 @@ pub fn run_test_case(
             expected,
             actual,
             duration: start.elapsed(),
+            covers: test_case.covers.clone(),
+            modified: test_case.modified.clone(),
         }
     };
 
     // Check dependencies
-    for dep in &test_case.dependencies {
-        if !check_dependency(dep, engine) {
-            return make_result(
-                TestOutcome::NotApplicable,
-                None,
-                Some(format!("Dependency not satisfied: {} = {}", dep.dep_type, dep.value)),
-            );
-        }
+    if let Some(dep) = unsatisfied_dependency(test_case, engine) {
+        return make_result(
+            TestOutcome::NotApplicable,
+            None,
+            Some(format!("Dependency not satisfied: {} = {}", dep.dep_type, dep.value)),
+        );
     }
 
     // Set up environment
-    let env = match &test_case.environment {
-        Some(EnvironmentRef::Named(name)) => environments.get(name).cloned(),
-        Some(EnvironmentRef::Inline(env)) => Some(env.clone()),
-        None => None,
+    let env = resolve_environment(test_case, environments);
+
+    // Resolve the collation used for string comparisons
+    let collator: Box<dyn Collator> = match collation_uri(test_case, env.as_ref()) {
+        Some(uri) => match collator_for_uri(&uri) {
+            Some(c) => c,
+            None => {
+                return make_result(
+                    TestOutcome::NotApplicable,
+                    None,
+                    Some(format!("Unsupported collation: {}", uri)),
+                );
+            }
+        },
+        None => Box::new(CodepointCollator),
     };
 
+    // Apply the environment's static base URI (or clear a previous test's)
+    engine.set_static_base_uri(env.as_ref().and_then(|e| e.static_base_uri.as_deref()));
+    engine.set_decimal_formats(env.as_ref().map(|e| e.decimal_formats.as_slice()).unwrap_or(&[]));
+    // A namespace binding with an empty prefix is the default element namespace
+    engine.set_default_element_namespace(
+        env.as_ref().and_then(|e| e.namespaces.get("")).map(String::as_str),
+    );
+    let timezone = test_case
+        .dependencies
+        .iter()
+        .find(|dep| dep.dep_type == "implicit-timezone")
+        .and_then(|dep| timezone_offset_minutes(&dep.value));
+    match timezone {
+        Some(offset_minutes) => engine.set_implicit_timezone(offset_minutes),
+        None => engine.clear_implicit_timezone(),
+    }
+
     // Load context document if specified
     let context_doc = if let Some(env) = &env {
         // Find the context item source (role = ".")
@@ -792,6 +1087,16 @@ pub fn run_test_case(
         if let Some(source) = context_source {
             match engine.parse_file(&source.file) {
                 Ok(doc) => Some(doc),
+                // Missing test data is not an engine failure
+                Err(crate::error::Error::IoError(e))
+                    if e.kind() == std::io::ErrorKind::NotFound =>
+                {
+                    return make_result(
+                        TestOutcome::Skipped,
+                        None,
+                        Some(format!("Missing source file: {}", source.file.display())),
+                    );
+                }
                 Err(e) => {
                     return make_result(
                         TestOutcome::Error(format!("Failed to load context: {}", e)),
@@ -807,37 +1112,176 @@ pub fn run_test_case(
         None
     };
 
-    // Execute test
-    let result = if let Some(doc) = &context_doc {
-        engine.xpath(doc, &test_case.test)
-    } else {
-        // No context - try to evaluate anyway
-        // Many tests work without a context document
-        let empty_doc = match engine.parse("<empty/>") {
-            Ok(d) => d,
+    // Execute test; without a context source the context is an empty document
+    let doc = match context_doc {
+        Some(doc) => doc,
+        None => match engine.empty_document() {
+            Ok(doc) => doc,
             Err(e) => {
                 return make_result(
-                    TestOutcome::Error(format!("Failed to create empty doc: {}", e)),
+                    TestOutcome::Error(format!("Failed to create empty context: {}", e)),
                     None,
                     None,
                 );
             }
-        };
-        engine.xpath(&empty_doc, &test_case.test)
+        },
     };
 
+    // Load sources bound to variables (role `$name`)
+    let mut variables = Vec::new();
+    for source in env.iter().flat_map(|e| &e.sources) {
+        let Some(name) = source.role.strip_prefix('$') else {
+            continue;
+        };
+        match engine.parse_file(&source.file) {
+            Ok(variable) => variables.push((name.to_string(), variable)),
+            Err(crate::error::Error::IoError(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                return make_result(
+                    TestOutcome::Skipped,
+                    None,
+                    Some(format!("Missing source file: {}", source.file.display())),
+                );
+            }
+            Err(e) => {
+                return make_result(
+                    TestOutcome::Error(format!("Failed to load source ${}: {}", name, e)),
+                    None,
+                    None,
+                );
+            }
+        }
+    }
+    let result = engine.xpath_with_variables(&doc, &variables, &test_case.test);
+
     // Check assertion
+    let ctx = AssertionContext {
+        collator: collator.as_ref(),
+        doc: &doc,
+        expression: &test_case.test,
+        base_dir,
+        allow_error_as_empty: options.allow_error_as_empty,
+    };
     let outcome = match &result {
-        Ok(query_result) => check_assertion(&test_case.result, Ok(query_result), engine),
-        Err(e) => check_assertion(&test_case.result, Err(e), engine),
+        Ok(query_result) => check_assertion(&test_case.result, Ok(query_result), engine, &ctx),
+        Err(e) => check_assertion(&test_case.result, Err(e), engine, &ctx),
+    };
+
+    // Say what the result was made of, to help read node-set mismatches
+    let outcome = match (outcome, &result) {
+        (TestOutcome::Fail(message), Ok(r)) if !r.is_empty_sequence() => {
+            TestOutcome::Fail(with_histogram(&message, r))
+        }
+        (outcome, _) => outcome,
     };
 
     let actual = match &result {
+        Ok(r) if r.is_empty_sequence() => Some("()".to_string()),
         Ok(r) => Some(r.to_string()),
         Err(e) => Some(format!("Error: {}", e)),
     };
 
-    make_result(outcome, Some(format!("{:?}", test_case.result)), actual)
+    make_result(outcome, Some(test_case.result.describe()), actual)
+}
+
+/// Check a test case's applicability without evaluating it
+///
+/// Returns `NotApplicable` when a dependency or collation is unsupported,
+/// and `Skipped` otherwise.
+pub fn dry_run_test_case(
+    engine: &XEngine,
+    test_case: &TestCase,
+    test_set_name: &str,
+    environments: &HashMap<String, Environment>,
+) -> TestResult {
+    let (outcome, reason) = if let Some(dep) = unsatisfied_dependency(test_case, engine) {
+        (
+            TestOutcome::NotApplicable,
+            format!("Dependency not satisfied: {} = {}", dep.dep_type, dep.value),
+        )
+    } else {
+        match collation_uri(test_case, resolve_environment(test_case, environments).as_ref()) {
+            Some(uri) if collator_for_uri(&uri).is_none() => {
+                (TestOutcome::NotApplicable, format!("Unsupported collation: {}", uri))
+            }
+            _ => (TestOutcome::Skipped, "Dry run".to_string()),
+        }
+    };
+
+    let mut result = TestResult::new(
+        test_case.name.clone(),
+        test_set_name,
+        "qt3",
+        Some(test_case.description.clone()),
+        outcome,
+        std::time::Duration::ZERO,
+    )
+    .with_values(None, Some(reason));
+    result.covers = test_case.covers.clone();
+    result.modified = test_case.modified.clone();
+    result
+}
+
+/// Find the first dependency the engine doesn't satisfy
+fn unsatisfied_dependency<'a>(test_case: &'a TestCase, engine: &XEngine) -> Option<&'a Dependency> {
+    test_case.dependencies.iter().find(|dep| !check_dependency(dep, engine))
+}
+
+/// Get the environment a test case runs in, if any
+fn resolve_environment(
+    test_case: &TestCase,
+    environments: &HashMap<String, Environment>,
+) -> Option<Environment> {
+    match &test_case.environment {
+        Some(EnvironmentRef::Named(name)) => environments.get(name).cloned(),
+        Some(EnvironmentRef::Inline(env)) => Some(env.clone()),
+        None => None,
+    }
+}
+
+/// Get the collation URI a test case compares strings with, if not the default
+fn collation_uri(test_case: &TestCase, env: Option<&Environment>) -> Option<String> {
+    env.and_then(|e| e.default_collation.clone()).or_else(|| {
+        test_case
+            .dependencies
+            .iter()
+            .find(|d| d.dep_type == "collation")
+            .map(|d| d.value.clone())
+    })
+}
+
+/// Per-test state used while checking assertions
+struct AssertionContext<'a> {
+    /// Collation for string comparisons
+    collator: &'a dyn Collator,
+    /// Document the test expression was evaluated against
+    doc: &'a XDocument,
+    /// The test expression itself
+    expression: &'a str,
+    /// Directory of the test set, against which assertion `file`s resolve
+    base_dir: &'a Path,
+    /// Accept a dynamic error where the empty sequence is expected
+    allow_error_as_empty: bool,
+}
+
+impl AssertionContext<'_> {
+    /// Get an assertion's expected content: the inline value if present,
+    /// otherwise the contents of its `file` relative to the test set
+    fn expected_content(&self, inline: &Option<String>, file: &Option<String>) -> Result<Option<String>> {
+        match (inline, file) {
+            (Some(value), _) => Ok(Some(value.clone())),
+            (None, Some(file)) => {
+                let path = resolve_source_path(self.base_dir, file);
+                fs::read_to_string(&path).map(Some).map_err(|e| {
+                    crate::error::Error::EngineError(format!(
+                        "Failed to read expected output {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })
+            }
+            (None, None) => Ok(None),
+        }
+    }
 }
 
 /// Check if a result satisfies an assertion
@@ -845,11 +1289,12 @@ fn check_assertion(
     assertion: &Assertion,
     result: std::result::Result<&XQueryResult, &crate::error::Error>,
     engine: &mut XEngine,
+    ctx: &AssertionContext,
 ) -> TestOutcome {
     match assertion {
         Assertion::AllOf(assertions) => {
             for a in assertions {
-                match check_assertion(a, result, engine) {
+                match check_assertion(a, result, engine, ctx) {
                     TestOutcome::Pass => continue,
                     other => return other,
                 }
@@ -860,7 +1305,7 @@ fn check_assertion(
         Assertion::AnyOf(assertions) => {
             let mut last_failure = None;
             for a in assertions {
-                match check_assertion(a, result, engine) {
+                match check_assertion(a, result, engine, ctx) {
                     TestOutcome::Pass => return TestOutcome::Pass,
                     other => last_failure = Some(other),
                 }
@@ -869,7 +1314,7 @@ fn check_assertion(
         }
 
         Assertion::Not(inner) => {
-            match check_assertion(inner, result, engine) {
+            match check_assertion(inner, result, engine, ctx) {
                 TestOutcome::Pass => TestOutcome::Fail("Expected NOT to pass".to_string()),
                 TestOutcome::Fail(_) => TestOutcome::Pass,
                 other => other,
@@ -878,13 +1323,36 @@ fn check_assertion(
 
         Assertion::AssertEq(expected) => {
             match result {
+                Ok(r) if r.is_empty_sequence() => TestOutcome::Fail(format!(
+                    "Value mismatch: expected '{}', got empty sequence",
+                    expected.trim()
+                )),
                 Ok(r) => {
                     let actual = r.to_string().trim().to_string();
                     let expected = expected.trim();
-                    if actual == expected {
-                        TestOutcome::Pass
-                    } else {
-                        TestOutcome::Fail(format!("Expected '{}', got '{}'", expected, actual))
+                    match engine.xpath(ctx.doc, expected) {
+                        // The expected value is an XPath expression; compare typed items
+                        Ok(value) => {
+                            let (expected_items, actual_items) = (value.items(), r.items());
+                            if typed_equal(&expected_items, &actual_items, ctx.collator) {
+                                TestOutcome::Pass
+                            } else if ctx.collator.equals(&actual, value.to_string().trim()) {
+                                TestOutcome::Fail(format!(
+                                    "Type mismatch: expected {} '{}', got {} '{}'",
+                                    sequence_type_name(&expected_items),
+                                    expected,
+                                    sequence_type_name(&actual_items),
+                                    actual
+                                ))
+                            } else {
+                                TestOutcome::Fail(mismatch_message("Value mismatch", expected, &actual))
+                            }
+                        }
+                        // Loose fallback: compare string forms
+                        Err(_) if ctx.collator.equals(&actual, expected) => TestOutcome::Pass,
+                        Err(_) => {
+                            TestOutcome::Fail(mismatch_message("Value mismatch", expected, &actual))
+                        }
                     }
                 }
                 Err(e) => TestOutcome::Fail(format!("Expected value, got error: {}", e)),
@@ -907,49 +1375,62 @@ fn check_assertion(
 
         Assertion::AssertEmpty => {
             match result {
-                Ok(r) => {
-                    if r.is_empty() {
-                        TestOutcome::Pass
-                    } else {
-                        TestOutcome::Fail(format!("Expected empty, got {} items", r.count()))
-                    }
-                }
-                Err(e) => TestOutcome::Fail(format!("Expected empty, got error: {}", e)),
+                // Only the empty sequence passes, never a lone empty string
+                Ok(r) if r.is_empty_sequence() => TestOutcome::Pass,
+                Ok(r) => TestOutcome::Fail(format!(
+                    "Expected empty sequence, got {} item(s): '{}'",
+                    r.count(),
+                    r.to_string()
+                )),
+                Err(_) if ctx.allow_error_as_empty => TestOutcome::Pass,
+                Err(e) => TestOutcome::Fail(format!("Expected empty sequence, got error: {}", e)),
             }
         }
 
         Assertion::AssertTrue => {
             match result {
-                Ok(r) => {
-                    let s = r.to_string().trim().to_lowercase();
-                    if s == "true" {
-                        TestOutcome::Pass
-                    } else {
-                        TestOutcome::Fail(format!("Expected true, got '{}'", r.to_string()))
-                    }
-                }
+                Ok(r) => match r.effective_boolean() {
+                    Ok(true) => TestOutcome::Pass,
+                    Ok(_) => TestOutcome::Fail(format!("Expected true, got '{}'", r.to_string())),
+                    Err(e) => TestOutcome::Fail(format!("Expected true, got '{}': {}", r.to_string(), e)),
+                },
                 Err(e) => TestOutcome::Fail(format!("Expected true, got error: {}", e)),
             }
         }
 
         Assertion::AssertFalse => {
             match result {
-                Ok(r) => {
-                    let s = r.to_string().trim().to_lowercase();
-                    if s == "false" {
-                        TestOutcome::Pass
-                    } else {
-                        TestOutcome::Fail(format!("Expected false, got '{}'", r.to_string()))
-                    }
-                }
+                Ok(r) => match r.effective_boolean() {
+                    Ok(false) => TestOutcome::Pass,
+                    Ok(_) => TestOutcome::Fail(format!("Expected false, got '{}'", r.to_string())),
+                    Err(e) => TestOutcome::Fail(format!("Expected false, got '{}': {}", r.to_string(), e)),
+                },
                 Err(e) => TestOutcome::Fail(format!("Expected false, got error: {}", e)),
             }
         }
 
-        Assertion::AssertType(_type_name) => {
-            // Type checking is complex - for now just check we got a result
+        Assertion::AssertType(type_name) => {
+            // Decide simple types from the result items; leave the rest to
+            // the engine's `instance of`
             match result {
-                Ok(_) => TestOutcome::Pass, // Simplified: assume type matches if we got a result
+                Ok(r) => {
+                    let matched = match SequenceType::parse(type_name).and_then(|t| t.try_match(&r.items())) {
+                        Some(matched) => Ok(matched),
+                        None => engine
+                            .xpath_with_result(
+                                ctx.doc,
+                                ctx.expression,
+                                r,
+                                &format!("$result instance of {}", type_name),
+                            )
+                            .map(|r| r.to_string().trim() == "true"),
+                    };
+                    match matched {
+                        Ok(true) => TestOutcome::Pass,
+                        Ok(false) => TestOutcome::Fail(format!("Result is not an instance of {}", type_name)),
+                        Err(e) => TestOutcome::Fail(format!("Type check for {} failed: {}", type_name, e)),
+                    }
+                }
                 Err(e) => TestOutcome::Fail(format!("Expected typed value, got error: {}", e)),
             }
         }
@@ -967,7 +1448,7 @@ fn check_assertion(
                     } else {
                         value.clone()
                     };
-                    if actual == expected {
+                    if ctx.collator.equals(&actual, &expected) {
                         TestOutcome::Pass
                     } else {
                         TestOutcome::Fail(format!("Expected '{}', got '{}'", expected, actual))
@@ -980,29 +1461,73 @@ fn check_assertion(
         Assertion::Error(expected_code) => {
             match result {
                 Ok(r) => TestOutcome::Fail(format!("Expected error {}, got result: {}", expected_code, r.to_string())),
-                Err(_e) => {
-                    // For now, accept any error as matching
-                    // A proper implementation would check the error code
-                    if expected_code == "*" {
-                        TestOutcome::Pass
-                    } else {
-                        // Simplified: accept any error
-                        TestOutcome::Pass
-                    }
+                // Only xee reports structured error codes; on the other
+                // backends any error is accepted
+                Err(crate::error::Error::XPathError { code: Some(code), .. })
+                    if engine.backend() == Backend::Xee
+                        && expected_code != "*"
+                        && code != expected_code =>
+                {
+                    TestOutcome::Fail(format!("Expected error {}, got error {}", expected_code, code))
                 }
+                Err(_) => TestOutcome::Pass,
+            }
+        }
+
+        Assertion::SerializationError(expected_code) => {
+            match result {
+                Ok(r) => match r.serialize() {
+                    Ok(s) => TestOutcome::Fail(format!(
+                        "Expected serialization error {}, got output: {}",
+                        expected_code, s
+                    )),
+                    Err(e) => {
+                        let message = e.to_string();
+                        if expected_code == "*" || message.contains(expected_code.as_str()) {
+                            TestOutcome::Pass
+                        } else {
+                            TestOutcome::Fail(format!(
+                                "Expected serialization error {}, got: {}",
+                                expected_code, message
+                            ))
+                        }
+                    }
+                },
+                Err(e) => TestOutcome::Fail(format!(
+                    "Expected serialization error {}, got evaluation error: {}",
+                    expected_code, e
+                )),
             }
         }
 
-        Assertion::AssertXml { xml, file: _, ignore_prefixes: _ } => {
+        Assertion::AssertXml { xml, file, ignore_prefixes, ignore_whitespace } => {
+            let expected = match ctx.expected_content(xml, file) {
+                Ok(expected) => expected,
+                Err(e) => return TestOutcome::Error(e.to_string()),
+            };
             match result {
                 Ok(r) => {
-                    if let Some(expected_xml) = xml {
-                        // Simplified XML comparison
-                        let actual = r.to_string();
-                        if actual.contains(expected_xml.trim()) || expected_xml.contains(actual.trim()) {
+                    if let Some(expected_xml) = expected.as_deref() {
+                        let options = CanonicalOptions {
+                            ignore_whitespace: *ignore_whitespace,
+                            ignore_prefixes: *ignore_prefixes,
+                            ..Default::default()
+                        };
+                        // The canonical comparison already ignores prefixes
+                        // when asked to, so only keep them when they matter
+                        let actual = if *ignore_prefixes {
+                            r.to_string()
+                        } else {
+                            r.serialize_with_options(&SerializationOptions::default())
+                                .unwrap_or_else(|_| r.to_string())
+                        };
+                        if xml_equivalent(expected_xml, &actual, &options) {
                             TestOutcome::Pass
                         } else {
-                            TestOutcome::Fail(format!("XML mismatch: expected '{}', got '{}'", expected_xml, actual))
+                            match (canonicalize(expected_xml, &options), canonicalize(&actual, &options)) {
+                                (Ok(e), Ok(a)) => TestOutcome::Fail(mismatch_message("XML mismatch", &e, &a)),
+                                _ => TestOutcome::Fail(mismatch_message("XML mismatch", expected_xml, &actual)),
+                            }
                         }
                     } else {
                         TestOutcome::Pass // No expected XML specified
@@ -1021,20 +1546,19 @@ fn check_assertion(
         }
 
         Assertion::Assert(xpath) => {
-            // Custom XPath assertion
+            // Custom XPath assertion, evaluated with $result bound to the test
+            // result and the test's context document as context item
             match result {
-                Ok(r) => {
-                    // We would need to evaluate the assertion XPath with $result bound
-                    // For now, simplified: if we got a result, try evaluating the assertion
-                    let items = r.items();
-                    if items.is_empty() {
-                        // Empty result - assertion likely fails
-                        TestOutcome::Fail(format!("Custom assertion '{}' with empty result", xpath))
-                    } else {
-                        // Non-empty result - assume pass for now
-                        TestOutcome::Pass
+                Ok(r) => match engine.xpath_with_result(ctx.doc, ctx.expression, r, xpath) {
+                    Ok(r) => {
+                        if r.to_string().trim() == "true" {
+                            TestOutcome::Pass
+                        } else {
+                            TestOutcome::Fail(format!("Assertion '{}' returned '{}'", xpath, r.to_string()))
+                        }
                     }
-                }
+                    Err(e) => TestOutcome::Fail(format!("Assertion '{}' failed to evaluate: {}", xpath, e)),
+                },
                 Err(e) => TestOutcome::Fail(format!("Got error: {}", e)),
             }
         }
@@ -1046,6 +1570,74 @@ fn check_assertion(
     }
 }
 
+/// Append a result's item kinds to the first line of a failure message,
+/// e.g. `[element=2, string=1]`
+fn with_histogram(message: &str, result: &XQueryResult) -> String {
+    let mut kinds: Vec<_> = result.type_histogram().into_iter().collect();
+    kinds.sort();
+    let kinds: Vec<String> = kinds.iter().map(|(kind, n)| format!("{}={}", kind, n)).collect();
+    let (first, rest) = message.split_once('\n').map_or((message, None), |(f, r)| (f, Some(r)));
+    match rest {
+        Some(rest) => format!("{} [{}]\n{}", first, kinds.join(", "), rest),
+        None => format!("{} [{}]", first, kinds.join(", ")),
+    }
+}
+
+/// Build a failure message for an expected/actual mismatch
+///
+/// Single-line values are reported inline; multi-line values get a
+/// line-oriented diff on the lines following the summary.
+fn mismatch_message(summary: &str, expected: &str, actual: &str) -> String {
+    if expected.contains('\n') || actual.contains('\n') {
+        format!("{}:\n{}", summary, render_diff(expected, actual))
+    } else {
+        format!("{}: expected '{}', got '{}'", summary, expected, actual)
+    }
+}
+
+/// Compare two sequences item by item, as `fn:deep-equal` does
+///
+/// Numbers compare by value across integer and double; other items only
+/// equal items of the same type. Strings use the test's collation.
+fn typed_equal(expected: &[ResultItem], actual: &[ResultItem], collator: &dyn Collator) -> bool {
+    expected.len() == actual.len()
+        && expected.iter().zip(actual).all(|(e, a)| match (e, a) {
+            (ResultItem::String(e), ResultItem::String(a)) => collator.equals(a, e),
+            (ResultItem::Integer(e), ResultItem::Integer(a)) => e == a,
+            (
+                ResultItem::Integer(_) | ResultItem::Double(_),
+                ResultItem::Integer(_) | ResultItem::Double(_),
+            ) => {
+                let (e, a) = (e.to_f64().unwrap_or(f64::NAN), a.to_f64().unwrap_or(f64::NAN));
+                e == a || (e.is_nan() && a.is_nan())
+            }
+            (
+                ResultItem::QName { uri: e_uri, local: e_local, .. },
+                ResultItem::QName { uri: a_uri, local: a_local, .. },
+            ) => e_uri == a_uri && e_local == a_local,
+            _ => std::mem::discriminant(e) == std::mem::discriminant(a) && e.as_string() == a.as_string(),
+        })
+}
+
+/// Name the item type of a sequence for mismatch messages
+fn sequence_type_name(items: &[ResultItem]) -> &'static str {
+    let [item] = items else {
+        return if items.is_empty() { "empty-sequence()" } else { "sequence" };
+    };
+    match item {
+        ResultItem::Node(_) => "node()",
+        ResultItem::String(_) => "xs:string",
+        ResultItem::Integer(_) => "xs:integer",
+        ResultItem::Double(_) => "xs:double",
+        ResultItem::Boolean(_) => "xs:boolean",
+        ResultItem::Date(_) => "xs:date",
+        ResultItem::DateTime(_) => "xs:dateTime",
+        ResultItem::Duration(_) => "xs:duration",
+        ResultItem::QName { .. } => "xs:QName",
+        ResultItem::Empty => "empty-sequence()",
+    }
+}
+
 fn normalize_whitespace(s: &str) -> String {
     s.split_whitespace().collect::<Vec<_>>().join(" ")
 }
@@ -1058,13 +1650,62 @@ pub fn run_xpath_tests(
     catalog_path: &Path,
     filter: Option<&str>,
 ) -> Vec<TestResult> {
+    run_xpath_tests_with_options(engine, catalog_path, filter, &RunOptions::default())
+}
+
+/// Run QT3 XPath tests against an engine, honoring the given run options
+pub fn run_xpath_tests_with_options(
+    engine: &mut XEngine,
+    catalog_path: &Path,
+    filter: Option<&str>,
+    options: &RunOptions,
+) -> Vec<TestResult> {
+    run_xpath_tests_with_stats(engine, catalog_path, filter, options).0
+}
+
+/// Run QT3 XPath tests, also reporting how many test sets and cases matched
+pub fn run_xpath_tests_with_stats(
+    engine: &mut XEngine,
+    catalog_path: &Path,
+    filter: Option<&str>,
+    options: &RunOptions,
+) -> (Vec<TestResult>, RunStats) {
     let mut results = Vec::new();
+    let stats = run_xpath_tests_streaming_with_options(engine, catalog_path, filter, options, &mut |r| {
+        results.push(r)
+    });
+    (results, stats)
+}
+
+/// Run QT3 XPath tests, handing each result to `sink` as it is produced
+///
+/// Test sets are parsed and run one at a time and dropped before moving on,
+/// so memory stays bounded regardless of suite size.
+pub fn run_xpath_tests_streaming(
+    engine: &mut XEngine,
+    catalog_path: &Path,
+    filter: Option<&str>,
+    sink: &mut dyn FnMut(TestResult),
+) -> RunStats {
+    run_xpath_tests_streaming_with_options(engine, catalog_path, filter, &RunOptions::default(), sink)
+}
+
+/// Run QT3 XPath tests in streaming mode, honoring the given run options
+pub fn run_xpath_tests_streaming_with_options(
+    engine: &mut XEngine,
+    catalog_path: &Path,
+    filter: Option<&str>,
+    options: &RunOptions,
+    sink: &mut dyn FnMut(TestResult),
+) -> RunStats {
+    let mut stats = RunStats::default();
+    install_env_trace(engine);
 
     // Parse catalog
-    let catalog = match parse_catalog(catalog_path) {
+    let catalog = match parse_catalog_cached(catalog_path) {
         Ok(c) => c,
         Err(e) => {
-            results.push(TestResult {
+            sink(TestResult {
                 test_id: "catalog_parse".to_string(),
                 test_set: "catalog".to_string(),
                 test_suite: "qt3".to_string(),
@@ -1073,11 +1714,18 @@ pub fn run_xpath_tests(
                 expected: None,
                 actual: None,
                 duration: std::time::Duration::ZERO,
+                covers: None,
+                modified: None,
             });
-            return results;
+            return stats;
         }
     };
 
+    // Report skipped entries individually and carry on with the rest
+    for (idx, message) in catalog.errors.iter().enumerate() {
+        sink(catalog_entry_error("qt3", idx, message));
+    }
+
     let base_dir = catalog_path.parent().unwrap_or(Path::new("."));
 
     // Filter test sets to run
@@ -1091,24 +1739,25 @@ pub fn run_xpath_tests(
         })
         .collect();
     let total_test_sets = test_sets_to_run.len();
+    stats.sets_matched = total_test_sets;
 
     // Run each test set
     for (set_idx, test_set_ref) in test_sets_to_run.iter().enumerate() {
         // Progress reporting
         eprintln!("[{}/{}] Processing test set: {}", set_idx + 1, total_test_sets, test_set_ref.name);
 
-        let test_set_path = base_dir.join(&test_set_ref.file);
+        let test_set_path = resolve_source_path(base_dir, &test_set_ref.file);
         let test_set_name = &test_set_ref.name;
 
         // Wrap test set parsing in catch_unwind to handle panics
         let parse_result = panic::catch_unwind(AssertUnwindSafe(|| {
-            parse_test_set(&test_set_path, &catalog.environments)
+            parse_test_set_cached(&test_set_path, &catalog.environments)
         }));
 
         let test_set = match parse_result {
             Ok(Ok(ts)) => ts,
             Ok(Err(e)) => {
-                results.push(TestResult {
+                sink(TestResult {
                     test_id: format!("{}/parse", test_set_name),
                     test_set: test_set_name.to_string(),
                     test_suite: "qt3".to_string(),
@@ -1117,7 +1766,12 @@ pub fn run_xpath_tests(
                     expected: None,
                     actual: None,
                     duration: std::time::Duration::ZERO,
+                    covers: None,
+                    modified: None,
                 });
+                if options.fail_fast {
+                    return stats;
+                }
                 continue;
             }
             Err(panic_info) => {
@@ -1128,7 +1782,7 @@ pub fn run_xpath_tests(
                 } else {
                     "Unknown panic".to_string()
                 };
-                results.push(TestResult {
+                sink(TestResult {
                     test_id: format!("{}/parse", test_set_name),
                     test_set: test_set_name.to_string(),
                     test_suite: "qt3".to_string(),
@@ -1137,51 +1791,134 @@ pub fn run_xpath_tests(
                     expected: None,
                     actual: Some("PANIC".to_string()),
                     duration: std::time::Duration::ZERO,
+                    covers: None,
+                    modified: None,
                 });
+                if options.fail_fast {
+                    return stats;
+                }
                 continue;
             }
         };
 
-        // Run each test case
+        // Run each test case, isolating engine panics so one crashing
+        // expression doesn't abort the whole run
         for test_case in &test_set.test_cases {
-            let start = Instant::now();
-            let test_id = test_case.name.clone();
-            let description = test_case.description.clone();
-
-            // Wrap in catch_unwind to handle engine panics gracefully
-            let test_set_name_clone = test_set_name.clone();
-            let result = panic::catch_unwind(AssertUnwindSafe(|| {
-                run_test_case(engine, test_case, &test_set_name_clone, &test_set.environments, &test_set_path.parent().unwrap_or(Path::new(".")))
-            }));
-
-            let test_result = match result {
-                Ok(r) => r,
-                Err(panic_info) => {
-                    // Extract panic message
-                    let panic_msg = if let Some(s) = panic_info.downcast_ref::<&str>() {
-                        s.to_string()
-                    } else if let Some(s) = panic_info.downcast_ref::<String>() {
-                        s.clone()
-                    } else {
-                        "Unknown panic".to_string()
-                    };
-                    TestResult {
-                        test_id,
-                        test_set: test_set_name.to_string(),
-                        test_suite: "qt3".to_string(),
-                        description: Some(description),
-                        outcome: TestOutcome::Error(format!("Engine panic: {}", panic_msg)),
-                        expected: None,
-                        actual: Some("PANIC".to_string()),
-                        duration: start.elapsed(),
-                    }
-                }
-            };
-            results.push(test_result);
+            if options.is_excluded(test_set_name, &test_case.name) {
+                let mut result = excluded_result(
+                    test_case.name.clone(),
+                    test_set_name.clone(),
+                    "qt3",
+                    Some(test_case.description.clone()),
+                );
+                result.covers = test_case.covers.clone();
+                result.modified = test_case.modified.clone();
+                sink(result);
+                continue;
+            }
+            if options.dry_run {
+                stats.cases_run += 1;
+                sink(dry_run_test_case(engine, test_case, test_set_name, &test_set.environments));
+                continue;
+            }
+
+            let mut test_result = run_isolated(
+                engine,
+                &test_case.name,
+                test_set_name,
+                "qt3",
+                Some(test_case.description.clone()),
+                |engine| {
+                    run_test_case_with_options(
+                        engine,
+                        test_case,
+                        test_set_name,
+                        &test_set.environments,
+                        test_set_path.parent().unwrap_or(Path::new(".")),
+                        options,
+                    )
+                },
+            );
+            // Keep metadata on results produced by the panic handler too
+            test_result.covers = test_case.covers.clone();
+            test_result.modified = test_case.modified.clone();
+            options.apply_xfail(&mut test_result);
+            stats.cases_run += 1;
+            let stop = options.should_stop(&test_result);
+            sink(test_result);
+            if stop {
+                return stats;
+            }
         }
     }
 
-    results
+    stats
+}
+
+/// Parse one test set, located by name
+///
+/// Only the named test set is parsed; the rest of the catalog is skipped.
+/// Returns the test set and the path of its file.
+fn find_test_set(catalog_path: &Path, test_set: &str) -> Result<(Rc<TestSet>, PathBuf)> {
+    let catalog = parse_catalog_cached(catalog_path)?;
+    let test_set_ref = catalog.test_sets.iter().find(|ts| ts.name == test_set).ok_or_else(|| {
+        crate::error::Error::EngineError(format!("Test set '{}' not found in catalog", test_set))
+    })?;
+
+    let base_dir = catalog_path.parent().unwrap_or(Path::new("."));
+    let test_set_path = resolve_source_path(base_dir, &test_set_ref.file);
+    let parsed = parse_test_set_cached(&test_set_path, &catalog.environments)?;
+    Ok((parsed, test_set_path))
+}
+
+/// Dump a parsed test set as pretty-printed JSON
+///
+/// Shows the environments, dependencies and assertions exactly as the
+/// driver sees them.
+pub fn dump_test_set(catalog_path: &Path, test_set: &str) -> Result<String> {
+    let (parsed, _) = find_test_set(catalog_path, test_set)?;
+    serde_json::to_string_pretty(&*parsed)
+        .map_err(|e| crate::error::Error::SerializationError(e.to_string()))
+}
+
+/// Run one test case, located by test set name and test case name
+///
+/// Only the named test set is parsed; the rest of the catalog is skipped.
+pub fn run_single(
+    engine: &mut XEngine,
+    catalog_path: &Path,
+    test_set: &str,
+    test_id: &str,
+) -> Result<TestResult> {
+    install_env_trace(engine);
+
+    let (parsed, test_set_path) = find_test_set(catalog_path, test_set)?;
+    let test_case = parsed.test_cases.iter().find(|tc| tc.name == test_id).ok_or_else(|| {
+        crate::error::Error::EngineError(format!(
+            "Test case '{}' not found in test set '{}'",
+            test_id, test_set
+        ))
+    })?;
+
+    let mut result = run_isolated(
+        engine,
+        &test_case.name,
+        test_set,
+        "qt3",
+        Some(test_case.description.clone()),
+        |engine| {
+            run_test_case(
+                engine,
+                test_case,
+                test_set,
+                &parsed.environments,
+                test_set_path.parent().unwrap_or(Path::new(".")),
+            )
+        },
+    );
+    result.covers = test_case.covers.clone();
+    result.modified = test_case.modified.clone();
+    Ok(result)
 }
 
 /// Run QT3 XQuery tests against an engine
@@ -10,7 +10,10 @@ use std::time::Instant;
 use crate::error::Result;
 use crate::unified::XEngine;
 
-use super::{TestOutcome, TestResult};
+use super::{
+    catalog_entry_error, excluded_result, install_env_trace, resolve_source_path, RunOptions,
+    TestOutcome, TestResult,
+};
 
 // ============== Data Model ==============
 
@@ -20,6 +23,8 @@ pub struct TestSuite {
     pub name: String,
     /// Test set references
     pub test_set_refs: Vec<TestSetRef>,
+    /// Malformed suite entries that were skipped, one message per entry
+    pub errors: Vec<String>,
 }
 
 /// Reference to a test set file
@@ -99,161 +104,209 @@ pub fn parse_suite(suite_path: &Path) -> Result<TestSuite> {
     let mut suite = TestSuite {
         name: name.trim().to_string(),
         test_set_refs: Vec::new(),
+        errors: Vec::new(),
     };
 
-    // Parse testSetRef elements using indexed queries
-    let count_result = engine.xpath(&doc, "count(//*[local-name()='testSetRef'])")?;
-    let count: usize = count_result.to_string().trim().parse().unwrap_or(0);
+    // Parse testSetRef elements, skipping (and recording) malformed entries
+    let count = engine.xpath_as_usize(&doc, "count(//*[local-name()='testSetRef'])")?;
 
     for idx in 1..=count {
         let href_xpath = format!("string(//*[local-name()='testSetRef'][{}]/@*[local-name()='href'])", idx);
-        let href = engine.xpath(&doc, &href_xpath)?.to_string().trim().to_string();
-        if !href.is_empty() {
-            suite.test_set_refs.push(TestSetRef { href });
+        match engine.xpath_as_string(&doc, &href_xpath) {
+            Ok(href) => {
+                if href.is_empty() {
+                    suite.errors.push(format!("testSetRef #{}: missing @xlink:href", idx));
+                } else {
+                    suite.test_set_refs.push(TestSetRef { href });
+                }
+            }
+            Err(e) => suite.errors.push(format!("testSetRef #{}: {}", idx, e)),
         }
     }
 
     Ok(suite)
 }
 
+/// List the names of the test sets referenced by the XSD suite catalog
+///
+/// Names are the file stems of the `testSetRef` hrefs, matching the names
+/// used when running; the test-set files are not read.
+pub fn list_test_sets(suite_path: &Path) -> Result<Vec<String>> {
+    let suite = parse_suite(suite_path)?;
+    Ok(suite
+        .test_set_refs
+        .iter()
+        .filter_map(|ts| Path::new(&ts.href).file_stem().and_then(|s| s.to_str()))
+        .map(|s| s.to_string())
+        .collect())
+}
+
+/// Bookkeeping from a single test set parse
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    /// Tree nodes visited while building the test set
+    pub nodes_visited: usize,
+}
+
 /// Parse a test set file
 pub fn parse_test_set(test_set_path: &Path) -> Result<TestSet> {
+    parse_test_set_with_stats(test_set_path).map(|(test_set, _)| test_set)
+}
+
+/// Parse a test set file, also reporting how much of the tree was visited
+///
+/// The tree is walked once: every node is visited at most a constant number
+/// of times, so parsing stays linear in the size of the test set.
+pub fn parse_test_set_with_stats(test_set_path: &Path) -> Result<(TestSet, ParseStats)> {
     let content = fs::read_to_string(test_set_path)
         .map_err(|e| crate::error::Error::EngineError(format!("Failed to read test set: {}", e)))?;
 
-    let mut engine = XEngine::xee();
-    let doc = engine.parse(&content)?;
+    let mut xot = xot::Xot::new();
+    let root = xot
+        .parse(&content)
+        .map_err(|e| crate::error::Error::ParseError(e.to_string()))?;
+    let document_element = xot
+        .document_element(root)
+        .map_err(|e| crate::error::Error::ParseError(e.to_string()))?;
 
     let base_dir = test_set_path.parent().unwrap_or(Path::new("."));
-
-    // Get test set attributes
-    let name = engine.xpath(&doc, "string(/*/@name)")?.to_string();
-    let contributor = engine.xpath(&doc, "string(/*/@contributor)")?.to_string();
+    let mut stats = ParseStats::default();
 
     let mut test_set = TestSet {
-        name: name.trim().to_string(),
-        contributor: contributor.trim().to_string(),
+        name: attribute(&xot, document_element, "name").unwrap_or_default(),
+        contributor: attribute(&xot, document_element, "contributor").unwrap_or_default(),
         test_groups: Vec::new(),
     };
+    collect_test_groups(&xot, root, base_dir, &mut test_set.test_groups, &mut stats);
 
-    // Parse test groups using indexed queries
-    let group_count_result = engine.xpath(&doc, "count(//*[local-name()='testGroup'])")?;
-    let group_count: usize = group_count_result.to_string().trim().parse().unwrap_or(0);
-
-    for group_idx in 1..=group_count {
-        // Get group name
-        let name_xpath = format!("string(//*[local-name()='testGroup'][{}]/@name)", group_idx);
-        let group_name = engine.xpath(&doc, &name_xpath)?.to_string().trim().to_string();
-        if group_name.is_empty() { continue; }
-
-        // Get group details
-        let title_xpath = format!(
-            "string(//*[local-name()='testGroup'][{}]//*[local-name()='Title'])",
-            group_idx
-        );
-        let title = engine.xpath(&doc, &title_xpath)
-            .map(|r| r.to_string().trim().to_string())
-            .unwrap_or_default();
-
-        let desc_xpath = format!(
-            "string(//*[local-name()='testGroup'][{}]//*[local-name()='Description'])",
-            group_idx
-        );
-        let description = engine.xpath(&doc, &desc_xpath)
-            .map(|r| r.to_string().trim().to_string())
-            .unwrap_or_default();
-
-        let mut test_group = TestGroup {
-            name: group_name.clone(),
-            title: title.trim().to_string(),
-            description: description.trim().to_string(),
-            schema_test: None,
-            instance_tests: Vec::new(),
-        };
+    Ok((test_set, stats))
+}
 
-        // Parse schema test using indexed query
-        let schema_name_xpath = format!(
-            "string(//*[local-name()='testGroup'][{}]//*[local-name()='schemaTest']/@name)",
-            group_idx
-        );
-        let schema_name = engine.xpath(&doc, &schema_name_xpath)
-            .map(|r| r.to_string().trim().to_string())
-            .unwrap_or_default();
-
-        if !schema_name.is_empty() {
-            let schema_doc_xpath = format!(
-                "string(//*[local-name()='testGroup'][{}]//*[local-name()='schemaTest']//*[local-name()='schemaDocument']/@*[local-name()='href'])",
-                group_idx
-            );
-            let schema_doc = engine.xpath(&doc, &schema_doc_xpath)
-                .map(|r| r.to_string().trim().to_string())
-                .unwrap_or_default();
-
-            let validity_xpath = format!(
-                "string(//*[local-name()='testGroup'][{}]//*[local-name()='schemaTest']//*[local-name()='expected']/@validity)",
-                group_idx
-            );
-            let validity_str = engine.xpath(&doc, &validity_xpath)
-                .map(|r| r.to_string().trim().to_string())
-                .unwrap_or_default();
-
-            if !schema_doc.is_empty() {
-                test_group.schema_test = Some(SchemaTest {
-                    name: schema_name,
-                    schema_document: base_dir.join(&schema_doc),
-                    expected_validity: Validity::from_str(&validity_str),
-                });
+/// Collect the test groups below `node`, without descending into them twice
+fn collect_test_groups(
+    xot: &xot::Xot,
+    node: xot::Node,
+    base_dir: &Path,
+    groups: &mut Vec<TestGroup>,
+    stats: &mut ParseStats,
+) {
+    for child in xot.children(node) {
+        stats.nodes_visited += 1;
+        match local_name(xot, child) {
+            Some("testGroup") => {
+                if let Some(group) = parse_test_group(xot, child, base_dir, stats) {
+                    groups.push(group);
+                }
             }
+            Some(_) => collect_test_groups(xot, child, base_dir, groups, stats),
+            None => {}
         }
+    }
+}
 
-        // Parse instance tests using indexed queries
-        let instance_count_xpath = format!(
-            "count(//*[local-name()='testGroup'][{}]//*[local-name()='instanceTest'])",
-            group_idx
-        );
-        let instance_count: usize = engine.xpath(&doc, &instance_count_xpath)
-            .map(|r| r.to_string().trim().parse().unwrap_or(0))
-            .unwrap_or(0);
-
-        for instance_idx in 1..=instance_count {
-            let instance_name_xpath = format!(
-                "string(//*[local-name()='testGroup'][{}]//*[local-name()='instanceTest'][{}]/@name)",
-                group_idx, instance_idx
-            );
-            let instance_name = engine.xpath(&doc, &instance_name_xpath)
-                .map(|r| r.to_string().trim().to_string())
-                .unwrap_or_default();
-            if instance_name.is_empty() { continue; }
-
-            let instance_doc_xpath = format!(
-                "string(//*[local-name()='testGroup'][{}]//*[local-name()='instanceTest'][{}]//*[local-name()='instanceDocument']/@*[local-name()='href'])",
-                group_idx, instance_idx
-            );
-            let instance_doc = engine.xpath(&doc, &instance_doc_xpath)
-                .map(|r| r.to_string().trim().to_string())
-                .unwrap_or_default();
-
-            let validity_xpath = format!(
-                "string(//*[local-name()='testGroup'][{}]//*[local-name()='instanceTest'][{}]//*[local-name()='expected']/@validity)",
-                group_idx, instance_idx
-            );
-            let validity_str = engine.xpath(&doc, &validity_xpath)
-                .map(|r| r.to_string().trim().to_string())
-                .unwrap_or_default();
-
-            if !instance_doc.is_empty() {
-                test_group.instance_tests.push(InstanceTest {
-                    name: instance_name,
-                    instance_document: base_dir.join(&instance_doc),
-                    expected_validity: Validity::from_str(&validity_str),
-                });
+/// The test within a group whose descendants are currently being read
+enum CurrentTest {
+    None,
+    Schema,
+    Instance,
+}
+
+/// Build a test group from one walk over its descendants
+fn parse_test_group(
+    xot: &xot::Xot,
+    group: xot::Node,
+    base_dir: &Path,
+    stats: &mut ParseStats,
+) -> Option<TestGroup> {
+    let name = attribute(xot, group, "name").filter(|name| !name.is_empty())?;
+
+    let mut title = None;
+    let mut description = None;
+    // (name, document href, validity) of the schema test and instance tests
+    let mut schema: Option<(String, Option<String>, Option<String>)> = None;
+    let mut instances: Vec<(String, Option<String>, Option<String>)> = Vec::new();
+    let mut current = CurrentTest::None;
+
+    for node in xot.descendants(group).skip(1) {
+        stats.nodes_visited += 1;
+        let Some(element) = local_name(xot, node) else {
+            continue;
+        };
+        let test = match current {
+            CurrentTest::None => None,
+            CurrentTest::Schema => schema.as_mut(),
+            CurrentTest::Instance => instances.last_mut(),
+        };
+        match element {
+            "Title" if title.is_none() => title = Some(xot.string_value(node).trim().to_string()),
+            "Description" if description.is_none() => {
+                description = Some(xot.string_value(node).trim().to_string())
+            }
+            "schemaTest" if schema.is_none() => {
+                schema = Some((attribute(xot, node, "name").unwrap_or_default(), None, None));
+                current = CurrentTest::Schema;
+            }
+            "instanceTest" => {
+                instances.push((attribute(xot, node, "name").unwrap_or_default(), None, None));
+                current = CurrentTest::Instance;
+            }
+            "schemaDocument" | "instanceDocument" => {
+                if let Some((_, href @ None, _)) = test {
+                    *href = attribute(xot, node, "href");
+                }
+            }
+            "expected" => {
+                if let Some((_, _, validity @ None)) = test {
+                    *validity = attribute(xot, node, "validity");
+                }
             }
+            _ => {}
         }
-
-        test_set.test_groups.push(test_group);
     }
 
-    Ok(test_set)
+    let schema_test = schema
+        .filter(|(name, _, _)| !name.is_empty())
+        .and_then(|(name, href, validity)| {
+            let href = href.filter(|href| !href.is_empty())?;
+            Some(SchemaTest {
+                name,
+                schema_document: resolve_source_path(base_dir, &href),
+                expected_validity: Validity::from_str(&validity.unwrap_or_default()),
+            })
+        });
+    let instance_tests = instances
+        .into_iter()
+        .filter(|(name, _, _)| !name.is_empty())
+        .filter_map(|(name, href, validity)| {
+            let href = href.filter(|href| !href.is_empty())?;
+            Some(InstanceTest {
+                name,
+                instance_document: resolve_source_path(base_dir, &href),
+                expected_validity: Validity::from_str(&validity.unwrap_or_default()),
+            })
+        })
+        .collect();
+
+    Some(TestGroup {
+        name,
+        title: title.unwrap_or_default(),
+        description: description.unwrap_or_default(),
+        schema_test,
+        instance_tests,
+    })
+}
+
+/// Local name of an element node; `None` for any other node
+fn local_name(xot: &xot::Xot, node: xot::Node) -> Option<&str> {
+    xot.element(node).map(|element| xot.local_name_str(element.name()))
+}
+
+/// Trimmed value of the attribute with the given local name, in any namespace
+fn attribute(xot: &xot::Xot, node: xot::Node, name: &str) -> Option<String> {
+    xot.attributes(node)
+        .iter()
+        .find(|(attr, _)| xot.local_name_str(*attr) == name)
+        .map(|(_, value)| value.trim().to_string())
 }
 
 // ============== Test Execution ==============
@@ -290,6 +343,8 @@ fn run_schema_test(
                 expected: Some(format!("{:?}", test.expected_validity)),
                 actual: Some(format!("{:?}", actual_validity)),
                 duration: start.elapsed(),
+                covers: None,
+                modified: None,
             }
         }
         Err(e) => {
@@ -309,6 +364,8 @@ fn run_schema_test(
                 expected: Some(format!("{:?}", test.expected_validity)),
                 actual: Some(format!("Error: {}", e)),
                 duration: start.elapsed(),
+                covers: None,
+                modified: None,
             }
         }
     }
@@ -338,14 +395,16 @@ fn run_instance_test(
                 expected: None,
                 actual: Some("No schema for validation".to_string()),
                 duration: start.elapsed(),
+                covers: None,
+                modified: None,
             };
         }
     };
 
     // Validate instance against schema
-    match engine.validate_instance(&test.instance_document, schema_path) {
-        Ok(valid) => {
-            let actual_validity = if valid { Validity::Valid } else { Validity::Invalid };
+    match engine.validate_instance_detailed(&test.instance_document, schema_path) {
+        Ok(validation) => {
+            let actual_validity = if validation.valid { Validity::Valid } else { Validity::Invalid };
             let outcome = if actual_validity == test.expected_validity {
                 TestOutcome::Pass
             } else {
@@ -355,6 +414,14 @@ fn run_instance_test(
                 ))
             };
 
+            // Show why an instance expected to be valid was rejected
+            let actual = match validation.errors.first() {
+                Some(error) if test.expected_validity == Validity::Valid => {
+                    format!("{:?}: {}", actual_validity, error.message)
+                }
+                _ => format!("{:?}", actual_validity),
+            };
+
             TestResult {
                 test_id,
                 test_set: test_set_name.to_string(),
@@ -362,8 +429,10 @@ fn run_instance_test(
                 description: Some(format!("Instance validation: {}", test.name)),
                 outcome,
                 expected: Some(format!("{:?}", test.expected_validity)),
-                actual: Some(format!("{:?}", actual_validity)),
+                actual: Some(actual),
                 duration: start.elapsed(),
+                covers: None,
+                modified: None,
             }
         }
         Err(e) => {
@@ -382,6 +451,8 @@ fn run_instance_test(
                 expected: Some(format!("{:?}", test.expected_validity)),
                 actual: Some(format!("Error: {}", e)),
                 duration: start.elapsed(),
+                covers: None,
+                modified: None,
             }
         }
     }
@@ -394,8 +465,19 @@ pub fn run_xsd_tests(
     engine: &mut XEngine,
     suite_path: &Path,
     filter: Option<&str>,
+) -> Vec<TestResult> {
+    run_xsd_tests_with_options(engine, suite_path, filter, &RunOptions::default())
+}
+
+/// Run XSD tests against an engine, honoring the given run options
+pub fn run_xsd_tests_with_options(
+    engine: &mut XEngine,
+    suite_path: &Path,
+    filter: Option<&str>,
+    options: &RunOptions,
 ) -> Vec<TestResult> {
     let mut results = Vec::new();
+    install_env_trace(engine);
 
     // Parse suite
     let suite = match parse_suite(suite_path) {
@@ -410,11 +492,18 @@ pub fn run_xsd_tests(
                 expected: None,
                 actual: None,
                 duration: std::time::Duration::ZERO,
+                covers: None,
+                modified: None,
             });
             return results;
         }
     };
 
+    // Report skipped entries individually and carry on with the rest
+    for (idx, message) in suite.errors.iter().enumerate() {
+        results.push(catalog_entry_error("xsd", idx, message));
+    }
+
     let base_dir = suite_path.parent().unwrap_or(Path::new("."));
 
     // Filter test sets
@@ -431,7 +520,7 @@ pub fn run_xsd_tests(
 
     // Run each test set
     for (set_idx, test_set_ref) in test_sets_to_run.iter().enumerate() {
-        let test_set_path = base_dir.join(&test_set_ref.href);
+        let test_set_path = resolve_source_path(base_dir, &test_set_ref.href);
         let test_set_name = test_set_path.file_stem()
             .and_then(|s| s.to_str())
             .unwrap_or("unknown")
@@ -456,7 +545,12 @@ pub fn run_xsd_tests(
                     expected: None,
                     actual: None,
                     duration: std::time::Duration::ZERO,
+                    covers: None,
+                    modified: None,
                 });
+                if options.fail_fast {
+                    return results;
+                }
                 continue;
             }
             Err(panic_info) => {
@@ -476,21 +570,58 @@ pub fn run_xsd_tests(
                     expected: None,
                     actual: Some("PANIC".to_string()),
                     duration: std::time::Duration::ZERO,
+                    covers: None,
+                    modified: None,
                 });
+                if options.fail_fast {
+                    return results;
+                }
                 continue;
             }
         };
 
         // Run each test group
         for group in &test_set.test_groups {
+            if options.dry_run {
+                let schema_names = group.schema_test.iter().map(|t| (&t.name, "Schema validation"));
+                let instance_names = group.instance_tests.iter().map(|t| (&t.name, "Instance validation"));
+                for (name, kind) in schema_names.chain(instance_names) {
+                    results.push(
+                        TestResult::new(
+                            format!("{}/{}", group.name, name),
+                            test_set_name.clone(),
+                            "xsd",
+                            Some(format!("{}: {}", kind, name)),
+                            TestOutcome::Skipped,
+                            std::time::Duration::ZERO,
+                        )
+                        .with_values(None, Some("Dry run".to_string())),
+                    );
+                }
+                continue;
+            }
+
+            // Don't let the previous group's schema leak into this one
+            engine.clear_schema();
+
             // Run schema test if present
-            if let Some(schema_test) = &group.schema_test {
+            let excluded = |name: &str| {
+                options.is_excluded(&test_set_name, &format!("{}/{}", group.name, name))
+            };
+            if let Some(schema_test) = group.schema_test.as_ref().filter(|t| excluded(&t.name)) {
+                results.push(excluded_result(
+                    format!("{}/{}", group.name, schema_test.name),
+                    test_set_name.clone(),
+                    "xsd",
+                    Some(format!("Schema test: {}", schema_test.name)),
+                ));
+            } else if let Some(schema_test) = &group.schema_test {
                 let start = Instant::now();
                 let result = panic::catch_unwind(AssertUnwindSafe(|| {
                     run_schema_test(engine, schema_test, &test_set_name, &group.name)
                 }));
 
-                let test_result = match result {
+                let mut test_result = match result {
                     Ok(r) => r,
                     Err(panic_info) => {
                         let panic_msg = if let Some(s) = panic_info.downcast_ref::<&str>() {
@@ -509,22 +640,38 @@ pub fn run_xsd_tests(
                             expected: None,
                             actual: Some("PANIC".to_string()),
                             duration: start.elapsed(),
+                            covers: None,
+                            modified: None,
                         }
                     }
                 };
+                options.apply_xfail(&mut test_result);
+                let stop = options.should_stop(&test_result);
                 results.push(test_result);
+                if stop {
+                    return results;
+                }
             }
 
             // Run instance tests
             let schema_path = group.schema_test.as_ref().map(|st| st.schema_document.as_path());
 
             for instance_test in &group.instance_tests {
+                if excluded(&instance_test.name) {
+                    results.push(excluded_result(
+                        format!("{}/{}", group.name, instance_test.name),
+                        test_set_name.clone(),
+                        "xsd",
+                        Some(format!("Instance test: {}", instance_test.name)),
+                    ));
+                    continue;
+                }
                 let start = Instant::now();
                 let result = panic::catch_unwind(AssertUnwindSafe(|| {
                     run_instance_test(engine, instance_test, schema_path, &test_set_name, &group.name)
                 }));
 
-                let test_result = match result {
+                let mut test_result = match result {
                     Ok(r) => r,
                     Err(panic_info) => {
                         let panic_msg = if let Some(s) = panic_info.downcast_ref::<&str>() {
@@ -543,10 +690,17 @@ pub fn run_xsd_tests(
                             expected: None,
                             actual: Some("PANIC".to_string()),
                             duration: start.elapsed(),
+                            covers: None,
+                            modified: None,
                         }
                     }
                 };
+                options.apply_xfail(&mut test_result);
+                let stop = options.should_stop(&test_result);
                 results.push(test_result);
+                if stop {
+                    return results;
+                }
             }
         }
     }
@@ -8,10 +8,15 @@ use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
+use crate::canonical::{xml_equivalent, CanonicalOptions};
 use crate::error::Result;
-use crate::unified::XEngine;
+use crate::reporter::render_diff;
+use crate::unified::{XDocument, XEngine};
 
-use super::{TestOutcome, TestResult};
+use super::{
+    catalog_entry_error, excluded_result, install_env_trace, resolve_source_path, RunOptions,
+    TestOutcome, TestResult,
+};
 
 // ============== Data Model ==============
 
@@ -20,6 +25,8 @@ use super::{TestOutcome, TestResult};
 pub struct Catalog {
     /// Test set references
     pub test_sets: Vec<TestSetRef>,
+    /// Malformed catalog entries that were skipped, one message per entry
+    pub errors: Vec<String>,
 }
 
 /// Reference to a test set file
@@ -94,12 +101,20 @@ pub enum ExpectedResult {
     AssertResult(String),
     /// Expect specific XML output
     AssertXml { file: Option<PathBuf>, content: Option<String> },
+    /// Expect specific serialized output, inline or from a bundled file
+    ///
+    /// Compared canonically unless `method` is `text`.
+    AssertSerialization { file: Option<PathBuf>, content: Option<String>, method: Option<String> },
     /// Expect an error
     Error(String),
     /// All of these must match
     AllOf(Vec<ExpectedResult>),
     /// Any of these can match
     AnyOf(Vec<ExpectedResult>),
+    /// An assertion the driver can't check yet, by element name
+    ///
+    /// Never passes, so it can't satisfy an `any-of` on its own.
+    Unsupported(String),
 }
 
 // ============== Catalog Parsing ==============
@@ -114,32 +129,45 @@ pub fn parse_catalog(catalog_path: &Path) -> Result<Catalog> {
 
     let mut catalog = Catalog {
         test_sets: Vec::new(),
+        errors: Vec::new(),
     };
 
-    // Parse test-set references using indexed queries with string() function
-    let count_result = engine.xpath(&doc, "count(//*[local-name()='test-set'])")?;
-    let count_str = count_result.to_string();
-    let count: usize = count_str.trim().parse().unwrap_or(0);
+    // Parse test-set references, skipping (and recording) malformed entries
+    let count = engine.xpath_as_usize(&doc, "count(//*[local-name()='test-set'])")?;
 
     for idx in 1..=count {
-        // Use string() function to extract attribute values
         let name_xpath = format!("string(//*[local-name()='test-set'][{}]/@name)", idx);
         let file_xpath = format!("string(//*[local-name()='test-set'][{}]/@file)", idx);
 
-        let name_result = engine.xpath(&doc, &name_xpath)?;
-        let file_result = engine.xpath(&doc, &file_xpath)?;
-
-        let name = name_result.to_string().trim().to_string();
-        let file = file_result.to_string().trim().to_string();
+        let (name, file) = match (
+            engine.xpath_as_string(&doc, &name_xpath),
+            engine.xpath_as_string(&doc, &file_xpath),
+        ) {
+            (Ok(name), Ok(file)) => (name, file),
+            (Err(e), _) | (_, Err(e)) => {
+                catalog.errors.push(format!("test-set #{}: {}", idx, e));
+                continue;
+            }
+        };
 
-        if !name.is_empty() && !file.is_empty() {
-            catalog.test_sets.push(TestSetRef { name, file });
+        match (name.is_empty(), file.is_empty()) {
+            (false, false) => catalog.test_sets.push(TestSetRef { name, file }),
+            (true, _) => catalog.errors.push(format!("test-set #{}: missing @name", idx)),
+            (false, true) => catalog.errors.push(format!("test-set #{} ({}): missing @file", idx, name)),
         }
     }
 
     Ok(catalog)
 }
 
+/// List the names of the test sets referenced by an XSLT 3.0 catalog
+///
+/// Only the catalog itself is parsed; the test-set files are not read.
+pub fn list_test_sets(catalog_path: &Path) -> Result<Vec<String>> {
+    let catalog = parse_catalog(catalog_path)?;
+    Ok(catalog.test_sets.into_iter().map(|ts| ts.name).collect())
+}
+
 /// Parse a test set file
 pub fn parse_test_set(test_set_path: &Path, _global_envs: &HashMap<String, Environment>) -> Result<TestSet> {
     let content = fs::read_to_string(test_set_path)
@@ -162,13 +190,12 @@ pub fn parse_test_set(test_set_path: &Path, _global_envs: &HashMap<String, Envir
     let base_dir = test_set_path.parent().unwrap_or(Path::new("."));
 
     // Parse environments using indexed queries
-    let env_count_result = engine.xpath(&doc, "count(/*[local-name()='test-set']/*[local-name()='environment'])")?;
-    let env_count: usize = env_count_result.to_string().trim().parse().unwrap_or(0);
+    let env_count = engine.xpath_as_usize(&doc, "count(/*[local-name()='test-set']/*[local-name()='environment'])")?;
 
     for env_idx in 1..=env_count {
         // Get environment name
         let env_name_xpath = format!("string(/*[local-name()='test-set']/*[local-name()='environment'][{}]/@name)", env_idx);
-        let env_name = engine.xpath(&doc, &env_name_xpath)?.to_string().trim().to_string();
+        let env_name = engine.xpath_as_string(&doc, &env_name_xpath)?;
         if env_name.is_empty() { continue; }
 
         let mut env = Environment {
@@ -180,22 +207,17 @@ pub fn parse_test_set(test_set_path: &Path, _global_envs: &HashMap<String, Envir
 
         // Parse source elements in this environment
         let source_count_xpath = format!("count(/*[local-name()='test-set']/*[local-name()='environment'][{}]/*[local-name()='source'])", env_idx);
-        let source_count: usize = engine.xpath(&doc, &source_count_xpath)
-            .map(|r| r.to_string().trim().parse().unwrap_or(0))
-            .unwrap_or(0);
+        let source_count = engine.xpath_as_usize(&doc, &source_count_xpath)?;
 
         for src_idx in 1..=source_count {
             let role_xpath = format!("string(/*[local-name()='test-set']/*[local-name()='environment'][{}]/*[local-name()='source'][{}]/@role)", env_idx, src_idx);
-            let role = engine.xpath(&doc, &role_xpath)
-                .map(|r| r.to_string().trim().to_string())
-                .unwrap_or_default();
+            let role = engine.xpath_as_string(&doc, &role_xpath).unwrap_or_default();
 
             let file_xpath = format!("string(/*[local-name()='test-set']/*[local-name()='environment'][{}]/*[local-name()='source'][{}]/@file)", env_idx, src_idx);
-            let file = engine.xpath(&doc, &file_xpath)
+            let file = engine.xpath_as_string(&doc, &file_xpath)
                 .ok()
-                .map(|r| r.to_string().trim().to_string())
                 .filter(|s| !s.is_empty())
-                .map(|s| base_dir.join(s));
+                .map(|s| resolve_source_path(base_dir, &s));
 
             let content_xpath = format!("string(/*[local-name()='test-set']/*[local-name()='environment'][{}]/*[local-name()='source'][{}]/*[local-name()='content'])", env_idx, src_idx);
             let content = engine.xpath(&doc, &content_xpath)
@@ -215,50 +237,47 @@ pub fn parse_test_set(test_set_path: &Path, _global_envs: &HashMap<String, Envir
     }
 
     // Parse test cases using indexed queries
-    let test_case_count_result = engine.xpath(&doc, "count(//*[local-name()='test-case'])")?;
-    let test_case_count: usize = test_case_count_result.to_string().trim().parse().unwrap_or(0);
+    let test_case_count = engine.xpath_as_usize(&doc, "count(//*[local-name()='test-case'])")?;
 
     for idx in 1..=test_case_count {
         // Get test case name
         let name_xpath = format!("string(//*[local-name()='test-case'][{}]/@name)", idx);
-        let name = engine.xpath(&doc, &name_xpath)?.to_string().trim().to_string();
+        let name = engine.xpath_as_string(&doc, &name_xpath)?;
         if name.is_empty() { continue; }
 
         // Get test case details
         let desc_xpath = format!("string(//*[local-name()='test-case'][{}]/*[local-name()='description'])", idx);
-        let desc = engine.xpath(&doc, &desc_xpath)
-            .map(|r| r.to_string().trim().to_string())
-            .unwrap_or_default();
+        let desc = engine.xpath_as_string(&doc, &desc_xpath).unwrap_or_default();
 
         // Get stylesheet from test/stylesheet/@file
         let style_xpath = format!("string(//*[local-name()='test-case'][{}]/*[local-name()='test']/*[local-name()='stylesheet']/@file)", idx);
-        let stylesheet = engine.xpath(&doc, &style_xpath)
+        let stylesheet = engine.xpath_as_string(&doc, &style_xpath)
             .ok()
-            .map(|r| r.to_string().trim().to_string())
             .filter(|s| !s.is_empty())
-            .map(|s| base_dir.join(s));
+            .map(|s| resolve_source_path(base_dir, &s));
 
         // Get environment ref
         let env_xpath = format!("string(//*[local-name()='test-case'][{}]/*[local-name()='environment']/@ref)", idx);
-        let env_ref = engine.xpath(&doc, &env_xpath)
+        let env_ref = engine.xpath_as_string(&doc, &env_xpath)
             .ok()
-            .map(|r| r.to_string().trim().to_string())
             .filter(|s| !s.is_empty());
 
         // Get initial-template
         let init_template_xpath = format!("string(//*[local-name()='test-case'][{}]/*[local-name()='test']/*[local-name()='initial-template']/@name)", idx);
-        let initial_template = engine.xpath(&doc, &init_template_xpath)
+        let initial_template = engine.xpath_as_string(&doc, &init_template_xpath)
             .ok()
-            .map(|r| r.to_string().trim().to_string())
             .filter(|s| !s.is_empty());
 
         // Get initial-mode
         let init_mode_xpath = format!("string(//*[local-name()='test-case'][{}]/*[local-name()='test']/*[local-name()='initial-mode']/@name)", idx);
-        let initial_mode = engine.xpath(&doc, &init_mode_xpath)
+        let initial_mode = engine.xpath_as_string(&doc, &init_mode_xpath)
             .ok()
-            .map(|r| r.to_string().trim().to_string())
             .filter(|s| !s.is_empty());
 
+        // Get expected result
+        let result_prefix = format!("//*[local-name()='test-case'][{}]/*[local-name()='result']", idx);
+        let result = parse_expected_result(&mut engine, &doc, &result_prefix, base_dir)?;
+
         test_set.test_cases.push(TestCase {
             name,
             description: desc,
@@ -267,13 +286,78 @@ pub fn parse_test_set(test_set_path: &Path, _global_envs: &HashMap<String, Envir
             initial_mode,
             initial_template,
             dependencies: Vec::new(),
-            result: ExpectedResult::AssertResult(String::new()),
+            result,
         });
     }
 
     Ok(test_set)
 }
 
+/// Parse the children of a `<result>` (or `all-of`/`any-of`) element
+///
+/// A single child is returned as-is; several children must all hold.
+fn parse_expected_result(
+    engine: &mut XEngine,
+    doc: &XDocument,
+    prefix: &str,
+    base_dir: &Path,
+) -> Result<ExpectedResult> {
+    let mut expected = parse_nested_results(engine, doc, prefix, base_dir)?;
+    if expected.len() == 1 {
+        Ok(expected.remove(0))
+    } else {
+        Ok(ExpectedResult::AllOf(expected))
+    }
+}
+
+fn parse_nested_results(
+    engine: &mut XEngine,
+    doc: &XDocument,
+    prefix: &str,
+    base_dir: &Path,
+) -> Result<Vec<ExpectedResult>> {
+    let mut results = Vec::new();
+
+    let children_count = engine.xpath_as_usize(doc, &format!("count({}/*)", prefix))?;
+
+    for idx in 1..=children_count {
+        let child_prefix = format!("{}/*[{}]", prefix, idx);
+
+        let local_name = engine.xpath_as_string(doc, &format!("local-name({})", child_prefix))?;
+
+        let expected = match local_name.as_str() {
+            "all-of" => ExpectedResult::AllOf(parse_nested_results(engine, doc, &child_prefix, base_dir)?),
+            "any-of" => ExpectedResult::AnyOf(parse_nested_results(engine, doc, &child_prefix, base_dir)?),
+            "error" => {
+                let code = engine.xpath_as_string(doc, &format!("string({}/@code)", child_prefix))?;
+                ExpectedResult::Error(code)
+            }
+            "assert-xml" => {
+                let content = engine.xpath_as_string(doc, &format!("string({})", child_prefix))?;
+                let file = engine.xpath_as_string(doc, &format!("string({}/@file)", child_prefix))?;
+                ExpectedResult::AssertXml {
+                    file: if file.is_empty() { None } else { Some(resolve_source_path(base_dir, &file)) },
+                    content: if content.is_empty() { None } else { Some(content) },
+                }
+            }
+            "assert-serialization" => {
+                let content = engine.xpath_as_string(doc, &format!("string({})", child_prefix))?;
+                let file = engine.xpath_as_string(doc, &format!("string({}/@file)", child_prefix))?;
+                let method = engine.xpath_as_string(doc, &format!("string({}/@method)", child_prefix))?;
+                ExpectedResult::AssertSerialization {
+                    file: if file.is_empty() { None } else { Some(resolve_source_path(base_dir, &file)) },
+                    content: if content.is_empty() { None } else { Some(content) },
+                    method: if method.is_empty() { None } else { Some(method) },
+                }
+            }
+            other => ExpectedResult::Unsupported(other.to_string()),
+        };
+        results.push(expected);
+    }
+
+    Ok(results)
+}
+
 // ============== Test Execution ==============
 
 /// Run a single XSLT test case
@@ -297,6 +381,8 @@ fn run_test_case(
             expected,
             actual,
             duration: start.elapsed(),
+            covers: None,
+            modified: None,
         }
     };
 
@@ -393,24 +479,116 @@ fn run_test_case(
     };
 
     // Run transformation
-    match engine.transform(&source_doc, &stylesheet_content) {
-        Ok(result) => {
-            // For now, just check that transformation succeeded
-            // Full implementation would compare against expected result
-            make_result(TestOutcome::Pass, None, Some(result))
+    let output = engine.transform(&source_doc, &stylesheet_content);
+    let outcome = check_expected(&test_case.result, &output);
+    let actual = match output {
+        Ok(result) => result,
+        Err(e) if outcome.is_pass() => format!("Expected error: {}", e),
+        Err(e) => e.to_string(),
+    };
+    make_result(outcome, None, Some(actual))
+}
+
+/// Check a transform's output against an expected result
+fn check_expected(expected: &ExpectedResult, output: &Result<String>) -> TestOutcome {
+    match expected {
+        ExpectedResult::AssertResult(_) => match output {
+            Ok(_) => TestOutcome::Pass,
+            Err(e) => TestOutcome::Fail(format!("Transform failed: {}", e)),
+        },
+
+        ExpectedResult::Error(code) => match output {
+            Ok(_) => TestOutcome::Fail(format!("Expected error {}, but transform succeeded", code)),
+            Err(_) => TestOutcome::Pass,
+        },
+
+        ExpectedResult::AssertXml { file, content } => {
+            let actual = match output {
+                Ok(actual) => actual,
+                Err(e) => return TestOutcome::Fail(format!("Transform failed: {}", e)),
+            };
+            let expected_xml = match expected_output(content, file) {
+                Ok(Some(xml)) => xml,
+                Ok(None) => return TestOutcome::Pass,
+                Err(outcome) => return outcome,
+            };
+            compare_xml(&expected_xml, actual)
         }
-        Err(e) => {
-            // Check if error was expected
-            if let ExpectedResult::Error(_) = &test_case.result {
-                make_result(TestOutcome::Pass, None, Some(format!("Expected error: {}", e)))
-            } else {
-                make_result(
-                    TestOutcome::Fail(format!("Transform failed: {}", e)),
-                    None,
-                    Some(e.to_string()),
-                )
+
+        ExpectedResult::AssertSerialization { file, content, method } => {
+            let actual = match output {
+                Ok(actual) => actual,
+                Err(e) => return TestOutcome::Fail(format!("Transform failed: {}", e)),
+            };
+            let expected = match expected_output(content, file) {
+                Ok(Some(expected)) => expected,
+                Ok(None) => return TestOutcome::Pass,
+                Err(outcome) => return outcome,
+            };
+            match method.as_deref() {
+                Some("text") if expected.trim() == actual.trim() => TestOutcome::Pass,
+                Some("text") => TestOutcome::Fail(format!(
+                    "Serialization mismatch:\n{}",
+                    render_diff(expected.trim(), actual.trim())
+                )),
+                _ => compare_xml(&expected, actual),
             }
         }
+
+        ExpectedResult::Unsupported(_) => TestOutcome::NotApplicable,
+
+        // A failing part decides the outcome even if another can't be checked
+        ExpectedResult::AllOf(all) => {
+            let outcomes: Vec<_> = all.iter().map(|e| check_expected(e, output)).collect();
+            outcomes
+                .iter()
+                .find(|outcome| !outcome.is_pass() && !matches!(outcome, TestOutcome::NotApplicable))
+                .or_else(|| outcomes.iter().find(|outcome| !outcome.is_pass()))
+                .cloned()
+                .unwrap_or(TestOutcome::Pass)
+        }
+
+        // Without a match, an alternative that can't be checked leaves the
+        // outcome open rather than failed
+        ExpectedResult::AnyOf(any) => {
+            let mut reasons = Vec::new();
+            let mut undecided = false;
+            for e in any {
+                match check_expected(e, output) {
+                    TestOutcome::Pass => return TestOutcome::Pass,
+                    TestOutcome::NotApplicable => undecided = true,
+                    other => reasons.push(other.message().unwrap_or("no match").to_string()),
+                }
+            }
+            if undecided {
+                return TestOutcome::NotApplicable;
+            }
+            TestOutcome::Fail(format!("None of the any-of alternatives matched: {}", reasons.join("; ")))
+        }
+    }
+}
+
+/// Load an expected output given inline or as a file
+///
+/// Returns `None` when neither is present; a file that can't be read is an
+/// error outcome.
+fn expected_output(content: &Option<String>, file: &Option<PathBuf>) -> std::result::Result<Option<String>, TestOutcome> {
+    match (content, file) {
+        (Some(content), _) => Ok(Some(content.clone())),
+        (None, Some(file)) => fs::read_to_string(file)
+            .map(Some)
+            .map_err(|e| TestOutcome::Error(format!("Failed to read expected output {:?}: {}", file, e))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Compare expected and actual XML canonically, ignoring whitespace
+fn compare_xml(expected: &str, actual: &str) -> TestOutcome {
+    let options = CanonicalOptions { ignore_whitespace: true, ..Default::default() };
+    if xml_equivalent(expected, actual, &options) {
+        TestOutcome::Pass
+    } else {
+        TestOutcome::Fail(format!("XML mismatch:\n{}", render_diff(expected.trim(), actual.trim())))
     }
 }
 
@@ -421,8 +599,19 @@ pub fn run_xslt_tests(
     engine: &mut XEngine,
     catalog_path: &Path,
     filter: Option<&str>,
+) -> Vec<TestResult> {
+    run_xslt_tests_with_options(engine, catalog_path, filter, &RunOptions::default())
+}
+
+/// Run XSLT 3.0 tests against an engine, honoring the given run options
+pub fn run_xslt_tests_with_options(
+    engine: &mut XEngine,
+    catalog_path: &Path,
+    filter: Option<&str>,
+    options: &RunOptions,
 ) -> Vec<TestResult> {
     let mut results = Vec::new();
+    install_env_trace(engine);
 
     // Parse catalog
     let catalog = match parse_catalog(catalog_path) {
@@ -437,11 +626,18 @@ pub fn run_xslt_tests(
                 expected: None,
                 actual: None,
                 duration: std::time::Duration::ZERO,
+                covers: None,
+                modified: None,
             });
             return results;
         }
     };
 
+    // Report skipped entries individually and carry on with the rest
+    for (idx, message) in catalog.errors.iter().enumerate() {
+        results.push(catalog_entry_error("xslt30", idx, message));
+    }
+
     let base_dir = catalog_path.parent().unwrap_or(Path::new("."));
 
     // Filter test sets
@@ -460,7 +656,7 @@ pub fn run_xslt_tests(
     for (set_idx, test_set_ref) in test_sets_to_run.iter().enumerate() {
         eprintln!("[{}/{}] Processing test set: {}", set_idx + 1, total_test_sets, test_set_ref.name);
 
-        let test_set_path = base_dir.join(&test_set_ref.file);
+        let test_set_path = resolve_source_path(base_dir, &test_set_ref.file);
         let test_set_name = &test_set_ref.name;
 
         // Parse test set with panic handling
@@ -480,7 +676,12 @@ pub fn run_xslt_tests(
                     expected: None,
                     actual: None,
                     duration: std::time::Duration::ZERO,
+                    covers: None,
+                    modified: None,
                 });
+                if options.fail_fast {
+                    return results;
+                }
                 continue;
             }
             Err(panic_info) => {
@@ -500,13 +701,42 @@ pub fn run_xslt_tests(
                     expected: None,
                     actual: Some("PANIC".to_string()),
                     duration: std::time::Duration::ZERO,
+                    covers: None,
+                    modified: None,
                 });
+                if options.fail_fast {
+                    return results;
+                }
                 continue;
             }
         };
 
         // Run each test case
         for test_case in &test_set.test_cases {
+            if options.is_excluded(test_set_name, &test_case.name) {
+                results.push(excluded_result(
+                    test_case.name.clone(),
+                    test_set_name.clone(),
+                    "xslt30",
+                    Some(test_case.description.clone()),
+                ));
+                continue;
+            }
+            if options.dry_run {
+                results.push(
+                    TestResult::new(
+                        test_case.name.clone(),
+                        test_set_name.clone(),
+                        "xslt30",
+                        Some(test_case.description.clone()),
+                        TestOutcome::Skipped,
+                        std::time::Duration::ZERO,
+                    )
+                    .with_values(None, Some("Dry run".to_string())),
+                );
+                continue;
+            }
+
             let start = Instant::now();
             let test_id = test_case.name.clone();
             let description = test_case.description.clone();
@@ -522,7 +752,7 @@ pub fn run_xslt_tests(
                 )
             }));
 
-            let test_result = match result {
+            let mut test_result = match result {
                 Ok(r) => r,
                 Err(panic_info) => {
                     let panic_msg = if let Some(s) = panic_info.downcast_ref::<&str>() {
@@ -541,10 +771,17 @@ pub fn run_xslt_tests(
                         expected: None,
                         actual: Some("PANIC".to_string()),
                         duration: start.elapsed(),
+                        covers: None,
+                        modified: None,
                     }
                 }
             };
+            options.apply_xfail(&mut test_result);
+            let stop = options.should_stop(&test_result);
             results.push(test_result);
+            if stop {
+                return results;
+            }
         }
     }
 
@@ -2,6 +2,8 @@
 
 use std::path::Path;
 
+use serde::Serialize;
+
 use crate::error::Result;
 use crate::result::{ResultItem, ValidationResult};
 
@@ -37,6 +39,97 @@ pub enum XsdVersion {
     V1_1,
 }
 
+/// Optional features an engine may provide
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    XPath,
+    XQuery,
+    Xslt,
+    XsdValidation,
+    /// XQuery Update Facility (`insert node`, `delete node`, ...)
+    XQueryUpdate,
+    /// Setting the implicit timezone of the dynamic context
+    ImplicitTimezone,
+}
+
+/// Callback receiving one trace line per query evaluation
+pub type TraceHook = Box<dyn FnMut(&str)>;
+
+/// Loader for `fn:unparsed-text` resources, given the `href` as written
+pub type TextResolver = Box<dyn Fn(&str) -> Result<String>>;
+
+/// A decimal format used by `fn:format-number`
+///
+/// Unset properties keep the default decimal format's value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DecimalFormat {
+    /// Format name; `None` for the default decimal format
+    pub name: Option<String>,
+    pub decimal_separator: Option<char>,
+    pub grouping_separator: Option<char>,
+    pub exponent_separator: Option<char>,
+    pub zero_digit: Option<char>,
+    pub digit: Option<char>,
+    pub minus_sign: Option<char>,
+    pub percent: Option<char>,
+    pub per_mille: Option<char>,
+    pub pattern_separator: Option<char>,
+    pub infinity: Option<String>,
+    pub nan: Option<String>,
+}
+
+impl DecimalFormat {
+    /// All property names, as written on `decimal-format` declarations
+    pub const PROPERTIES: &'static [&'static str] = &[
+        "decimal-separator",
+        "grouping-separator",
+        "exponent-separator",
+        "zero-digit",
+        "digit",
+        "minus-sign",
+        "percent",
+        "per-mille",
+        "pattern-separator",
+        "infinity",
+        "NaN",
+    ];
+
+    /// Set a property from its declaration attribute
+    ///
+    /// Returns `false` for unknown properties and for single-character
+    /// properties given anything but one character.
+    pub fn set_property(&mut self, property: &str, value: &str) -> bool {
+        let slot = match property {
+            "infinity" => {
+                self.infinity = Some(value.to_string());
+                return true;
+            }
+            "NaN" => {
+                self.nan = Some(value.to_string());
+                return true;
+            }
+            "decimal-separator" => &mut self.decimal_separator,
+            "grouping-separator" => &mut self.grouping_separator,
+            "exponent-separator" => &mut self.exponent_separator,
+            "zero-digit" => &mut self.zero_digit,
+            "digit" => &mut self.digit,
+            "minus-sign" => &mut self.minus_sign,
+            "percent" => &mut self.percent,
+            "per-mille" => &mut self.per_mille,
+            "pattern-separator" => &mut self.pattern_separator,
+            _ => return false,
+        };
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => {
+                *slot = Some(c);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
 /// XML document handle - opaque reference to parsed XML
 pub trait XmlDocument {
     /// Serialize the document to a string
@@ -74,6 +167,11 @@ pub trait QueryResult {
 
     /// Get all items in the result
     fn items(&self) -> Vec<ResultItem>;
+
+    /// Get the first item in the result without materializing the rest
+    fn first(&self) -> Option<ResultItem> {
+        self.items().into_iter().next()
+    }
 }
 
 /// XPath evaluation capability
@@ -142,6 +240,9 @@ pub trait XsdValidator: XmlParser {
         self.load_schema(&content)
     }
 
+    /// Discard the loaded schema, if any
+    fn clear_schema(&mut self) {}
+
     /// Validate a document against the loaded schema
     fn validate(&self, doc: &Self::Document) -> Result<ValidationResult>;
 
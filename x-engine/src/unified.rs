@@ -6,12 +6,27 @@
 use crate::engine_xee::{XeeDocument, XeeEngine, XeeQueryResult};
 use crate::engine_xrust::{XrustDocument, XrustEngine, XrustQueryResult};
 use crate::engine_xust::{XustDocument, XustEngine, XustQueryResult};
+use crate::collation;
+use crate::current_time::{reads_clock, timezone_from_minutes};
+use crate::diagnostics;
+use crate::query_cache::QueryCache;
+use crate::unparsed_text::{filesystem_text_resolver, resolve_unparsed_text};
+use crate::xinclude;
 use crate::error::{Error, Result};
-use crate::result::{ResultItem, ValidationResult};
+use crate::serialization::{
+    apply_output_method, apply_serialization_options, reindent, OutputMethod, OutputParams,
+    SerializationOptions,
+};
+use crate::parse_limits::ParseLimits;
+use crate::result::{NodeId, NodeInfo, NodeType, ResultItem, ValidationResult};
 use crate::traits::{
-    QueryResult, XPathEngine, XPathVersion, XQueryEngine, XQueryVersion, XmlParser, XsdValidator,
-    XsdVersion, XsltEngine, XsltVersion,
+    Capability, DecimalFormat, QueryResult, TextResolver, TraceHook, XPathEngine, XPathVersion,
+    XQueryEngine, XQueryVersion, XmlParser, XsdValidator, XsdVersion, XsltEngine, XsltVersion,
 };
+use chrono::{DateTime, FixedOffset};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::path::Path;
 
 /// Backend engine selection
@@ -25,6 +40,20 @@ pub enum Backend {
     Xust,
 }
 
+impl Backend {
+    /// Every backend, in the order reports list them
+    pub const ALL: [Backend; 3] = [Backend::Xee, Backend::Xrust, Backend::Xust];
+
+    /// Backend name as used in reports and on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Xee => "xee",
+            Backend::Xrust => "xrust",
+            Backend::Xust => "xust",
+        }
+    }
+}
+
 /// Unified XML engine with runtime backend selection
 pub enum XEngine {
     Xee(XeeEngine),
@@ -40,12 +69,26 @@ pub enum XDocument {
 }
 
 /// Unified query result
+#[derive(Clone)]
 pub enum XQueryResult {
     Xee(XeeQueryResult),
     Xrust(XrustQueryResult),
     Xust(XustQueryResult),
 }
 
+/// An XSLT stylesheet compiled by [`XEngine::compile_stylesheet`]
+///
+/// Only valid with the engine instance that compiled it, until
+/// [`XEngine::drop_stylesheet`] frees it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StylesheetHandle {
+    backend: Backend,
+    /// Id of the engine instance that compiled the stylesheet
+    engine: u64,
+    index: usize,
+    method: OutputMethod,
+}
+
 impl XEngine {
     /// Create a new engine with the xee backend (XPath 3.1, XSLT 3.0)
     pub fn xee() -> Self {
@@ -71,6 +114,19 @@ impl XEngine {
         }
     }
 
+    /// Start over with fresh backend state, keeping the configuration
+    ///
+    /// Static context, resolvers, limits, the pinned time, the trace hook,
+    /// the query cache (emptied) and any loaded schema survive; documents
+    /// parsed before may no longer be used with this engine.
+    pub fn reset(&mut self) {
+        match self {
+            Self::Xee(e) => e.reset(),
+            Self::Xrust(e) => e.reset(),
+            Self::Xust(e) => e.reset(),
+        }
+    }
+
     /// Get the current backend
     pub fn backend(&self) -> Backend {
         match self {
@@ -80,8 +136,363 @@ impl XEngine {
         }
     }
 
+    /// Check whether this engine's backend provides a capability
+    pub fn supports(&self, capability: Capability) -> bool {
+        match (self, capability) {
+            (_, Capability::XPath) => true,
+            (Self::Xee(_) | Self::Xrust(_), Capability::Xslt) => true,
+            (Self::Xust(_), Capability::XQuery | Capability::XsdValidation) => true,
+            (Self::Xee(_) | Self::Xust(_), Capability::ImplicitTimezone) => true,
+            // No backend implements the XQuery Update Facility
+            _ => false,
+        }
+    }
+
+    /// List the features this engine's backend provides
+    ///
+    /// Names are versioned languages (`xpath-3.1`, `xquery-3.1`, `xslt-3.0`,
+    /// `xsd-1.1`) plus `maps-arrays` and `higher-order-functions`, available
+    /// from XPath 3.1 and 3.0 respectively.
+    pub fn supported_features(&self) -> Vec<String> {
+        let xpath = self.xpath_version();
+        let mut features = vec![format!(
+            "xpath-{}",
+            match xpath {
+                XPathVersion::V1_0 => "1.0",
+                XPathVersion::V2_0 => "2.0",
+                XPathVersion::V3_0 => "3.0",
+                XPathVersion::V3_1 => "3.1",
+            }
+        )];
+        if self.supports(Capability::XQuery) {
+            let version = match self.xquery_version() {
+                XQueryVersion::V1_0 => "1.0",
+                XQueryVersion::V3_0 => "3.0",
+                XQueryVersion::V3_1 => "3.1",
+            };
+            features.push(format!("xquery-{}", version));
+        }
+        if self.supports(Capability::Xslt) {
+            let version = match self.xslt_version() {
+                XsltVersion::V1_0 => "1.0",
+                XsltVersion::V2_0 => "2.0",
+                XsltVersion::V3_0 => "3.0",
+            };
+            features.push(format!("xslt-{}", version));
+        }
+        if self.supports(Capability::XsdValidation) {
+            let version = match self.xsd_version() {
+                XsdVersion::V1_0 => "1.0",
+                XsdVersion::V1_1 => "1.1",
+            };
+            features.push(format!("xsd-{}", version));
+        }
+        if xpath == XPathVersion::V3_1 {
+            features.push("maps-arrays".to_string());
+        }
+        if matches!(xpath, XPathVersion::V3_0 | XPathVersion::V3_1) {
+            features.push("higher-order-functions".to_string());
+        }
+        features
+    }
+
+    // ==================== Static Context ====================
+
+    /// Set the static base URI used by `fn:static-base-uri()` and relative
+    /// URI resolution in subsequent evaluations
+    pub fn set_static_base_uri(&mut self, uri: Option<&str>) {
+        self.clear_query_cache();
+        match self {
+            Self::Xee(e) => e.set_static_base_uri(uri),
+            // xrust has no static base URI in its static context
+            Self::Xrust(_) => {}
+            Self::Xust(e) => e.set_static_base_uri(uri),
+        }
+    }
+
+    /// Set the namespace that unprefixed element names in subsequent XPath
+    /// and XQuery expressions resolve against
+    ///
+    /// `None` restores the default (no namespace).
+    pub fn set_default_element_namespace(&mut self, uri: Option<&str>) {
+        self.clear_query_cache();
+        match self {
+            Self::Xee(e) => e.set_default_element_namespace(uri),
+            // xrust has no default element namespace in its static context
+            Self::Xrust(_) => {}
+            Self::Xust(e) => e.set_default_element_namespace(uri),
+        }
+    }
+
+    /// Set the decimal formats used by `fn:format-number` in subsequent
+    /// evaluations, replacing any set before
+    pub fn set_decimal_formats(&mut self, formats: &[DecimalFormat]) {
+        self.clear_query_cache();
+        match self {
+            Self::Xee(e) => e.set_decimal_formats(formats.to_vec()),
+            // Neither xrust nor xust accept decimal formats in their static context
+            Self::Xrust(_) | Self::Xust(_) => {}
+        }
+    }
+
+    /// Load `fn:unparsed-text` resources with `resolver` instead of the
+    /// filesystem
+    ///
+    /// The resolver receives the `href` exactly as written in the expression.
+    /// No backend takes a resolver, so only calls whose `href` is a string
+    /// literal are resolved, before evaluation; calls with a computed `href`
+    /// are left to the backend.
+    pub fn set_text_resolver(&mut self, resolver: TextResolver) {
+        self.clear_query_cache();
+        match self {
+            Self::Xee(e) => e.set_text_resolver(Some(resolver)),
+            Self::Xrust(e) => e.set_text_resolver(Some(resolver)),
+            Self::Xust(e) => e.set_text_resolver(Some(resolver)),
+        }
+    }
+
+    /// Go back to loading `fn:unparsed-text` resources from the filesystem,
+    /// relative to the static base URI
+    pub fn clear_text_resolver(&mut self) {
+        self.clear_query_cache();
+        match self {
+            Self::Xee(e) => e.set_text_resolver(None),
+            Self::Xrust(e) => e.set_text_resolver(None),
+            Self::Xust(e) => e.set_text_resolver(None),
+        }
+    }
+
+    /// Pin `fn:current-dateTime` (and `current-date`/`current-time`) to
+    /// `datetime` in subsequent evaluations
+    ///
+    /// With `None`, each evaluation reads the clock once when it starts.
+    /// Either way the value is stable within one evaluation. Only xee
+    /// honours the setting; xrust and xust read the clock themselves.
+    pub fn set_current_datetime(&mut self, datetime: Option<DateTime<FixedOffset>>) {
+        self.clear_query_cache();
+        match self {
+            Self::Xee(e) => e.set_current_datetime(datetime),
+            // Neither dynamic context has a current dateTime to set
+            Self::Xrust(_) | Self::Xust(_) => {}
+        }
+    }
+
+    /// Whether current date/time calls see a pinned dateTime
+    fn current_datetime_pinned(&self) -> bool {
+        match self {
+            Self::Xee(e) => e.current_datetime().is_some(),
+            Self::Xrust(_) | Self::Xust(_) => false,
+        }
+    }
+
+    /// Set the implicit timezone, as an offset in minutes east of UTC, for
+    /// subsequent evaluations
+    ///
+    /// It applies to the current dateTime, `implicit-timezone()` and
+    /// timezone-less comparisons and adjustments. Offsets beyond 14 hours
+    /// are clamped. xrust has no implicit timezone to set and keeps using
+    /// the local timezone; see [`Capability::ImplicitTimezone`].
+    pub fn set_implicit_timezone(&mut self, offset_minutes: i32) {
+        self.clear_query_cache();
+        let timezone = Some(timezone_from_minutes(offset_minutes));
+        match self {
+            Self::Xee(e) => e.set_implicit_timezone(timezone),
+            Self::Xrust(_) => {}
+            Self::Xust(e) => e.set_implicit_timezone(timezone),
+        }
+    }
+
+    /// Go back to the local timezone as the implicit timezone
+    pub fn clear_implicit_timezone(&mut self) {
+        self.clear_query_cache();
+        match self {
+            Self::Xee(e) => e.set_implicit_timezone(None),
+            Self::Xrust(_) => {}
+            Self::Xust(e) => e.set_implicit_timezone(None),
+        }
+    }
+
+    /// Prepare `expr` for evaluation: resolve `unparsed-text` calls with
+    /// literal arguments
+    fn resolve_expression<'a>(&self, expr: &'a str) -> Result<Cow<'a, str>> {
+        let (resolver, base_uri) = match self {
+            Self::Xee(e) => (e.text_resolver(), e.static_base_uri()),
+            Self::Xrust(e) => (e.text_resolver(), None),
+            Self::Xust(e) => (e.text_resolver(), e.static_base_uri()),
+        };
+        resolve_unparsed_text(expr, &mut |href| match resolver {
+            Some(resolve) => resolve(href),
+            None => filesystem_text_resolver(base_uri, href),
+        })
+    }
+
+    /// Register a collation by URI for string comparisons in assertions
+    /// and `collation` dependencies
+    ///
+    /// The codepoint and HTML ASCII case-insensitive collations are
+    /// pre-registered. Registrations apply to all engines on this thread.
+    pub fn register_collation(uri: &str, collator: Box<dyn Fn(&str, &str) -> Ordering>) {
+        collation::register_collation(uri, collator);
+    }
+
+    // ==================== Query Cache ====================
+
+    /// Cache up to `capacity` XPath results keyed by document and expression
+    ///
+    /// Repeated identical queries against the same document then return a
+    /// clone of the earlier result. The cache is cleared whenever a document
+    /// is parsed or the static context changes. Queries calling
+    /// `current-dateTime` and friends are only cached while it is pinned.
+    pub fn enable_query_cache(&mut self, capacity: usize) {
+        match self {
+            Self::Xee(e) => e.set_query_cache(Some(QueryCache::new(capacity))),
+            Self::Xrust(e) => e.set_query_cache(Some(QueryCache::new(capacity))),
+            Self::Xust(e) => e.set_query_cache(Some(QueryCache::new(capacity))),
+        }
+    }
+
+    /// Remove the query result cache
+    pub fn disable_query_cache(&mut self) {
+        match self {
+            Self::Xee(e) => e.set_query_cache(None),
+            Self::Xrust(e) => e.set_query_cache(None),
+            Self::Xust(e) => e.set_query_cache(None),
+        }
+    }
+
+    /// Number of queries answered from the cache (0 when disabled)
+    pub fn query_cache_hits(&mut self) -> usize {
+        match self {
+            Self::Xee(e) => e.query_cache().map_or(0, |c| c.hits()),
+            Self::Xrust(e) => e.query_cache().map_or(0, |c| c.hits()),
+            Self::Xust(e) => e.query_cache().map_or(0, |c| c.hits()),
+        }
+    }
+
+    fn clear_query_cache(&mut self) {
+        match self {
+            Self::Xee(e) => {
+                if let Some(cache) = e.query_cache() {
+                    cache.clear();
+                }
+            }
+            Self::Xrust(e) => {
+                if let Some(cache) = e.query_cache() {
+                    cache.clear();
+                }
+            }
+            Self::Xust(e) => {
+                if let Some(cache) = e.query_cache() {
+                    cache.clear();
+                }
+            }
+        }
+    }
+
+    /// Look up a cached XPath result for `doc`
+    fn cached_xpath(&mut self, doc: &XDocument, xpath: &str) -> Option<XQueryResult> {
+        let id = doc.id();
+        match self {
+            Self::Xee(e) => e.query_cache()?.get(id, xpath).map(XQueryResult::Xee),
+            Self::Xrust(e) => e.query_cache()?.get(id, xpath).map(XQueryResult::Xrust),
+            Self::Xust(e) => e.query_cache()?.get(id, xpath).map(XQueryResult::Xust),
+        }
+    }
+
+    /// Remember an XPath result for `doc`, if caching is enabled
+    fn cache_xpath(&mut self, doc: &XDocument, xpath: &str, result: &XQueryResult) {
+        let id = doc.id();
+        match (self, result) {
+            (Self::Xee(e), XQueryResult::Xee(r)) => {
+                if let Some(cache) = e.query_cache() {
+                    cache.insert(id, xpath, r.clone());
+                }
+            }
+            (Self::Xrust(e), XQueryResult::Xrust(r)) => {
+                if let Some(cache) = e.query_cache() {
+                    cache.insert(id, xpath, r.clone());
+                }
+            }
+            (Self::Xust(e), XQueryResult::Xust(r)) => {
+                if let Some(cache) = e.query_cache() {
+                    cache.insert(id, xpath, r.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // ==================== Tracing ====================
+
+    /// Install (or with `None`, remove) a hook receiving a trace line for
+    /// each `xpath`/`xquery` evaluation
+    ///
+    /// Lines name the backend, language, static base URI (if set), the
+    /// expression and a truncated result.
+    pub fn set_trace(&mut self, hook: Option<TraceHook>) {
+        match self {
+            Self::Xee(e) => e.set_trace(hook),
+            Self::Xrust(e) => e.set_trace(hook),
+            Self::Xust(e) => e.set_trace(hook),
+        }
+    }
+
+    /// Report an evaluation to the trace hook, if one is installed
+    fn trace(&mut self, language: &str, expr: &str, result: &Result<XQueryResult>) {
+        const MAX_RESULT_LEN: usize = 80;
+
+        let backend = self.backend();
+        let (hook, base_uri) = match self {
+            Self::Xee(e) => {
+                let base_uri = e.static_base_uri().map(String::from);
+                (e.trace_hook(), base_uri)
+            }
+            Self::Xrust(e) => (e.trace_hook(), None),
+            Self::Xust(e) => {
+                let base_uri = e.static_base_uri().map(String::from);
+                (e.trace_hook(), base_uri)
+            }
+        };
+        let Some(hook) = hook else {
+            return;
+        };
+
+        let outcome = match result {
+            Ok(r) => {
+                let value = r.to_string().replace('\n', " ");
+                if value.chars().count() > MAX_RESULT_LEN {
+                    format!("{}...", value.chars().take(MAX_RESULT_LEN).collect::<String>())
+                } else {
+                    value
+                }
+            }
+            Err(e) => format!("error: {}", e),
+        };
+        let context = base_uri.map(|uri| format!(" (base-uri {})", uri)).unwrap_or_default();
+        hook(&format!("[{:?}] {}{}: {} => {}", backend, language, context, expr, outcome));
+    }
+
     // ==================== XML Parsing ====================
 
+    /// Check subsequent parses against `limits`, failing with a
+    /// `limit exceeded` parse error when one is tripped
+    pub fn set_parse_limits(&mut self, limits: ParseLimits) {
+        match self {
+            Self::Xee(e) => e.set_parse_limits(Some(limits)),
+            Self::Xrust(e) => e.set_parse_limits(Some(limits)),
+            Self::Xust(e) => e.set_parse_limits(Some(limits)),
+        }
+    }
+
+    /// Parse without limits again
+    pub fn clear_parse_limits(&mut self) {
+        match self {
+            Self::Xee(e) => e.set_parse_limits(None),
+            Self::Xrust(e) => e.set_parse_limits(None),
+            Self::Xust(e) => e.set_parse_limits(None),
+        }
+    }
+
     /// Parse XML from a string
     pub fn parse(&mut self, xml: &str) -> Result<XDocument> {
         match self {
@@ -91,6 +502,25 @@ impl XEngine {
         }
     }
 
+    /// Parse XML from a string after expanding its XIncludes
+    ///
+    /// `xi:include` hrefs resolve against `base_dir`; see
+    /// [`expand_xinclude`](crate::xinclude::expand_xinclude).
+    pub fn parse_with_xinclude(&mut self, xml: &str, base_dir: &Path) -> Result<XDocument> {
+        let expanded = xinclude::expand_xinclude(xml, base_dir)?;
+        self.parse(&expanded)
+    }
+
+    /// Parse XML from a string, also returning non-fatal warnings
+    ///
+    /// Fatal errors still fail the parse. The warnings point at constructs
+    /// (redundant namespace declarations, DOCTYPEs, ...) that backends may
+    /// treat differently.
+    pub fn parse_with_diagnostics(&mut self, xml: &str) -> Result<(XDocument, Vec<String>)> {
+        let doc = self.parse(xml)?;
+        Ok((doc, diagnostics::scan(xml)))
+    }
+
     /// Parse XML from a file
     pub fn parse_file(&mut self, path: &Path) -> Result<XDocument> {
         match self {
@@ -100,21 +530,224 @@ impl XEngine {
         }
     }
 
+    /// Parse `xml` and serialize it straight back
+    ///
+    /// Shows what the backend preserves of a document; compare the output
+    /// with [`xml_equivalent`](crate::canonical::xml_equivalent).
+    pub fn roundtrip(&mut self, xml: &str) -> Result<String> {
+        self.parse(xml)?.to_string()
+    }
+
+    /// Parse `xml` and serialize it indented by `indent` spaces per level
+    ///
+    /// Whitespace in mixed content is significant and kept as-is; see
+    /// [`reindent`](crate::serialization::reindent).
+    pub fn reformat(&mut self, xml: &str, indent: usize) -> Result<String> {
+        let serialized = match self {
+            // xot's serializer indents by two spaces
+            Self::Xee(e) if indent == 2 => return e.pretty_print(xml),
+            Self::Xee(e) => e.pretty_print(xml)?,
+            Self::Xrust(e) => XDocument::Xrust(e.parse(xml)?).to_string()?,
+            Self::Xust(e) => XDocument::Xust(e.parse(xml)?).to_string()?,
+        };
+        reindent(&serialized, indent)
+    }
+
     // ==================== XPath ====================
 
     /// Evaluate an XPath expression
     pub fn xpath(&mut self, doc: &XDocument, xpath: &str) -> Result<XQueryResult> {
-        match (self, doc) {
-            (Self::Xee(e), XDocument::Xee(d)) => e.evaluate_xpath(d, xpath).map(XQueryResult::Xee),
+        // Results read from a running clock go stale
+        let cacheable = self.current_datetime_pinned() || !reads_clock(xpath);
+        if let Some(cached) = cacheable.then(|| self.cached_xpath(doc, xpath)).flatten() {
+            let result = Ok(cached);
+            self.trace("xpath", xpath, &result);
+            return result;
+        }
+        let expr = match self.resolve_expression(xpath) {
+            Ok(expr) => expr,
+            Err(e) => {
+                let result = Err(e);
+                self.trace("xpath", xpath, &result);
+                return result;
+            }
+        };
+        let result = match (&mut *self, doc) {
+            (Self::Xee(e), XDocument::Xee(d)) => e.evaluate_xpath(d, &expr).map(XQueryResult::Xee),
             (Self::Xrust(e), XDocument::Xrust(d)) => {
-                e.evaluate_xpath(d, xpath).map(XQueryResult::Xrust)
+                e.evaluate_xpath(d, &expr).map(XQueryResult::Xrust)
             }
             (Self::Xust(e), XDocument::Xust(d)) => {
-                e.evaluate_xpath(d, xpath).map(XQueryResult::Xust)
+                e.evaluate_xpath(d, &expr).map(XQueryResult::Xust)
+            }
+            (engine, doc) => Err(mismatch(engine, doc)),
+        };
+        match &result {
+            Ok(r) if cacheable => self.cache_xpath(doc, xpath, r),
+            _ => {}
+        }
+        self.trace("xpath", xpath, &result);
+        result
+    }
+
+    /// Evaluate `assertion` with `$result` bound to `result`, the result of
+    /// evaluating `expr` against `doc`
+    ///
+    /// On xee the variable holds the result's own items, so node identity
+    /// comparisons such as `$result[1] is //item[1]` work. `expr` is only
+    /// evaluated again if the engine no longer holds the result (it was
+    /// served from the query cache, say). xust cannot bind variables, so it
+    /// runs a single `let $result := (expr) return (assertion)` evaluation,
+    /// which rules out a prolog in `expr`. xrust only speaks XPath 1.0,
+    /// which has no `let`, so it returns [`Error::FeatureNotSupported`].
+    pub fn xpath_with_result(
+        &mut self,
+        doc: &XDocument,
+        expr: &str,
+        result: &XQueryResult,
+        assertion: &str,
+    ) -> Result<XQueryResult> {
+        if self.backend() == Backend::Xrust {
+            let result = Err(Error::FeatureNotSupported(
+                "Binding $result requires XPath 2.0 or later".to_string(),
+            ));
+            self.trace("xpath", assertion, &result);
+            return result;
+        }
+        if self.backend() == Backend::Xust {
+            let combined = format!("let $result := ({}) return ({})", expr, assertion);
+            return self.xpath(doc, &combined);
+        }
+        let expr = self.resolve_expression(expr)?;
+        let resolved = self.resolve_expression(assertion)?;
+        let (Self::Xee(e), XDocument::Xee(d)) = (&mut *self, doc) else {
+            return Err(mismatch(self, doc));
+        };
+        let XQueryResult::Xee(r) = result else {
+            return Err(Error::EngineError("Result is from another backend".to_string()));
+        };
+        let fresh;
+        let r = if e.holds_result(r) {
+            r
+        } else {
+            fresh = e.evaluate_xpath(d, &expr)?;
+            &fresh
+        };
+        let result = e.evaluate_xpath_with_result(d, r, &resolved).map(XQueryResult::Xee);
+        self.trace("xpath", assertion, &result);
+        result
+    }
+
+    /// Evaluate an XPath expression with documents bound to variables
+    ///
+    /// Each `(name, document)` pair is bound as the external variable
+    /// `$name`. Only xee lets variables be declared from outside the
+    /// expression, so this is unsupported on xrust and xust.
+    pub fn xpath_with_variables(
+        &mut self,
+        doc: &XDocument,
+        variables: &[(String, XDocument)],
+        xpath: &str,
+    ) -> Result<XQueryResult> {
+        if variables.is_empty() {
+            return self.xpath(doc, xpath);
+        }
+        let expr = match self.resolve_expression(xpath) {
+            Ok(expr) => expr,
+            Err(e) => {
+                let result = Err(e);
+                self.trace("xpath", xpath, &result);
+                return result;
             }
-            _ => Err(Error::EngineError(
-                "Document was created with a different engine".to_string(),
-            )),
+        };
+        let result = match (&mut *self, doc) {
+            (Self::Xee(e), XDocument::Xee(d)) => variables
+                .iter()
+                .map(|(name, variable)| match variable {
+                    XDocument::Xee(v) => Ok((name.clone(), v)),
+                    other => {
+                        Err(Error::BackendMismatch { expected: Backend::Xee, got: other.backend() })
+                    }
+                })
+                .collect::<Result<Vec<_>>>()
+                .and_then(|bound| e.evaluate_xpath_with_variables(d, &bound, &expr))
+                .map(XQueryResult::Xee),
+            (Self::Xrust(_) | Self::Xust(_), _) => Err(Error::Unsupported),
+            (engine, doc) => Err(mismatch(engine, doc)),
+        };
+        self.trace("xpath", xpath, &result);
+        result
+    }
+
+    /// Evaluate an XPath expression with an atomic value as the context item
+    ///
+    /// The value is set as the dynamic context's item, so path expressions
+    /// raise `XPDY0050`. Unsupported on xrust, whose context item is always
+    /// a node. Node items must be evaluated with [`xpath`](Self::xpath)
+    /// against their document instead.
+    pub fn xpath_with_context_item(&mut self, item: ResultItem, xpath: &str) -> Result<XQueryResult> {
+        if self.backend() == Backend::Xrust {
+            return Err(Error::Unsupported);
+        }
+        let literal = item.to_xpath_literal().ok_or_else(|| {
+            Error::EngineError(format!("Cannot use {:?} as an atomic context item", item))
+        })?;
+        let result = self.resolve_expression(xpath).and_then(|expr| match self {
+            Self::Xee(e) => {
+                e.evaluate_xpath_with_context_item(&literal, &expr).map(XQueryResult::Xee)
+            }
+            Self::Xust(e) => {
+                e.execute_xquery_with_context_item(&literal, &expr).map(XQueryResult::Xust)
+            }
+            Self::Xrust(_) => Err(Error::Unsupported),
+        });
+        self.trace("xpath", xpath, &result);
+        result
+    }
+
+    /// Evaluate an XPath expression to its string value, trimmed
+    ///
+    /// The empty sequence gives an empty string; otherwise the first item's
+    /// string value is used.
+    pub fn xpath_as_string(&mut self, doc: &XDocument, xpath: &str) -> Result<String> {
+        let result = self.xpath(doc, xpath)?;
+        Ok(result.first_string().unwrap_or_default().trim().to_string())
+    }
+
+    /// Evaluate an XPath expression that yields a single non-negative integer
+    ///
+    /// Engine errors are returned as-is; a result that isn't a non-negative
+    /// integer (including the empty sequence) is an `XPTY0004` error.
+    pub fn xpath_as_usize(&mut self, doc: &XDocument, xpath: &str) -> Result<usize> {
+        let result = self.xpath(doc, xpath)?;
+        let value = match result.items().as_slice() {
+            [item] => item.string_value(),
+            _ => result.to_string(),
+        };
+        value.trim().parse().map_err(|_| {
+            Error::xpath(format!(
+                "XPTY0004: expected a non-negative integer from {}, got '{}'",
+                xpath, value
+            ))
+        })
+    }
+
+    /// Evaluate an XPath expression without a context document
+    ///
+    /// The context item is an empty document node, so path expressions such
+    /// as `//*` select nothing.
+    pub fn xpath_no_context(&mut self, xpath: &str) -> Result<XQueryResult> {
+        let doc = self.empty_document()?;
+        self.xpath(&doc, xpath)
+    }
+
+    /// Create a document node with no children, for evaluating without a
+    /// context document
+    pub fn empty_document(&self) -> Result<XDocument> {
+        match self {
+            Self::Xee(e) => Ok(XDocument::Xee(e.empty_document())),
+            Self::Xrust(e) => Ok(XDocument::Xrust(e.empty_document())),
+            Self::Xust(e) => e.empty_document().map(XDocument::Xust),
         }
     }
 
@@ -131,18 +764,92 @@ impl XEngine {
 
     /// Execute an XQuery expression
     pub fn xquery(&mut self, doc: &XDocument, query: &str) -> Result<XQueryResult> {
-        match (self, doc) {
-            (Self::Xee(e), XDocument::Xee(d)) => e.execute_xquery(d, query).map(XQueryResult::Xee),
+        let expr = match self.resolve_expression(query) {
+            Ok(expr) => expr,
+            Err(e) => {
+                let result = Err(e);
+                self.trace("xquery", query, &result);
+                return result;
+            }
+        };
+        let result = match (&mut *self, doc) {
+            (Self::Xee(e), XDocument::Xee(d)) => e.execute_xquery(d, &expr).map(XQueryResult::Xee),
             (Self::Xrust(e), XDocument::Xrust(d)) => {
-                e.execute_xquery(d, query).map(XQueryResult::Xrust)
+                e.execute_xquery(d, &expr).map(XQueryResult::Xrust)
             }
             (Self::Xust(e), XDocument::Xust(d)) => {
-                e.execute_xquery(d, query).map(XQueryResult::Xust)
+                e.execute_xquery(d, &expr).map(XQueryResult::Xust)
             }
-            _ => Err(Error::EngineError(
-                "Document was created with a different engine".to_string(),
-            )),
+            (engine, doc) => Err(mismatch(engine, doc)),
+        };
+        self.trace("xquery", query, &result);
+        result
+    }
+
+    /// Evaluate an expression as XQuery where the backend supports it,
+    /// falling back to XPath otherwise
+    ///
+    /// XQuery-only constructs (e.g. FLWOR) work on xust; plain XPath works
+    /// on every backend.
+    pub fn evaluate(&mut self, doc: &XDocument, expr: &str) -> Result<XQueryResult> {
+        match self.backend() {
+            Backend::Xust => self.xquery(doc, expr),
+            Backend::Xee | Backend::Xrust => self.xpath(doc, expr),
+        }
+    }
+
+    /// Execute a query over a set of registered documents
+    ///
+    /// Each `(uri, path)` document is registered under its URI, so `doc(uri)`
+    /// returns it; relative URIs resolve against the static base URI. There
+    /// is no context document. The query runs as XQuery on xust and as
+    /// XPath 3.1 on xee; xrust has no way to register documents, so this is
+    /// unsupported there.
+    pub fn xquery_with_docs(
+        &mut self,
+        docs: &[(String, &Path)],
+        query: &str,
+    ) -> Result<XQueryResult> {
+        if self.backend() == Backend::Xrust {
+            return Err(Error::Unsupported);
         }
+        let expr = match self.resolve_expression(query) {
+            Ok(expr) => expr,
+            Err(e) => {
+                let result = Err(e);
+                self.trace("xquery", query, &result);
+                return result;
+            }
+        };
+        let result = match self {
+            Self::Xee(_) => {
+                let mut parsed = Vec::with_capacity(docs.len());
+                for (uri, path) in docs {
+                    parsed.push((uri.clone(), self.parse_file(path)?));
+                }
+                let doc = self.empty_document()?;
+                match (&mut *self, &doc) {
+                    (Self::Xee(e), XDocument::Xee(d)) => {
+                        let registered: Vec<_> = parsed
+                            .iter()
+                            .filter_map(|(uri, doc)| match doc {
+                                XDocument::Xee(doc) => Some((uri.clone(), doc)),
+                                _ => None,
+                            })
+                            .collect();
+                        e.evaluate_xpath_with_documents(d, &registered, &expr)
+                            .map(XQueryResult::Xee)
+                    }
+                    (engine, doc) => Err(mismatch(engine, doc)),
+                }
+            }
+            Self::Xust(e) => {
+                e.execute_xquery_with_documents(docs, &expr).map(XQueryResult::Xust)
+            }
+            Self::Xrust(_) => Err(Error::Unsupported),
+        };
+        self.trace("xquery", query, &result);
+        result
     }
 
     /// Get the XQuery version supported by this engine
@@ -164,21 +871,119 @@ impl XEngine {
                 e.transform(d, stylesheet).map(XDocument::Xrust)
             }
             (Self::Xust(e), XDocument::Xust(d)) => e.transform(d, stylesheet).map(XDocument::Xust),
-            _ => Err(Error::EngineError(
-                "Document was created with a different engine".to_string(),
-            )),
+            (engine, doc) => Err(mismatch(engine, doc)),
         }
     }
 
     /// Transform a document to string using an XSLT stylesheet
+    ///
+    /// The output is serialized using the method declared by the stylesheet's
+    /// `xsl:output` (`xml` when none is declared).
     pub fn xslt_to_string(&mut self, doc: &XDocument, stylesheet: &str) -> Result<String> {
+        let xml = self.transform_unserialized(doc, stylesheet)?;
+        apply_output_method(&xml, OutputMethod::from_stylesheet(stylesheet))
+    }
+
+    /// Transform with XSLT and serialize with `overrides` taking precedence
+    /// over the stylesheet's `xsl:output` parameters
+    ///
+    /// Lets a harness normalize serialization for comparison, e.g. force
+    /// `indent=no` whatever the stylesheet declares.
+    pub fn transform_to_string_with_output_override(
+        &mut self,
+        doc: &XDocument,
+        stylesheet: &str,
+        overrides: &OutputParams,
+    ) -> Result<String> {
+        let xml = self.transform_unserialized(doc, stylesheet)?;
+        OutputParams::from_stylesheet(stylesheet).merge(overrides).apply(&xml)
+    }
+
+    /// Transform with XSLT, returning the backend's XML serialization
+    fn transform_unserialized(&mut self, doc: &XDocument, stylesheet: &str) -> Result<String> {
         match (self, doc) {
             (Self::Xee(e), XDocument::Xee(d)) => e.transform_to_string(d, stylesheet),
             (Self::Xrust(e), XDocument::Xrust(d)) => e.transform_to_string(d, stylesheet),
             (Self::Xust(e), XDocument::Xust(d)) => e.transform_to_string(d, stylesheet),
-            _ => Err(Error::EngineError(
-                "Document was created with a different engine".to_string(),
-            )),
+            (engine, doc) => Err(mismatch(engine, doc)),
+        }
+    }
+
+    /// Compile a stylesheet once, for [`transform_compiled`](Self::transform_compiled)
+    /// over any number of documents
+    pub fn compile_stylesheet(&mut self, stylesheet: &str) -> Result<StylesheetHandle> {
+        let (engine, index) = match self {
+            Self::Xee(e) => (e.id(), e.compile_stylesheet(stylesheet)?),
+            Self::Xrust(e) => (e.id(), e.compile_stylesheet(stylesheet)?),
+            Self::Xust(_) => return Err(Error::Unsupported),
+        };
+        let method = OutputMethod::from_stylesheet(stylesheet);
+        Ok(StylesheetHandle { backend: self.backend(), engine, index, method })
+    }
+
+    /// Free a stylesheet from [`compile_stylesheet`](Self::compile_stylesheet)
+    ///
+    /// Transforms with the handle fail afterwards.
+    pub fn drop_stylesheet(&mut self, stylesheet: &StylesheetHandle) -> Result<()> {
+        self.check_stylesheet(stylesheet)?;
+        let dropped = match self {
+            Self::Xee(e) => e.drop_stylesheet(stylesheet.index),
+            Self::Xrust(e) => e.drop_stylesheet(stylesheet.index),
+            Self::Xust(_) => false,
+        };
+        if !dropped {
+            return Err(Error::XsltError("Unknown compiled stylesheet".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Check that `stylesheet` was compiled by this engine instance
+    fn check_stylesheet(&self, stylesheet: &StylesheetHandle) -> Result<()> {
+        if stylesheet.backend != self.backend() {
+            return Err(Error::BackendMismatch {
+                expected: stylesheet.backend,
+                got: self.backend(),
+            });
+        }
+        let engine = match self {
+            Self::Xee(e) => e.id(),
+            Self::Xrust(e) => e.id(),
+            Self::Xust(_) => return Err(Error::Unsupported),
+        };
+        if stylesheet.engine != engine {
+            return Err(Error::XsltError(
+                "Stylesheet was compiled by another engine instance".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Transform with a compiled stylesheet, like [`transform`](Self::transform)
+    pub fn transform_compiled(
+        &mut self,
+        doc: &XDocument,
+        stylesheet: &StylesheetHandle,
+    ) -> Result<String> {
+        self.check_stylesheet(stylesheet)?;
+        let xml = match (self, doc) {
+            (Self::Xee(e), XDocument::Xee(d)) => {
+                e.transform_compiled_to_string(d, stylesheet.index)?
+            }
+            (Self::Xrust(e), XDocument::Xrust(d)) => {
+                XDocument::Xrust(e.transform_compiled(d, stylesheet.index)?).to_string()?
+            }
+            (Self::Xust(_), XDocument::Xust(_)) => return Err(Error::Unsupported),
+            (engine, doc) => return Err(mismatch(engine, doc)),
+        };
+        apply_output_method(&xml, stylesheet.method)
+    }
+
+    /// Number of XSLT stylesheet compilations this engine has performed
+    pub fn stylesheet_compilations(&self) -> usize {
+        match self {
+            Self::Xee(e) => e.stylesheet_compilations(),
+            Self::Xrust(e) => e.stylesheet_compilations(),
+            Self::Xust(_) => 0,
         }
     }
 
@@ -194,6 +999,9 @@ impl XEngine {
     // ==================== XSD Validation ====================
 
     /// Load an XSD schema from a string
+    ///
+    /// xust reads schemas from files only; it goes through a temp file, so
+    /// without the `native` feature this is unsupported there.
     pub fn load_schema(&mut self, xsd: &str) -> Result<()> {
         match self {
             Self::Xee(e) => e.load_schema(xsd),
@@ -211,18 +1019,35 @@ impl XEngine {
         }
     }
 
+    /// Discard the loaded schema so the next validation starts clean
+    ///
+    /// Idempotent; a no-op when no schema is loaded.
+    pub fn clear_schema(&mut self) {
+        match self {
+            Self::Xee(e) => e.clear_schema(),
+            Self::Xrust(e) => e.clear_schema(),
+            Self::Xust(e) => e.clear_schema(),
+        }
+    }
+
     /// Validate a document against the loaded schema
     pub fn validate(&self, doc: &XDocument) -> Result<ValidationResult> {
         match (self, doc) {
             (Self::Xee(e), XDocument::Xee(d)) => e.validate(d),
             (Self::Xrust(e), XDocument::Xrust(d)) => e.validate(d),
             (Self::Xust(e), XDocument::Xust(d)) => e.validate(d),
-            _ => Err(Error::EngineError(
-                "Document was created with a different engine".to_string(),
-            )),
+            (engine, doc) => Err(mismatch(engine, doc)),
         }
     }
 
+    /// Validate a document, keeping the individual validation errors
+    ///
+    /// Unlike [`validate_instance`](Self::validate_instance), which reduces
+    /// the outcome to a bool, this exposes why a document is invalid.
+    pub fn validate_detailed(&self, doc: &XDocument) -> Result<ValidationResult> {
+        self.validate(doc)
+    }
+
     /// Get the XSD version supported by this engine
     pub fn xsd_version(&self) -> XsdVersion {
         match self {
@@ -239,6 +1064,21 @@ impl XEngine {
         self.xslt_to_string(doc, stylesheet)
     }
 
+    /// Transform with XSLT and take the truth value of the output's text
+    ///
+    /// The text content is trimmed; `true`/`1` and `false`/`0` (the
+    /// `xs:boolean` lexical forms) give their value, any other text is true
+    /// unless empty, as for a string's effective boolean value.
+    pub fn transform_to_boolean(&mut self, doc: &XDocument, stylesheet: &str) -> Result<bool> {
+        let output = self.xslt_to_string(doc, stylesheet)?;
+        let text = apply_output_method(&output, OutputMethod::Text)?;
+        Ok(match text.trim() {
+            "true" | "1" => true,
+            "false" | "0" => false,
+            other => !other.is_empty(),
+        })
+    }
+
     /// Validate a schema file (checks if the schema itself is valid)
     pub fn validate_schema(&mut self, schema_path: &Path) -> Result<bool> {
         match self.load_schema_file(schema_path) {
@@ -250,6 +1090,16 @@ impl XEngine {
 
     /// Validate an instance document against a schema file
     pub fn validate_instance(&mut self, instance_path: &Path, schema_path: &Path) -> Result<bool> {
+        self.validate_instance_detailed(instance_path, schema_path)
+            .map(|result| result.valid)
+    }
+
+    /// Validate an instance document against a schema file, keeping error details
+    pub fn validate_instance_detailed(
+        &mut self,
+        instance_path: &Path,
+        schema_path: &Path,
+    ) -> Result<ValidationResult> {
         // Load schema
         self.load_schema_file(schema_path)?;
 
@@ -259,12 +1109,106 @@ impl XEngine {
         let doc = self.parse(&instance_content)?;
 
         // Validate
-        let result = self.validate(&doc)?;
-        Ok(result.valid)
+        self.validate_detailed(&doc)
+    }
+
+    /// Validate an instance against a schema, reporting each element's schema type
+    ///
+    /// Returns `(path, type)` pairs from the post-schema-validation infoset.
+    /// Only the xust backend exposes type annotations.
+    pub fn validate_to_typed(&mut self, instance: &str, schema: &str) -> Result<Vec<(String, String)>> {
+        self.load_schema(schema)?;
+        let doc = self.parse(instance)?;
+        match (&*self, &doc) {
+            (Self::Xust(e), XDocument::Xust(d)) => e.validate_to_typed(d),
+            _ => Err(Error::Unsupported),
+        }
     }
+
+    /// Evaluate an XPath expression against the validated (typed) tree
+    ///
+    /// [`xpath`](Self::xpath) queries the untyped tree, where element
+    /// content atomizes to `xs:untypedAtomic`. Here `doc` is first validated
+    /// against the loaded schema, so values carry their schema types and, for
+    /// example, `xs:int` content compares numerically. Only xust builds typed
+    /// trees.
+    pub fn query_validated(&mut self, doc: &XDocument, xpath: &str) -> Result<XQueryResult> {
+        let typed = match (&*self, doc) {
+            (Self::Xust(e), XDocument::Xust(d)) => XDocument::Xust(e.validated_document(d)?),
+            (engine @ Self::Xust(_), doc) => return Err(mismatch(engine, doc)),
+            _ => return Err(Error::Unsupported),
+        };
+        self.xpath(&typed, xpath)
+    }
+
+    /// Validate an instance against a schema and, if it is valid, query it
+    ///
+    /// The query result is `None` when the instance is invalid.
+    pub fn validate_and_query(
+        &mut self,
+        instance: &str,
+        schema: &str,
+        xpath: &str,
+    ) -> Result<(ValidationResult, Option<XQueryResult>)> {
+        self.validate_and_query_with(instance, schema, xpath, false)
+    }
+
+    /// Validate an instance against a schema, then query it
+    ///
+    /// With `always_query` the query also runs on invalid instances.
+    pub fn validate_and_query_with(
+        &mut self,
+        instance: &str,
+        schema: &str,
+        xpath: &str,
+        always_query: bool,
+    ) -> Result<(ValidationResult, Option<XQueryResult>)> {
+        self.load_schema(schema)?;
+        let doc = self.parse(instance)?;
+        let validation = self.validate_detailed(&doc)?;
+        let result = if validation.valid || always_query {
+            Some(self.xpath(&doc, xpath)?)
+        } else {
+            None
+        };
+        Ok((validation, result))
+    }
+}
+
+/// Error for a document used with an engine of another backend
+fn mismatch(engine: &XEngine, doc: &XDocument) -> Error {
+    Error::BackendMismatch { expected: engine.backend(), got: doc.backend() }
 }
 
 impl XDocument {
+    /// Backend that created the document
+    pub fn backend(&self) -> Backend {
+        match self {
+            Self::Xee(_) => Backend::Xee,
+            Self::Xrust(_) => Backend::Xrust,
+            Self::Xust(_) => Backend::Xust,
+        }
+    }
+
+    /// Process-unique identifier assigned when the document was created
+    pub fn id(&self) -> u64 {
+        match self {
+            Self::Xee(d) => d.id(),
+            Self::Xrust(d) => d.id(),
+            Self::Xust(d) => d.id(),
+        }
+    }
+
+    /// Describe the document element (rather than the document node), e.g.
+    /// to start walking the tree from it
+    pub fn root_element(&self) -> Result<NodeInfo> {
+        match self {
+            Self::Xee(d) => d.document_element(),
+            Self::Xrust(d) => d.document_element(),
+            Self::Xust(d) => d.document_element(),
+        }
+    }
+
     /// Serialize the document to a string
     pub fn to_string(&self) -> Result<String> {
         use crate::traits::XmlDocument;
@@ -286,6 +1230,53 @@ impl XQueryResult {
         }
     }
 
+    /// Check if the result is the empty sequence
+    ///
+    /// Prefer this (or [`is_empty`](Self::is_empty)) over testing
+    /// `to_string().is_empty()`, which can't tell `()` from `('')`.
+    pub fn is_empty_sequence(&self) -> bool {
+        self.is_empty()
+    }
+
+    /// Get the effective boolean value of the result
+    ///
+    /// Follows the XPath rules: `()` is false, a sequence starting with a
+    /// node is true, and a single boolean, string or number gives its own
+    /// truth value; anything else is a `FORG0006` type error.
+    pub fn effective_boolean(&self) -> Result<bool> {
+        let items = self.items();
+        let item = match items.as_slice() {
+            [] => return Ok(false),
+            [ResultItem::Node(_), ..] => return Ok(true),
+            [item] => item,
+            _ => {
+                return Err(Error::xpath(
+                    "FORG0006: effective boolean value of a sequence of two or more atomic values",
+                ))
+            }
+        };
+        match item {
+            ResultItem::Empty => Ok(false),
+            ResultItem::Boolean(b) => Ok(*b),
+            ResultItem::String(s) => Ok(!s.is_empty()),
+            ResultItem::Integer(i) => Ok(*i != 0),
+            ResultItem::Double(d) => Ok(*d != 0.0 && !d.is_nan()),
+            other => Err(Error::xpath(format!(
+                "FORG0006: no effective boolean value for {}",
+                other.as_string()
+            ))),
+        }
+    }
+
+    /// Count the result's items by kind (see [`ResultItem::kind_name`])
+    pub fn type_histogram(&self) -> HashMap<&'static str, usize> {
+        let mut histogram = HashMap::new();
+        for item in self.items() {
+            *histogram.entry(item.kind_name()).or_insert(0) += 1;
+        }
+        histogram
+    }
+
     /// Get the number of items in the result
     pub fn count(&self) -> usize {
         match self {
@@ -321,6 +1312,104 @@ impl XQueryResult {
             Self::Xust(r) => r.items(),
         }
     }
+
+    /// Get the first item in the result
+    pub fn first(&self) -> Option<ResultItem> {
+        match self {
+            Self::Xee(r) => r.first(),
+            Self::Xrust(r) => r.first(),
+            Self::Xust(r) => r.first(),
+        }
+    }
+
+    /// Get the item at `index`
+    fn item(&self, index: usize) -> Option<ResultItem> {
+        if index == 0 {
+            self.first()
+        } else {
+            self.items().into_iter().nth(index)
+        }
+    }
+
+    /// Get the string value of the first item in the result
+    ///
+    /// Node items yield their text content rather than their markup.
+    pub fn first_string(&self) -> Option<String> {
+        self.first().map(|item| item.string_value())
+    }
+
+    /// Get the attributes (local name, value) of the node item at `index`
+    ///
+    /// Empty for non-element items and out-of-range indexes.
+    pub fn attributes_of(&self, index: usize) -> Vec<(String, String)> {
+        match self.item(index) {
+            Some(ResultItem::Node(info)) => info.attributes,
+            _ => Vec::new(),
+        }
+    }
+
+    /// Convert every item to a double
+    ///
+    /// Items with no numeric interpretation are dropped when
+    /// `skip_non_numeric` is set and become NaN otherwise.
+    pub fn as_f64_vec(&self, skip_non_numeric: bool) -> Vec<f64> {
+        self.items()
+            .iter()
+            .filter_map(|item| match item.to_f64() {
+                Some(value) => Some(value),
+                None if skip_non_numeric => None,
+                None => Some(f64::NAN),
+            })
+            .collect()
+    }
+
+    /// Get the identity of the node item at `index`
+    ///
+    /// Returns `None` for atomic items, out-of-range indexes, and xrust
+    /// nodes, which don't track node identity. Compare identities for `is`
+    /// semantics and order them with
+    /// [`document_order_lt`](crate::result::document_order_lt).
+    pub fn node_identity(&self, index: usize) -> Option<NodeId> {
+        match self.item(index) {
+            Some(ResultItem::Node(info)) => info.identity(),
+            _ => None,
+        }
+    }
+
+    /// Serialize the result, raising serialization errors (e.g. SENR0001)
+    /// for items that cannot appear at the top level of serialized output
+    pub fn serialize(&self) -> Result<String> {
+        for item in self.items() {
+            if let ResultItem::Node(info) = item {
+                if matches!(info.node_type, NodeType::Attribute | NodeType::Namespace) {
+                    return Err(Error::SerializationError(format!(
+                        "SENR0001: cannot serialize a top-level {:?} node",
+                        info.node_type
+                    )));
+                }
+            }
+        }
+        self.to_xml()
+    }
+
+    /// Serialize the result using an explicit output method
+    pub fn serialize_with_method(&self, method: OutputMethod) -> Result<String> {
+        apply_output_method(&self.serialize()?, method)
+    }
+
+    /// Serialize the result with explicit serialization options
+    ///
+    /// With `preserve_prefixes`, xee nodes are written again by xot from
+    /// their tree, declaring the prefixes in scope there.
+    pub fn serialize_with_options(&self, options: &SerializationOptions) -> Result<String> {
+        let xml = self.serialize()?;
+        match self {
+            Self::Xee(r) if options.preserve_prefixes => {
+                apply_output_method(&r.serialize_preserving_prefixes()?, options.method)
+            }
+            _ => apply_serialization_options(&xml, options),
+        }
+    }
 }
 
 impl Default for XEngine {
@@ -329,3 +1418,34 @@ impl Default for XEngine {
         Self::xee()
     }
 }
+
+/// Evaluate one expression against the same document on several backends
+///
+/// Each backend parses `xml` afresh and evaluates `expr` with
+/// [`XEngine::evaluate`]; results are stringified so disagreements between
+/// engines are easy to spot.
+pub fn compare_expression(expr: &str, xml: &str, backends: &[Backend]) -> Vec<(Backend, Result<String>)> {
+    backends
+        .iter()
+        .map(|&backend| {
+            let mut engine = XEngine::with_backend(backend);
+            let result = engine
+                .parse(xml)
+                .and_then(|doc| engine.evaluate(&doc, expr))
+                .map(|result| result.to_string());
+            (backend, result)
+        })
+        .collect()
+}
+
+/// Round-trip the same document through several backends
+///
+/// Each backend parses `xml` afresh and serializes it with
+/// [`XEngine::roundtrip`], so differences in what backends preserve are easy
+/// to spot.
+pub fn roundtrip_diff(xml: &str, backends: &[Backend]) -> Vec<(Backend, Result<String>)> {
+    backends
+        .iter()
+        .map(|&backend| (backend, XEngine::with_backend(backend).roundtrip(xml)))
+        .collect()
+}
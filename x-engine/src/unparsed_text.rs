@@ -0,0 +1,198 @@
+//! `fn:unparsed-text` resolution
+//!
+//! None of the backends expose a hook for loading text resources (xee has
+//! no registry for extension functions either), so calls to
+//! `unparsed-text`, `unparsed-text-lines` and `unparsed-text-available`
+//! whose `href` is a string literal are resolved before evaluation and
+//! replaced by their value. A resource that can't be read becomes an
+//! `fn:error` call, so it only raises if that branch is evaluated. Calls with
+//! computed arguments are left to the backend.
+//!
+//! The same scan points literal `fn:doc` URIs at registered files for
+//! backends without a document resolver.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::result::ResultItem;
+
+/// The functions resolved here, longest first so prefixes don't shadow them
+const FUNCTIONS: &[&str] = &["unparsed-text-available", "unparsed-text-lines", "unparsed-text"];
+
+/// Load a text resource from the filesystem, relative to the static base URI
+///
+/// `file:` URIs and absolute paths are used as-is. Relative references
+/// resolve against the base URI's directory, or the current directory when
+/// no base URI is set.
+pub fn filesystem_text_resolver(base_uri: Option<&str>, href: &str) -> Result<String> {
+    std::fs::read_to_string(resolve_href(base_uri, href)).map_err(Error::from)
+}
+
+fn resolve_href(base_uri: Option<&str>, href: &str) -> PathBuf {
+    let strip_scheme = |uri: &str| -> String {
+        let path = uri.strip_prefix("file://").or_else(|| uri.strip_prefix("file:")).unwrap_or(uri);
+        path.to_string()
+    };
+    let href = strip_scheme(href);
+    if Path::new(&href).is_absolute() {
+        return PathBuf::from(href);
+    }
+    match base_uri.map(strip_scheme) {
+        Some(base) if base.ends_with('/') => Path::new(&base).join(href),
+        Some(base) => Path::new(&base).parent().unwrap_or(Path::new(".")).join(href),
+        None => PathBuf::from(href),
+    }
+}
+
+/// Replace unparsed-text calls with literal arguments by their values
+///
+/// `resolve` loads the text for an `href`. A failed load is replaced by a
+/// call raising `FOUT1170` for `unparsed-text(-lines)` and by `false()` for
+/// `unparsed-text-available`.
+pub(crate) fn resolve_unparsed_text<'a>(
+    expr: &'a str,
+    resolve: &mut dyn FnMut(&str) -> Result<String>,
+) -> Result<Cow<'a, str>> {
+    if !expr.contains("unparsed-text") {
+        return Ok(Cow::Borrowed(expr));
+    }
+    rewrite_calls(expr, FUNCTIONS, &mut |function, href| {
+        let replacement = match (function, resolve(href)) {
+            ("unparsed-text-available", text) => format!("{}()", text.is_ok()),
+            (_, Err(e)) => format!(
+                "error(QName('http://www.w3.org/2005/xqt-errors', 'err:FOUT1170'), {})",
+                literal(&format!("cannot read {}: {}", href, e))
+            ),
+            ("unparsed-text-lines", Ok(text)) => {
+                let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                let text = text.strip_suffix('\n').unwrap_or(&text);
+                if text.is_empty() {
+                    "()".to_string()
+                } else {
+                    let lines: Vec<String> = text.split('\n').map(literal).collect();
+                    format!("({})", lines.join(", "))
+                }
+            }
+            (_, Ok(text)) => literal(&text),
+        };
+        Ok(Some(replacement))
+    })
+}
+
+/// Replace calls to `functions` whose arguments are string literals
+///
+/// `replace` gets the function name and `href` argument and returns the
+/// replacement, or `None` to keep the call.
+fn rewrite_calls<'a>(
+    expr: &'a str,
+    functions: &[&str],
+    replace: &mut dyn FnMut(&str, &str) -> Result<Option<String>>,
+) -> Result<Cow<'a, str>> {
+    let mut out = String::with_capacity(expr.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < expr.len() {
+        let rest = &expr[i..];
+        let c = rest.chars().next().unwrap_or(' ');
+        if c == '"' || c == '\'' {
+            i += string_literal(rest).map_or(rest.len(), |(_, len)| len);
+            continue;
+        }
+        if rest.starts_with("(:") {
+            i += rest.find(":)").map_or(rest.len(), |end| end + 2);
+            continue;
+        }
+        let at_boundary = !expr[..i].ends_with(|c: char| is_name_char(c) || c == '$');
+        if at_boundary {
+            if let Some((function, href, len)) = function_call(rest, functions) {
+                if let Some(replacement) = replace(function, &href)? {
+                    out.push_str(&expr[copied..i]);
+                    out.push_str(&replacement);
+                    i += len;
+                    copied = i;
+                    continue;
+                }
+            }
+        }
+        i += c.len_utf8();
+    }
+
+    if copied == 0 {
+        return Ok(Cow::Borrowed(expr));
+    }
+    out.push_str(&expr[copied..]);
+    Ok(Cow::Owned(out))
+}
+
+/// Match a call to one of `functions` with a literal `href` at the start of
+/// `s`, returning the function, the `href` and the length of the call
+fn function_call<'f>(s: &str, functions: &[&'f str]) -> Option<(&'f str, String, usize)> {
+    let unprefixed = s.strip_prefix("fn:").unwrap_or(s);
+    let function = *functions.iter().find(|f| unprefixed.starts_with(**f))?;
+    let mut pos = s.len() - unprefixed.len() + function.len();
+    if s[pos..].starts_with(is_name_char) {
+        return None;
+    }
+
+    let skip_space = |pos: usize| pos + s[pos..].len() - s[pos..].trim_start().len();
+    pos = skip_space(pos);
+    if !s[pos..].starts_with('(') {
+        return None;
+    }
+    pos = skip_space(pos + 1);
+    let (href, len) = string_literal(&s[pos..])?;
+    pos = skip_space(pos + len);
+    // An optional literal encoding argument; only UTF-8 is read
+    if s[pos..].starts_with(',') {
+        pos = skip_space(pos + 1);
+        let (_, len) = string_literal(&s[pos..])?;
+        pos = skip_space(pos + len);
+    }
+    if !s[pos..].starts_with(')') {
+        return None;
+    }
+    Some((function, href, pos + 1))
+}
+
+/// Parse a string literal at the start of `s`, returning its value and length
+pub(crate) fn string_literal(s: &str) -> Option<(String, usize)> {
+    let quote = s.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let mut value = String::new();
+    let mut chars = s.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c == quote {
+            // A doubled quote is an escaped quote
+            if chars.next_if(|(_, next)| *next == quote).is_none() {
+                return Some((value, i + 1));
+            }
+        }
+        value.push(c);
+    }
+    None
+}
+
+/// Point `doc` and `doc-available` calls with a literal URI elsewhere
+///
+/// `redirect` gets each URI and returns the one to load instead, or `None`
+/// to keep the call. This is how documents are registered with backends
+/// whose `fn:doc` only loads from the filesystem.
+pub(crate) fn redirect_document_calls<'a>(
+    expr: &'a str,
+    redirect: &mut dyn FnMut(&str) -> Option<String>,
+) -> Result<Cow<'a, str>> {
+    if !expr.contains("doc") {
+        return Ok(Cow::Borrowed(expr));
+    }
+    rewrite_calls(expr, &["doc-available", "doc"], &mut |function, uri| {
+        Ok(redirect(uri).map(|target| format!("{}({})", function, literal(&target))))
+    })
+}
+
+fn literal(text: &str) -> String {
+    ResultItem::String(text.to_string()).to_xpath_literal().unwrap_or_default()
+}
+
+pub(crate) fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | ':')
+}
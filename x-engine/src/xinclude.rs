@@ -0,0 +1,150 @@
+//! XInclude expansion
+//!
+//! Replaces `xi:include` elements with the resources they reference before
+//! the document is handed to a backend, since none of the backends process
+//! XInclude themselves. Supports `parse="xml"` (the default) and
+//! `parse="text"`; `xpointer` and `xi:fallback` are not supported.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::canonical::{doctype_end, namespace_declarations, parse_start_tag};
+use crate::error::{Error, Result};
+use crate::serialization::{escape, markup_end};
+
+/// The XInclude namespace
+pub const XINCLUDE_NS: &str = "http://www.w3.org/2001/XInclude";
+
+/// Expand every `xi:include` in `xml`, resolving `href`s against `base_dir`
+///
+/// Included XML documents are expanded recursively, relative to their own
+/// directory. An inclusion loop is an error.
+pub fn expand_xinclude(xml: &str, base_dir: &Path) -> Result<String> {
+    expand(xml, base_dir, &mut Vec::new())
+}
+
+fn expand(xml: &str, base_dir: &Path, including: &mut Vec<PathBuf>) -> Result<String> {
+    let mut out = String::with_capacity(xml.len());
+    // In-scope namespace bindings (prefix -> URI), one frame per open element
+    let mut scopes: Vec<HashMap<String, String>> = Vec::new();
+
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = if rest.starts_with("<!DOCTYPE") {
+            doctype_end(rest)?
+        } else {
+            markup_end(rest)?
+        };
+        let markup = &rest[..end];
+        rest = &rest[end..];
+
+        if markup.starts_with("<!") || markup.starts_with("<?") {
+            out.push_str(markup);
+        } else if markup.starts_with("</") {
+            out.push_str(markup);
+            scopes.pop();
+        } else {
+            let empty = markup.ends_with("/>");
+            let inner = markup[1..markup.len() - if empty { 2 } else { 1 }].trim();
+            let (name, attributes) = parse_start_tag(inner)?;
+            scopes.push(namespace_declarations(&attributes));
+
+            let (prefix, local) = name.split_once(':').unwrap_or(("", name.as_str()));
+            let namespace = scopes.iter().rev().find_map(|scope| scope.get(prefix));
+            if local != "include" || namespace.map(String::as_str) != Some(XINCLUDE_NS) {
+                out.push_str(markup);
+                if empty {
+                    scopes.pop();
+                }
+                continue;
+            }
+
+            scopes.pop();
+            if !empty {
+                // Drop the include's children (only xi:fallback is allowed there)
+                rest = &rest[element_content_end(rest, &name)?..];
+            }
+            let attribute = |name: &str| {
+                attributes.iter().find(|(attr, _)| attr == name).map(|(_, value)| value.as_str())
+            };
+            if attribute("xpointer").is_some() {
+                return Err(Error::FeatureNotSupported("XInclude xpointer".to_string()));
+            }
+            let href = attribute("href")
+                .ok_or_else(|| Error::ParseError("xi:include without href".to_string()))?;
+            out.push_str(&include(href, attribute("parse"), base_dir, including)?);
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Load the resource an `xi:include` refers to, ready to splice in
+fn include(
+    href: &str,
+    parse: Option<&str>,
+    base_dir: &Path,
+    including: &mut Vec<PathBuf>,
+) -> Result<String> {
+    let path = base_dir.join(href);
+    let content = fs::read_to_string(&path)
+        .map_err(|e| Error::ParseError(format!("XInclude: cannot read {}: {}", path.display(), e)))?;
+
+    match parse.unwrap_or("xml") {
+        "text" => Ok(escape(&content)),
+        "xml" => {
+            if including.contains(&path) {
+                return Err(Error::ParseError(format!(
+                    "XInclude: inclusion loop at {}",
+                    path.display()
+                )));
+            }
+            including.push(path.clone());
+            let expanded = expand(
+                without_prolog(&content)?,
+                path.parent().unwrap_or(Path::new(".")),
+                including,
+            );
+            including.pop();
+            expanded
+        }
+        other => Err(Error::ParseError(format!("XInclude: unknown parse=\"{}\"", other))),
+    }
+}
+
+/// Strip the XML declaration and DOCTYPE from an included document
+fn without_prolog(xml: &str) -> Result<&str> {
+    let mut rest = xml.trim_start_matches('\u{feff}').trim_start();
+    if rest.starts_with("<?xml ") || rest.starts_with("<?xml?") {
+        rest = rest[markup_end(rest)?..].trim_start();
+    }
+    if rest.starts_with("<!DOCTYPE") {
+        rest = rest[doctype_end(rest)?..].trim_start();
+    }
+    Ok(rest.trim_end())
+}
+
+/// Find the end (exclusive) of the content and end tag of element `name`,
+/// whose start tag has just been consumed from `xml`
+fn element_content_end(xml: &str, name: &str) -> Result<usize> {
+    let mut depth = 1usize;
+    let mut pos = 0;
+    while let Some(start) = xml[pos..].find('<') {
+        let at = pos + start;
+        let end = at + markup_end(&xml[at..])?;
+        let markup = &xml[at..end];
+        if markup.starts_with("</") {
+            depth -= 1;
+            if depth == 0 {
+                return Ok(end);
+            }
+        } else if !markup.starts_with("<!") && !markup.starts_with("<?") && !markup.ends_with("/>") {
+            depth += 1;
+        }
+        pos = end;
+    }
+    Err(Error::ParseError(format!("unterminated <{}>", name)))
+}
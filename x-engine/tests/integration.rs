@@ -5,7 +5,8 @@
 use x_engine::engine_xee::XeeEngine;
 use x_engine::engine_xrust::XrustEngine;
 use x_engine::engine_xust::XustEngine;
-use x_engine::traits::{QueryResult, XPathEngine, XQueryEngine, XmlParser, XsltEngine};
+use x_engine::traits::{QueryResult, XPathEngine, XQueryEngine, XmlParser, XsdValidator, XsltEngine};
+use x_engine::ResultItem;
 
 const SIMPLE_XML: &str = r#"<?xml version="1.0"?>
 <root>
@@ -50,6 +51,87 @@ fn xee_xpath_select_nodes() {
     assert_eq!(result.count(), 3, "Should find 3 item elements");
 }
 
+#[test]
+fn xee_xpath_qname_is_structured() {
+    let mut engine = XeeEngine::new();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let result = engine.evaluate_xpath(&doc, "fn:QName('http://x', 'p:a')").unwrap();
+    match result.items().into_iter().next() {
+        Some(ResultItem::QName { prefix, local, uri }) => {
+            assert_eq!(prefix.as_deref(), Some("p"));
+            assert_eq!(local, "a");
+            assert_eq!(uri.as_deref(), Some("http://x"));
+        }
+        other => panic!("Expected a QName, got {:?}", other),
+    }
+    assert_eq!(result.to_string(), "p:a");
+}
+
+#[test]
+fn xee_xpath_temporal_lexical_forms() {
+    let mut engine = XeeEngine::new();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+
+    let date = engine.evaluate_xpath(&doc, "xs:date('2020-01-01')").unwrap();
+    assert!(matches!(date.items().first(), Some(ResultItem::Date(d)) if d == "2020-01-01"));
+
+    let date_time = engine.evaluate_xpath(&doc, "xs:dateTime('2020-01-01T00:00:00Z')").unwrap();
+    assert_eq!(date_time.to_string(), "2020-01-01T00:00:00Z");
+
+    let duration = engine.evaluate_xpath(&doc, "xs:yearMonthDuration('P1Y')").unwrap();
+    assert!(matches!(duration.items().first(), Some(ResultItem::Duration(d)) if d == "P1Y"));
+    assert_eq!(engine.evaluate_xpath(&doc, "xs:duration('P1Y2M')").unwrap().to_string(), "P1Y2M");
+}
+
+#[test]
+fn xee_count_only_query_skips_string_building() {
+    let xml = format!("<root>{}</root>", "<item/>".repeat(10_000));
+    let mut engine = XeeEngine::new();
+    let doc = engine.parse(&xml).unwrap();
+    let result = engine.evaluate_xpath(&doc, "//item").unwrap();
+    assert_eq!(result.count(), 10_000);
+    assert!(!result.has_string_repr(), "count() should not build the joined string");
+
+    assert_eq!(result.to_string().lines().count(), 10_000);
+    assert!(result.has_string_repr());
+}
+
+#[test]
+fn xee_xpath_node_namespace() {
+    let mut engine = XeeEngine::new();
+    let doc = engine
+        .parse(r#"<root xmlns:a="urn:a" xmlns:b="urn:b"><a:item/><b:item/></root>"#)
+        .unwrap();
+    let result = engine.evaluate_xpath(&doc, "/root/*[2]").unwrap();
+    match result.items().first() {
+        Some(ResultItem::Node(info)) => {
+            assert_eq!(info.name.as_deref(), Some("item"));
+            assert_eq!(info.namespace_uri.as_deref(), Some("urn:b"));
+            assert_eq!(info.prefix.as_deref(), Some("b"));
+        }
+        other => panic!("Expected a node result, got {:?}", other),
+    }
+
+    let root = engine.evaluate_xpath(&doc, "/root").unwrap();
+    match root.items().first() {
+        Some(ResultItem::Node(info)) => assert!(info.namespace_uri.is_none()),
+        other => panic!("Expected a node result, got {:?}", other),
+    }
+}
+
+#[test]
+fn xee_xpath_node_path() {
+    let mut engine = XeeEngine::new();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let result = engine.evaluate_xpath(&doc, "//item[2]").unwrap();
+    match result.items().first() {
+        Some(ResultItem::Node(info)) => {
+            assert_eq!(info.path().as_deref(), Some("/root[1]/item[2]"));
+        }
+        other => panic!("Expected a node result, got {:?}", other),
+    }
+}
+
 // ============== XrustEngine Tests ==============
 
 #[test]
@@ -91,6 +173,23 @@ fn xrust_xslt_identity() {
     assert!(result.is_ok(), "XrustEngine should handle identity XSLT");
 }
 
+#[test]
+fn xrust_xpath_node_namespace() {
+    let mut engine = XrustEngine::new();
+    let doc = engine
+        .parse(r#"<root xmlns:a="urn:a" xmlns:b="urn:b"><a:item/><b:item/></root>"#)
+        .unwrap();
+    let result = engine.evaluate_xpath(&doc, "/root/*[2]").unwrap();
+    match result.items().first() {
+        Some(ResultItem::Node(info)) => {
+            assert_eq!(info.name.as_deref(), Some("item"));
+            assert_eq!(info.namespace_uri.as_deref(), Some("urn:b"));
+            assert_eq!(info.prefix.as_deref(), Some("b"));
+        }
+        other => panic!("Expected a node result, got {:?}", other),
+    }
+}
+
 // ============== XustEngine Tests ==============
 
 #[test]
@@ -117,6 +216,27 @@ fn xust_xquery_flwor() {
     assert_eq!(result.count(), 3, "XQuery FLWOR should find 3 ids");
 }
 
+#[test]
+fn xust_xquery_typed_atomics() {
+    let mut engine = XustEngine::new();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let query = "(count(//item), 2.5, true(), QName('http://example.com/ns', 'p:local'), 'text')";
+    let result = engine.execute_xquery(&doc, query).unwrap();
+    let items = result.items();
+    assert!(matches!(items[0], ResultItem::Integer(3)), "{:?}", items[0]);
+    assert!(matches!(items[1], ResultItem::Double(d) if d == 2.5), "{:?}", items[1]);
+    assert!(matches!(items[2], ResultItem::Boolean(true)), "{:?}", items[2]);
+    match &items[3] {
+        ResultItem::QName { prefix, local, uri } => {
+            assert_eq!(prefix.as_deref(), Some("p"));
+            assert_eq!(local, "local");
+            assert_eq!(uri.as_deref(), Some("http://example.com/ns"));
+        }
+        other => panic!("Expected a QName, got {:?}", other),
+    }
+    assert!(matches!(&items[4], ResultItem::String(s) if s == "text"), "{:?}", items[4]);
+}
+
 #[test]
 fn xust_xpath_select_nodes() {
     let mut engine = XustEngine::new();
@@ -125,6 +245,45 @@ fn xust_xpath_select_nodes() {
     assert_eq!(result.count(), 3, "Should find 3 item elements");
 }
 
+#[test]
+fn xust_xpath_node_path() {
+    let mut engine = XustEngine::new();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let result = engine.evaluate_xpath(&doc, "//item[2]").unwrap();
+    match result.items().first() {
+        Some(ResultItem::Node(info)) => {
+            assert_eq!(info.path().as_deref(), Some("/root[1]/item[2]"));
+        }
+        other => panic!("Expected a node result, got {:?}", other),
+    }
+}
+
+#[test]
+fn xust_xpath_paths_of_siblings() {
+    let mut engine = XustEngine::new();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let result = engine.evaluate_xpath(&doc, "//item/text() | //item/@id").unwrap();
+    let paths: Vec<_> = result
+        .items()
+        .iter()
+        .map(|item| match item {
+            ResultItem::Node(info) => info.path().unwrap(),
+            other => panic!("Expected a node result, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "/root[1]/item[1]/@id",
+            "/root[1]/item[1]/text()[1]",
+            "/root[1]/item[2]/@id",
+            "/root[1]/item[2]/text()[1]",
+            "/root[1]/item[3]/@id",
+            "/root[1]/item[3]/text()[1]",
+        ]
+    );
+}
+
 // ============== Error Handling Tests ==============
 
 #[test]
@@ -172,6 +331,29 @@ fn xust_invalid_xpath() {
     assert!(result.is_err(), "Should fail on invalid XPath");
 }
 
+#[test]
+fn xee_error_code_is_structured() {
+    let mut engine = XEngine::xee();
+    let doc = engine.parse("<root/>").unwrap();
+    let error = engine.xpath(&doc, "1 idiv 0").unwrap_err();
+    assert!(
+        matches!(&error, x_engine::Error::XPathError { code: Some(code), .. } if code == "FOAR0001"),
+        "{:?}",
+        error
+    );
+    assert_eq!(error.code(), Some("FOAR0001"));
+
+    // The QT3 error assertion compares codes on xee
+    let suite = write_qt3_suite(&[
+        ("idiv-001", "1 idiv 0", "<error code=\"FOAR0001\"/>"),
+        ("idiv-002", "1 idiv 0", "<error code=\"XPTY0004\"/>"),
+        ("idiv-003", "1 idiv 0", "<error code=\"*\"/>"),
+    ]);
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    let outcomes: Vec<bool> = results.iter().map(|r| r.outcome.is_pass()).collect();
+    assert_eq!(outcomes, vec![true, false, true], "{:?}", results);
+}
+
 // ============== Unsupported Feature Tests ==============
 
 #[test]
@@ -198,6 +380,20 @@ fn xust_xslt_unsupported() {
     assert!(result.is_err(), "XustEngine should not support XSLT");
 }
 
+#[test]
+fn xrust_xpath2_syntax_unsupported() {
+    let mut engine = XrustEngine::new();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let result = engine.evaluate_xpath(&doc, "if (true()) then 1 else 2");
+    match result {
+        Err(x_engine::Error::FeatureNotSupported(feature)) => assert!(feature.contains("XPath 2.0")),
+        _ => panic!("XrustEngine should reject XPath 2.0 conditionals"),
+    }
+    assert!(engine.evaluate_xpath(&doc, "for $i in //item return $i").is_err());
+    let count = engine.evaluate_xpath(&doc, "count(//item[@id != 'if'])").unwrap();
+    assert_eq!(count.to_string(), "3");
+}
+
 // ============== Unified XEngine API Tests ==============
 
 use x_engine::{Backend, XEngine};
@@ -242,6 +438,456 @@ fn unified_with_backend() {
     assert!(!result.is_empty());
 }
 
+#[test]
+fn unified_first_string() {
+    for mut engine in [XEngine::xee(), XEngine::xust()] {
+        let backend = engine.backend();
+        let doc = engine.parse(SIMPLE_XML).unwrap();
+        let result = engine.xpath(&doc, "//item").unwrap();
+        assert_eq!(result.first_string().as_deref(), Some("First"), "{:?}", backend);
+        assert!(matches!(result.first(), Some(ResultItem::Node(_))));
+
+        let empty = engine.xpath(&doc, "//missing").unwrap();
+        assert!(empty.first().is_none());
+        assert_eq!(empty.first_string(), None);
+    }
+
+    // Markup characters in the text survive the round trip through the node's XML
+    let mut engine = XEngine::xust();
+    let doc = engine.parse("<r><a>1 &lt; 2 &amp; 3</a></r>").unwrap();
+    let result = engine.xpath(&doc, "/r/a").unwrap();
+    assert_eq!(result.first_string().as_deref(), Some("1 < 2 & 3"));
+    assert!(result.to_string().starts_with("<a>"), "{}", result.to_string());
+}
+
+#[test]
+fn unified_xpath_as_usize_and_string() {
+    let mut engine = XEngine::xee();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    assert_eq!(engine.xpath_as_usize(&doc, "count(//item)").unwrap(), 3);
+    assert_eq!(engine.xpath_as_string(&doc, "string(//item[2]/@id)").unwrap(), "2");
+    assert_eq!(engine.xpath_as_string(&doc, "//missing").unwrap(), "");
+
+    // A value that isn't a count is a type error, distinct from engine errors
+    let not_a_number = engine.xpath_as_usize(&doc, "string(//item[1])").unwrap_err();
+    assert_eq!(not_a_number.classify(), x_engine::error::ErrorClass::Type, "{}", not_a_number);
+    let engine_error = engine.xpath_as_usize(&doc, "count(//item").unwrap_err();
+    assert_ne!(engine_error.classify(), x_engine::error::ErrorClass::Type, "{}", engine_error);
+}
+
+#[test]
+fn unified_atomic_context_item() {
+    for mut engine in [XEngine::xee(), XEngine::xust()] {
+        let result = engine
+            .xpath_with_context_item(ResultItem::Integer(21), ". * 2")
+            .unwrap();
+        assert_eq!(result.first_string().as_deref(), Some("42"));
+        // The context item isn't a node, so there is no root to go to
+        assert!(engine.xpath_with_context_item(ResultItem::Integer(21), "/").is_err());
+    }
+    let error = XEngine::xee().xpath_with_context_item(ResultItem::Integer(21), "/").unwrap_err();
+    assert!(
+        matches!(&error, x_engine::Error::XPathError { code: Some(code), .. } if code == "XPDY0050"),
+        "{}",
+        error
+    );
+
+    let mut engine = XEngine::xee();
+    let result = engine
+        .xpath_with_context_item(ResultItem::String("it's \"quoted\"".into()), "string-length(.)")
+        .unwrap();
+    assert_eq!(result.first_string().as_deref(), Some("13"));
+    assert!(matches!(
+        XEngine::xrust().xpath_with_context_item(ResultItem::Integer(1), ". + 1"),
+        Err(x_engine::Error::Unsupported)
+    ));
+}
+
+#[test]
+fn unified_parse_with_diagnostics() {
+    let mut engine = XEngine::xee();
+    let (doc, warnings) = engine
+        .parse_with_diagnostics(r#"<a xmlns:x="urn:x"><x:b xmlns:x="urn:x">text</x:b></a>"#)
+        .unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("Redundant declaration of prefix 'x'"));
+    assert_eq!(engine.xpath(&doc, "string(/a)").unwrap().first_string().as_deref(), Some("text"));
+
+    let (_, warnings) = engine.parse_with_diagnostics(SIMPLE_XML).unwrap();
+    assert!(warnings.is_empty());
+    assert!(engine.parse_with_diagnostics("<unclosed>").is_err());
+}
+
+#[test]
+fn unified_evaluate_selects_language() {
+    let mut engine = XEngine::xust();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let result = engine
+        .evaluate(&doc, "for $i in //item where $i/@id = '2' return string($i)")
+        .unwrap();
+    assert_eq!(result.first_string().as_deref(), Some("Second"));
+
+    let mut engine = XEngine::xee();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let result = engine.evaluate(&doc, "count(/root/item)").unwrap();
+    assert_eq!(result.first_string().as_deref(), Some("3"));
+}
+
+#[test]
+fn unified_as_f64_vec() {
+    let mut engine = XEngine::xee();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let result = engine.xpath(&doc, "(1, 2.5, 3)").unwrap();
+    assert_eq!(result.as_f64_vec(false), vec![1.0, 2.5, 3.0]);
+
+    let mixed = engine.xpath(&doc, "(1, 'two', '3')").unwrap();
+    assert_eq!(mixed.as_f64_vec(true), vec![1.0, 3.0]);
+    let with_nan = mixed.as_f64_vec(false);
+    assert_eq!(with_nan.len(), 3);
+    assert!(with_nan[1].is_nan());
+}
+
+#[test]
+fn unified_register_collation() {
+    use std::cmp::Ordering;
+    use x_engine::collation::{collator_for_uri, CODEPOINT_COLLATION};
+
+    const REVERSE: &str = "http://example.com/collation/reverse";
+    assert!(collator_for_uri(REVERSE).is_none());
+    XEngine::register_collation(REVERSE, Box::new(|a: &str, b: &str| b.cmp(a)));
+
+    let reverse = collator_for_uri(REVERSE).unwrap();
+    assert_eq!(reverse.compare("apple", "banana"), Ordering::Greater);
+    assert!(reverse.equals("same", "same"));
+
+    let codepoint = collator_for_uri(CODEPOINT_COLLATION).unwrap();
+    assert_eq!(codepoint.compare("apple", "banana"), Ordering::Less);
+}
+
+#[test]
+fn unified_trace_hook_receives_expression() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let sink = lines.clone();
+
+    let mut engine = XEngine::xee();
+    engine.set_trace(Some(Box::new(move |line| sink.borrow_mut().push(line.to_string()))));
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    engine.xpath(&doc, "count(//item)").unwrap();
+
+    let lines = lines.borrow();
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains("count(//item)"), "{}", lines[0]);
+    assert!(lines[0].contains("Xee"));
+    assert!(lines[0].ends_with("=> 3"));
+}
+
+#[test]
+fn unified_attributes_of_node_result() {
+    for mut engine in [XEngine::xee(), XEngine::xrust(), XEngine::xust()] {
+        let doc = engine.parse(SIMPLE_XML).unwrap();
+        let result = engine.xpath(&doc, "//item[1]").unwrap();
+        assert_eq!(result.attributes_of(0), vec![("id".to_string(), "1".to_string())]);
+        assert!(result.attributes_of(1).is_empty());
+
+        let text = engine.xpath(&doc, "//item[1]/text()").unwrap();
+        assert!(text.attributes_of(0).is_empty());
+    }
+}
+
+#[test]
+fn unified_parse_with_xinclude() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("fragment.xml"),
+        r#"<?xml version="1.0"?><chapter id="c1"><title>Included</title></chapter>"#,
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("note.txt"), "a < b").unwrap();
+    let xml = r#"<book xmlns:xi="http://www.w3.org/2001/XInclude">
+  <xi:include href="fragment.xml"/>
+  <note><xi:include href="note.txt" parse="text"/></note>
+</book>"#;
+
+    let mut engine = XEngine::xee();
+    let doc = engine.parse_with_xinclude(xml, dir.path()).unwrap();
+    let title = engine.xpath(&doc, "string(/book/chapter[@id='c1']/title)").unwrap();
+    assert_eq!(title.to_string(), "Included");
+    let note = engine.xpath(&doc, "string(/book/note)").unwrap();
+    assert_eq!(note.to_string(), "a < b");
+
+    let plain = engine.parse(xml).unwrap();
+    let count = engine.xpath(&plain, "count(//chapter)").unwrap();
+    assert_eq!(count.to_string(), "0", "Plain parse leaves includes unexpanded");
+}
+
+#[test]
+fn unified_xquery_with_docs_joins_documents() {
+    let dir = tempfile::tempdir().unwrap();
+    let orders = dir.path().join("orders.xml");
+    let customers = dir.path().join("customers.xml");
+    std::fs::write(
+        &orders,
+        r#"<orders><order customer="c1"/><order customer="c2"/><order customer="c9"/></orders>"#,
+    )
+    .unwrap();
+    std::fs::write(&customers, r#"<customers><customer id="c1"/><customer id="c2"/></customers>"#)
+        .unwrap();
+    let docs = [
+        ("orders.xml".to_string(), orders.as_path()),
+        ("customers.xml".to_string(), customers.as_path()),
+    ];
+    let query = r#"count(
+  for $o in doc('orders.xml')//order, $c in doc("customers.xml")//customer
+  return $o[@customer = $c/@id]
+)"#;
+
+    for mut engine in [XEngine::xee(), XEngine::xust()] {
+        engine.set_static_base_uri(Some("file:///data/"));
+        let result = engine.xquery_with_docs(&docs, query).unwrap();
+        assert_eq!(result.to_string(), "2", "{:?}", engine.backend());
+        // Repeated calls return the same document
+        let same = engine.xquery_with_docs(&docs, "doc('orders.xml') is doc('orders.xml')");
+        assert_eq!(same.unwrap().to_string(), "true", "{:?}", engine.backend());
+        let absolute =
+            engine.xquery_with_docs(&docs, "count(doc('file:///data/customers.xml')//*)");
+        assert_eq!(absolute.unwrap().to_string(), "3", "{:?}", engine.backend());
+    }
+
+    let mut engine = XEngine::xrust();
+    let result = engine.xquery_with_docs(&docs, query);
+    assert!(matches!(result, Err(x_engine::Error::Unsupported)));
+}
+
+#[test]
+fn unified_query_cache_hits() {
+    let mut engine = XEngine::xee();
+    engine.enable_query_cache(8);
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+
+    let first = engine.xpath(&doc, "count(//item)").unwrap();
+    assert_eq!(engine.query_cache_hits(), 0);
+    let second = engine.xpath(&doc, "count(//item)").unwrap();
+    assert_eq!(engine.query_cache_hits(), 1);
+    assert_eq!(first.to_string(), second.to_string());
+
+    // A new parse invalidates cached results, even for the old document
+    let other = engine.parse("<root><item/></root>").unwrap();
+    engine.xpath(&doc, "count(//item)").unwrap();
+    assert_eq!(engine.query_cache_hits(), 1);
+    let other_count = engine.xpath(&other, "count(//item)").unwrap();
+    assert_eq!(other_count.to_string(), "1");
+    assert_eq!(engine.query_cache_hits(), 1, "Results are keyed by document");
+
+    engine.disable_query_cache();
+    engine.xpath(&doc, "count(//item)").unwrap();
+    assert_eq!(engine.query_cache_hits(), 0);
+}
+
+#[test]
+fn unified_unparsed_text() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("data.txt"), "alpha\nbeta\n").unwrap();
+
+    let mut engine = XEngine::xee();
+    let base_uri = format!("file://{}/", dir.path().display());
+    engine.set_static_base_uri(Some(&base_uri));
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let text = engine.xpath(&doc, "unparsed-text('data.txt')").unwrap();
+    assert_eq!(text.to_string(), "alpha\nbeta\n");
+    let lines = engine.xpath(&doc, "count(unparsed-text-lines('data.txt'))").unwrap();
+    assert_eq!(lines.to_string(), "2");
+    let missing = engine.xpath(&doc, "unparsed-text-available('missing.txt')").unwrap();
+    assert_eq!(missing.to_string(), "false");
+    assert!(engine.xpath(&doc, "unparsed-text('missing.txt')").is_err());
+    // A missing resource only raises if its branch is taken
+    let untaken = "if (false()) then unparsed-text('missing.txt') else 1";
+    assert_eq!(engine.xpath(&doc, untaken).unwrap().to_string(), "1");
+
+    engine.set_text_resolver(Box::new(|href| Ok(format!("resolved {}", href))));
+    let custom = engine.xpath(&doc, "unparsed-text('anything')").unwrap();
+    assert_eq!(custom.to_string(), "resolved anything");
+
+    // Results read through an earlier resolver aren't served from the cache
+    engine.enable_query_cache(8);
+    engine.xpath(&doc, "unparsed-text('anything')").unwrap();
+    engine.set_text_resolver(Box::new(|href| Ok(format!("other {}", href))));
+    let other = engine.xpath(&doc, "unparsed-text('anything')").unwrap();
+    assert_eq!(other.to_string(), "other anything");
+}
+
+#[test]
+fn unified_compare_expression() {
+    let results = x_engine::compare_expression(
+        "count(//item)",
+        SIMPLE_XML,
+        &[Backend::Xee, Backend::Xrust, Backend::Xust],
+    );
+    assert_eq!(results.len(), 3);
+    for (backend, result) in results {
+        assert_eq!(result.unwrap(), "3", "{:?} disagrees", backend);
+    }
+}
+
+#[test]
+fn unified_roundtrip_preserves_document() {
+    use x_engine::canonical::{xml_equivalent, CanonicalOptions};
+
+    let xml = "<a b='c'><d/></a>";
+    let results = x_engine::roundtrip_diff(xml, &[Backend::Xee, Backend::Xrust, Backend::Xust]);
+    assert_eq!(results.len(), 3);
+    for (backend, result) in results {
+        let output = result.unwrap_or_else(|e| panic!("{:?} failed to round-trip: {}", backend, e));
+        assert!(
+            xml_equivalent(xml, &output, &CanonicalOptions::default()),
+            "{:?} changed the document: {}",
+            backend,
+            output
+        );
+    }
+    assert!(XEngine::xee().roundtrip("<a>").is_err());
+}
+
+#[test]
+fn unified_reformat_indents_children() {
+    for backend in [Backend::Xee, Backend::Xrust, Backend::Xust] {
+        let mut engine = XEngine::with_backend(backend);
+        let output = engine.reformat("<a><b>x</b></a>", 2).unwrap();
+        assert!(output.trim().ends_with("<a>\n  <b>x</b>\n</a>"), "{:?}: {}", backend, output);
+    }
+    let output = XEngine::xrust().reformat("<a><p>one <i>two</i></p></a>", 4).unwrap();
+    assert!(output.trim().ends_with("<a>\n    <p>one <i>two</i></p>\n</a>"), "{}", output);
+}
+
+#[test]
+fn shared_engine_serves_several_threads() {
+    use x_engine::SharedEngine;
+
+    let engine = SharedEngine::new(Backend::Xee);
+    let handles: Vec<_> = (1..=2)
+        .map(|n| {
+            let engine = engine.clone();
+            std::thread::spawn(move || {
+                let doc = engine.parse(SIMPLE_XML).unwrap();
+                let result = engine.xpath(&doc, &format!("string(//item[@id='{}'])", n)).unwrap();
+                result.to_string()
+            })
+        })
+        .collect();
+    let values: Vec<String> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!(values, vec!["First", "Second"]);
+
+    let other = SharedEngine::new(Backend::Xee);
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    assert!(other.xpath(&doc, "count(//item)").is_err(), "Documents belong to their engine");
+}
+
+#[test]
+fn engine_pool_services_queued_jobs() {
+    use std::sync::Arc;
+    use x_engine::EnginePool;
+
+    let pool = Arc::new(EnginePool::new(XEngine::xee, 4));
+    assert_eq!(pool.size(), 4);
+    let handles: Vec<_> = (0..100)
+        .map(|i| {
+            let pool = Arc::clone(&pool);
+            std::thread::spawn(move || {
+                pool.with(move |engine| {
+                    let doc = engine.parse(&format!("<n>{}</n>", i))?;
+                    engine.xpath(&doc, "concat(/n, '-', count(//n))").map(|r| r.to_string())
+                })
+            })
+        })
+        .collect();
+    for (i, handle) in handles.into_iter().enumerate() {
+        let value = handle.join().unwrap().unwrap().unwrap();
+        assert_eq!(value, format!("{}-1", i));
+    }
+
+    assert!(pool.with(|_| -> u32 { panic!("job failure") }).is_err());
+    assert_eq!(pool.with(|engine| engine.backend()).unwrap(), Backend::Xee);
+}
+
+#[test]
+fn unified_type_histogram() {
+    let mut engine = XEngine::xee();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let result = engine.xpath(&doc, "(//item[1], //item[2], 'three')").unwrap();
+    let histogram = result.type_histogram();
+    assert_eq!(histogram.len(), 2, "{:?}", histogram);
+    assert_eq!(histogram["element"], 2);
+    assert_eq!(histogram["string"], 1);
+
+    // Failure messages name the kinds of items the query returned
+    let suite = write_qt3_suite(&[("kinds-001", "(1, 'a')", "<assert-count>3</assert-count>")]);
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    let message = results[0].outcome.message().unwrap();
+    assert!(message.contains("[integer=1, string=1]"), "{}", message);
+}
+
+#[test]
+fn unified_effective_boolean() {
+    for mut engine in [XEngine::xee(), XEngine::xust()] {
+        let backend = engine.backend();
+        let doc = engine.parse(SIMPLE_XML).unwrap();
+        let mut ebv = |expr: &str| engine.xpath(&doc, expr).unwrap().effective_boolean();
+
+        assert!(!ebv("()").unwrap(), "{:?}", backend);
+        assert!(!ebv("0").unwrap(), "{:?}", backend);
+        assert!(ebv("'x'").unwrap(), "{:?}", backend);
+        assert!(!ebv("''").unwrap(), "{:?}", backend);
+        assert!(ebv("'false'").unwrap(), "A non-empty string is true: {:?}", backend);
+        assert!(ebv("//item").unwrap(), "{:?}", backend);
+        assert!(!ebv("false()").unwrap(), "{:?}", backend);
+        assert!(ebv("(1, 2)").is_err(), "{:?}", backend);
+    }
+}
+
+#[test]
+fn unified_xquery_update_not_supported() {
+    use x_engine::Capability;
+
+    let mut engine = XEngine::xust();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    match engine.xquery(&doc, "insert node <item id=\"4\"/> into /root") {
+        Err(e @ x_engine::Error::FeatureNotSupported(_)) => {
+            assert_eq!(e.to_string(), "XQuery Update is not supported by this engine");
+        }
+        other => panic!("Expected FeatureNotSupported, got {:?}", other.map(|r| r.to_string())),
+    }
+    // Element names and constructor text that happen to be update keywords
+    // are not updates
+    assert!(engine.xquery(&doc, "count(/root/insert/node)").is_ok());
+    let text = engine.xquery(&doc, "string(<a>insert node</a>)").unwrap();
+    assert_eq!(text.to_string(), "insert node");
+    // A syntax error stays a syntax error
+    let error = engine.xquery(&doc, "count(/root").unwrap_err();
+    assert!(matches!(error, x_engine::Error::XQueryError(_)), "{:?}", error);
+
+    for backend in [Backend::Xee, Backend::Xrust, Backend::Xust] {
+        assert!(!XEngine::with_backend(backend).supports(Capability::XQueryUpdate));
+    }
+    assert!(engine.supports(Capability::XQuery));
+}
+
+#[test]
+fn unified_supported_features() {
+    let xust = XEngine::xust().supported_features();
+    assert!(xust.contains(&"xquery-3.1".to_string()));
+    assert!(xust.contains(&"maps-arrays".to_string()));
+
+    let xee = XEngine::xee().supported_features();
+    assert!(!xee.contains(&"xquery-3.1".to_string()));
+    assert!(xee.contains(&"xslt-3.0".to_string()));
+
+    let xrust = XEngine::xrust().supported_features();
+    assert!(xrust.contains(&"xpath-1.0".to_string()));
+    assert!(!xrust.contains(&"higher-order-functions".to_string()));
+}
+
 #[test]
 fn unified_xslt_with_xrust() {
     let mut engine = XEngine::xrust();
@@ -258,6 +904,147 @@ fn unified_xslt_with_xrust() {
     assert!(result.is_ok(), "Unified xrust should support XSLT");
 }
 
+#[test]
+fn unified_xslt_html_output_method() {
+    let mut engine = XEngine::xrust();
+    let doc = engine.parse("<root>Hello</root>").unwrap();
+    let stylesheet = r#"<?xml version="1.0"?>
+<xsl:stylesheet version="1.0" xmlns:xsl="http://www.w3.org/1999/XSL/Transform">
+    <xsl:output method="html"/>
+    <xsl:template match="/">
+        <p><xsl:value-of select="root"/><br/></p>
+    </xsl:template>
+</xsl:stylesheet>"#;
+    let output = engine.xslt_to_string(&doc, stylesheet).unwrap();
+    assert!(output.contains("<br>"), "HTML output should not close void elements: {}", output);
+    assert!(!output.contains("<br/>"), "HTML output should not close void elements: {}", output);
+}
+
+#[test]
+fn unified_xslt_text_output_method() {
+    let mut engine = XEngine::xrust();
+    let doc = engine.parse("<root>Fish &amp; Chips</root>").unwrap();
+    let stylesheet = r#"<?xml version="1.0"?>
+<xsl:stylesheet version="1.0" xmlns:xsl="http://www.w3.org/1999/XSL/Transform">
+    <xsl:output method="text"/>
+    <xsl:template match="/">
+        <out><xsl:value-of select="root"/></out>
+    </xsl:template>
+</xsl:stylesheet>"#;
+    let output = engine.xslt_to_string(&doc, stylesheet).unwrap();
+    assert!(!output.contains('<'), "Text output should contain no markup: {}", output);
+    assert_eq!(output.trim(), "Fish & Chips");
+}
+
+#[test]
+fn unified_transform_to_boolean() {
+    let mut engine = XEngine::xrust();
+    let stylesheet = r#"<?xml version="1.0"?>
+<xsl:stylesheet version="1.0" xmlns:xsl="http://www.w3.org/1999/XSL/Transform">
+    <xsl:output method="text"/>
+    <xsl:template match="/">
+        <xsl:choose>
+            <xsl:when test="root/@flag = 'on'">true</xsl:when>
+            <xsl:otherwise>false</xsl:otherwise>
+        </xsl:choose>
+    </xsl:template>
+</xsl:stylesheet>"#;
+    let on = engine.parse(r#"<root flag="on"/>"#).unwrap();
+    assert!(engine.transform_to_boolean(&on, stylesheet).unwrap());
+    let off = engine.parse(r#"<root flag="off"/>"#).unwrap();
+    assert!(!engine.transform_to_boolean(&off, stylesheet).unwrap());
+}
+
+#[test]
+fn unified_transform_output_override() {
+    use x_engine::serialization::OutputParams;
+
+    let mut engine = XEngine::xrust();
+    let doc = engine.parse("<root><a>1</a><b>2</b></root>").unwrap();
+    let stylesheet = r#"<?xml version="1.0"?>
+<xsl:stylesheet version="1.0" xmlns:xsl="http://www.w3.org/1999/XSL/Transform">
+    <xsl:output method="xml" indent="yes"/>
+    <xsl:template match="/">
+        <out><xsl:copy-of select="root/*"/></out>
+    </xsl:template>
+</xsl:stylesheet>"#;
+
+    let declared = engine
+        .transform_to_string_with_output_override(&doc, stylesheet, &OutputParams::default())
+        .unwrap();
+    assert!(declared.contains("<out>\n  <a>1</a>\n  <b>2</b>\n</out>"), "{}", declared);
+
+    let flat = OutputParams { indent: Some(false), ..Default::default() };
+    let output = engine.transform_to_string_with_output_override(&doc, stylesheet, &flat).unwrap();
+    assert!(output.contains("<out><a>1</a><b>2</b></out>"), "{}", output);
+}
+
+#[test]
+fn unified_compiled_stylesheet_is_reused() {
+    let stylesheet = r#"<?xml version="1.0"?>
+<xsl:stylesheet version="1.0" xmlns:xsl="http://www.w3.org/1999/XSL/Transform">
+    <xsl:template match="/">
+        <count><xsl:value-of select="count(//item)"/></count>
+    </xsl:template>
+</xsl:stylesheet>"#;
+    for mut engine in [XEngine::xrust(), XEngine::xee()] {
+        let backend = engine.backend();
+        let docs: Vec<_> = ["<r><item/></r>", "<r><item/><item/></r>", "<r/>"]
+            .iter()
+            .map(|xml| engine.parse(xml).unwrap())
+            .collect();
+
+        let compiled = engine.compile_stylesheet(stylesheet).unwrap();
+        let outputs: Vec<String> =
+            docs.iter().map(|doc| engine.transform_compiled(doc, &compiled).unwrap()).collect();
+        assert_eq!(engine.stylesheet_compilations(), 1, "One compile for three: {:?}", backend);
+
+        for (doc, output) in docs.iter().zip(&outputs) {
+            assert_eq!(*output, engine.transform(doc, stylesheet).unwrap());
+        }
+        assert!(outputs[1].contains("<count>2</count>"), "{:?}: {}", backend, outputs[1]);
+    }
+
+    let mut engine = XEngine::xrust();
+    let compiled = engine.compile_stylesheet(stylesheet).unwrap();
+    let mut other = XEngine::xee();
+    let doc = other.parse("<r/>").unwrap();
+    let result = other.transform_compiled(&doc, &compiled);
+    assert!(
+        matches!(
+            result,
+            Err(x_engine::Error::BackendMismatch { expected: Backend::Xrust, got: Backend::Xee })
+        ),
+        "{:?}",
+        result
+    );
+
+    // Handles belong to one engine instance, and stop working once dropped
+    let mut other = XEngine::xrust();
+    let doc = other.parse("<r/>").unwrap();
+    assert!(other.transform_compiled(&doc, &compiled).is_err());
+    let doc = engine.parse("<r/>").unwrap();
+    assert!(engine.transform_compiled(&doc, &compiled).is_ok());
+    engine.drop_stylesheet(&compiled).unwrap();
+    assert!(engine.transform_compiled(&doc, &compiled).is_err());
+    assert!(engine.drop_stylesheet(&compiled).is_err());
+}
+
+#[test]
+fn output_method_from_stylesheet() {
+    use x_engine::serialization::{apply_output_method, OutputMethod};
+
+    assert_eq!(
+        OutputMethod::from_stylesheet(r#"<xsl:output indent="yes" method='text'/>"#),
+        OutputMethod::Text
+    );
+    assert_eq!(OutputMethod::from_stylesheet("<xsl:stylesheet/>"), OutputMethod::Xml);
+    assert_eq!(
+        apply_output_method("<div><br/><p/></div>", OutputMethod::Html).unwrap(),
+        "<div><br><p></p></div>"
+    );
+}
+
 #[test]
 fn unified_xquery_unsupported_on_xee() {
     let mut engine = XEngine::xee();
@@ -284,10 +1071,1763 @@ fn unified_document_engine_mismatch() {
     // Trying to use xee document with xrust engine should fail
     let result = xrust.xpath(&xee_doc, "//root");
     assert!(result.is_err(), "Should fail when document and engine mismatch");
+    match result {
+        Err(x_engine::Error::BackendMismatch { expected, got }) => {
+            assert_eq!((expected, got), (Backend::Xrust, Backend::Xee));
+        }
+        other => panic!("Expected BackendMismatch, got {:?}", other),
+    }
+    assert_eq!(xee_doc.backend(), Backend::Xee);
+    assert!(matches!(
+        xrust.xslt(&xee_doc, "<xsl:stylesheet/>"),
+        Err(x_engine::Error::BackendMismatch { expected: Backend::Xrust, got: Backend::Xee })
+    ));
 }
 
 #[test]
-fn unified_default_is_xee() {
-    let engine = XEngine::default();
-    assert_eq!(engine.backend(), Backend::Xee);
+fn unified_serialize_top_level_attribute_errors() {
+    let mut engine = XEngine::xee();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let result = engine.xpath(&doc, "//item[1]/@id").unwrap();
+    match result.serialize() {
+        Err(x_engine::Error::SerializationError(msg)) => {
+            assert!(msg.contains("SENR0001"), "Unexpected message: {}", msg);
+        }
+        other => panic!("Expected SENR0001 serialization error, got {:?}", other),
+    }
+}
+
+#[test]
+fn unified_static_base_uri_xee() {
+    let mut engine = XEngine::xee();
+    engine.set_static_base_uri(Some("http://example.com/base/"));
+    let doc = engine.parse("<root/>").unwrap();
+    let result = engine.xpath(&doc, "string(static-base-uri())").unwrap();
+    assert_eq!(result.to_string(), "http://example.com/base/");
+}
+
+#[test]
+fn unified_static_base_uri_xust() {
+    let mut engine = XEngine::xust();
+    engine.set_static_base_uri(Some("http://example.com/base/"));
+    let doc = engine.parse("<root/>").unwrap();
+    let result = engine.xquery(&doc, "string(static-base-uri())").unwrap();
+    assert_eq!(result.to_string(), "http://example.com/base/");
+}
+
+#[test]
+fn unified_current_datetime_is_stable() {
+    let mut engine = XEngine::xee();
+    let doc = engine.parse("<root/>").unwrap();
+    let same = engine.xpath(&doc, "current-dateTime() eq current-dateTime()").unwrap();
+    assert_eq!(same.to_string(), "true");
+
+    let pinned = chrono::DateTime::parse_from_rfc3339("2020-01-02T03:04:05+01:00").unwrap();
+    engine.set_current_datetime(Some(pinned));
+    let now = engine.xpath(&doc, "string(current-dateTime())").unwrap();
+    assert_eq!(now.to_string(), "2020-01-02T03:04:05+01:00");
+    let today = engine.xpath(&doc, "string(fn:current-date())").unwrap();
+    assert_eq!(today.to_string(), "2020-01-02+01:00");
+    let literal = engine.xpath(&doc, "'current-dateTime()'").unwrap();
+    assert_eq!(literal.to_string(), "current-dateTime()", "String literals are left alone");
+}
+
+#[test]
+fn unified_query_cache_skips_running_clock() {
+    let mut engine = XEngine::xee();
+    engine.enable_query_cache(8);
+    let doc = engine.parse("<root/>").unwrap();
+    let expr = "string(current-dateTime())";
+
+    let first = engine.xpath(&doc, expr).unwrap().to_string();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    let second = engine.xpath(&doc, expr).unwrap().to_string();
+    assert_ne!(first, second, "Each evaluation reads the clock");
+    assert_eq!(engine.query_cache_hits(), 0);
+
+    let pinned = chrono::DateTime::parse_from_rfc3339("2020-01-02T03:04:05+01:00").unwrap();
+    engine.set_current_datetime(Some(pinned));
+    engine.xpath(&doc, expr).unwrap();
+    let cached = engine.xpath(&doc, expr).unwrap();
+    assert_eq!(cached.to_string(), "2020-01-02T03:04:05+01:00");
+    assert_eq!(engine.query_cache_hits(), 1, "A pinned dateTime can be cached");
+}
+
+#[test]
+fn unified_default_element_namespace() {
+    let xml = r#"<list xmlns="http://example.com/ns"><item/><item/></list>"#;
+    for backend in [Backend::Xee, Backend::Xust] {
+        let mut engine = XEngine::with_backend(backend);
+        let doc = engine.parse(xml).unwrap();
+        let unbound = engine.xpath(&doc, "count(//item)").unwrap();
+        assert_eq!(unbound.to_string(), "0", "{:?}", backend);
+
+        engine.set_default_element_namespace(Some("http://example.com/ns"));
+        let bound = engine.xpath(&doc, "count(//item)").unwrap();
+        assert_eq!(bound.to_string(), "2", "{:?}", backend);
+
+        engine.set_default_element_namespace(None);
+        let cleared = engine.xpath(&doc, "count(//item)").unwrap();
+        assert_eq!(cleared.to_string(), "0", "{:?}", backend);
+    }
+}
+
+#[test]
+fn unified_implicit_timezone_adjusts_datetimes() {
+    use x_engine::Capability;
+
+    let expr = "string(adjust-dateTime-to-timezone(xs:dateTime('2020-01-01T12:00:00Z')))";
+    for backend in [Backend::Xee, Backend::Xust] {
+        let mut engine = XEngine::with_backend(backend);
+        assert!(engine.supports(Capability::ImplicitTimezone));
+        let doc = engine.parse("<root/>").unwrap();
+
+        engine.set_implicit_timezone(60);
+        let east = engine.xpath(&doc, expr).unwrap();
+        assert_eq!(east.to_string(), "2020-01-01T13:00:00+01:00", "{:?}", backend);
+        let implicit = engine.xpath(&doc, "string(implicit-timezone())").unwrap();
+        assert_eq!(implicit.to_string(), "PT1H", "{:?}", backend);
+
+        engine.set_implicit_timezone(-300);
+        let west = engine.xpath(&doc, expr).unwrap();
+        assert_eq!(west.to_string(), "2020-01-01T07:00:00-05:00", "{:?}", backend);
+        let removed = expr.replace("Z'))", "Z'), ())");
+        let explicit = engine.xpath(&doc, &removed).unwrap();
+        assert_eq!(explicit.to_string(), "2020-01-01T12:00:00", "{:?}", backend);
+    }
+    assert!(!XEngine::xrust().supports(Capability::ImplicitTimezone));
+}
+
+#[test]
+fn unified_implicit_timezone_leaves_xquery_content_alone() {
+    let mut engine = XEngine::xust();
+    engine.set_implicit_timezone(60);
+    let doc = engine.parse("<root/>").unwrap();
+    let result = engine.xquery(&doc, "string(<a>adjust-date-to-timezone(x)</a>)").unwrap();
+    assert_eq!(result.to_string(), "adjust-date-to-timezone(x)");
+}
+
+#[test]
+fn unified_root_element_is_document_element() {
+    let xml = "<!-- header --><catalog><book id=\"1\"/></catalog>";
+    for backend in [Backend::Xee, Backend::Xrust, Backend::Xust] {
+        let mut engine = XEngine::with_backend(backend);
+        let doc = engine.parse(xml).unwrap();
+        let root = doc.root_element().unwrap();
+        assert_eq!(root.node_type, x_engine::NodeType::Element, "{:?}", backend);
+        assert_eq!(root.name.as_deref(), Some("catalog"), "{:?}", backend);
+        assert_eq!(root.namespace_uri, None, "{:?}", backend);
+    }
+
+    for backend in [Backend::Xee, Backend::Xrust] {
+        let mut engine = XEngine::with_backend(backend);
+        let doc = engine.parse(r#"<p:catalog xmlns:p="http://example.com/ns"/>"#).unwrap();
+        let root = doc.root_element().unwrap();
+        assert_eq!(root.name.as_deref(), Some("catalog"), "{:?}", backend);
+        assert_eq!(root.namespace_uri.as_deref(), Some("http://example.com/ns"), "{:?}", backend);
+        assert_eq!(root.prefix.as_deref(), Some("p"), "{:?}", backend);
+    }
+    let doc = XEngine::xee().parse("<catalog/>").unwrap();
+    assert_eq!(doc.root_element().unwrap().path().as_deref(), Some("/catalog[1]"));
+}
+
+#[test]
+fn unified_parse_limits_reject_deep_nesting() {
+    let deep = format!("{}{}", "<a>".repeat(100_000), "</a>".repeat(100_000));
+    for backend in [Backend::Xee, Backend::Xrust, Backend::Xust] {
+        let mut engine = XEngine::with_backend(backend);
+        let limits = x_engine::ParseLimits { max_depth: 1_000, ..Default::default() };
+        engine.set_parse_limits(limits);
+        let Err(x_engine::Error::ParseError(message)) = engine.parse(&deep) else {
+            panic!("{:?}: expected a parse error", backend);
+        };
+        assert!(message.starts_with("limit exceeded"), "{}", message);
+        assert!(message.contains("deeper than 1000"), "{}", message);
+
+        // Documents within the limits still parse
+        let doc = engine.parse("<a><b/><c>text</c></a>").unwrap();
+        assert_eq!(engine.xpath(&doc, "count(//*)").unwrap().to_string(), "3", "{:?}", backend);
+    }
+}
+
+#[test]
+fn parse_limits_cap_entity_expansion() {
+    let entities: String = (1..=5)
+        .map(|n| format!("<!ENTITY e{} \"{}\">", n, format!("&e{};", n - 1).repeat(10)))
+        .collect();
+    let bomb = format!("<!DOCTYPE r [<!ENTITY e0 \"lol\">{}]><r>&e5;</r>", entities);
+    let error = x_engine::ParseLimits::default().check(&bomb).unwrap_err();
+    assert!(error.to_string().contains("entity expansions"), "{}", error);
+    // A `]` inside an entity value doesn't end the internal subset
+    let bomb = bomb.replace("\"lol\"", "\"l]ol\"");
+    assert!(x_engine::ParseLimits::default().check(&bomb).is_err());
+
+    let limits = x_engine::ParseLimits { max_nodes: 3, ..Default::default() };
+    assert!(limits.check("<a><b/>text</a>").is_ok());
+    assert!(limits.check("<a><b/>text<!-- note --></a>").is_err());
+}
+
+#[test]
+fn unified_result_var_shares_context_tree() {
+    let mut engine = XEngine::xee();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let items = engine.xpath(&doc, "//item").unwrap();
+    let result = engine.xpath_with_result(&doc, "//item", &items, "$result[1] is //item[1]");
+    assert_eq!(result.unwrap().to_string(), "true");
+    let result = engine.xpath_with_result(&doc, "//item", &items, "$result[2] is //item[1]");
+    assert_eq!(result.unwrap().to_string(), "false");
+
+    // The bound items keep their types
+    let date = engine.xpath(&doc, "xs:date('2024-01-31')").unwrap();
+    let check = "$result instance of xs:date";
+    let result = engine.xpath_with_result(&doc, "xs:date('2024-01-31')", &date, check);
+    assert_eq!(result.unwrap().to_string(), "true");
+
+    // A result the engine no longer holds is computed again
+    let result = engine.xpath_with_result(&doc, "//item", &items, "count($result)");
+    assert_eq!(result.unwrap().to_string(), "3");
+
+    let mut xrust = XEngine::xrust();
+    let doc = xrust.parse(SIMPLE_XML).unwrap();
+    let items = xrust.xpath(&doc, "//item").unwrap();
+    let result = xrust.xpath_with_result(&doc, "//item", &items, "count($result)");
+    assert!(matches!(result, Err(x_engine::Error::FeatureNotSupported(_))), "{:?}", result);
+}
+
+#[test]
+fn unified_node_identity_and_document_order() {
+    for mut engine in [XEngine::xee(), XEngine::xust()] {
+        let backend = engine.backend();
+        let doc = engine.parse(SIMPLE_XML).unwrap();
+        let other_doc = engine.parse(SIMPLE_XML).unwrap();
+
+        let first = engine.xpath(&doc, "//item[1]").unwrap();
+        let first_again = engine.xpath(&doc, "/root/item[@id='1']").unwrap();
+        let second = engine.xpath(&doc, "//item[2]").unwrap();
+        let other_first = engine.xpath(&other_doc, "//item[1]").unwrap();
+
+        let id1 = first.node_identity(0).expect("node result should have an identity");
+        let id1_again = first_again.node_identity(0).unwrap();
+        let id2 = second.node_identity(0).unwrap();
+
+        assert_eq!(id1, id1_again, "Same element should have equal identity: {:?}", backend);
+        assert_ne!(id1, id2, "{:?}", backend);
+        assert_ne!(id1, other_first.node_identity(0).unwrap(), "{:?}", backend);
+        assert!(x_engine::document_order_lt(&id1, &id2), "item[1] should precede item[2]");
+        assert!(!x_engine::document_order_lt(&id2, &id1));
+
+        let count = engine.xpath(&doc, "count(//item)").unwrap();
+        assert!(count.node_identity(0).is_none(), "Atomic items have no identity");
+    }
+
+    let mut engine = XEngine::xrust();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let first = engine.xpath(&doc, "//item[1]").unwrap();
+    assert!(first.node_identity(0).is_none(), "xrust doesn't track node identity");
+}
+
+#[test]
+fn unified_validate_detailed_reports_errors() {
+    let mut engine = XEngine::xust();
+    engine
+        .load_schema(
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="count" type="xs:integer"/>
+</xs:schema>"#,
+        )
+        .unwrap();
+
+    let valid = engine.parse("<count>42</count>").unwrap();
+    let result = engine.validate_detailed(&valid).unwrap();
+    assert!(result.valid);
+    assert!(result.errors.is_empty());
+
+    let invalid = engine.parse("<count>forty-two</count>").unwrap();
+    let result = engine.validate_detailed(&invalid).unwrap();
+    assert!(!result.valid);
+    assert!(!result.errors.is_empty(), "Invalid instance should report errors");
+    assert!(!result.errors[0].message.trim().is_empty(), "Error message should explain the failure");
+}
+
+#[test]
+fn unified_validation_error_kind() {
+    use x_engine::ValidationErrorKind;
+
+    let mut engine = XEngine::xust();
+    engine
+        .load_schema(
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="item">
+    <xs:complexType>
+      <xs:attribute name="id" type="xs:integer" use="required"/>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#,
+        )
+        .unwrap();
+    let doc = engine.parse("<item/>").unwrap();
+    let result = engine.validate_detailed(&doc).unwrap();
+    assert!(!result.valid);
+    assert_eq!(result.errors[0].kind, ValidationErrorKind::MissingRequired, "{}", result.errors[0].message);
+    let doc = engine.parse(r#"<item id="x"/>"#).unwrap();
+    let result = engine.validate_detailed(&doc).unwrap();
+    assert_eq!(result.errors[0].kind, ValidationErrorKind::SimpleType, "{}", result.errors[0].message);
+
+    let kind = ValidationErrorKind::from_message("cvc-datatype-valid.1.2.1: 'x' is not a valid value");
+    assert_eq!(kind, ValidationErrorKind::SimpleType);
+    let kind = ValidationErrorKind::from_message("cvc-complex-type.2.4.a: Invalid content");
+    assert_eq!(kind, ValidationErrorKind::UnexpectedElement);
+    assert_eq!(ValidationErrorKind::from_message("odd"), ValidationErrorKind::Other);
+}
+
+#[test]
+fn unified_validate_and_query() {
+    let schema = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="count" type="xs:integer"/>
+</xs:schema>"#;
+    let mut engine = XEngine::xust();
+
+    let (validation, result) = engine.validate_and_query("<count>forty-two</count>", schema, "string(/count)").unwrap();
+    assert!(!validation.valid);
+    assert!(!validation.errors.is_empty());
+    assert!(result.is_none(), "Invalid instance should not be queried");
+
+    let (validation, result) = engine.validate_and_query("<count>42</count>", schema, "string(/count)").unwrap();
+    assert!(validation.valid);
+    assert_eq!(result.unwrap().to_string(), "42");
+
+    let (_, result) = engine
+        .validate_and_query_with("<count>forty-two</count>", schema, "string(/count)", true)
+        .unwrap();
+    assert_eq!(result.unwrap().to_string(), "forty-two");
+}
+
+#[test]
+fn unified_validate_to_typed() {
+    let schema = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="n" type="xs:int"/>
+</xs:schema>"#;
+    let mut engine = XEngine::xust();
+    let types = engine.validate_to_typed("<n>42</n>", schema).unwrap();
+    assert_eq!(types, vec![("/n[1]".to_string(), "xs:int".to_string())]);
+
+    let mut xee = XEngine::xee();
+    assert!(xee.validate_to_typed("<n>42</n>", schema).is_err());
+}
+
+#[test]
+fn unified_query_validated_uses_types() {
+    let mut engine = XEngine::xust();
+    engine
+        .load_schema(
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="r">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element name="n" type="xs:int" maxOccurs="unbounded"/>
+      </xs:sequence>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#,
+        )
+        .unwrap();
+    let doc = engine.parse("<r><n>10</n><n>9</n></r>").unwrap();
+    let compare = "/r/n[1] > /r/n[2]";
+
+    let untyped = engine.xpath(&doc, compare).unwrap();
+    assert_eq!(untyped.to_string(), "false", "Untyped values compare as strings");
+    let typed = engine.query_validated(&doc, compare).unwrap();
+    assert_eq!(typed.to_string(), "true", "xs:int values compare as numbers");
+
+    let mut xee = XEngine::xee();
+    let doc = xee.parse("<r/>").unwrap();
+    assert!(matches!(xee.query_validated(&doc, "1"), Err(x_engine::Error::Unsupported)));
+}
+
+#[test]
+fn unified_clear_schema() {
+    let mut engine = XEngine::xust();
+    engine
+        .load_schema(
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="root" type="xs:string"/>
+</xs:schema>"#,
+        )
+        .unwrap();
+    let doc = engine.parse("<root/>").unwrap();
+    assert!(engine.validate(&doc).is_ok());
+
+    engine.clear_schema();
+    engine.clear_schema();
+    match engine.validate(&doc) {
+        Err(x_engine::Error::XsdError(msg)) => assert_eq!(msg, "No schema loaded"),
+        other => panic!("Expected XsdError after clear_schema, got {:?}", other.map(|r| r.valid)),
+    }
+}
+
+#[cfg(feature = "native")]
+#[test]
+fn xust_schema_from_string_validates() {
+    let mut engine = XustEngine::new();
+    engine
+        .load_schema(
+            r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="count" type="xs:integer"/>
+</xs:schema>"#,
+        )
+        .unwrap();
+    let valid = engine.parse("<count>42</count>").unwrap();
+    assert!(engine.validate(&valid).unwrap().valid);
+    let invalid = engine.parse("<count>forty-two</count>").unwrap();
+    assert!(!engine.validate(&invalid).unwrap().valid);
+}
+
+#[cfg(not(feature = "native"))]
+#[test]
+fn xust_schema_from_string_needs_native() {
+    let mut engine = XustEngine::new();
+    let schema = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"/>"#;
+    assert!(matches!(engine.load_schema(schema), Err(x_engine::Error::Unsupported)));
+}
+
+#[test]
+fn unified_default_is_xee() {
+    let engine = XEngine::default();
+    assert_eq!(engine.backend(), Backend::Xee);
+}
+
+// ============== Reporter Tests ==============
+
+use x_engine::reporter::{render_diff, ComplianceReport, ComplianceSummary, ExitPolicy};
+use x_engine::testdriver::TestOutcome;
+use std::time::Duration;
+
+fn sample_results() -> Vec<TestResult> {
+    vec![
+        TestResult::new("t-001", "demo", "qt3", None, TestOutcome::Pass, Duration::from_millis(5)),
+        TestResult::new("t-002", "demo", "qt3", None, TestOutcome::Fail("wrong".into()), Duration::from_millis(20)),
+        TestResult::new("t-003", "demo", "qt3", None, TestOutcome::Error("boom".into()), Duration::from_millis(10)),
+    ]
+}
+
+#[test]
+fn render_diff_marks_changed_lines() {
+    let diff = render_diff("<a>\n  <b>1</b>\n</a>", "<a>\n  <b>2</b>\n</a>");
+    let lines: Vec<&str> = diff.lines().collect();
+    assert_eq!(
+        lines,
+        vec!["  <a>", "-   <b>1</b>", "+   <b>2</b>", "  </a>"],
+        "Diff should keep common lines and annotate the changed one"
+    );
+}
+
+#[test]
+fn report_writers_match_string_forms() {
+    let report = ComplianceReport::new("xee", "qt3", sample_results());
+
+    let mut markdown = Vec::new();
+    report.write_markdown(&mut markdown).unwrap();
+    assert_eq!(String::from_utf8(markdown).unwrap(), report.to_markdown());
+
+    let mut csv = Vec::new();
+    report.write_csv(&mut csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), report.to_csv());
+
+    let mut json = Vec::new();
+    report.write_json(&mut json).unwrap();
+    assert_eq!(String::from_utf8(json).unwrap(), report.to_json());
+}
+
+#[test]
+fn report_bincode_roundtrip() {
+    let report = ComplianceReport::new("xee", "qt3", sample_results());
+    let bytes = report.to_bincode().unwrap();
+    assert!(bytes.len() < report.to_json().len(), "Bincode should be more compact than JSON");
+    assert_eq!(ComplianceReport::from_bincode(&bytes).unwrap(), report);
+    assert!(ComplianceReport::from_bincode(&bytes[..bytes.len() / 2]).is_err());
+}
+
+/// Split RFC 4180 CSV into records of fields
+fn parse_csv(csv: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut chars = csv.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    records
+}
+
+#[test]
+fn csv_quotes_fields_per_rfc4180() {
+    let message = "a,b\n\"c\"";
+    let results = vec![TestResult::new(
+        "t,001",
+        "demo",
+        "qt3",
+        None,
+        TestOutcome::Fail(message.into()),
+        Duration::ZERO,
+    )];
+    let csv = ComplianceReport::new("xee", "qt3", results).to_csv();
+
+    let records = parse_csv(&csv);
+    assert_eq!(records.len(), 2, "Header plus one record: {:?}", records);
+    assert_eq!(records[0].len(), 7);
+    assert_eq!(records[1][2], "t,001");
+    assert_eq!(records[1][5], message);
+}
+
+#[test]
+fn csv_rows_append_reports_under_one_header() {
+    let first = ComplianceReport::new("xee", "qt3", sample_results());
+    let second = ComplianceReport::new("xust", "qt3", sample_results());
+
+    let mut buffer = Vec::new();
+    first.write_csv_rows(&mut buffer, true).unwrap();
+    second.write_csv_rows(&mut buffer, false).unwrap();
+    let csv = String::from_utf8(buffer).unwrap();
+
+    let records = parse_csv(&csv);
+    assert_eq!(records.len(), 1 + 3 + 3, "One header plus every row: {:?}", records);
+    assert_eq!(records.iter().filter(|r| r[0] == "test_suite").count(), 1);
+}
+
+#[test]
+fn feature_matrix_grid() {
+    let passing = || vec![TestResult::new("t-001", "demo", "qt3", None, TestOutcome::Pass, Duration::ZERO)];
+    let reports = vec![
+        ComplianceReport::new("xee", "qt3", sample_results()),
+        ComplianceReport::new("xee", "xsd", passing()),
+        ComplianceReport::new("xust", "qt3", passing()),
+        ComplianceReport::new("xust", "xsd", vec![]),
+    ];
+    let matrix = x_engine::reporter::feature_matrix(&reports);
+    let rows: Vec<&str> = matrix.lines().take_while(|l| l.starts_with('|')).collect();
+
+    assert_eq!(rows.len(), 4, "Header, separator and one row per engine:\n{}", matrix);
+    assert_eq!(rows[0], "| Engine | qt3 | xsd |");
+    assert_eq!(rows[2], "| xee | 33.3% | 100.0% |");
+    assert_eq!(rows[3], "| xust | 100.0% | 0.0% |");
+    assert!(matrix.contains("**Legend:**"));
+}
+
+#[test]
+fn console_failure_list_verbose_lists_all() {
+    use x_engine::reporter::{write_console_summary, write_failure_list, CONSOLE_FAILURE_LIMIT};
+
+    let results: Vec<TestResult> = (1..=12)
+        .map(|i| {
+            let outcome = TestOutcome::Fail(format!("wrong {}\nsecond line", i));
+            TestResult::new(format!("t-{:03}", i), "demo", "qt3", None, outcome, Duration::ZERO)
+        })
+        .collect();
+
+    let mut compact = Vec::new();
+    write_failure_list(&mut compact, &results, false).unwrap();
+    let compact = String::from_utf8(compact).unwrap();
+    assert!(compact.starts_with(&format!("First {} failures:", CONSOLE_FAILURE_LIMIT)));
+    assert!(!compact.contains("t-011"));
+
+    let mut verbose = Vec::new();
+    write_failure_list(&mut verbose, &results, true).unwrap();
+    let verbose = String::from_utf8(verbose).unwrap();
+    assert!(verbose.starts_with("12 failures:"));
+    for i in 1..=12 {
+        assert!(verbose.contains(&format!("t-{:03}", i)), "t-{:03} missing:\n{}", i, verbose);
+    }
+    assert!(!verbose.contains("second line"), "Outcome messages are one line");
+
+    let mut summary = Vec::new();
+    write_console_summary(&mut summary, &ComplianceSummary::from_results(&results)).unwrap();
+    assert!(String::from_utf8(summary).unwrap().contains("Failed:         12"));
+}
+
+#[test]
+fn comparison_report_sorts_engines_by_name() {
+    let reports = vec![
+        ComplianceReport::new("xust", "qt3", sample_results()),
+        ComplianceReport::new("xee", "qt3", sample_results()),
+        ComplianceReport::new("xrust", "qt3", sample_results()),
+    ];
+    let comparison = x_engine::reporter::compare_reports(&reports);
+    let names: Vec<&str> = comparison.engines.iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, vec!["xee", "xrust", "xust"]);
+    assert_eq!(comparison.suite, "qt3");
+}
+
+#[test]
+fn exit_policy_thresholds() {
+    // 1 pass, 1 fail, 1 error: 33.3% pass rate, 2 failures
+    let summary = ComplianceSummary::from_results(&sample_results());
+
+    let (code, decision) = ExitPolicy::default().decide(&summary);
+    assert_eq!(code, 0, "No thresholds should keep the default exit code");
+    assert!(decision.is_none());
+
+    let below = ExitPolicy { min_pass_rate: Some(50.0), ..Default::default() };
+    let (code, decision) = below.decide(&summary);
+    assert_eq!(code, 1);
+    assert!(decision.unwrap().contains("pass rate"));
+
+    let too_many = ExitPolicy { max_failures: Some(1), ..Default::default() };
+    assert_eq!(too_many.decide(&summary).0, 1);
+
+    let lenient = ExitPolicy { min_pass_rate: Some(30.0), max_failures: Some(2) };
+    assert_eq!(lenient.decide(&summary).0, 0);
+
+    let mut streamed = ComplianceSummary::default();
+    for result in &sample_results() {
+        streamed.add(result);
+    }
+    assert_eq!(streamed.failed + streamed.errors, 2);
+    assert!((streamed.pass_rate - summary.pass_rate).abs() < f64::EPSILON);
+}
+
+#[test]
+fn report_slowest_tests_and_durations() {
+    let report = ComplianceReport::new("xee", "qt3", sample_results());
+
+    let slowest: Vec<_> = report.slowest_tests(2).iter().map(|r| r.test_id.as_str()).collect();
+    assert_eq!(slowest, vec!["t-002", "t-003"]);
+    assert_eq!(report.slowest_tests(10).len(), 3);
+
+    assert_eq!(report.summary.total_duration_ms, 35);
+    assert!((report.summary.mean_duration_ms - 35.0 / 3.0).abs() < 1e-9);
+    assert!(report.to_markdown().contains("## Slowest Tests"));
+}
+
+#[test]
+fn summary_adds_up_sub_millisecond_durations() {
+    let results: Vec<TestResult> = (0..1000)
+        .map(|i| {
+            let duration = Duration::from_micros(500);
+            TestResult::new(format!("t-{}", i), "demo", "qt3", None, TestOutcome::Pass, duration)
+        })
+        .collect();
+
+    let summary = ComplianceSummary::from_results(&results);
+    assert_eq!(summary.total_duration_ms, 500);
+    assert!((summary.mean_duration_ms - 0.5).abs() < 1e-9);
+
+    let mut streamed = ComplianceSummary::default();
+    for result in &results {
+        streamed.add(result);
+    }
+    assert_eq!(streamed.total_duration_ms, 500);
+
+    // The exact total survives a round trip, and streaming carries on from it
+    let json = serde_json::to_string(&streamed).unwrap();
+    let mut restored: ComplianceSummary = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, streamed);
+    for result in &results {
+        restored.add(result);
+    }
+    assert_eq!(restored.total_duration_ms, 1000);
+}
+
+#[test]
+fn test_outcome_display() {
+    assert_eq!(TestOutcome::Fail("x".to_string()).to_string(), "fail: x");
+    assert_eq!(TestOutcome::Error("boom".to_string()).to_string(), "error: boom");
+    assert_eq!(TestOutcome::Pass.to_string(), "pass");
+    assert_eq!(TestOutcome::NotApplicable.to_string(), "n/a");
+}
+
+#[test]
+fn report_failures_by_coverage() {
+    let mut results = sample_results();
+    results[0].covers = Some("fn-abs".to_string());
+    results[1].covers = Some("fn-abs".to_string());
+    results[2].covers = Some("fn-abs op-numeric-add".to_string());
+
+    let report = ComplianceReport::new("xee", "qt3", results);
+    let buckets = report.failures_by_coverage();
+    assert_eq!(
+        buckets,
+        vec![("fn-abs".to_string(), 2), ("op-numeric-add".to_string(), 1)],
+        "Only failing tests should be counted"
+    );
+}
+
+#[test]
+fn qt3_covers_metadata_reaches_results() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("catalog.xml"),
+        r#"<catalog xmlns="http://www.w3.org/2010/09/qt-fots-catalog" test-suite="QT3">
+  <test-set name="demo" file="demo.xml"/>
+</catalog>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("demo.xml"),
+        r#"<test-set xmlns="http://www.w3.org/2010/09/qt-fots-catalog" name="demo">
+  <test-case name="demo-001" covers="fn:abs">
+    <description>failing abs</description>
+    <created by="someone" on="2009-01-01"/>
+    <modified by="someone" on="2012-05-01" change="fix"/>
+    <modified by="someone" on="2015-03-02" change="fix again"/>
+    <test>abs(-1)</test>
+    <result><assert-eq>2</assert-eq></result>
+  </test-case>
+</test-set>"#,
+    )
+    .unwrap();
+
+    let mut engine = XEngine::xee();
+    let results = run_xpath_tests(&mut engine, &dir.path().join("catalog.xml"), None);
+    assert_eq!(results.len(), 1);
+    assert!(results[0].outcome.is_fail());
+    assert_eq!(results[0].covers.as_deref(), Some("fn:abs"));
+    assert_eq!(results[0].modified.as_deref(), Some("2015-03-02"));
+
+    let report = ComplianceReport::new("xee", "qt3", results);
+    assert_eq!(report.failures_by_coverage(), vec![("fn:abs".to_string(), 1)]);
+}
+
+#[test]
+fn error_classification() {
+    use x_engine::{Error, ErrorClass};
+
+    let mut engine = XEngine::xee();
+    let parse_error = engine.parse("<unclosed>").err().expect("malformed XML should not parse");
+    assert_eq!(parse_error.classify(), ErrorClass::Syntax);
+    assert_eq!(Error::Unsupported.classify(), ErrorClass::Unsupported);
+    assert_eq!(Error::xpath("XPTY0004: wrong type").classify(), ErrorClass::Type);
+    assert_eq!(Error::XsdError("not valid".into()).classify(), ErrorClass::Validation);
+    assert_eq!(ErrorClass::from_code("FOAR0001"), Some(ErrorClass::Dynamic));
+    assert_eq!(ErrorClass::from_code("aébcdef"), None);
+
+    let mut results = sample_results();
+    results.push(TestResult::new(
+        "t-004",
+        "demo",
+        "qt3",
+        None,
+        TestOutcome::Error("Operation not supported by this engine".into()),
+        Duration::ZERO,
+    ));
+    let report = ComplianceReport::new("xee", "qt3", results);
+    let classes = report.errors_by_class();
+    assert_eq!(classes.iter().map(|(_, n)| n).sum::<usize>(), 2);
+    assert!(classes.contains(&(ErrorClass::Unsupported, 1)));
+}
+
+#[test]
+fn report_filtered_keeps_requested_outcomes() {
+    let report = ComplianceReport::new("xee", "qt3", sample_results());
+    let failures = report.filtered(&["fail"]);
+
+    assert_eq!(failures.results.len(), 1);
+    assert_eq!(failures.results[0].test_id, "t-002");
+    assert_eq!(failures.summary.total, 3, "Summary should still describe the whole run");
+    assert_eq!(failures.summary.passed, 1);
+    assert_eq!(failures.summary.errors, 1);
+}
+
+// ============== Collation Tests ==============
+
+use x_engine::collation::{
+    collator_for_uri, CODEPOINT_COLLATION, HTML_ASCII_CASE_INSENSITIVE_COLLATION,
+};
+
+#[test]
+fn collation_case_insensitive_equality() {
+    let codepoint = collator_for_uri(CODEPOINT_COLLATION).unwrap();
+    let case_insensitive = collator_for_uri(HTML_ASCII_CASE_INSENSITIVE_COLLATION).unwrap();
+
+    assert!(!codepoint.equals("ABC", "abc"), "Codepoint collation is case-sensitive");
+    assert!(case_insensitive.equals("ABC", "abc"), "Case-insensitive collation should ignore case");
+    assert!(!case_insensitive.equals("ABC", "abd"));
+}
+
+#[test]
+fn collation_unknown_uri() {
+    assert!(collator_for_uri("http://example.com/no-such-collation").is_none());
+}
+
+// ============== QT3 Driver Tests ==============
+
+use x_engine::testdriver::qt3::{
+    run_xpath_tests, run_xpath_tests_streaming, run_xpath_tests_with_options,
+    run_xpath_tests_with_stats,
+};
+use x_engine::testdriver::{run_isolated, RunOptions, RunStats, TestResult};
+
+/// Write a single-test-set QT3 catalog into a temp dir
+///
+/// Each case is `(name, expression, result assertion XML)`.
+fn write_qt3_suite(cases: &[(&str, &str, &str)]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("catalog.xml"),
+        r#"<catalog xmlns="http://www.w3.org/2010/09/qt-fots-catalog" test-suite="QT3">
+  <test-set name="demo" file="demo.xml"/>
+</catalog>"#,
+    )
+    .unwrap();
+
+    let mut test_set = String::from(
+        r#"<test-set xmlns="http://www.w3.org/2010/09/qt-fots-catalog" name="demo">"#,
+    );
+    for (name, expr, result) in cases {
+        test_set.push_str(&format!(
+            "<test-case name=\"{}\"><description>{}</description><test>{}</test><result>{}</result></test-case>",
+            name, name, expr, result
+        ));
+    }
+    test_set.push_str("</test-set>");
+    std::fs::write(dir.path().join("demo.xml"), test_set).unwrap();
+
+    dir
+}
+
+#[test]
+fn qt3_fail_fast_stops_after_first_failure() {
+    let suite = write_qt3_suite(&[
+        ("demo-001", "1 + 1", "<assert-eq>2</assert-eq>"),
+        ("demo-002", "1 + 1", "<assert-eq>3</assert-eq>"),
+        ("demo-003", "2 + 2", "<assert-eq>4</assert-eq>"),
+    ]);
+    let catalog = suite.path().join("catalog.xml");
+
+    let mut engine = XEngine::xee();
+    let all = run_xpath_tests(&mut engine, &catalog, None);
+    assert_eq!(all.len(), 3);
+
+    let options = RunOptions { fail_fast: true, ..Default::default() };
+    let results = run_xpath_tests_with_options(&mut engine, &catalog, None, &options);
+    assert_eq!(results.len(), 2, "Fail-fast should stop after the failing test");
+    assert!(results[0].outcome.is_pass());
+    assert!(results[1].outcome.is_fail());
+}
+
+#[test]
+fn qt3_assert_xml_loads_expected_file() {
+    let expr = "parse-xml('&lt;empty/&gt;')/empty";
+    let suite = write_qt3_suite(&[
+        ("xml-001", expr, r#"<assert-xml file="expected/out.xml"/>"#),
+        ("xml-002", expr, r#"<assert-xml file="expected/other.xml"/>"#),
+        ("xml-003", expr, r#"<assert-xml file="expected/missing.xml"/>"#),
+    ]);
+    std::fs::create_dir(suite.path().join("expected")).unwrap();
+    std::fs::write(suite.path().join("expected/out.xml"), "<empty></empty>\n").unwrap();
+    std::fs::write(suite.path().join("expected/other.xml"), "<other/>").unwrap();
+
+    let mut engine = XEngine::xee();
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    assert_eq!(results.len(), 3);
+    assert!(results[0].outcome.is_pass(), "{:?}", results[0].outcome);
+    assert!(results[1].outcome.is_fail());
+    assert!(results[2].outcome.is_error());
+    assert!(results[2].outcome.message().unwrap().contains("missing.xml"));
+}
+
+#[test]
+fn qt3_empty_sequence_distinct_from_empty_string() {
+    let mut engine = XEngine::xee();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let empty = engine.xpath(&doc, "()").unwrap();
+    let empty_string = engine.xpath(&doc, "('')").unwrap();
+    assert!(empty.is_empty_sequence());
+    assert!(!empty_string.is_empty_sequence());
+    assert_eq!(empty.to_string(), empty_string.to_string());
+
+    let suite = write_qt3_suite(&[
+        ("empty-001", "()", "<assert-empty/>"),
+        ("empty-002", "('')", "<assert-empty/>"),
+        ("empty-003", "()", "<assert-eq></assert-eq>"),
+        ("empty-004", "('')", "<assert-eq></assert-eq>"),
+    ]);
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    let outcomes: Vec<bool> = results.iter().map(|r| r.outcome.is_pass()).collect();
+    assert_eq!(outcomes, vec![true, false, false, true]);
+    assert_eq!(results[0].actual.as_deref(), Some("()"));
+}
+
+#[test]
+fn qt3_no_context_source_has_no_elements() {
+    let suite = write_qt3_suite(&[
+        ("ctx-001", "count(//*)", "<assert-eq>0</assert-eq>"),
+        ("ctx-002", "1 + 1", "<assert-eq>2</assert-eq>"),
+    ]);
+    let mut engine = XEngine::xee();
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    assert!(results.iter().all(|r| r.outcome.is_pass()), "{:?}", results);
+
+    for mut engine in [XEngine::xee(), XEngine::xrust(), XEngine::xust()] {
+        let count = engine.xpath_no_context("count(//*)").unwrap();
+        assert_eq!(count.to_string(), "0");
+        let root = engine.xpath_no_context(". instance of document-node()").unwrap();
+        assert_eq!(root.to_string(), "true");
+    }
+}
+
+#[test]
+fn qt3_assert_eq_is_typed() {
+    let suite = write_qt3_suite(&[
+        ("eq-001", "'1'", "<assert-eq>1</assert-eq>"),
+        ("eq-002", "1", "<assert-eq>1</assert-eq>"),
+        ("eq-003", "1.0", "<assert-eq>1</assert-eq>"),
+        ("eq-004", "'1'", "<assert-eq>\"1\"</assert-eq>"),
+        ("eq-005", "'a b'", "<assert-eq>a b</assert-eq>"),
+    ]);
+    let mut engine = XEngine::xee();
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    let outcomes: Vec<bool> = results.iter().map(|r| r.outcome.is_pass()).collect();
+    assert_eq!(outcomes, vec![false, true, true, true, true], "{:?}", results);
+    let message = results[0].outcome.message().unwrap();
+    assert!(message.contains("Type mismatch"), "{}", message);
+    assert!(message.contains("xs:integer") && message.contains("xs:string"), "{}", message);
+}
+
+#[test]
+fn qt3_assert_empty_messages_and_error_policy() {
+    let suite = write_qt3_suite(&[
+        ("empty-001", "()", "<assert-empty/>"),
+        ("empty-002", "('')", "<assert-empty/>"),
+        ("empty-003", "error(QName('', 'demo'), 'raised')", "<assert-empty/>"),
+    ]);
+    let catalog = suite.path().join("catalog.xml");
+    let mut engine = XEngine::xee();
+
+    let results = run_xpath_tests(&mut engine, &catalog, None);
+    assert!(results[0].outcome.is_pass(), "{:?}", results[0].outcome);
+    match &results[1].outcome {
+        TestOutcome::Fail(message) => assert!(message.contains("got 1 item(s)"), "{}", message),
+        other => panic!("('') must not count as empty: {:?}", other),
+    }
+    match &results[2].outcome {
+        TestOutcome::Fail(message) => assert!(message.contains("got error:"), "{}", message),
+        other => panic!("An error fails by default: {:?}", other),
+    }
+
+    let lenient = RunOptions { allow_error_as_empty: true, ..Default::default() };
+    let results = run_xpath_tests_with_options(&mut engine, &catalog, None, &lenient);
+    let outcomes: Vec<bool> = results.iter().map(|r| r.outcome.is_pass()).collect();
+    assert_eq!(outcomes, vec![true, false, true]);
+}
+
+#[test]
+fn qt3_environment_decimal_format() {
+    let suite = write_qt3_suite(&[]);
+    std::fs::write(
+        suite.path().join("demo.xml"),
+        r#"<test-set xmlns="http://www.w3.org/2010/09/qt-fots-catalog" name="demo">
+  <test-case name="format-number-001">
+    <description>Grouping under a configured decimal format</description>
+    <environment>
+      <decimal-format decimal-separator="," grouping-separator="."/>
+    </environment>
+    <test>format-number(1234.5, '#.##0,0')</test>
+    <result><assert-eq>"1.234,5"</assert-eq></result>
+  </test-case>
+</test-set>"#,
+    )
+    .unwrap();
+
+    let mut engine = XEngine::xee();
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    assert_eq!(results.len(), 1);
+    assert!(results[0].outcome.is_pass(), "{:?}", results[0]);
+}
+
+#[test]
+fn qt3_missing_source_file_is_skipped() {
+    let suite = write_qt3_suite(&[]);
+    std::fs::write(
+        suite.path().join("demo.xml"),
+        r#"<test-set xmlns="http://www.w3.org/2010/09/qt-fots-catalog" name="demo">
+  <test-case name="missing-source-001">
+    <description>Context document that isn't on disk</description>
+    <environment>
+      <source role="." file="does-not-exist.xml"/>
+    </environment>
+    <test>count(//*)</test>
+    <result><assert-eq>1</assert-eq></result>
+  </test-case>
+</test-set>"#,
+    )
+    .unwrap();
+
+    let mut engine = XEngine::xee();
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    assert_eq!(results.len(), 1);
+    assert!(matches!(results[0].outcome, TestOutcome::Skipped), "{:?}", results[0].outcome);
+    let actual = results[0].actual.as_deref().unwrap_or_default();
+    assert!(actual.contains("Missing source file"), "{}", actual);
+}
+
+#[test]
+fn qt3_environment_variable_source() {
+    let suite = write_qt3_suite(&[]);
+    std::fs::write(suite.path().join("rows.xml"), "<rows><row/><row/><row/></rows>").unwrap();
+    std::fs::write(
+        suite.path().join("demo.xml"),
+        r#"<test-set xmlns="http://www.w3.org/2010/09/qt-fots-catalog" name="demo">
+  <test-case name="variable-source-001">
+    <description>Second document bound to $doc2</description>
+    <environment>
+      <source role="$doc2" file="rows.xml"/>
+    </environment>
+    <test>count($doc2//row)</test>
+    <result><assert-eq>3</assert-eq></result>
+  </test-case>
+</test-set>"#,
+    )
+    .unwrap();
+
+    let mut engine = XEngine::xee();
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    assert_eq!(results.len(), 1);
+    assert!(results[0].outcome.is_pass(), "{:?}", results[0]);
+
+    // The variable is the parsed document itself, not a copy
+    let doc = engine.parse("<a/>").unwrap();
+    let rows = engine.parse("<rows><row/></rows>").unwrap();
+    let variables = [("rows".to_string(), rows)];
+    let result = engine.xpath_with_variables(&doc, &variables, "$rows/rows/row").unwrap();
+    let row = engine.xpath(&variables[0].1, "/rows/row").unwrap();
+    assert_eq!(engine.node_identity(&result, 0), engine.node_identity(&row, 0));
+
+    let mut xust = XEngine::xust();
+    let doc = xust.parse("<a/>").unwrap();
+    let variables = [("rows".to_string(), xust.parse("<rows/>").unwrap())];
+    let result = xust.xpath_with_variables(&doc, &variables, "$rows");
+    assert!(matches!(result, Err(x_engine::Error::Unsupported)));
+}
+
+#[test]
+fn qt3_test_set_cache_reuses_parsed_structure() {
+    use x_engine::testdriver::qt3::{catalog_cache_hits, parse_test_set_cached, CatalogCache};
+
+    let suite = write_qt3_suite(&[("demo-001", "1 + 1", "<assert-eq>2</assert-eq>")]);
+    let test_set_path = suite.path().join("demo.xml");
+    let envs = std::collections::HashMap::new();
+
+    let mut cache = CatalogCache::new();
+    let first = cache.test_set(&test_set_path, &envs).unwrap();
+    assert_eq!(cache.hits(), 0);
+    let second = cache.test_set(&test_set_path, &envs).unwrap();
+    assert_eq!(cache.hits(), 1);
+    assert!(std::rc::Rc::ptr_eq(&first, &second));
+
+    let hits = catalog_cache_hits();
+    let first = parse_test_set_cached(&test_set_path, &envs).unwrap();
+    let second = parse_test_set_cached(&test_set_path, &envs).unwrap();
+    assert_eq!(catalog_cache_hits(), hits + 1);
+    assert_eq!(first.test_cases.len(), second.test_cases.len());
+}
+
+#[test]
+fn qt3_malformed_catalog_entry_is_skipped() {
+    let suite = write_qt3_suite(&[("demo-001", "1 + 1", "<assert-eq>2</assert-eq>")]);
+    std::fs::write(
+        suite.path().join("catalog.xml"),
+        r#"<catalog xmlns="http://www.w3.org/2010/09/qt-fots-catalog" test-suite="QT3">
+  <test-set name="demo" file="demo.xml"/>
+  <test-set name="broken"/>
+</catalog>"#,
+    )
+    .unwrap();
+    let catalog = suite.path().join("catalog.xml");
+
+    let parsed = x_engine::testdriver::qt3::parse_catalog(&catalog).unwrap();
+    assert_eq!(parsed.test_sets.len(), 1);
+    assert_eq!(parsed.errors, vec!["test-set #2 (broken): missing @file".to_string()]);
+
+    let mut engine = XEngine::xee();
+    let results = run_xpath_tests(&mut engine, &catalog, None);
+    assert_eq!(results.len(), 2);
+
+    let entry = results.iter().find(|r| r.test_id == "catalog_entry_1").unwrap();
+    assert!(entry.outcome.is_error());
+    let demo = results.iter().find(|r| r.test_id == "demo-001").unwrap();
+    assert!(demo.outcome.is_pass(), "{:?}", demo.outcome);
+}
+
+#[test]
+fn qt3_lenient_catalog_skips_malformed_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let catalog = dir.path().join("catalog.xml");
+    std::fs::write(
+        &catalog,
+        r#"<catalog xmlns="http://www.w3.org/2010/09/qt-fots-catalog" test-suite="QT3">
+  <test-set name="first" file="first.xml"/>
+  <test-set name="fish & chips" file="fish.xml"/>
+  <test-set name="last" file="last.xml"/>
+</catalog>"#,
+    )
+    .unwrap();
+
+    assert!(x_engine::testdriver::qt3::parse_catalog(&catalog).is_err());
+    let parsed = x_engine::testdriver::qt3::parse_catalog_lenient(&catalog).unwrap();
+    let names: Vec<&str> = parsed.test_sets.iter().map(|ts| ts.name.as_str()).collect();
+    assert_eq!(names, ["first", "last"]);
+    assert_eq!(parsed.errors.len(), 1);
+    assert!(parsed.errors[0].starts_with("test-set #2"), "{}", parsed.errors[0]);
+}
+
+#[test]
+fn qt3_test_loads_query_from_file() {
+    let suite = write_qt3_suite(&[]);
+    std::fs::write(
+        suite.path().join("demo.xml"),
+        r#"<test-set xmlns="http://www.w3.org/2010/09/qt-fots-catalog" name="demo">
+  <test-case name="demo-001">
+    <description>external query</description>
+    <test file="queries/q1.xq"/>
+    <result><assert-eq>42</assert-eq></result>
+  </test-case>
+  <test-case name="demo-002">
+    <description>missing query file</description>
+    <test file="queries/missing.xq"/>
+    <result><assert-eq>42</assert-eq></result>
+  </test-case>
+</test-set>"#,
+    )
+    .unwrap();
+    std::fs::create_dir(suite.path().join("queries")).unwrap();
+    std::fs::write(suite.path().join("queries/q1.xq"), "6 * 7\n").unwrap();
+
+    let test_set = x_engine::testdriver::qt3::parse_test_set(
+        &suite.path().join("demo.xml"),
+        &Default::default(),
+    )
+    .unwrap();
+    assert_eq!(test_set.test_cases.len(), 1);
+    assert_eq!(test_set.test_cases[0].test, "6 * 7");
+    assert!(test_set.errors[0].contains("missing.xq"), "{:?}", test_set.errors);
+
+    let mut engine = XEngine::xee();
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    assert_eq!(results.len(), 1);
+    assert!(results[0].outcome.is_pass(), "{:?}", results[0].outcome);
+}
+
+#[test]
+fn qt3_panicking_test_is_isolated() {
+    let mut engine = XEngine::xee();
+
+    let crashed = run_isolated(&mut engine, "crash-001", "demo", "qt3", None, |_| {
+        panic!("crafted engine crash")
+    });
+    assert_eq!(crashed.test_id, "crash-001");
+    assert!(crashed.outcome.is_error());
+    assert_eq!(crashed.outcome.message(), Some("Panic: crafted engine crash"));
+
+    // The engine is reinitialized and subsequent tests still run
+    let next = run_isolated(&mut engine, "next-001", "demo", "qt3", None, |engine| {
+        let doc = engine.parse(SIMPLE_XML).unwrap();
+        let count = engine.xpath(&doc, "count(//item)").unwrap().to_string();
+        let outcome = if count == "3" {
+            x_engine::testdriver::TestOutcome::Pass
+        } else {
+            x_engine::testdriver::TestOutcome::Fail(count)
+        };
+        TestResult::new("next-001", "demo", "qt3", None, outcome, std::time::Duration::ZERO)
+    });
+    assert!(next.outcome.is_pass());
+    assert_eq!(engine.backend(), Backend::Xee);
+}
+
+#[test]
+fn run_isolated_keeps_engine_configuration_after_panic() {
+    let mut engine = XEngine::xee();
+    let pinned = chrono::DateTime::parse_from_rfc3339("2024-01-02T03:04:05+01:00").unwrap();
+    engine.set_current_datetime(Some(pinned));
+    engine.set_parse_limits(x_engine::ParseLimits { max_depth: 2, ..Default::default() });
+    engine.enable_query_cache(8);
+
+    run_isolated(&mut engine, "crash-001", "demo", "qt3", None, |_| {
+        panic!("crafted engine crash")
+    });
+
+    assert!(engine.parse("<a><b><c/></b></a>").is_err(), "Parse limits should survive");
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let now = engine.xpath(&doc, "string(current-dateTime())").unwrap().to_string();
+    assert_eq!(now, "2024-01-02T03:04:05+01:00");
+    engine.xpath(&doc, "count(//item)").unwrap();
+    engine.xpath(&doc, "count(//item)").unwrap();
+    assert_eq!(engine.query_cache_hits(), 1, "The query cache should survive");
+}
+
+#[test]
+fn qt3_streaming_matches_batch() {
+    let suite = write_qt3_suite(&[
+        ("demo-001", "1 + 1", "<assert-eq>2</assert-eq>"),
+        ("demo-002", "1 + 1", "<assert-eq>3</assert-eq>"),
+        ("demo-003", "()", "<assert-empty/>"),
+    ]);
+    let catalog = suite.path().join("catalog.xml");
+    let mut engine = XEngine::xee();
+
+    let batch = run_xpath_tests(&mut engine, &catalog, None);
+
+    let mut streamed = Vec::new();
+    run_xpath_tests_streaming(&mut engine, &catalog, None, &mut |r| streamed.push(r));
+
+    let summarize = |results: &[TestResult]| -> Vec<(String, String)> {
+        results
+            .iter()
+            .map(|r| (r.test_id.clone(), r.outcome.as_str().to_string()))
+            .collect()
+    };
+    assert_eq!(summarize(&batch), summarize(&streamed));
+    assert_eq!(streamed.len(), 3);
+}
+
+#[test]
+fn list_test_sets_from_catalogs() {
+    let dir = tempfile::tempdir().unwrap();
+    let qt3_catalog = dir.path().join("catalog.xml");
+    std::fs::write(
+        &qt3_catalog,
+        r#"<catalog xmlns="http://www.w3.org/2010/09/qt-fots-catalog" test-suite="QT3">
+  <test-set name="fn-abs" file="fn/abs.xml"/>
+  <test-set name="fn-concat" file="fn/concat.xml"/>
+  <test-set name="op-numeric-add" file="op/numeric-add.xml"/>
+</catalog>"#,
+    )
+    .unwrap();
+    let names = x_engine::testdriver::qt3::list_test_sets(&qt3_catalog).unwrap();
+    assert_eq!(names, vec!["fn-abs", "fn-concat", "op-numeric-add"]);
+
+    let xsd_suite = dir.path().join("suite.xml");
+    std::fs::write(
+        &xsd_suite,
+        r#"<testSuite xmlns="http://www.w3.org/XML/2004/xml-schema-test-suite/" xmlns:xlink="http://www.w3.org/1999/xlink" name="demo">
+  <testSetRef xlink:href="nist/nistMeta.testSet"/>
+  <testSetRef xlink:href="sun/sunMeta.testSet"/>
+</testSuite>"#,
+    )
+    .unwrap();
+    let names = x_engine::testdriver::xsd::list_test_sets(&xsd_suite).unwrap();
+    assert_eq!(names, vec!["nistMeta", "sunMeta"]);
+}
+
+#[test]
+fn qt3_stats_report_unmatched_filter() {
+    let suite = write_qt3_suite(&[
+        ("demo-001", "1 + 1", "<assert-eq>2</assert-eq>"),
+        ("demo-002", "2 + 2", "<assert-eq>4</assert-eq>"),
+    ]);
+    let catalog = suite.path().join("catalog.xml");
+    let mut engine = XEngine::xee();
+
+    let (results, stats) =
+        run_xpath_tests_with_stats(&mut engine, &catalog, Some("demo"), &RunOptions::default());
+    assert_eq!(results.len(), 2);
+    assert_eq!(stats, RunStats { sets_matched: 1, cases_run: 2 });
+    assert!(stats.no_match_warning(Some("demo")).is_none());
+
+    let (results, stats) =
+        run_xpath_tests_with_stats(&mut engine, &catalog, Some("dmeo"), &RunOptions::default());
+    assert!(results.is_empty());
+    assert_eq!(stats.sets_matched, 0);
+    assert_eq!(stats.cases_run, 0);
+    let warning = stats.no_match_warning(Some("dmeo")).expect("should warn when nothing matched");
+    assert!(warning.contains("dmeo"));
+}
+
+#[test]
+fn xsd_test_set_parse_is_single_pass() {
+    use x_engine::testdriver::xsd::{parse_test_set_with_stats, Validity};
+
+    let mut xml = String::from(
+        r#"<testSet xmlns="http://www.w3.org/XML/2004/xml-schema-test-suite/" xmlns:xlink="http://www.w3.org/1999/xlink" name="synthetic" contributor="demo">"#,
+    );
+    for i in 0..200 {
+        xml.push_str(&format!(
+            r#"
+  <testGroup name="g{i}">
+    <annotation><documentation><Title>Group {i}</Title><Description> Desc {i} </Description></documentation></annotation>
+    <schemaTest name="g{i}s"><schemaDocument xlink:href="s{i}.xsd"/><expected validity="valid"/></schemaTest>
+    <instanceTest name="g{i}v"><instanceDocument xlink:href="v{i}.xml"/><expected validity="valid"/></instanceTest>
+    <instanceTest name="g{i}i"><instanceDocument xlink:href="i{i}.xml"/><expected validity="invalid"/></instanceTest>
+  </testGroup>"#
+        ));
+    }
+    xml.push_str("\n</testSet>");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("synthetic.testSet");
+    std::fs::write(&path, &xml).unwrap();
+
+    let (test_set, stats) = parse_test_set_with_stats(&path).unwrap();
+    assert_eq!(test_set.name, "synthetic");
+    assert_eq!(test_set.contributor, "demo");
+    assert_eq!(test_set.test_groups.len(), 200);
+    for (i, group) in test_set.test_groups.iter().enumerate() {
+        assert_eq!(group.name, format!("g{}", i));
+        assert_eq!(group.title, format!("Group {}", i));
+        assert_eq!(group.description, format!("Desc {}", i));
+        let schema = group.schema_test.as_ref().expect("schema test");
+        assert_eq!(schema.name, format!("g{}s", i));
+        assert_eq!(schema.schema_document, dir.path().join(format!("s{}.xsd", i)));
+        assert_eq!(schema.expected_validity, Validity::Valid);
+        let instances: Vec<_> = group
+            .instance_tests
+            .iter()
+            .map(|t| (t.name.clone(), t.instance_document.clone(), t.expected_validity))
+            .collect();
+        assert_eq!(
+            instances,
+            vec![
+                (format!("g{}v", i), dir.path().join(format!("v{}.xml", i)), Validity::Valid),
+                (format!("g{}i", i), dir.path().join(format!("i{}.xml", i)), Validity::Invalid),
+            ]
+        );
+    }
+
+    // Every element contributes at most one node plus surrounding text
+    let markup = xml.matches('<').count();
+    assert!(
+        stats.nodes_visited <= 2 * markup,
+        "visited {} nodes for {} tags",
+        stats.nodes_visited,
+        markup
+    );
+}
+
+#[test]
+fn run_suite_returns_compliance_report() {
+    use x_engine::testdriver::{run_suite, Suite};
+
+    let suite = write_qt3_suite(&[
+        ("demo-001", "1 + 1", "<assert-eq>2</assert-eq>"),
+        ("demo-002", "1 + 1", "<assert-eq>3</assert-eq>"),
+    ]);
+    let mut engine = XEngine::xee();
+
+    let report = run_suite(&mut engine, Suite::Qt3, &suite.path().join("catalog.xml"), None);
+    assert_eq!(report.engine, "xee");
+    assert_eq!(report.suite, "qt3");
+    assert_eq!(report.summary.total, 2);
+    assert_eq!(report.summary.passed, 1);
+    assert_eq!(report.summary.failed, 1);
+    assert_eq!(report.results.len(), 2);
+}
+
+#[test]
+fn survey_backends_summarizes_each_backend() {
+    use x_engine::testdriver::Suite;
+
+    let suite = write_qt3_suite(&[
+        ("demo-001", "1 + 1", "<assert-eq>2</assert-eq>"),
+        ("demo-002", "2 * 3", "<assert-eq>6</assert-eq>"),
+    ]);
+    let survey = x_engine::survey_backends(Suite::Qt3, &suite.path().join("catalog.xml"), None);
+
+    for backend in [Backend::Xee, Backend::Xust] {
+        let (_, summary) = survey.iter().find(|(b, _)| *b == backend).unwrap();
+        assert_eq!(summary.total, 2, "{:?}", backend);
+        assert!(summary.passed > 0, "{:?}: {:?}", backend, summary);
+    }
+
+    // Only xust validates schemas
+    let xsd = x_engine::survey_backends(Suite::Xsd, &suite.path().join("catalog.xml"), None);
+    let backends: Vec<Backend> = xsd.iter().map(|(backend, _)| *backend).collect();
+    assert_eq!(backends, vec![Backend::Xust]);
+}
+
+#[test]
+fn qt3_run_single_runs_one_test_case() {
+    let suite = write_qt3_suite(&[
+        ("demo-001", "1 + 1", "<assert-eq>2</assert-eq>"),
+        ("demo-002", "1 + 1", "<assert-eq>3</assert-eq>"),
+    ]);
+    let catalog = suite.path().join("catalog.xml");
+    let mut engine = XEngine::xee();
+
+    let result = x_engine::testdriver::qt3::run_single(&mut engine, &catalog, "demo", "demo-002").unwrap();
+    assert_eq!(result.test_id, "demo-002");
+    assert_eq!(result.test_set, "demo");
+    assert!(result.outcome.is_fail(), "{:?}", result.outcome);
+
+    assert!(x_engine::testdriver::qt3::run_single(&mut engine, &catalog, "demo", "demo-404").is_err());
+    assert!(x_engine::testdriver::qt3::run_single(&mut engine, &catalog, "nope", "demo-001").is_err());
+}
+
+#[test]
+fn qt3_dump_test_set_shows_cases_and_assertions() {
+    let suite = write_qt3_suite(&[
+        ("demo-001", "1 + 1", "<assert-eq>2</assert-eq>"),
+        ("demo-002", "true()", "<all-of><assert-true/><assert-count>1</assert-count></all-of>"),
+    ]);
+    let catalog = suite.path().join("catalog.xml");
+
+    let json = x_engine::testdriver::qt3::dump_test_set(&catalog, "demo").unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let cases = value["test_cases"].as_array().unwrap();
+    assert_eq!(cases.len(), 2);
+    assert_eq!(cases[0]["name"], "demo-001");
+    assert_eq!(cases[0]["result"], serde_json::json!({ "AssertEq": "2" }));
+    assert_eq!(cases[1]["name"], "demo-002");
+    assert_eq!(
+        cases[1]["result"],
+        serde_json::json!({ "AllOf": ["AssertTrue", { "AssertCount": 1 }] })
+    );
+
+    assert!(x_engine::testdriver::qt3::dump_test_set(&catalog, "nope").is_err());
+}
+
+#[test]
+fn qt3_assertion_describe() {
+    use x_engine::testdriver::qt3::Assertion;
+
+    assert_eq!(Assertion::Error("XPTY0004".to_string()).describe(), "error XPTY0004");
+    assert_eq!(Assertion::AssertEq("3".to_string()).describe(), "assert-eq '3'");
+    let any_of = Assertion::AnyOf(vec![Assertion::AssertEmpty, Assertion::AssertCount(2)]);
+    assert_eq!(any_of.describe(), "any-of[assert-empty, assert-count 2]");
+
+    let suite = write_qt3_suite(&[("demo-001", "1 + 1", "<assert-eq>2</assert-eq>")]);
+    let mut engine = XEngine::xee();
+    let results = run_xpath_tests(&mut engine, &suite.path().join("catalog.xml"), None);
+    assert_eq!(results[0].expected.as_deref(), Some("assert-eq '2'"));
+}
+
+// ============== Canonical XML Tests ==============
+
+use x_engine::canonical::{xml_equivalent, CanonicalOptions};
+
+#[test]
+fn canonical_ignores_boundary_whitespace() {
+    let insignificant = CanonicalOptions { ignore_whitespace: true, ..Default::default() };
+    let significant = CanonicalOptions::default();
+
+    assert!(xml_equivalent("<a>\n  <b/>\n</a>", "<a><b/></a>", &insignificant));
+    assert!(!xml_equivalent("<a>\n  <b/>\n</a>", "<a><b/></a>", &significant));
+
+    assert!(!xml_equivalent("<a>x</a>", "<a>y</a>", &insignificant));
+    assert!(!xml_equivalent("<a>x</a>", "<a>y</a>", &significant));
+    assert!(!xml_equivalent("<a> x </a>", "<a>x</a>", &insignificant), "Significant text is preserved");
+
+    assert!(xml_equivalent(r#"<a x="1" y="2"></a>"#, r#"<a y='2' x="1"/>"#, &significant));
+}
+
+#[test]
+fn canonical_ignore_prefixes_compares_namespaces() {
+    let by_namespace = CanonicalOptions { ignore_prefixes: true, ..Default::default() };
+    let a = r#"<p:root xmlns:p="ns"><p:item/></p:root>"#;
+    let b = r#"<root xmlns="ns"><q:item xmlns:q="ns"/></root>"#;
+    assert!(xml_equivalent(a, b, &by_namespace));
+    assert!(!xml_equivalent(a, b, &CanonicalOptions::default()));
+    assert!(!xml_equivalent(a, r#"<p:root xmlns:p="other"><p:item/></p:root>"#, &by_namespace));
+}
+
+#[test]
+fn canonical_ignores_prolog_by_default() {
+    let default = CanonicalOptions::default();
+    assert!(default.ignore_prolog);
+    assert!(xml_equivalent(r#"<?xml version="1.0"?><a/>"#, "<a/>", &default));
+    assert!(xml_equivalent(
+        "<!DOCTYPE a [<!ELEMENT a ANY>]>\n<a/>",
+        "<a/>",
+        &CanonicalOptions { ignore_whitespace: true, ..Default::default() }
+    ));
+    assert!(!xml_equivalent("<?pi data?><a/>", "<a/>", &default), "Other PIs still count");
+
+    let strict = CanonicalOptions { ignore_prolog: false, ..Default::default() };
+    assert!(!xml_equivalent(r#"<?xml version="1.0"?><a/>"#, "<a/>", &strict));
+    assert!(!xml_equivalent("<!DOCTYPE a><a/>", "<a/>", &strict));
+    assert!(xml_equivalent("<!DOCTYPE  a><a/>", "<!DOCTYPE a><a/>", &strict));
+}
+
+#[test]
+fn xml_equal_compares_canonically() {
+    use x_engine::{xml_equal, CompareOptions};
+
+    let default = CompareOptions::default();
+    assert!(xml_equal(r#"<a x="1" y="2"/>"#, r#"<a y="2" x="1"></a>"#, default).unwrap());
+    assert!(!xml_equal(r#"<a x="1"/>"#, r#"<a x="2"/>"#, default).unwrap());
+
+    let remapped = (r#"<p:a xmlns:p="urn:x"><p:b/></p:a>"#, r#"<q:a xmlns:q="urn:x"><q:b/></q:a>"#);
+    let by_namespace = CompareOptions { ignore_prefixes: true, ..Default::default() };
+    assert!(xml_equal(remapped.0, remapped.1, by_namespace).unwrap());
+    assert!(!xml_equal(remapped.0, remapped.1, default).unwrap());
+
+    let indented = "<?xml version=\"1.0\"?>\n<a>\n  <b>text</b>\n</a>";
+    let whitespace = CompareOptions { ignore_whitespace: true, ..Default::default() };
+    assert!(xml_equal(indented, "<a><b>text</b></a>", whitespace).unwrap());
+    assert!(!xml_equal(indented, "<a><b>text</b></a>", default).unwrap());
+    let strict = CompareOptions { ignore_prolog: false, ..whitespace };
+    assert!(!xml_equal(indented, "<a><b>text</b></a>", strict).unwrap());
+
+    assert!(xml_equal("<a", "<a/>", default).is_err(), "Malformed input is an error");
+}
+
+#[test]
+fn serialization_preserves_prefixes_when_requested() {
+    use x_engine::serialization::SerializationOptions;
+
+    let mut engine = XEngine::xee();
+    let doc = engine
+        .parse(r#"<p:root xmlns:p="ns"><p:item>x</p:item></p:root>"#)
+        .unwrap();
+    let result = engine.xpath(&doc, "//*[local-name()='item']").unwrap();
+
+    let preserved = result.serialize_with_options(&SerializationOptions::default()).unwrap();
+    assert!(preserved.contains("<p:item"), "{}", preserved);
+    assert!(preserved.contains(r#"xmlns:p="ns""#), "Bound on the ancestor: {}", preserved);
+
+    let renamed = SerializationOptions { preserve_prefixes: false, ..Default::default() };
+    let renamed = result.serialize_with_options(&renamed).unwrap();
+    assert!(renamed.contains("<ns1:item"), "{}", renamed);
+}
+
+#[test]
+fn sequence_type_occurrence_and_item_types() {
+    use x_engine::sequence_type::SequenceType;
+
+    let integers = SequenceType::parse("xs:integer+").unwrap();
+    let items = [ResultItem::Integer(1), ResultItem::Integer(2), ResultItem::Integer(3)];
+    assert!(integers.matches(&items));
+    assert!(!integers.matches(&[]));
+    assert!(!integers.matches(&[ResultItem::String("1".into())]));
+
+    assert!(SequenceType::parse("item()?").unwrap().matches(&[]));
+    assert!(SequenceType::parse("empty-sequence()").unwrap().matches(&[]));
+    assert!(!SequenceType::parse("xs:boolean").unwrap().matches(&[ResultItem::Boolean(true), ResultItem::Boolean(false)]));
+    assert_eq!(SequenceType::parse("xs:int").unwrap().try_match(&items[..1]), None);
+    assert!(SequenceType::parse("map(*)").is_none());
+
+    let mut engine = XEngine::xee();
+    let doc = engine.parse(SIMPLE_XML).unwrap();
+    let nodes = engine.xpath(&doc, "//item").unwrap().items();
+    assert!(SequenceType::parse("element(item)*").unwrap().matches(&nodes));
+    assert!(SequenceType::parse("node()+").unwrap().matches(&nodes));
+    assert!(!SequenceType::parse("element(other)*").unwrap().matches(&nodes));
+    assert!(!SequenceType::parse("xs:string*").unwrap().matches(&nodes));
+}
+
+#[test]
+fn resolve_source_path_uri_semantics() {
+    use std::path::{Path, PathBuf};
+    use x_engine::testdriver::resolve_source_path;
+
+    let base = Path::new("/a/b");
+    assert_eq!(resolve_source_path(base, "../c/d.xml"), PathBuf::from("/a/c/d.xml"));
+    assert_eq!(resolve_source_path(base, "./e.xml"), PathBuf::from("/a/b/e.xml"));
+    assert_eq!(resolve_source_path(base, "/abs/f.xml"), PathBuf::from("/abs/f.xml"));
+    assert_eq!(resolve_source_path(base, "file:///x/my%20doc.xml"), PathBuf::from("/x/my doc.xml"));
+}
+
+// ============== XSLT Driver Tests ==============
+
+#[test]
+fn xslt_any_of_matches_second_alternative() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("catalog.xml"),
+        r#"<catalog xmlns="http://www.w3.org/2012/10/xslt-test-catalog">
+  <test-set name="demo" file="demo.xml"/>
+</catalog>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("demo.xsl"),
+        r#"<xsl:stylesheet version="1.0" xmlns:xsl="http://www.w3.org/1999/XSL/Transform">
+    <xsl:template match="/"><out>2</out></xsl:template>
+</xsl:stylesheet>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("demo.xml"),
+        r#"<test-set xmlns="http://www.w3.org/2012/10/xslt-test-catalog" name="demo">
+  <test-case name="demo-001">
+    <description>any-of with a matching second alternative</description>
+    <test><stylesheet file="demo.xsl"/></test>
+    <result>
+      <any-of>
+        <assert-xml><![CDATA[<out>1</out>]]></assert-xml>
+        <assert-xml><![CDATA[<out>2</out>]]></assert-xml>
+      </any-of>
+    </result>
+  </test-case>
+  <test-case name="demo-002">
+    <description>any-of with no matching alternative</description>
+    <test><stylesheet file="demo.xsl"/></test>
+    <result>
+      <any-of>
+        <assert-xml><![CDATA[<out>1</out>]]></assert-xml>
+        <error code="XTDE0000"/>
+      </any-of>
+    </result>
+  </test-case>
+  <test-case name="demo-003">
+    <description>any-of with an assertion the driver can't check</description>
+    <test><stylesheet file="demo.xsl"/></test>
+    <result>
+      <any-of>
+        <assert-xml><![CDATA[<out>1</out>]]></assert-xml>
+        <assert>/out = 3</assert>
+      </any-of>
+    </result>
+  </test-case>
+</test-set>"#,
+    )
+    .unwrap();
+
+    let mut engine = XEngine::xrust();
+    let results =
+        x_engine::testdriver::xslt30::run_xslt_tests(&mut engine, &dir.path().join("catalog.xml"), None);
+    assert_eq!(results.len(), 3);
+    assert!(results[0].outcome.is_pass(), "Second alternative should match: {:?}", results[0].outcome);
+    assert!(results[1].outcome.is_fail(), "No alternative should match: {:?}", results[1].outcome);
+    assert!(matches!(results[2].outcome, TestOutcome::NotApplicable), "{:?}", results[2].outcome);
+}
+
+#[test]
+fn xslt_assert_serialization_compares_expected_file() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("catalog.xml"),
+        r#"<catalog xmlns="http://www.w3.org/2012/10/xslt-test-catalog">
+  <test-set name="demo" file="demo.xml"/>
+</catalog>"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join("demo.xsl"),
+        r#"<xsl:stylesheet version="1.0" xmlns:xsl="http://www.w3.org/1999/XSL/Transform">
+    <xsl:template match="/"><out><item n="1"/></out></xsl:template>
+</xsl:stylesheet>"#,
+    )
+    .unwrap();
+    std::fs::create_dir(dir.path().join("expected")).unwrap();
+    std::fs::write(
+        dir.path().join("expected/expected.xml"),
+        "<?xml version=\"1.0\"?>\n<out>\n  <item n=\"1\"></item>\n</out>\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("expected/other.xml"), "<out/>").unwrap();
+    std::fs::write(
+        dir.path().join("demo.xml"),
+        r#"<test-set xmlns="http://www.w3.org/2012/10/xslt-test-catalog" name="demo">
+  <test-case name="demo-001">
+    <description>Output matches the bundled file</description>
+    <test><stylesheet file="demo.xsl"/></test>
+    <result><assert-serialization method="xml" file="expected/expected.xml"/></result>
+  </test-case>
+  <test-case name="demo-002">
+    <description>Output differs from the bundled file</description>
+    <test><stylesheet file="demo.xsl"/></test>
+    <result><assert-serialization file="expected/other.xml"/></result>
+  </test-case>
+  <test-case name="demo-003">
+    <description>Bundled file is missing</description>
+    <test><stylesheet file="demo.xsl"/></test>
+    <result><assert-serialization file="expected/missing.xml"/></result>
+  </test-case>
+</test-set>"#,
+    )
+    .unwrap();
+
+    let mut engine = XEngine::xrust();
+    let results =
+        x_engine::testdriver::xslt30::run_xslt_tests(&mut engine, &dir.path().join("catalog.xml"), None);
+    assert_eq!(results.len(), 3);
+    assert!(results[0].outcome.is_pass(), "{:?}", results[0].outcome);
+    assert!(results[1].outcome.is_fail(), "{:?}", results[1].outcome);
+    assert!(results[2].outcome.is_error(), "{:?}", results[2].outcome);
+}
+
+#[test]
+fn qt3_dry_run_skips_evaluation() {
+    let suite = write_qt3_suite(&[]);
+    std::fs::write(
+        suite.path().join("demo.xml"),
+        r#"<test-set xmlns="http://www.w3.org/2010/09/qt-fots-catalog" name="demo">
+  <test-case name="demo-001">
+    <description>would raise if evaluated</description>
+    <test>error()</test>
+    <result><assert-true/></result>
+  </test-case>
+  <test-case name="demo-002">
+    <description>needs XQuery 1.0</description>
+    <dependency type="spec" value="XQ10"/>
+    <test>1</test>
+    <result><assert-eq>1</assert-eq></result>
+  </test-case>
+</test-set>"#,
+    )
+    .unwrap();
+
+    let mut engine = XEngine::xee();
+    let options = RunOptions { dry_run: true, ..Default::default() };
+    let results = run_xpath_tests_with_options(&mut engine, &suite.path().join("catalog.xml"), None, &options);
+
+    assert_eq!(results.len(), 2, "One result per discovered test");
+    assert!(matches!(results[0].outcome, TestOutcome::Skipped), "{:?}", results[0].outcome);
+    assert!(matches!(results[1].outcome, TestOutcome::NotApplicable), "{:?}", results[1].outcome);
+    assert!(results.iter().all(|r| r.duration.is_zero()), "Nothing should be evaluated");
+}
+
+#[test]
+fn qt3_excluded_tests_are_skipped() {
+    let suite = write_qt3_suite(&[
+        ("demo-001", "error()", "<assert-true/>"),
+        ("demo-002", "1 + 1", "<assert-eq>2</assert-eq>"),
+    ]);
+
+    let exclude = x_engine::testdriver::parse_exclusions("# known failures\n\ndemo/demo-001\n");
+    let options = RunOptions { exclude, ..Default::default() };
+    let mut engine = XEngine::xee();
+    let (results, stats) =
+        run_xpath_tests_with_stats(&mut engine, &suite.path().join("catalog.xml"), None, &options);
+
+    assert_eq!(results.len(), 2);
+    assert!(matches!(results[0].outcome, TestOutcome::Skipped), "{:?}", results[0].outcome);
+    assert_eq!(results[0].actual.as_deref(), Some("Excluded"));
+    assert!(results[0].duration.is_zero(), "Excluded test should not be evaluated");
+    assert!(results[1].outcome.is_pass(), "{:?}", results[1].outcome);
+    assert_eq!(stats.cases_run, 1, "Only the remaining test should run");
+}
+
+#[test]
+fn qt3_xfail_marks_expected_and_unexpected_outcomes() {
+    let suite = write_qt3_suite(&[
+        ("demo-001", "1 + 1", "<assert-eq>3</assert-eq>"),
+        ("demo-002", "1 + 1", "<assert-eq>2</assert-eq>"),
+        ("demo-003", "1 + 1", "<assert-eq>2</assert-eq>"),
+    ]);
+
+    let xfail = x_engine::testdriver::parse_exclusions("demo/demo-001\ndemo/demo-002\n");
+    let options = RunOptions { xfail, fail_fast: true, ..Default::default() };
+    let mut engine = XEngine::xee();
+    let results =
+        run_xpath_tests_with_options(&mut engine, &suite.path().join("catalog.xml"), None, &options);
+
+    assert_eq!(results.len(), 3, "An expected failure must not trip --fail-fast");
+    assert!(matches!(results[0].outcome, TestOutcome::ExpectedFail(_)), "{:?}", results[0].outcome);
+    assert!(matches!(results[1].outcome, TestOutcome::UnexpectedPass), "{:?}", results[1].outcome);
+    assert!(results[2].outcome.is_pass(), "{:?}", results[2].outcome);
+
+    let summary = ComplianceSummary::from_results(&results);
+    assert_eq!((summary.expected_failures, summary.unexpected_passes), (1, 1));
+    assert_eq!(summary.failed, 0);
+    assert!((summary.pass_rate - 100.0).abs() < f64::EPSILON, "{}", summary.pass_rate);
+
+    let mut streamed = ComplianceSummary::default();
+    for result in &results {
+        streamed.add(result);
+    }
+    assert_eq!(streamed, summary);
 }